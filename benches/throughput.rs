@@ -0,0 +1,145 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// Throughput of the crate's own work: decoding listings, building and
+// checking URLs, scheduling through `Pipeline`, whole list, import, and
+// export calls answered by `MockLaserfiche`, and what the pooled HTTP
+// client saves over a fresh one per call. No server or credentials are
+// needed, so results compare across commits on the same machine.
+//
+//     cargo bench --features bench --bench throughput
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use laserfiche_rs::client::LfClient;
+use laserfiche_rs::config::ConcurrencyConfig;
+use laserfiche_rs::fixtures::{FIXTURE_DOCUMENT_ID, FIXTURE_FOLDER_ID};
+use laserfiche_rs::laserfiche::*;
+use laserfiche_rs::pipeline::Pipeline;
+use laserfiche_rs::testing::MockLaserfiche;
+use laserfiche_rs::validation;
+use tokio::runtime::Runtime;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const PAGE_SIZE: usize = 100;
+const PIPELINE_ITEMS: u64 = 1_000;
+
+fn serde(c: &mut Criterion) {
+    let page = Entries::fixture_page(PAGE_SIZE);
+    let json = serde_json::to_string(&page).unwrap();
+
+    let mut group = c.benchmark_group("serde");
+    group.throughput(Throughput::Elements(PAGE_SIZE as u64));
+    group.bench_function("encode_page", |b| b.iter(|| serde_json::to_string(&page).unwrap()));
+    group.bench_function("decode_page", |b| b.iter(|| serde_json::from_str::<Entries>(&json).unwrap()));
+    group.finish();
+}
+
+fn urls(c: &mut Criterion) {
+    let api_server = LFApiServer::fixture();
+    let next_link = Entries::fixture_page(1).with_next_link(&api_server, 100).odata_next_link.unwrap();
+
+    let mut group = c.benchmark_group("urls");
+    group.bench_function("next_link", |b| {
+        b.iter(|| Entries::fixture_page(0).with_next_link(&api_server, 100))
+    });
+    group.bench_function("validate_next_link", |b| {
+        b.iter(|| validation::validate_next_link(&next_link, &api_server.address, &api_server.repository).unwrap())
+    });
+    group.finish();
+}
+
+fn pipeline(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("pipeline");
+    group.throughput(Throughput::Elements(PIPELINE_ITEMS));
+    for max_in_flight in [1, 16] {
+        let pipeline = Pipeline::new(ConcurrencyConfig { max_in_flight, ..Default::default() });
+        group.bench_function(format!("run_{}_in_flight", max_in_flight), |b| {
+            b.to_async(&runtime).iter(|| async {
+                let mut done = 0;
+                pipeline.run(0..PIPELINE_ITEMS, |i| async move { i }, |_| done += 1).await;
+                done
+            })
+        });
+    }
+    group.finish();
+}
+
+fn flows(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mock = runtime.block_on(mock_repository());
+    let client = mock.client();
+    let content = vec![0u8; 64 * 1024];
+
+    let mut group = c.benchmark_group("flows");
+    group.throughput(Throughput::Elements(PAGE_SIZE as u64));
+    group.bench_function("list_page", |b| {
+        b.to_async(&runtime).iter(|| async { client.list_entries(FIXTURE_FOLDER_ID).await.unwrap() })
+    });
+
+    group.throughput(Throughput::Bytes(content.len() as u64));
+    group.bench_function("import_bytes", |b| {
+        b.to_async(&runtime).iter_batched(
+            || content.clone(),
+            |content| async {
+                client.import_bytes(content, "bench.pdf".to_string(), FIXTURE_FOLDER_ID, None).await.unwrap()
+            },
+            BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("export_bytes", |b| {
+        b.to_async(&runtime).iter(|| async { client.export_bytes(FIXTURE_DOCUMENT_ID).await.unwrap() })
+    });
+    group.finish();
+}
+
+fn pooled_client(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mock = runtime.block_on(mock_repository());
+    let pooled = mock.client();
+
+    let mut group = c.benchmark_group("pooled_client");
+    group.bench_function("list_pooled", |b| {
+        b.to_async(&runtime).iter(|| async { pooled.list_entries(FIXTURE_FOLDER_ID).await.unwrap() })
+    });
+    // A new connection pool per call, so every listing opens a connection
+    group.bench_function("list_fresh_client", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let fresh = LfClient::with_http_client(mock.api_server(), Auth::fixture_fresh(), mock.http_client());
+            fresh.list_entries(FIXTURE_FOLDER_ID).await.unwrap()
+        })
+    });
+    group.finish();
+}
+
+/// A mock answering one page of listing, imports into the fixture folder,
+/// and 64 KiB downloads of the fixture document
+async fn mock_repository() -> MockLaserfiche {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(PAGE_SIZE)).await;
+
+    let created = ImportResult {
+        operations: Operations {
+            entry_create: EntryCreate { entry_id: FIXTURE_DOCUMENT_ID, exceptions: Vec::new() },
+            ..Default::default()
+        },
+        document_link: String::new(),
+    };
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/bench.pdf", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&created))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("GET"))
+        .and(path(mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0u8; 64 * 1024]))
+        .mount(mock.server())
+        .await;
+    mock
+}
+
+criterion_group!(benches, serde, urls, pipeline, flows, pooled_client);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// Imports every file in a local directory into a repository folder with
+// `BulkImporter`, printing progress as it goes, and saves an import manifest
+// beside the files.
+//
+//     cargo run --example bulk_import --features testing -- [DIR] [FOLDER_ID]
+
+mod common;
+
+use common::{Result, Session};
+use laserfiche_rs::config::ConcurrencyConfig;
+use laserfiche_rs::fixtures::FIXTURE_FOLDER_ID;
+use laserfiche_rs::importer::{BulkImporter, ImportJob};
+use laserfiche_rs::laserfiche::{EntryCreate, ImportResult, Operations};
+use laserfiche_rs::manifest::{self, ImportManifest};
+use laserfiche_rs::pipeline::Pipeline;
+use laserfiche_rs::progress::LineProgress;
+use laserfiche_rs::state::FileStore;
+use std::sync::Arc;
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let session = Session::connect().await?;
+    let folder_id = common::folder_arg(2)?;
+    let dir = match session.mock() {
+        Some(mock) => {
+            Mock::given(method("POST"))
+                .and(path_regex(format!(r"/Entries/{}/[^/]+$", FIXTURE_FOLDER_ID)))
+                .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult {
+                    operations: Operations {
+                        entry_create: EntryCreate { entry_id: 501, exceptions: Vec::new() },
+                        ..Default::default()
+                    },
+                    document_link: String::new(),
+                }))
+                .mount(mock.server())
+                .await;
+            let dir = common::scratch_dir("bulk-import")?;
+            for i in 1..=5 {
+                std::fs::write(dir.join(format!("Invoice {:04}.pdf", i)), b"%PDF-1.7")?;
+            }
+            dir
+        }
+        None => common::arg(1, ".").into(),
+    };
+
+    let mut jobs = Vec::new();
+    for file in std::fs::read_dir(&dir)? {
+        let path = file?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext != "json") {
+            jobs.push(ImportJob::new(path, folder_id));
+        }
+    }
+    println!("Importing {} files from {} into folder {}", jobs.len(), dir.display(), folder_id);
+
+    let pipeline = Pipeline::new(ConcurrencyConfig::from_env()?)
+        .with_progress(Arc::new(LineProgress::stderr("import")));
+    let importer = BulkImporter::with_pipeline(pipeline);
+    let report = session.scope(importer.run(session.api_server(), session.auth(), jobs)).await;
+
+    let mut manifest = ImportManifest::new();
+    for success in &report.imported {
+        let sha256 = manifest::sha256_file(&success.path)?;
+        let source_path = success.path.display().to_string();
+        manifest.record(&source_path, &success.result, sha256, None, Default::default());
+    }
+    manifest.save_to(&FileStore::new(&dir), "import-manifest.json")?;
+
+    for failure in &report.failed {
+        eprintln!("{}: {} (after {} attempts)", failure.path.display(), failure.error, failure.attempts);
+    }
+    println!(
+        "Imported {}, failed {}, {} retries in {:?}",
+        report.imported.len(),
+        report.failed.len(),
+        report.stats.retries,
+        report.stats.wall_time
+    );
+    Ok(())
+}
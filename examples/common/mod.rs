@@ -0,0 +1,85 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// Setup shared by the examples: a client for the server named by the LF_*
+// variables, or for a `MockLaserfiche` when they are unset, so every example
+// runs as-is with `cargo run --example <name> --features testing`.
+
+#![allow(dead_code)]
+
+use laserfiche_rs::client::{LfClient, LfClientOrError};
+use laserfiche_rs::config::Config;
+use laserfiche_rs::fixtures::FIXTURE_FOLDER_ID;
+use laserfiche_rs::laserfiche::{Auth, LFApiServer};
+use laserfiche_rs::testing::MockLaserfiche;
+use std::error::Error;
+use std::future::Future;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// A signed-in client, and the mock it talks to when there is no server
+pub struct Session {
+    pub client: LfClient,
+    mock: Option<MockLaserfiche>,
+}
+
+impl Session {
+    /// Sign in with `Config::from_env`, or start a mock repository if the
+    /// LF_* variables are unset
+    pub async fn connect() -> Result<Self> {
+        let Ok(config) = Config::from_env() else {
+            println!("LF_* variables not set; running against a mock repository");
+            let mock = MockLaserfiche::start().await;
+            return Ok(Session { client: mock.client(), mock: Some(mock) });
+        };
+
+        let api_server = LFApiServer { address: config.api_address, repository: config.repository };
+        match LfClient::login(api_server, config.username, config.password).await? {
+            LfClientOrError::LfClient(client) => Ok(Session { client, mock: None }),
+            LfClientOrError::LFAPIError(error) => Err(format!("Authentication failed: {:?}", error).into()),
+        }
+    }
+
+    /// The mock, for mounting the responses an example needs
+    pub fn mock(&self) -> Option<&MockLaserfiche> {
+        self.mock.as_ref()
+    }
+
+    pub fn api_server(&self) -> LFApiServer {
+        self.client.api_server().clone()
+    }
+
+    pub fn auth(&self) -> Auth {
+        self.client.auth()
+    }
+
+    /// Run `future`, a call to a static API such as `BulkImporter::run`,
+    /// against the mock when there is one
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        match &self.mock {
+            Some(mock) => mock.scope(future).await,
+            None => future.await,
+        }
+    }
+}
+
+/// Command-line argument `index`, or `default` when it is missing
+pub fn arg(index: usize, default: &str) -> String {
+    std::env::args().nth(index).unwrap_or_else(|| default.to_string())
+}
+
+/// Repository folder id from argument `index`, defaulting to the mock's
+/// fixture folder
+pub fn folder_arg(index: usize) -> Result<i64> {
+    Ok(arg(index, &FIXTURE_FOLDER_ID.to_string()).parse()?)
+}
+
+/// A fresh directory under the system temp directory, for mock runs
+pub fn scratch_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("laserfiche-rs-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
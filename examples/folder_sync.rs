@@ -0,0 +1,63 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// Keeps a local directory and a repository folder in step with
+// `FolderSync`: `down` mirrors the folder into the directory, `up` the
+// directory into the folder. Run it again and only what changed is
+// transferred; the JSON report is written to standard output.
+//
+//     cargo run --example folder_sync --features testing -- [DIR] [FOLDER_ID] [down|up]
+
+mod common;
+
+use common::{Result, Session};
+use laserfiche_rs::config::ConcurrencyConfig;
+use laserfiche_rs::fixtures::FIXTURE_FOLDER_ID;
+use laserfiche_rs::laserfiche::Entries;
+use laserfiche_rs::pipeline::Pipeline;
+use laserfiche_rs::sync::{ChangeDetection, FolderSync, SyncDirection};
+use std::path::PathBuf;
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let session = Session::connect().await?;
+    let folder_id = common::folder_arg(2)?;
+    let direction = match common::arg(3, "down").as_str() {
+        "down" => SyncDirection::Download,
+        "up" => SyncDirection::Upload,
+        other => return Err(format!("Unknown direction '{}'; expected down or up", other).into()),
+    };
+    let (dir, runs): (PathBuf, u32) = match session.mock() {
+        Some(mock) => {
+            mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(3)).await;
+            Mock::given(method("GET"))
+                .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+                .mount(mock.server())
+                .await;
+            // A second run shows that nothing is downloaded twice
+            (common::scratch_dir("folder-sync")?, 2)
+        }
+        None => (common::arg(1, "mirror").into(), 1),
+    };
+
+    let sync = FolderSync::new(&dir, folder_id, direction)
+        .detection(ChangeDetection::Hash)
+        .with_pipeline(Pipeline::new(ConcurrencyConfig::from_env()?));
+    for _ in 0..runs {
+        let report = session.scope(sync.run(session.api_server(), session.auth())).await?;
+        println!("{}", report.to_json()?);
+        eprintln!(
+            "Synced {} with folder {}: {} changed, {} unchanged, {} failed",
+            dir.display(),
+            folder_id,
+            report.changes.len(),
+            report.unchanged,
+            report.failed.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// Streams every result of a search with `LfClient::search_stream` and
+// downloads the matching documents into a local directory through a
+// `Pipeline`, a few at a time.
+//
+//     cargo run --example search_export --features testing -- [QUERY] [DIR]
+
+mod common;
+
+use common::{Result, Session};
+use futures_util::stream::TryStreamExt;
+use laserfiche_rs::config::ConcurrencyConfig;
+use laserfiche_rs::export;
+use laserfiche_rs::laserfiche::{Entries, Entry, SearchOptions};
+use laserfiche_rs::pipeline::Pipeline;
+use std::path::PathBuf;
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let session = Session::connect().await?;
+    let query = common::arg(1, "{LF:Name=\"Invoice*\", Type=\"D\"}");
+    let dir: PathBuf = match session.mock() {
+        Some(mock) => {
+            mock.mock_search(&Entries::fixture_page(4)).await;
+            Mock::given(method("GET"))
+                .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+                .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+                .mount(mock.server())
+                .await;
+            common::scratch_dir("search-export")?
+        }
+        None => {
+            let dir = PathBuf::from(common::arg(2, "search-results"));
+            std::fs::create_dir_all(&dir)?;
+            dir
+        }
+    };
+
+    let documents: Vec<Entry> = session.client
+        .search_stream(query.clone(), SearchOptions::default().page_size(100))
+        .try_filter(|entry| std::future::ready(entry.is_document()))
+        .try_collect()
+        .await?;
+    println!("{} documents match {}", documents.len(), query);
+
+    let pipeline = Pipeline::new(ConcurrencyConfig::from_env()?);
+    let (mut exported, mut failed) = (0, 0);
+    let client = session.client.clone();
+    let target_dir = dir.clone();
+    pipeline.run(documents, move |entry| {
+        let client = client.clone();
+        let target = target_dir.join(format!("{} {}", entry.id, export::export_file_name(&entry)));
+        async move {
+            let content = client.export(entry.id, &target.to_string_lossy()).await
+                .map_err(|error| error.to_string())?
+                .into_result()
+                .map_err(|error| error.to_string())?;
+            Ok::<_, String>((target, content.len()))
+        }
+    }, |outcome| match outcome {
+        Ok(Ok((target, bytes))) => {
+            exported += 1;
+            println!("{} ({} bytes)", target.display(), bytes);
+        }
+        Ok(Err(error)) => {
+            failed += 1;
+            eprintln!("Export failed: {}", error);
+        }
+        Err(panic) => {
+            failed += 1;
+            eprintln!("Export failed: {}", panic);
+        }
+    }).await;
+
+    println!("Exported {} documents into {}, {} failed", exported, dir.display(), failed);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+// A hot folder: polls a local directory and imports each file dropped into
+// it with `BulkImporter`, moving it to `imported/` or `failed/` afterwards.
+// Runs until Ctrl-C; against the mock it drops two batches and stops.
+//
+//     cargo run --example watch_folder --features testing -- [DIR] [FOLDER_ID] [INTERVAL_SECS]
+
+mod common;
+
+use common::{Result, Session};
+use laserfiche_rs::fixtures::FIXTURE_FOLDER_ID;
+use laserfiche_rs::importer::{BulkImporter, ImportJob};
+use laserfiche_rs::laserfiche::{EntryCreate, ImportResult, Operations};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let session = Session::connect().await?;
+    let folder_id = common::folder_arg(2)?;
+    let interval = Duration::from_secs(common::arg(3, "5").parse()?);
+    let (dir, polls): (PathBuf, Option<u32>) = match session.mock() {
+        Some(mock) => {
+            Mock::given(method("POST"))
+                .and(path_regex(format!(r"/Entries/{}/[^/]+$", FIXTURE_FOLDER_ID)))
+                .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult {
+                    operations: Operations {
+                        entry_create: EntryCreate { entry_id: 601, exceptions: Vec::new() },
+                        ..Default::default()
+                    },
+                    document_link: String::new(),
+                }))
+                .mount(mock.server())
+                .await;
+            (common::scratch_dir("watch-folder")?, Some(2))
+        }
+        None => (common::arg(1, "hot-folder").into(), None),
+    };
+    for subdir in ["imported", "failed"] {
+        std::fs::create_dir_all(dir.join(subdir))?;
+    }
+    println!("Watching {} for files to import into folder {}", dir.display(), folder_id);
+
+    let importer = BulkImporter::new(4);
+    let mut poll = 0;
+    loop {
+        if polls.is_some() {
+            // Stand-in for a scanner dropping files between polls
+            std::fs::write(dir.join(format!("Scan {:04}.pdf", poll + 1)), b"%PDF-1.7")?;
+        }
+
+        let jobs: Vec<ImportJob> = dropped_files(&dir)?
+            .into_iter()
+            .map(|path| ImportJob::new(path, folder_id))
+            .collect();
+        if !jobs.is_empty() {
+            let report = session.scope(importer.run(session.api_server(), session.auth(), jobs)).await;
+            for success in &report.imported {
+                let entry_id = success.result.operations.entry_create.entry_id;
+                println!("{} -> entry {}", success.path.display(), entry_id);
+                move_into(&success.path, &dir.join("imported"))?;
+            }
+            for failure in &report.failed {
+                eprintln!("{}: {}", failure.path.display(), failure.error);
+                move_into(&failure.path, &dir.join("failed"))?;
+            }
+        }
+
+        poll += 1;
+        if polls.is_some_and(|polls| poll >= polls) {
+            return Ok(());
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+    }
+}
+
+/// Files directly in `dir`, skipping hidden ones that may still be copying
+fn dropped_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        let hidden = path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+        if path.is_file() && !hidden {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn move_into(path: &Path, dir: &Path) -> Result<()> {
+    if let Some(name) = path.file_name() {
+        std::fs::rename(path, dir.join(name))?;
+    }
+    Ok(())
+}
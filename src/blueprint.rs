@@ -0,0 +1,261 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{impl_into_result, Auth, Entry, EntryOrError, FolderId, LFAPIError, LFApiServer, Result};
+use crate::snapshot::{self, AppliedOrError, EntrySnapshot, SnapshotField, SnapshotTag};
+use crate::validation;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Nested folder structure to provision under an existing folder
+///
+/// Blueprints are written in TOML or JSON:
+///
+/// ```toml
+/// volume_name = "DEFAULTVOL"
+///
+/// [[folders]]
+/// name = "Correspondence"
+/// template = "Matter"
+/// fields = { "Matter Type" = "Litigation" }
+///
+/// [[folders.children]]
+/// name = "Inbound"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Blueprint {
+    pub volume_name: String,
+    #[serde(default)]
+    pub folders: Vec<FolderBlueprint>,
+}
+
+/// One folder of a blueprint, with optional default metadata
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolderBlueprint {
+    pub name: String,
+    #[serde(default)]
+    pub template: Option<String>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: Vec<i64>,
+    #[serde(default)]
+    pub children: Vec<FolderBlueprint>,
+}
+
+pub enum BlueprintOrError {
+    /// Folders created, parents before children
+    Created(Vec<Entry>),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(BlueprintOrError::Created => Vec<Entry>);
+
+impl Blueprint {
+    /// Parse a TOML blueprint
+    pub fn from_toml(source: &str) -> Result<Self> {
+        let blueprint: Blueprint = toml::from_str(source)
+            .map_err(|e| format!("Invalid folder blueprint: {}", e))?;
+        blueprint.validate()?;
+        Ok(blueprint)
+    }
+
+    /// Parse a JSON blueprint
+    pub fn from_json(source: &str) -> Result<Self> {
+        let blueprint: Blueprint = serde_json::from_str(source)
+            .map_err(|e| format!("Invalid folder blueprint: {}", e))?;
+        blueprint.validate()?;
+        Ok(blueprint)
+    }
+
+    /// Check every folder name and field before anything is created
+    pub fn validate(&self) -> Result<()> {
+        if self.volume_name.trim().is_empty() {
+            return Err("Folder blueprint volume_name must not be empty".into());
+        }
+
+        let mut pending: Vec<&FolderBlueprint> = self.folders.iter().collect();
+        while let Some(folder) = pending.pop() {
+            validation::validate_file_name(&folder.name)?;
+            for (name, value) in &folder.fields {
+                validation::validate_field_name(name)?;
+                validation::validate_field_value(value)?;
+            }
+            for tag_id in &folder.tags {
+                validation::validate_entry_id(*tag_id)?;
+            }
+            pending.extend(folder.children.iter());
+        }
+
+        Ok(())
+    }
+
+    /// Total number of folders the blueprint creates
+    pub fn folder_count(&self) -> usize {
+        let mut count = 0;
+        let mut pending: Vec<&FolderBlueprint> = self.folders.iter().collect();
+        while let Some(folder) = pending.pop() {
+            count += 1;
+            pending.extend(folder.children.iter());
+        }
+        count
+    }
+}
+
+impl FolderBlueprint {
+    /// Default metadata for the folder, if any is declared
+    pub fn metadata_snapshot(&self) -> Option<EntrySnapshot> {
+        if self.template.is_none() && self.fields.is_empty() && self.tags.is_empty() {
+            return None;
+        }
+
+        Some(EntrySnapshot {
+            schema_version: EntrySnapshot::SCHEMA_VERSION,
+            template_name: self.template.clone(),
+            fields: self.fields
+                .iter()
+                .map(|(name, value)| SnapshotField {
+                    name: name.clone(),
+                    field_type: String::new(),
+                    values: vec![value.clone()],
+                })
+                .collect(),
+            tags: self.tags
+                .iter()
+                .map(|id| SnapshotTag { id: *id, name: String::new() })
+                .collect(),
+            links: Vec::new(),
+        })
+    }
+}
+
+/// Create the folders declared by `blueprint` under `parent_id`
+///
+/// Folders are created depth-first, each followed by its template, field
+/// values, and tags. Creation stops at the first API error; folders created
+/// before it are left in place.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `parent_id` - Folder to provision under
+/// * `blueprint` - Folder structure to create
+pub async fn apply_folder_blueprint(
+    api_server: LFApiServer,
+    auth: Auth,
+    parent_id: impl Into<FolderId>,
+    blueprint: &Blueprint
+) -> Result<BlueprintOrError> {
+    blueprint.validate()?;
+
+    let parent_id = parent_id.into();
+    let mut created = Vec::with_capacity(blueprint.folder_count());
+    let mut pending: Vec<(FolderId, &FolderBlueprint)> = blueprint.folders
+        .iter()
+        .rev()
+        .map(|folder| (parent_id, folder))
+        .collect();
+
+    while let Some((parent, folder)) = pending.pop() {
+        let entry = match Entry::new_path(
+            api_server.clone(),
+            auth.clone(),
+            folder.name.clone(),
+            blueprint.volume_name.clone(),
+            parent
+        ).await? {
+            EntryOrError::Entry(entry) => entry,
+            EntryOrError::LFAPIError(error) => return Ok(BlueprintOrError::LFAPIError(error)),
+        };
+
+        if let Some(metadata) = folder.metadata_snapshot() {
+            let applied = snapshot::apply_snapshot(api_server.clone(), auth.clone(), entry.entry_id(), &metadata).await?;
+            if let AppliedOrError::LFAPIError(error) = applied {
+                return Ok(BlueprintOrError::LFAPIError(error));
+            }
+        }
+
+        let folder_id = FolderId(entry.id);
+        pending.extend(folder.children.iter().rev().map(|child| (folder_id, child)));
+        created.push(entry);
+    }
+
+    Ok(BlueprintOrError::Created(created))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATTER_TOML: &str = r#"
+volume_name = "DEFAULTVOL"
+
+[[folders]]
+name = "Correspondence"
+template = "Matter"
+fields = { "Matter Type" = "Litigation" }
+tags = [3]
+
+[[folders.children]]
+name = "Inbound"
+
+[[folders.children]]
+name = "Outbound"
+
+[[folders]]
+name = "Pleadings"
+"#;
+
+    #[test]
+    fn test_blueprint_from_toml() {
+        let blueprint = Blueprint::from_toml(MATTER_TOML).unwrap();
+
+        assert_eq!(blueprint.volume_name, "DEFAULTVOL");
+        assert_eq!(blueprint.folders.len(), 2);
+        assert_eq!(blueprint.folders[0].children.len(), 2);
+        assert_eq!(blueprint.folder_count(), 4);
+        assert_eq!(blueprint.folders[0].fields.get("Matter Type").map(String::as_str), Some("Litigation"));
+    }
+
+    #[test]
+    fn test_blueprint_from_json() {
+        let json = r#"{
+            "volume_name": "DEFAULTVOL",
+            "folders": [{ "name": "Projects", "children": [{ "name": "Active" }] }]
+        }"#;
+        let blueprint = Blueprint::from_json(json).unwrap();
+
+        assert_eq!(blueprint.folder_count(), 2);
+        assert!(blueprint.folders[0].template.is_none());
+    }
+
+    #[test]
+    fn test_blueprint_rejects_invalid_names() {
+        let toml = r#"
+volume_name = "DEFAULTVOL"
+
+[[folders]]
+name = "Parent"
+
+[[folders.children]]
+name = "bad/name"
+"#;
+        assert!(Blueprint::from_toml(toml).is_err());
+        assert!(Blueprint::from_toml("volume_name = \"\"").is_err());
+        assert!(Blueprint::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn test_folder_metadata_snapshot() {
+        let blueprint = Blueprint::from_toml(MATTER_TOML).unwrap();
+
+        let snapshot = blueprint.folders[0].metadata_snapshot().unwrap();
+        assert_eq!(snapshot.template_name.as_deref(), Some("Matter"));
+        assert_eq!(snapshot.fields[0].values, vec!["Litigation".to_string()]);
+        assert_eq!(snapshot.tag_ids(), vec![3]);
+
+        assert!(blueprint.folders[0].children[0].metadata_snapshot().is_none());
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Work over many entries at once: import pipelines, bulk imports, folder
+//! blueprints, exports, folder sync, moves, renames, tagging, snapshots,
+//! and manifests
+//!
+//! Each module is also available at the crate root under its own name.
+
+pub use crate::blueprint;
+pub use crate::export;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::importer;
+pub use crate::manifest;
+pub use crate::migration;
+pub use crate::moves;
+pub use crate::naming;
+pub use crate::normalize;
+pub use crate::pipeline;
+pub use crate::progress;
+pub use crate::snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::sync;
+pub use crate::tagging;
@@ -0,0 +1,1274 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+
+use crate::laserfiche::{
+    self, impl_into_result, AuditReasons, AuditReasonsOrError, Auth, AuthOrError, BitsOrError, BoxStream, DeleteOptions, DocumentId, EdocDeletedOrError, EdocInfoOrError, EdocReaderOrError, EntriesOrError, Entry, EntryId, EntryOrError,
+    FolderId, FullEntryOrError, ImportResult, ImportResultOrError, LFAPIError, LFApiServer, LFObject, ListOptions, LinksOrError, MetadataResultOrError, NewLink,
+    NextLink, Result, SearchOptions, SharedAuth, Tag, TagsOrError, TemplateOrError, WrittenOrError,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{ExportOptions, ImportRequest};
+
+use crate::clock::Clock;
+use crate::config::{LockRetryConfig, RedirectPolicy, RetryPolicy};
+use crate::egress::EgressPolicy;
+use crate::outbox::{Outbox, ReplayReport};
+use crate::integrity::{self, ChecksumOrError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::integrity::ChecksummedImportOrError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::export::{self, CollisionPolicy, ExportManifest};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pipeline::Pipeline;
+use crate::preflight::{self, Operation, Preflight};
+use crate::progress::TransferProgress;
+use crate::search::{self, ContextHitsOrError, SearchRequest, SearchResultsOrError, SearchToken, SearchTokenOrError};
+use crate::link_definitions::{LinkDefinitions, LinkDefinitionsOrError};
+use crate::pages::{PageImageOptions, Pages, PagesDeletedOrError, PagesOrError};
+use crate::repositories::{Repositories, RepositoriesOrError};
+use crate::tag_definitions::{self, TagDefinitions, TagDefinitionsOrError, TagOrError};
+use crate::tasks::{CancelledOrError, ProgressOrError, Task};
+use crate::watch::{self, EntryEvent, EntryWatch};
+use crate::retry::{self, ApiResponse};
+use crate::server_info::{self, ApiVersion, ServerInfo};
+use crate::slow_requests::{self, SlowRequestLog};
+use crate::validation;
+
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Repository handle owning the server config, token, and HTTP client
+///
+/// Wraps the `Entry` functions so call sites no longer clone and pass
+/// `LFApiServer` and `Auth` on every call. All requests made through one
+/// `LfClient` (and its clones) share its `reqwest::Client` and its
+/// `SharedAuth`, which is refreshed before each request that would otherwise
+/// go out with an expiring token.
+///
+/// With a `LockRetryConfig`, imports and metadata, template, and tag updates
+/// rejected because the entry is locked are retried with backoff. With a
+/// `RetryPolicy`, every request that is rate limited or fails at a gateway
+/// is retried.
+#[derive(Debug, Clone)]
+pub struct LfClient {
+    api_server: LFApiServer,
+    auth: SharedAuth,
+    http: reqwest::Client,
+    lock_retry: Option<LockRetryConfig>,
+    retry_policy: Option<RetryPolicy>,
+    slow_request_log: Option<SlowRequestLog>,
+    server_info: Option<ServerInfo>,
+    api_version: ApiVersion,
+    max_file_size: Option<u64>,
+}
+
+/// Default `User-Agent` sent by clients built with `LfClientBuilder`
+pub const DEFAULT_USER_AGENT: &str = concat!("laserfiche-rs/", env!("CARGO_PKG_VERSION"));
+
+/// Configures the HTTP client behind an `LfClient`
+///
+/// reqwest 0.11 has no separate read timeout; `request_timeout` bounds the
+/// whole request, including reading the response body.
+#[derive(Debug, Clone)]
+pub struct LfClientBuilder {
+    api_server: LFApiServer,
+    auth: SharedAuth,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    proxy: Option<String>,
+    user_agent: String,
+    max_idle_per_host: Option<usize>,
+    redirect_policy: Option<RedirectPolicy>,
+    egress_policy: Option<EgressPolicy>,
+    lock_retry: Option<LockRetryConfig>,
+    retry_policy: Option<RetryPolicy>,
+    slow_request_log: Option<SlowRequestLog>,
+    api_version: ApiVersion,
+    max_file_size: Option<u64>,
+}
+
+impl LfClientBuilder {
+    pub fn new(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Self {
+        LfClientBuilder {
+            api_server,
+            auth: auth.into(),
+            connect_timeout: None,
+            request_timeout: None,
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            max_idle_per_host: None,
+            redirect_policy: None,
+            egress_policy: None,
+            lock_retry: None,
+            retry_policy: None,
+            slow_request_log: None,
+            api_version: ApiVersion::V1,
+            max_file_size: None,
+        }
+    }
+
+    /// Limit on establishing the TCP/TLS connection
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit on a whole request, from sending to reading the last body byte
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Send all traffic through an HTTP(S) proxy, e.g. `http://proxy.corp:3128`
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Maximum idle keep-alive connections kept per host
+    pub fn max_idle_per_host(mut self, max: usize) -> Self {
+        self.max_idle_per_host = Some(max);
+        self
+    }
+
+    /// How to follow redirects; reqwest's default follows up to 10 anywhere
+    pub fn redirect_policy(mut self, policy: RedirectPolicy) -> Self {
+        self.redirect_policy = Some(policy);
+        self
+    }
+
+    /// Only connect to hosts and addresses `policy` allows; building fails
+    /// if the API server itself is not allowed. See `crate::egress`.
+    pub fn egress_policy(mut self, policy: EgressPolicy) -> Self {
+        self.egress_policy = Some(policy);
+        self
+    }
+
+    /// Retry writes rejected because the entry is locked
+    pub fn lock_retry(mut self, config: LockRetryConfig) -> Self {
+        self.lock_retry = Some(config);
+        self
+    }
+
+    /// Retry rate-limited and gateway-failed requests
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Report requests slower than the log's threshold
+    pub fn slow_request_log(mut self, log: SlowRequestLog) -> Self {
+        self.slow_request_log = Some(log);
+        self
+    }
+
+    /// Route requests to the Repository API `version`; v1 by default
+    pub fn api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// Accept uploads up to `limit` bytes instead of `validation::MAX_FILE_SIZE`
+    pub fn max_file_size(mut self, limit: u64) -> Self {
+        self.max_file_size = Some(limit);
+        self
+    }
+
+    /// Judge token expiry by `clock` instead of the system clock
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.auth = self.auth.with_clock(clock);
+        self
+    }
+
+    /// Build an async `LfClient`
+    pub fn build(self) -> Result<LfClient> {
+        let http = self.http_client()?;
+        let client = LfClient::with_http_client(self.api_server, self.auth, http).with_api_version(self.api_version);
+        let client = match self.lock_retry {
+            Some(config) => client.with_lock_retry(config),
+            None => client,
+        };
+        let client = match self.slow_request_log {
+            Some(log) => client.with_slow_request_log(log),
+            None => client,
+        };
+        let client = match self.max_file_size {
+            Some(limit) => client.with_max_file_size(limit),
+            None => client,
+        };
+        Ok(match self.retry_policy {
+            Some(policy) => client.with_retry_policy(policy),
+            None => client,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn http_client(&self) -> Result<reqwest::Client> {
+        let mut http = reqwest::Client::builder().user_agent(self.user_agent.as_str());
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            http = http.timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        if let Some(max) = self.max_idle_per_host {
+            http = http.pool_max_idle_per_host(max);
+        }
+        match &self.egress_policy {
+            Some(egress) => {
+                egress.check(&self.api_server.base_url())?;
+                let redirect = self.redirect_policy.unwrap_or_default();
+                http = http.redirect(egress.redirect_policy(redirect)).dns_resolver(egress.resolver());
+            }
+            None => {
+                if let Some(policy) = self.redirect_policy {
+                    http = http.redirect(policy.to_reqwest());
+                }
+            }
+        }
+        Ok(http.build()?)
+    }
+
+    /// Under wasm32 requests go through the browser's fetch API, which
+    /// manages connections, timeouts, proxies, and redirects itself, so
+    /// asking for any of them is an error rather than silently ignored
+    #[cfg(target_arch = "wasm32")]
+    fn http_client(&self) -> Result<reqwest::Client> {
+        let unsupported = [
+            ("connect_timeout", self.connect_timeout.is_some()),
+            ("request_timeout", self.request_timeout.is_some()),
+            ("proxy", self.proxy.is_some()),
+            ("max_idle_per_host", self.max_idle_per_host.is_some()),
+            ("redirect_policy", self.redirect_policy.is_some()),
+        ];
+        if let Some((setting, _)) = unsupported.iter().find(|(_, set)| *set) {
+            return Err(format!("LfClientBuilder::{} is not supported under wasm32; the browser controls it", setting).into());
+        }
+        if let Some(egress) = &self.egress_policy {
+            egress.check(&self.api_server.base_url())?;
+        }
+        Ok(reqwest::Client::builder().user_agent(self.user_agent.as_str()).build()?)
+    }
+
+    /// Build an async `LfClient` and negotiate the API version with the
+    /// server; see `LfClient::negotiate`
+    pub async fn connect(self) -> Result<LfClient> {
+        self.build()?.negotiate().await
+    }
+
+    /// Build a `blocking::LfClient` with the same settings
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    pub fn build_blocking(self) -> crate::laserfiche::blocking::Result<blocking::LfClient> {
+        let mut http = reqwest::blocking::Client::builder().user_agent(self.user_agent.as_str());
+        if let Some(timeout) = self.connect_timeout {
+            http = http.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            http = http.timeout(timeout);
+        }
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(reqwest::Proxy::all(proxy.as_str())?);
+        }
+        if let Some(max) = self.max_idle_per_host {
+            http = http.pool_max_idle_per_host(max);
+        }
+        match &self.egress_policy {
+            Some(egress) => {
+                egress.check(&self.api_server.base_url())?;
+                http = http.redirect(egress.redirect_policy(self.redirect_policy.unwrap_or_default()));
+            }
+            None => {
+                if let Some(policy) = self.redirect_policy {
+                    http = http.redirect(policy.to_reqwest());
+                }
+            }
+        }
+
+        let client = blocking::LfClient::with_http_client(self.api_server, self.auth, http.build()?)
+            .with_api_version(self.api_version);
+        let client = match self.lock_retry {
+            Some(config) => client.with_lock_retry(config),
+            None => client,
+        };
+        let client = match self.slow_request_log {
+            Some(log) => client.with_slow_request_log(log),
+            None => client,
+        };
+        let client = match self.max_file_size {
+            Some(limit) => client.with_max_file_size(limit),
+            None => client,
+        };
+        Ok(match self.retry_policy {
+            Some(policy) => client.with_retry_policy(policy),
+            None => client,
+        })
+    }
+}
+
+pub enum LfClientOrError {
+    LfClient(LfClient),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(LfClientOrError::LfClient => LfClient);
+
+impl LfClient {
+    /// Create a client for `api_server` using an existing token and the
+    /// crate's shared connection pool
+    pub fn new(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Self {
+        Self::with_http_client(api_server, auth, laserfiche::shared_http_client())
+    }
+
+    /// Configure timeouts, proxy, user agent, and pooling before building
+    pub fn builder(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> LfClientBuilder {
+        LfClientBuilder::new(api_server, auth)
+    }
+
+    /// Create a client that sends requests through `http`
+    pub fn with_http_client(api_server: LFApiServer, auth: impl Into<SharedAuth>, http: reqwest::Client) -> Self {
+        LfClient {
+            api_server,
+            auth: auth.into(),
+            http,
+            lock_retry: None,
+            retry_policy: None,
+            slow_request_log: None,
+            server_info: None,
+            api_version: ApiVersion::V1,
+            max_file_size: None,
+        }
+    }
+
+    /// Retry writes rejected because the entry is locked
+    pub fn with_lock_retry(mut self, config: LockRetryConfig) -> Self {
+        self.lock_retry = Some(config);
+        self
+    }
+
+    /// Retry rate-limited and gateway-failed requests
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Report requests slower than the log's threshold
+    pub fn with_slow_request_log(mut self, log: SlowRequestLog) -> Self {
+        self.slow_request_log = Some(log);
+        self
+    }
+
+    /// Route requests to the Repository API `version`
+    pub fn with_api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// The Repository API version requests are routed to
+    pub fn api_version(&self) -> ApiVersion {
+        self.api_version
+    }
+
+    /// Accept uploads up to `limit` bytes instead of `validation::MAX_FILE_SIZE`
+    pub fn with_max_file_size(mut self, limit: u64) -> Self {
+        self.max_file_size = Some(limit);
+        self
+    }
+
+    /// Create a client and negotiate the API version with the server
+    pub async fn connect(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Result<Self> {
+        Self::new(api_server, auth).negotiate().await
+    }
+
+    /// Ask the server which API versions it supports and record them for
+    /// `require`; see `ServerInfo::detect`
+    pub async fn negotiate(mut self) -> Result<Self> {
+        self.server_info = Some(self.authed(ServerInfo::detect).await?);
+        Ok(self)
+    }
+
+    /// What the server reported in `negotiate`, if it has been called
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server_info.as_ref()
+    }
+
+    /// Fail with `ErrorKind::UnsupportedByServer` if negotiation found the
+    /// server too old for `feature`; passes when it has not negotiated
+    pub fn require(&self, version: ApiVersion, feature: &str) -> Result<()> {
+        match &self.server_info {
+            Some(info) => info.require(version, feature),
+            None => Ok(()),
+        }
+    }
+
+    /// Judge token expiry by `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.auth = self.auth.with_clock(clock);
+        self
+    }
+
+    /// Authenticate and build a client on success
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `username` - Repository user name
+    /// * `password` - Repository password
+    pub async fn login(
+        api_server: LFApiServer,
+        username: String,
+        password: String
+    ) -> Result<LfClientOrError> {
+        let http = laserfiche::shared_http_client();
+        let auth = laserfiche::with_http_client(http.clone(), Auth::new(api_server.clone(), username, password)).await?;
+        Ok(match auth {
+            AuthOrError::Auth(auth) => LfClientOrError::LfClient(Self::with_http_client(api_server, auth, http)),
+            AuthOrError::LFAPIError(error) => LfClientOrError::LFAPIError(error),
+        })
+    }
+
+    pub fn api_server(&self) -> &LFApiServer {
+        &self.api_server
+    }
+
+    /// The stored token, without checking its expiry
+    pub fn auth(&self) -> Auth {
+        self.auth.current()
+    }
+
+    /// The token shared by this client and its clones
+    pub fn shared_auth(&self) -> &SharedAuth {
+        &self.auth
+    }
+
+    /// Replace the stored token, e.g. after an external refresh
+    pub fn set_auth(&self, auth: Auth) {
+        self.auth.replace(auth);
+    }
+
+    pub fn http(&self) -> &reqwest::Client {
+        &self.http
+    }
+
+    /// Request a new token and store it on success
+    pub async fn refresh(&self) -> Result<AuthOrError> {
+        self.scoped(self.auth.refresh()).await
+    }
+
+    /// Get an entry by id
+    pub async fn get_entry(&self, entry_id: impl Into<EntryId>) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::get(api_server, auth, entry_id)).await
+    }
+
+    /// Get an entry with its field values, tags, and links; see `Entry::get_full`
+    pub async fn get_full_entry(&self, entry_id: impl Into<EntryId>) -> Result<FullEntryOrError> {
+        self.authed(|api_server, auth| Entry::get_full(api_server, auth, entry_id)).await
+    }
+
+    /// List the children of a folder
+    pub async fn list_entries(&self, folder_id: impl Into<FolderId>) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list(api_server, auth, folder_id)).await
+    }
+
+    /// List the children of a folder with OData and field options
+    pub async fn list_entries_with_options(
+        &self,
+        folder_id: impl Into<FolderId>,
+        options: &ListOptions
+    ) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list_with_options(api_server, auth, folder_id, options)).await
+    }
+
+    /// Ids of a folder's children named `names`; see
+    /// `Entry::find_children_by_names`
+    pub async fn find_children_by_names(
+        &self,
+        folder_id: impl Into<FolderId>,
+        names: &[&str]
+    ) -> Result<BTreeMap<String, EntryId>> {
+        self.authed(|api_server, auth| Entry::find_children_by_names(api_server, auth, folder_id, names)).await
+    }
+
+    /// Every child of a folder, following next links as the stream is read
+    ///
+    /// Each page is requested with a current token from this client.
+    pub fn list_all(&self, folder_id: impl Into<FolderId>) -> BoxStream<'static, Result<Entry>> {
+        let folder_id = folder_id.into();
+        let client = self.clone();
+        laserfiche::paginate(move |next_link| {
+            let client = client.clone();
+            async move {
+                match next_link {
+                    None => client.list_entries(folder_id).await,
+                    Some(next_link) => client.list_next(next_link).await,
+                }
+            }
+        })
+    }
+
+    /// Wait up to `timeout` for server-side processing to release an entry;
+    /// see `retry::wait_until_unlocked`
+    pub async fn wait_until_unlocked(&self, entry_id: impl Into<EntryId>, timeout: Duration) -> Result<()> {
+        self.authed(|api_server, auth| retry::wait_until_unlocked(api_server, auth, entry_id, timeout)).await
+    }
+
+    /// Fetch the next page of a list or search
+    pub async fn list_next(&self, next_link: NextLink) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list_custom(api_server, auth, next_link)).await
+    }
+
+    /// Search for entries
+    pub async fn search(&self, search_query: String, options: SearchOptions) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::search(api_server, auth, search_query, options)).await
+    }
+
+    /// Import a local file into a folder
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import(api_server, auth, file_path.clone(), file_name.clone(), folder_id)
+        }).await
+    }
+
+    /// Import a local file, reporting the bytes sent to `progress`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_progress(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        progress: &TransferProgress
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import_with_progress(api_server, auth, file_path.clone(), file_name.clone(), folder_id, progress)
+        }).await
+    }
+
+    /// Import a local file, returning the SHA-256 of the content sent; see
+    /// `integrity::import_checksummed`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_checksummed(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>
+    ) -> Result<ChecksummedImportOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            integrity::import_checksummed(api_server, auth, file_path.clone(), file_name.clone(), folder_id)
+        }).await
+    }
+
+    /// Import a local file with its template, field values, and tags
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_request(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        request: &ImportRequest
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import_with_request(api_server, auth, file_path.clone(), file_name.clone(), folder_id, request)
+        }).await
+    }
+
+    /// Get the entry an import created, from its `document_link`
+    ///
+    /// Fails with `validation::ErrorKind::UntrustedUrl` if the link is not
+    /// on this client's server and repository.
+    pub async fn fetch_document_link(&self, import_result: &ImportResult) -> Result<EntryOrError> {
+        let entry_id = import_result.linked_entry_id(&self.api_server)?;
+        self.get_entry(entry_id).await
+    }
+
+    /// Import a document streamed from `reader`; see `Entry::import_reader`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_reader<R>(
+        &self,
+        reader: R,
+        length: u64,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResultOrError>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        self.authed(|api_server, auth| Entry::import_reader(api_server, auth, reader, length, file_name, folder_id, mime_type)).await
+    }
+
+    /// Import a document from content in memory; see `Entry::import_bytes`
+    pub async fn import_bytes(
+        &self,
+        content: Vec<u8>,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import_bytes(api_server, auth, content.clone(), file_name.clone(), folder_id, mime_type)
+        }).await
+    }
+
+    /// Download a document's content into memory
+    pub async fn export_bytes(&self, document_id: impl Into<DocumentId>) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_bytes(api_server, auth, document_id)).await
+    }
+
+    /// Download a document's content to `file_path`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export(&self, document_id: impl Into<DocumentId>, file_path: &str) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export(api_server, auth, document_id, file_path)).await
+    }
+
+    /// Download a document's content to `file_path`, replacing an existing
+    /// file only if `options` allow it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_with_options(
+        &self,
+        document_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_with_options(api_server, auth, document_id, file_path, options)).await
+    }
+
+    /// Open a document's content for streaming reads
+    pub async fn open(&self, document_id: impl Into<DocumentId>) -> Result<EdocReaderOrError> {
+        self.authed(|api_server, auth| Entry::open(api_server, auth, document_id)).await
+    }
+
+    /// Download a document's content into `writer` without buffering it
+    pub async fn export_stream<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W
+    ) -> Result<WrittenOrError> {
+        self.authed(|api_server, auth| Entry::export_stream(api_server, auth, document_id, writer)).await
+    }
+
+    /// Download a document's content into `writer`, checking its SHA-256;
+    /// see `integrity::export_checksummed`
+    pub async fn export_checksummed<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W,
+        expected: Option<&str>
+    ) -> Result<ChecksumOrError> {
+        self.authed(|api_server, auth| integrity::export_checksummed(api_server, auth, document_id, writer, expected)).await
+    }
+
+    /// Check a document's content against the SHA-256 recorded for it
+    pub async fn verify_checksum(&self, document_id: impl Into<DocumentId>, expected: &str) -> Result<ChecksumOrError> {
+        self.authed(|api_server, auth| integrity::verify(api_server, auth, document_id, expected)).await
+    }
+
+    /// Download part of a document's content; see `Entry::export_range`
+    pub async fn export_range(
+        &self,
+        document_id: impl Into<DocumentId>,
+        start: u64,
+        end: Option<u64>
+    ) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_range(api_server, auth, document_id, start, end)).await
+    }
+
+    /// Download a document into `partial_file`, continuing an interrupted
+    /// download of the same version; see `Entry::export_resume`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_resume(&self, document_id: impl Into<DocumentId>, partial_file: &str) -> Result<WrittenOrError> {
+        self.authed(|api_server, auth| Entry::export_resume(api_server, auth, document_id, partial_file)).await
+    }
+
+    /// Download a document's content into `writer`, reporting the bytes
+    /// received to `progress`
+    pub async fn export_stream_with_progress<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W,
+        progress: &TransferProgress
+    ) -> Result<WrittenOrError> {
+        self.authed(|api_server, auth| Entry::export_stream_with_progress(api_server, auth, document_id, writer, progress)).await
+    }
+
+    /// Replace a document's content with content in memory; see `Entry::set_edoc_bytes`
+    pub async fn set_edoc_bytes(
+        &self,
+        document_id: impl Into<DocumentId>,
+        content: Vec<u8>,
+        mime_type: &str
+    ) -> Result<WrittenOrError> {
+        let document_id = document_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::set_edoc_bytes(api_server, auth, document_id, content.clone(), mime_type)
+        }).await
+    }
+
+    /// Replace a document's content with content streamed from `reader`;
+    /// see `Entry::set_edoc`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn set_edoc<R>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        reader: R,
+        length: u64,
+        mime_type: &str
+    ) -> Result<WrittenOrError>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        self.authed(|api_server, auth| Entry::set_edoc(api_server, auth, document_id, reader, length, mime_type)).await
+    }
+
+    /// Remove a document's content, keeping the entry
+    pub async fn delete_edoc(&self, document_id: impl Into<DocumentId>) -> Result<EdocDeletedOrError> {
+        let document_id = document_id.into();
+        self.authed_retrying(|api_server, auth| Entry::delete_edoc(api_server, auth, document_id)).await
+    }
+
+    /// Every page of a document; see `Pages::list_all`
+    pub async fn list_pages(&self, document_id: impl Into<DocumentId>) -> Result<PagesOrError> {
+        self.authed(|api_server, auth| Pages::list_all(api_server, auth, document_id)).await
+    }
+
+    /// Render one page of a document as an image; see `Pages::export_image`
+    pub async fn export_page_image(
+        &self,
+        document_id: impl Into<DocumentId>,
+        page_number: u32,
+        options: &PageImageOptions
+    ) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Pages::export_image(api_server, auth, document_id, page_number, options)).await
+    }
+
+    /// Delete pages from a document; see `Pages::delete`
+    pub async fn delete_pages(&self, document_id: impl Into<DocumentId>, page_numbers: &[u32]) -> Result<PagesDeletedOrError> {
+        let document_id = document_id.into();
+        self.authed_retrying(|api_server, auth| Pages::delete(api_server, auth, document_id, page_numbers)).await
+    }
+
+    /// Get a document's content headers
+    pub async fn edoc_head(&self, document_id: impl Into<DocumentId>) -> Result<EdocInfoOrError> {
+        self.authed(|api_server, auth| Entry::edoc_head(api_server, auth, document_id)).await
+    }
+
+    /// Create a folder
+    pub async fn create_folder(
+        &self,
+        folder_name: String,
+        volume_name: String,
+        parent_id: impl Into<FolderId>
+    ) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::new_path(api_server, auth, folder_name, volume_name, parent_id)).await
+    }
+
+    /// Get the folder at `path`, creating any missing folders; see
+    /// `Entry::ensure_path`
+    pub async fn ensure_path(&self, path: &str, volume_name: String) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::ensure_path(api_server, auth, path, volume_name)).await
+    }
+
+    /// Delete an entry
+    pub async fn delete_entry(&self, entry_id: impl Into<EntryId>, comment: String) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::delete(api_server, auth, entry_id, comment)).await
+    }
+
+    /// Delete an entry citing an audit reason
+    pub async fn delete_entry_with_options(&self, entry_id: impl Into<EntryId>, options: &DeleteOptions) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::delete_with_options(api_server, auth, entry_id, options)).await
+    }
+
+    /// Audit reasons configured in the repository
+    pub async fn audit_reasons(&self) -> Result<AuditReasonsOrError> {
+        self.authed(AuditReasons::list).await
+    }
+
+    /// Move and/or rename an entry
+    pub async fn patch_entry(
+        &self,
+        entry_id: impl Into<EntryId>,
+        parent_id: Option<FolderId>,
+        new_name: Option<String>
+    ) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::patch(api_server, auth, entry_id, parent_id, new_name)).await
+    }
+
+    /// Copy an entry into a folder
+    pub async fn copy_entry(
+        &self,
+        entry_id: impl Into<EntryId>,
+        target_folder_id: impl Into<FolderId>,
+        new_name: Option<String>
+    ) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::copy(api_server, auth, entry_id, target_folder_id, new_name)).await
+    }
+
+    /// Get an entry's field values
+    pub async fn get_metadata(&self, entry_id: impl Into<EntryId>) -> Result<MetadataResultOrError> {
+        self.authed(|api_server, auth| Entry::get_metadata(api_server, auth, entry_id)).await
+    }
+
+    /// Replace an entry's field values
+    pub async fn update_metadata(
+        &self,
+        entry_id: impl Into<EntryId>,
+        metadata: serde_json::Value
+    ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into();
+        self.authed_retrying(|api_server, auth| Entry::update_metadata(api_server, auth, entry_id, metadata.clone())).await
+    }
+
+    /// Get all fields of an entry
+    pub async fn get_fields(&self, entry_id: impl Into<EntryId>) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::get_fields(api_server, auth, entry_id)).await
+    }
+
+    /// Get one field of an entry
+    pub async fn get_field(&self, entry_id: impl Into<EntryId>, field_id: i64) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::get_field(api_server, auth, entry_id, field_id)).await
+    }
+
+    /// Get the template assigned to an entry
+    pub async fn get_template(&self, entry_id: impl Into<EntryId>) -> Result<TemplateOrError> {
+        self.authed(|api_server, auth| Entry::get_template(api_server, auth, entry_id)).await
+    }
+
+    /// Assign a template to an entry
+    pub async fn set_template(&self, entry_id: impl Into<EntryId>, template_name: String) -> Result<EntryOrError> {
+        let entry_id = entry_id.into();
+        self.authed_retrying(|api_server, auth| Entry::set_template(api_server, auth, entry_id, template_name.clone())).await
+    }
+
+    /// Remove an entry's template
+    pub async fn remove_template(&self, entry_id: impl Into<EntryId>) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::remove_template(api_server, auth, entry_id)).await
+    }
+
+    /// Get the tags assigned to an entry
+    pub async fn get_tags(&self, entry_id: impl Into<EntryId>) -> Result<TagsOrError> {
+        self.authed(|api_server, auth| Entry::get_tags(api_server, auth, entry_id)).await
+    }
+
+    /// Replace the tags assigned to an entry
+    pub async fn set_tags(&self, entry_id: impl Into<EntryId>, tag_ids: Vec<i64>) -> Result<TagsOrError> {
+        let entry_id = entry_id.into();
+        self.authed_retrying(|api_server, auth| Entry::set_tags(api_server, auth, entry_id, tag_ids.clone())).await
+    }
+
+    /// Get the links of an entry
+    pub async fn get_links(&self, entry_id: impl Into<EntryId>) -> Result<LinksOrError> {
+        self.authed(|api_server, auth| Entry::get_links(api_server, auth, entry_id)).await
+    }
+
+    /// Replace the links of an entry
+    pub async fn set_links(&self, entry_id: impl Into<EntryId>, links: Vec<NewLink>) -> Result<LinksOrError> {
+        self.authed(|api_server, auth| Entry::set_links(api_server, auth, entry_id, links)).await
+    }
+
+    /// Link an entry to another, keeping its existing links
+    pub async fn add_link(
+        &self,
+        source_id: impl Into<EntryId>,
+        target_id: impl Into<EntryId>,
+        link_type_id: i64,
+        description: Option<String>
+    ) -> Result<LinksOrError> {
+        self.authed(|api_server, auth| Entry::add_link(api_server, auth, source_id, target_id, link_type_id, description)).await
+    }
+
+    /// Remove every link from an entry to other entries
+    pub async fn delete_links(&self, entry_id: impl Into<EntryId>) -> Result<LinksOrError> {
+        self.authed(|api_server, auth| Entry::delete_links(api_server, auth, entry_id)).await
+    }
+
+    /// Every link definition in the repository; see `LinkDefinitions::list_all`
+    pub async fn list_link_definitions(&self) -> Result<LinkDefinitionsOrError> {
+        self.authed(LinkDefinitions::list_all).await
+    }
+
+    /// Every repository on the server; see `Repositories::list`
+    pub async fn list_repositories(&self) -> Result<RepositoriesOrError> {
+        self.authed(Repositories::list).await
+    }
+
+    /// First page of the repository's tag definitions
+    pub async fn list_tag_definitions(&self) -> Result<TagDefinitionsOrError> {
+        self.authed(TagDefinitions::list).await
+    }
+
+    /// Download every document below a folder into `target_dir`; see
+    /// `export::download_tree`
+    ///
+    /// The whole export runs with the token current when it starts.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_tree(
+        &self,
+        folder_id: impl Into<FolderId>,
+        target_dir: &Path,
+        collisions: CollisionPolicy,
+        manifest: &mut ExportManifest
+    ) -> Result<()> {
+        self.authed(|api_server, auth| export::download_tree(api_server, auth, folder_id, target_dir, collisions, manifest)).await
+    }
+
+    /// Apply the mutations queued in `outbox`; see `Outbox::replay`
+    pub async fn replay_outbox(&self, outbox: &mut Outbox) -> Result<ReplayReport> {
+        self.authed(|api_server, auth| outbox.replay(api_server, auth)).await
+    }
+
+    /// `download_tree`, several documents at a time with folders taking
+    /// turns; see `export::download_tree_with_pipeline`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_tree_with_pipeline(
+        &self,
+        pipeline: &Pipeline,
+        folder_id: impl Into<FolderId>,
+        target_dir: &Path,
+        collisions: CollisionPolicy,
+        manifest: &mut ExportManifest
+    ) -> Result<()> {
+        self.authed(|api_server, auth| {
+            export::download_tree_with_pipeline(pipeline, api_server, auth, folder_id, target_dir, collisions, manifest)
+        }).await
+    }
+
+    /// `download_tree`, starting no download once `budget` has passed; see
+    /// `export::download_tree_within_budget`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_tree_within_budget(
+        &self,
+        folder_id: impl Into<FolderId>,
+        target_dir: &Path,
+        collisions: CollisionPolicy,
+        budget: Duration,
+        manifest: &mut ExportManifest
+    ) -> Result<()> {
+        self.authed(|api_server, auth| {
+            export::download_tree_within_budget(api_server, auth, folder_id, target_dir, collisions, budget, manifest)
+        }).await
+    }
+
+    /// Poll an entry every `interval`, yielding an event each time its
+    /// modification time, field values, or folder change; see
+    /// `EntryWatch::watch`
+    ///
+    /// Each poll is made with a current token from this client.
+    pub fn watch_entry(&self, entry_id: impl Into<EntryId>, interval: Duration) -> BoxStream<'static, Result<EntryEvent>> {
+        let (client, entry_id) = (self.clone(), entry_id.into());
+        watch::watch_with(
+            move || {
+                let client = client.clone();
+                async move {
+                    let entry = client.get_entry(entry_id).await?;
+                    let metadata = client.get_metadata(entry_id).await?;
+                    EntryWatch::state(entry, metadata)
+                }
+            },
+            interval,
+        )
+    }
+
+    /// Every tag definition, following next links as the stream is read
+    ///
+    /// Each page is requested with a current token from this client.
+    pub fn tag_definitions_all(&self) -> BoxStream<'static, Result<Tag>> {
+        let client = self.clone();
+        tag_definitions::paginate_tags(move |next_link| {
+            let client = client.clone();
+            async move {
+                match next_link {
+                    None => client.list_tag_definitions().await,
+                    Some(next_link) => {
+                        client.authed(|api_server, auth| TagDefinitions::list_next(api_server, auth, next_link)).await
+                    }
+                }
+            }
+        })
+    }
+
+    /// Fetch one tag definition
+    pub async fn get_tag_definition(&self, tag_id: i64) -> Result<TagOrError> {
+        self.authed(|api_server, auth| TagDefinitions::get(api_server, auth, tag_id)).await
+    }
+
+    /// Translate tag names to ids for `set_tags`; see `TagDefinitions::resolve_names`
+    pub async fn resolve_tag_names(&self, names: &[&str]) -> Result<Vec<i64>> {
+        self.authed(|api_server, auth| TagDefinitions::resolve_names(api_server, auth, names)).await
+    }
+
+    /// Check how far a long-running operation has got; see `Task::status`
+    pub async fn task_status(&self, token: &str) -> Result<ProgressOrError> {
+        self.authed(|api_server, auth| Task::status(api_server, auth, token)).await
+    }
+
+    /// Ask the server to stop a long-running operation
+    pub async fn cancel_task(&self, token: &str) -> Result<CancelledOrError> {
+        self.authed(|api_server, auth| Task::cancel(api_server, auth, token)).await
+    }
+
+    /// Poll a long-running operation until it finishes; see
+    /// `Task::wait_for_completion`
+    pub async fn wait_for_task(&self, token: &str, timeout: Duration) -> Result<ProgressOrError> {
+        self.authed(|api_server, auth| Task::wait_for_completion(api_server, auth, token, timeout)).await
+    }
+
+    /// Every result of a search, following next links as the stream is read;
+    /// see `Entry::search_stream`
+    pub fn search_stream(&self, search_query: String, options: SearchOptions) -> BoxStream<'static, Result<Entry>> {
+        let client = self.clone();
+        laserfiche::paginate(move |next_link| {
+            let (client, search_query, options) = (client.clone(), search_query.clone(), options.clone());
+            async move {
+                match next_link {
+                    None => client.search(search_query, options).await,
+                    Some(next_link) => {
+                        client.authed(|api_server, auth| Entry::search_next(api_server, auth, next_link, &options)).await
+                    }
+                }
+            }
+        })
+    }
+
+    /// Start a long-running search; see `search::start`
+    pub async fn start_search(&self, request: &SearchRequest) -> Result<SearchTokenOrError> {
+        self.authed(|api_server, auth| search::start(api_server, auth, request)).await
+    }
+
+    /// Check how far a search has got
+    pub async fn search_status(&self, token: &SearchToken) -> Result<ProgressOrError> {
+        self.authed(|api_server, auth| search::status(api_server, auth, token)).await
+    }
+
+    /// One page of a finished search's results
+    pub async fn search_results(&self, token: &SearchToken, options: &ListOptions) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| search::results(api_server, auth, token, options)).await
+    }
+
+    /// Where the search terms were found in one result
+    pub async fn search_context_hits(&self, token: &SearchToken, row_number: i64) -> Result<ContextHitsOrError> {
+        self.authed(|api_server, auth| search::context_hits(api_server, auth, token, row_number)).await
+    }
+
+    /// Cancel a running search, or release a finished one's results
+    pub async fn cancel_search(&self, token: &SearchToken) -> Result<CancelledOrError> {
+        self.authed(|api_server, auth| search::cancel(api_server, auth, token)).await
+    }
+
+    /// Run a search to completion and fetch the first page of its results;
+    /// see `search::search_and_wait`
+    pub async fn search_and_wait(
+        &self,
+        request: &SearchRequest,
+        options: &ListOptions,
+        timeout: Duration
+    ) -> Result<SearchResultsOrError> {
+        self.authed(|api_server, auth| search::search_and_wait(api_server, auth, request, options, timeout)).await
+    }
+
+    /// Predict whether `operation` on an entry would succeed; see `preflight::can`
+    pub async fn can(&self, operation: Operation, entry_id: impl Into<EntryId>) -> Result<Preflight> {
+        self.authed(|api_server, auth| preflight::can(api_server, auth, operation, entry_id)).await
+    }
+
+    /// Run `call` with an unexpired token, scoped to this client's HTTP client
+    async fn authed<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: FnOnce(LFApiServer, Auth) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        self.scoped(async {
+            let auth = self.auth.token().await?;
+            call(self.api_server.clone(), auth).await
+        }).await
+    }
+
+    /// `authed`, repeated while the entry is locked if lock retry is enabled
+    async fn authed_retrying<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        T: ApiResponse,
+        F: Fn(LFApiServer, Auth) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        match &self.lock_retry {
+            Some(config) => retry::retry_while_locked(config, || self.authed(&call)).await,
+            None => self.authed(call).await,
+        }
+    }
+
+    /// Run `future` with this client's HTTP client, API version, upload
+    /// limit, and retry policy serving its requests
+    async fn scoped<F: std::future::Future>(&self, future: F) -> F::Output {
+        // Boxed so large call futures, e.g. `download_tree`'s, stay off the stack
+        let future = Box::pin(server_info::with_api_version(self.api_version, future));
+        let future = laserfiche::with_http_client(self.http.clone(), future);
+        let future = async {
+            match self.max_file_size {
+                Some(limit) => validation::with_max_file_size(limit, future).await,
+                None => future.await,
+            }
+        };
+        let future = async {
+            match &self.slow_request_log {
+                Some(log) => slow_requests::with_slow_request_log(log.clone(), future).await,
+                None => future.await,
+            }
+        };
+        match &self.retry_policy {
+            Some(policy) => retry::with_retry_policy(policy.clone(), future).await,
+            None => future.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> i64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as i64
+    }
+
+    fn mock_client() -> LfClient {
+        let api_server = LFApiServer {
+            address: "test.laserfiche.com".to_string(),
+            repository: "test-repo".to_string(),
+        };
+        let auth = Auth {
+            access_token: "token".to_string(),
+            expires_in: 3600,
+            timestamp: now(),
+            api_server: api_server.clone(),
+            ..Default::default()
+        };
+        LfClient::new(api_server, auth)
+    }
+
+    #[test]
+    fn test_require_after_negotiation() {
+        let mut client = mock_client();
+        assert!(client.server_info().is_none());
+        assert!(client.require(ApiVersion::V2, "attribute editing").is_ok());
+
+        client.server_info = Some(ServerInfo { versions: vec![ApiVersion::V1] });
+        assert!(client.require(ApiVersion::V1, "listing").is_ok());
+        assert!(matches!(
+            client.require(ApiVersion::V2, "attribute editing").unwrap_err().kind(),
+            laserfiche::ErrorKind::UnsupportedByServer(..)
+        ));
+    }
+
+    #[test]
+    fn test_client_accessors() {
+        let client = mock_client();
+        assert_eq!(client.api_server().repository, "test-repo");
+        assert_eq!(client.auth().access_token, "token");
+
+        // Clones share the token
+        let clone = client.clone();
+        let auth = Auth { access_token: "new-token".to_string(), ..client.auth() };
+        client.set_auth(auth);
+        assert_eq!(clone.auth().access_token, "new-token");
+    }
+
+    #[tokio::test]
+    async fn test_unexpired_token_is_not_refreshed() {
+        let client = mock_client();
+        // No credentials are stored, so a refresh attempt would fail
+        let auth = client.shared_auth().token().await.unwrap();
+        assert_eq!(auth.access_token, "token");
+    }
+
+    #[tokio::test]
+    async fn test_client_clock_drives_expiry() {
+        let clock = crate::clock::MockClock::new(std::time::SystemTime::now());
+        let client = mock_client().with_clock(Arc::new(clock.clone()));
+        assert!(client.shared_auth().token().await.is_ok());
+
+        // Past expiry the stored token is refreshed, which fails without credentials
+        clock.advance(Duration::from_secs(3600));
+        assert!(client.shared_auth().token().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_validates_before_sending() {
+        let client = mock_client();
+        // Invalid ids are rejected locally without a network round trip
+        assert!(client.get_entry(-1).await.is_err());
+        assert!(client.get_metadata(0).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_builder_applies_settings() {
+        let client = mock_client();
+        let built = LfClient::builder(client.api_server().clone(), client.auth())
+            .connect_timeout(Duration::from_secs(5))
+            .request_timeout(Duration::from_secs(60))
+            .proxy("http://proxy.example.com:3128")
+            .user_agent("records-sync/1.0")
+            .max_idle_per_host(4)
+            .redirect_policy(RedirectPolicy::SameHost { max: 5 })
+            .build()
+            .unwrap();
+
+        assert_eq!(built.api_server().repository, "test-repo");
+        assert_eq!(built.auth().access_token, "token");
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_proxy() {
+        let client = mock_client();
+        let builder = LfClient::builder(client.api_server().clone(), client.auth())
+            .proxy("not a proxy url");
+        assert!(builder.clone().build().is_err());
+        #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+        assert!(builder.build_blocking().is_err());
+    }
+
+    #[test]
+    fn test_builder_checks_egress_policy() {
+        let client = mock_client();
+        let builder = LfClient::builder(client.api_server().clone(), client.auth());
+
+        let allowed = EgressPolicy::new().allow_host("*.laserfiche.com");
+        assert!(builder.clone().egress_policy(allowed).build().is_ok());
+
+        let elsewhere = EgressPolicy::new().allow_host("lf.example.com");
+        let error = builder.clone().egress_policy(elsewhere.clone()).build().err().unwrap();
+        assert!(matches!(
+            error.kind(),
+            laserfiche::ErrorKind::ValidationError(inner) if matches!(inner.kind(), crate::validation::ErrorKind::EgressDenied(_))
+        ));
+        #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+        assert!(builder.egress_policy(elsewhere).build_blocking().is_err());
+    }
+
+    #[test]
+    fn test_default_user_agent() {
+        assert!(DEFAULT_USER_AGENT.starts_with("laserfiche-rs/"));
+    }
+}
@@ -0,0 +1,381 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::blocking::{self, Auth, Result};
+use crate::laserfiche::{
+    impl_into_result, AuditReasons, AuditReasonsOrError, AuthOrError, BitsOrError, DeleteOptions, DocumentId, EntriesOrError, ExportOptions, Entry, EntryId, EntryOrError, FolderId,
+    ImportRequest, ImportResult, ImportResultOrError, LFAPIError, LFApiServer, LFObject, ListOptions, MetadataResultOrError, NextLink,
+    SearchOptions, SharedAuth,
+};
+use crate::clock::Clock;
+use crate::config::{LockRetryConfig, RetryPolicy};
+use crate::retry::{self, ApiResponse};
+use crate::server_info::{self, ApiVersion, ServerInfo};
+use crate::slow_requests::{self, SlowRequestLog};
+use crate::validation;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Blocking repository handle owning the server config, token, and HTTP client
+///
+/// Wraps the `_blocking` functions of `Entry`. All requests made through one
+/// `LfClient` (and its clones) share its `reqwest::blocking::Client` and its
+/// self-refreshing `SharedAuth`, and retry locked writes and rate-limited
+/// requests like the async client.
+#[derive(Debug, Clone)]
+pub struct LfClient {
+    api_server: LFApiServer,
+    auth: SharedAuth,
+    http: reqwest::blocking::Client,
+    lock_retry: Option<LockRetryConfig>,
+    retry_policy: Option<RetryPolicy>,
+    slow_request_log: Option<SlowRequestLog>,
+    server_info: Option<ServerInfo>,
+    api_version: ApiVersion,
+    max_file_size: Option<u64>,
+}
+
+pub enum LfClientOrError {
+    LfClient(LfClient),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(LfClientOrError::LfClient => LfClient);
+
+impl LfClient {
+    /// Create a client for `api_server` using an existing token and the
+    /// crate's shared connection pool
+    pub fn new(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Self {
+        Self::with_http_client(api_server, auth, blocking::shared_http_client())
+    }
+
+    /// Create a client that sends requests through `http`
+    pub fn with_http_client(
+        api_server: LFApiServer,
+        auth: impl Into<SharedAuth>,
+        http: reqwest::blocking::Client
+    ) -> Self {
+        LfClient {
+            api_server,
+            auth: auth.into(),
+            http,
+            lock_retry: None,
+            retry_policy: None,
+            slow_request_log: None,
+            server_info: None,
+            api_version: ApiVersion::V1,
+            max_file_size: None,
+        }
+    }
+
+    /// Retry writes rejected because the entry is locked
+    pub fn with_lock_retry(mut self, config: LockRetryConfig) -> Self {
+        self.lock_retry = Some(config);
+        self
+    }
+
+    /// Retry rate-limited and gateway-failed requests
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Report requests slower than the log's threshold
+    pub fn with_slow_request_log(mut self, log: SlowRequestLog) -> Self {
+        self.slow_request_log = Some(log);
+        self
+    }
+
+    /// Route requests to the Repository API `version`
+    pub fn with_api_version(mut self, version: ApiVersion) -> Self {
+        self.api_version = version;
+        self
+    }
+
+    /// The Repository API version requests are routed to
+    pub fn api_version(&self) -> ApiVersion {
+        self.api_version
+    }
+
+    /// Accept uploads up to `limit` bytes instead of `validation::MAX_FILE_SIZE`
+    pub fn with_max_file_size(mut self, limit: u64) -> Self {
+        self.max_file_size = Some(limit);
+        self
+    }
+
+    /// Ask the server which API versions it supports and record them for
+    /// `require`; see `ServerInfo::detect`
+    pub fn negotiate(mut self) -> Result<Self> {
+        self.server_info = Some(self.authed(ServerInfo::detect_blocking)?);
+        Ok(self)
+    }
+
+    /// What the server reported in `negotiate`, if it has been called
+    pub fn server_info(&self) -> Option<&ServerInfo> {
+        self.server_info.as_ref()
+    }
+
+    /// Fail with `ErrorKind::UnsupportedByServer` if negotiation found the
+    /// server too old for `feature`; passes when it has not negotiated
+    pub fn require(&self, version: ApiVersion, feature: &str) -> Result<()> {
+        match &self.server_info {
+            Some(info) if !info.supports(version) => Err(blocking::ErrorKind::UnsupportedByServer(
+                feature.to_string(),
+                version.to_string(),
+                info.latest().to_string(),
+            ).into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Judge token expiry by `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.auth = self.auth.with_clock(clock);
+        self
+    }
+
+    /// Authenticate and build a client on success
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `username` - Repository user name
+    /// * `password` - Repository password
+    pub fn login(api_server: LFApiServer, username: String, password: String) -> Result<LfClientOrError> {
+        let http = blocking::shared_http_client();
+        let auth = blocking::with_http_client(&http, || Auth::new_blocking(api_server.clone(), username, password))?;
+        Ok(match auth {
+            AuthOrError::Auth(auth) => LfClientOrError::LfClient(Self::with_http_client(api_server, auth, http)),
+            AuthOrError::LFAPIError(error) => LfClientOrError::LFAPIError(error),
+        })
+    }
+
+    pub fn api_server(&self) -> &LFApiServer {
+        &self.api_server
+    }
+
+    /// The stored token, without checking its expiry
+    pub fn auth(&self) -> Auth {
+        self.auth.current()
+    }
+
+    /// The token shared by this client and its clones
+    pub fn shared_auth(&self) -> &SharedAuth {
+        &self.auth
+    }
+
+    /// Replace the stored token, e.g. after an external refresh
+    pub fn set_auth(&self, auth: Auth) {
+        self.auth.replace(auth);
+    }
+
+    pub fn http(&self) -> &reqwest::blocking::Client {
+        &self.http
+    }
+
+    /// Request a new token and store it on success
+    pub fn refresh(&self) -> Result<AuthOrError> {
+        self.scoped(|| self.auth.refresh_blocking())
+    }
+
+    /// Get an entry by id
+    pub fn get_entry(&self, entry_id: impl Into<EntryId>) -> Result<EntryOrError> {
+        self.authed(|api_server, auth| Entry::get_blocking(api_server, auth, entry_id))
+    }
+
+    /// List the children of a folder
+    pub fn list_entries(&self, folder_id: impl Into<FolderId>) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list_blocking(api_server, auth, folder_id))
+    }
+
+    /// List the children of a folder with OData and field options
+    pub fn list_entries_with_options(
+        &self,
+        folder_id: impl Into<FolderId>,
+        options: &ListOptions
+    ) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list_with_options_blocking(api_server, auth, folder_id, options))
+    }
+
+    /// Ids of a folder's children named `names`
+    pub fn find_children_by_names(
+        &self,
+        folder_id: impl Into<FolderId>,
+        names: &[&str]
+    ) -> Result<BTreeMap<String, EntryId>> {
+        self.authed(|api_server, auth| Entry::find_children_by_names_blocking(api_server, auth, folder_id, names))
+    }
+
+    /// Every child of a folder, following next links as the iterator is read
+    pub fn list_all(&self, folder_id: impl Into<FolderId>) -> impl Iterator<Item = Result<Entry>> + '_ {
+        let folder_id = folder_id.into();
+        blocking::paginate_blocking(move |next_link| match next_link {
+            None => self.list_entries(folder_id),
+            Some(next_link) => self.list_next(next_link),
+        })
+    }
+
+    /// Fetch the next page of a list or search
+    pub fn list_next(&self, next_link: NextLink) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::list_custom_blocking(api_server, auth, next_link))
+    }
+
+    /// Search for entries
+    pub fn search(&self, search_query: String, options: SearchOptions) -> Result<EntriesOrError> {
+        self.authed(|api_server, auth| Entry::search_blocking(api_server, auth, search_query, options))
+    }
+
+    /// Import a local file into a folder
+    pub fn import(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import_blocking(api_server, auth, file_path.clone(), file_name.clone(), folder_id)
+        })
+    }
+
+    /// Import a local file with its template, field values, and tags
+    pub fn import_with_request(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        request: &ImportRequest
+    ) -> Result<ImportResultOrError> {
+        let folder_id = folder_id.into();
+        self.authed_retrying(|api_server, auth| {
+            Entry::import_with_request_blocking(api_server, auth, file_path.clone(), file_name.clone(), folder_id, request)
+        })
+    }
+
+    /// Get the entry an import created, from its `document_link`
+    ///
+    /// Fails with `validation::ErrorKind::UntrustedUrl` if the link is not
+    /// on this client's server and repository.
+    pub fn fetch_document_link(&self, import_result: &ImportResult) -> Result<EntryOrError> {
+        let entry_id = import_result.linked_entry_id(&self.api_server)?;
+        self.get_entry(entry_id)
+    }
+
+    /// Download a document's content into memory without writing a file
+    pub fn export_bytes(&self, document_id: impl Into<DocumentId>) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_bytes_blocking(api_server, auth, document_id))
+    }
+
+    /// Download a document's content to `file_path`
+    pub fn export(&self, document_id: impl Into<DocumentId>, file_path: &str) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_blocking(api_server, auth, document_id, file_path))
+    }
+
+    /// Download a document's content to `file_path`, replacing an existing
+    /// file only if `options` allow it
+    pub fn export_with_options(
+        &self,
+        document_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<BitsOrError> {
+        self.authed(|api_server, auth| Entry::export_with_options_blocking(api_server, auth, document_id, file_path, options))
+    }
+
+    /// Delete an entry
+    pub fn delete_entry(&self, entry_id: impl Into<EntryId>, comment: String) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::delete_blocking(api_server, auth, entry_id, comment))
+    }
+
+    /// Delete an entry citing an audit reason
+    pub fn delete_entry_with_options(&self, entry_id: impl Into<EntryId>, options: &DeleteOptions) -> Result<LFObject> {
+        self.authed(|api_server, auth| Entry::delete_with_options_blocking(api_server, auth, entry_id, options))
+    }
+
+    /// Audit reasons configured in the repository
+    pub fn audit_reasons(&self) -> Result<AuditReasonsOrError> {
+        self.authed(AuditReasons::list_blocking)
+    }
+
+    /// Get an entry's field values
+    pub fn get_metadata(&self, entry_id: impl Into<EntryId>) -> Result<MetadataResultOrError> {
+        self.authed(|api_server, auth| Entry::get_metadata_blocking(api_server, auth, entry_id))
+    }
+
+    /// Replace an entry's field values
+    pub fn update_metadata(
+        &self,
+        entry_id: impl Into<EntryId>,
+        metadata: serde_json::Value
+    ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into();
+        self.authed_retrying(|api_server, auth| Entry::update_metadata_blocking(api_server, auth, entry_id, metadata.clone()))
+    }
+
+    /// Run `call` with an unexpired token, scoped to this client's HTTP client
+    fn authed<T>(&self, call: impl FnOnce(LFApiServer, Auth) -> Result<T>) -> Result<T> {
+        self.scoped(|| {
+            let auth = self.auth.token_blocking()?;
+            call(self.api_server.clone(), auth)
+        })
+    }
+
+    /// `authed`, repeated while the entry is locked if lock retry is enabled
+    fn authed_retrying<T: ApiResponse>(&self, call: impl Fn(LFApiServer, Auth) -> Result<T>) -> Result<T> {
+        match &self.lock_retry {
+            Some(config) => retry::retry_while_locked_blocking(config, || self.authed(&call)),
+            None => self.authed(call),
+        }
+    }
+
+    /// Run `f` with this client's HTTP client, API version, upload limit,
+    /// and retry policy serving its requests
+    fn scoped<T>(&self, f: impl FnOnce() -> T) -> T {
+        let f = || server_info::with_api_version_blocking(self.api_version, f);
+        let f = || match self.max_file_size {
+            Some(limit) => validation::with_max_file_size_blocking(limit, f),
+            None => f(),
+        };
+        let f = || match &self.slow_request_log {
+            Some(log) => slow_requests::with_slow_request_log_blocking(log, || blocking::with_http_client(&self.http, f)),
+            None => blocking::with_http_client(&self.http, f),
+        };
+        match &self.retry_policy {
+            Some(policy) => retry::with_retry_policy_blocking(policy, f),
+            None => f(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_client() -> LfClient {
+        let api_server = LFApiServer {
+            address: "test.laserfiche.com".to_string(),
+            repository: "test-repo".to_string(),
+        };
+        let auth = Auth {
+            access_token: "token".to_string(),
+            expires_in: 3600,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+            api_server: api_server.clone(),
+            ..Default::default()
+        };
+        LfClient::new(api_server, auth)
+    }
+
+    #[test]
+    fn test_blocking_client_validates_before_sending() {
+        let client = mock_client();
+        assert_eq!(client.api_server().address, "test.laserfiche.com");
+        assert!(client.get_entry(-1).is_err());
+        assert!(client.get_metadata(0).is_err());
+        assert!(client.export_bytes(-1).is_err());
+    }
+}
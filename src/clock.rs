@@ -0,0 +1,136 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Monotonic time for deadlines and request timings; `std`'s panics under
+/// wasm32, where this one reads the browser's `performance.now()`
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use instant::Instant;
+
+/// Source of the wall-clock time used for token expiry
+///
+/// Clients read the time through a `Clock` so expiry and refresh can be
+/// tested with a `MockClock` instead of waiting on the system clock.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// The system clock; the default for every client
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        system_now()
+    }
+}
+
+/// The current wall-clock time
+///
+/// `SystemTime::now` panics under wasm32, so there the time comes from the
+/// browser's `Date.now()`.
+pub(crate) fn system_now() -> SystemTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        SystemTime::now()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        UNIX_EPOCH + Duration::from_secs_f64(js_sys::Date::now() / 1000.0)
+    }
+}
+
+/// Wait for `duration` on tokio's timer, or under wasm32, where there is
+/// no tokio runtime, on the browser's `setTimeout`
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    {
+        use wasm_bindgen::{JsCast, JsValue};
+
+        let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+        let timer = js_sys::Promise::new(&mut |resolve, _reject| {
+            let set_timeout = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("setTimeout"))
+                .ok()
+                .and_then(|function| function.dyn_into::<js_sys::Function>().ok());
+            match set_timeout {
+                Some(set_timeout) => {
+                    let _ = set_timeout.call2(&JsValue::UNDEFINED, &resolve, &JsValue::from(millis));
+                }
+                None => {
+                    let _ = resolve.call0(&JsValue::UNDEFINED);
+                }
+            }
+        });
+        let _ = wasm_bindgen_futures::JsFuture::from(timer).await;
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// Clones share one time, so a test can keep a handle and advance the
+/// clock a client was given.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<SystemTime>>,
+}
+
+impl MockClock {
+    pub fn new(now: SystemTime) -> Self {
+        MockClock { now: Arc::new(Mutex::new(now)) }
+    }
+
+    /// A clock reading `secs` seconds after the Unix epoch
+    pub fn at_unix(secs: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = now;
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        *now += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Seconds since the Unix epoch, clamped to `0..=i64::MAX`
+pub(crate) fn unix_seconds(at: SystemTime) -> i64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        .try_into()
+        .unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::at_unix(1_700_000_000);
+        let handle = clock.clone();
+        assert_eq!(unix_seconds(clock.now()), 1_700_000_000);
+
+        handle.advance(Duration::from_secs(90));
+        assert_eq!(unix_seconds(clock.now()), 1_700_000_090);
+
+        handle.set(UNIX_EPOCH);
+        assert_eq!(unix_seconds(clock.now()), 0);
+        assert_eq!(unix_seconds(UNIX_EPOCH - Duration::from_secs(1)), 0);
+    }
+}
@@ -0,0 +1,307 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+use crate::laserfiche::blocking;
+use crate::laserfiche::{self, Auth, AuthOrError, LFAPIError, LFApiServer, Result};
+use crate::retry::RetryingSend;
+use crate::validation;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE, URL_SAFE_NO_PAD};
+use base64::Engine;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::{Signature, SigningKey};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::UNIX_EPOCH;
+
+/// Audience claim expected by the Laserfiche Cloud token endpoint
+const TOKEN_AUDIENCE: &str = "laserfiche.com";
+
+/// Lifetime of the signed client assertion, in seconds
+const ASSERTION_LIFETIME: i64 = 3600;
+
+/// EC P-256 private key from an access key, in JWK form
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    pub d: String,
+    pub x: String,
+    pub y: String,
+    pub kid: String,
+}
+
+/// Decoded Laserfiche Cloud access key, as downloaded from the Developer
+/// Console for a service app
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessKey {
+    pub customer_id: String,
+    pub client_id: String,
+    /// Cloud region, e.g. `laserfiche.com` or `laserfiche.ca`
+    pub domain: String,
+    pub jwk: Jwk,
+}
+
+impl AccessKey {
+    /// Decode the base64 access key string from the Developer Console
+    pub fn from_base64(encoded: &str) -> Result<Self> {
+        let encoded: String = encoded.chars().filter(|c| !c.is_whitespace()).collect();
+        let decoded = STANDARD.decode(&encoded)
+            .or_else(|_| URL_SAFE.decode(&encoded))
+            .map_err(|e| format!("Invalid access key: not base64: {}", e))?;
+        serde_json::from_slice(&decoded)
+            .map_err(|e| format!("Invalid access key: {}", e).into())
+    }
+}
+
+impl std::fmt::Debug for AccessKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessKey")
+            .field("customer_id", &self.customer_id)
+            .field("client_id", &self.client_id)
+            .field("domain", &self.domain)
+            .field("jwk", &"[redacted]")
+            .finish()
+    }
+}
+
+/// Service principal key and access key for the client-credentials flow
+#[derive(Clone, PartialEq, Eq)]
+pub struct CloudCredentials {
+    pub service_principal_key: String,
+    pub access_key: AccessKey,
+    /// Space-separated OAuth scopes, e.g. `repository.Read repository.Write`
+    pub scope: Option<String>,
+}
+
+impl std::fmt::Debug for CloudCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CloudCredentials")
+            .field("service_principal_key", &"[redacted]")
+            .field("access_key", &self.access_key)
+            .field("scope", &self.scope)
+            .finish()
+    }
+}
+
+impl CloudCredentials {
+    pub fn new(service_principal_key: String, access_key: AccessKey) -> Self {
+        CloudCredentials { service_principal_key, access_key, scope: None }
+    }
+
+    pub fn with_scope(mut self, scope: impl Into<String>) -> Self {
+        self.scope = Some(scope.into());
+        self
+    }
+
+    /// Repository API server for `repository_id` in this key's region
+    pub fn api_server(&self, repository_id: &str) -> LFApiServer {
+        LFApiServer::cloud(&self.access_key.domain, repository_id)
+    }
+
+    /// `https://signin.{domain}/oauth/Token`
+    fn token_url(&self) -> validation::Result<String> {
+        let domain = validation::validate_server_address(&self.access_key.domain)?;
+        Ok(format!("{}://signin.{}/oauth/Token", validation::API_SCHEME, domain))
+    }
+
+    fn token_params(&self) -> Vec<(&'static str, &str)> {
+        let mut params = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            params.push(("scope", scope.as_str()));
+        }
+        params
+    }
+
+    /// ES256-signed JWT asserting the service principal, issued at `now`
+    /// (seconds since the Unix epoch)
+    pub fn client_assertion(&self, now: i64) -> Result<String> {
+        let jwk = &self.access_key.jwk;
+        if jwk.kty != "EC" || jwk.crv != "P-256" {
+            return Err(format!("Unsupported access key: expected an EC P-256 key, got {} {}", jwk.kty, jwk.crv).into());
+        }
+        let secret = URL_SAFE_NO_PAD.decode(jwk.d.trim_end_matches('='))
+            .map_err(|e| format!("Invalid access key: bad private key encoding: {}", e))?;
+        let key = SigningKey::from_slice(&secret)
+            .map_err(|e| format!("Invalid access key: bad private key: {}", e))?;
+
+        let header = json!({ "alg": "ES256", "typ": "JWT", "kid": jwk.kid });
+        let claims = json!({
+            "client_id": self.access_key.client_id,
+            "client_secret": self.service_principal_key,
+            "aud": TOKEN_AUDIENCE,
+            "iat": now,
+            "nbf": now,
+            "exp": now + ASSERTION_LIFETIME,
+        });
+        let signing_input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+        let signature: Signature = key.sign(signing_input.as_bytes());
+
+        Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature.to_bytes())))
+    }
+
+    /// Fill in the fields the token response lacks
+    fn complete(&self, mut auth: Auth, repository_id: &str) -> Auth {
+        auth.api_server = self.api_server(repository_id);
+        auth.timestamp = unix_now();
+        auth.cloud_credentials = Some(self.clone());
+        auth
+    }
+}
+
+fn unix_now() -> i64 {
+    clock::system_now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+impl Auth {
+    /// Authenticate to Laserfiche Cloud with the OAuth client-credentials flow
+    ///
+    /// The returned token's `api_server` targets `repository_id` on
+    /// `api.{domain}`, and `refresh` repeats this exchange.
+    ///
+    /// # Arguments
+    /// * `credentials` - Service principal key and access key
+    /// * `repository_id` - Cloud repository id, e.g. `r-0001a2b3`
+    pub async fn new_cloud(credentials: CloudCredentials, repository_id: String) -> Result<AuthOrError> {
+        let repository_id = validation::validate_repository_name(&repository_id)?;
+        let token_url = credentials.token_url()?;
+        let assertion = credentials.client_assertion(unix_now())?;
+
+        let response = laserfiche::http_client()
+            .post(token_url)
+            .bearer_auth(assertion)
+            .form(&credentials.token_params())
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(AuthOrError::LFAPIError(error));
+        }
+
+        let auth = response.json::<Auth>().await?;
+        Ok(AuthOrError::Auth(credentials.complete(auth, &repository_id)))
+    }
+
+    /// Blocking version of `new_cloud`
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    pub fn new_cloud_blocking(credentials: CloudCredentials, repository_id: String) -> blocking::Result<AuthOrError> {
+        let repository_id = validation::validate_repository_name(&repository_id)?;
+        let token_url = credentials.token_url()?;
+        let assertion = credentials.client_assertion(unix_now())
+            .map_err(|e| e.to_string())?;
+
+        let response = blocking::http_client()
+            .post(token_url)
+            .bearer_auth(assertion)
+            .form(&credentials.token_params())
+            .send_retrying()?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>()?;
+            return Ok(AuthOrError::LFAPIError(error));
+        }
+
+        let auth = response.json::<Auth>()?;
+        Ok(AuthOrError::Auth(credentials.complete(auth, &repository_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::VerifyingKey;
+
+    /// A throwaway P-256 key; the private scalar is 1..=32
+    fn sample_credentials() -> CloudCredentials {
+        let secret: Vec<u8> = (1..=32).collect();
+        let key = SigningKey::from_slice(&secret).unwrap();
+        let point = key.verifying_key().to_encoded_point(false);
+
+        let access_key = AccessKey {
+            customer_id: "1234567890".to_string(),
+            client_id: "client-abc".to_string(),
+            domain: "laserfiche.com".to_string(),
+            jwk: Jwk {
+                kty: "EC".to_string(),
+                crv: "P-256".to_string(),
+                d: URL_SAFE_NO_PAD.encode(&secret),
+                x: URL_SAFE_NO_PAD.encode(point.x().unwrap()),
+                y: URL_SAFE_NO_PAD.encode(point.y().unwrap()),
+                kid: "key-1".to_string(),
+            },
+        };
+        CloudCredentials::new("sp-key".to_string(), access_key)
+    }
+
+    #[test]
+    fn test_access_key_from_base64() {
+        let credentials = sample_credentials();
+        let encoded = STANDARD.encode(serde_json::to_vec(&credentials.access_key).unwrap());
+
+        assert_eq!(AccessKey::from_base64(&encoded).unwrap(), credentials.access_key);
+        assert!(AccessKey::from_base64("not base64!").is_err());
+        assert!(AccessKey::from_base64(&STANDARD.encode("{}")).is_err());
+    }
+
+    #[test]
+    fn test_client_assertion_is_signed_es256() {
+        let credentials = sample_credentials();
+        let jwt = credentials.client_assertion(1_700_000_000).unwrap();
+        let parts: Vec<&str> = jwt.split('.').collect();
+        assert_eq!(parts.len(), 3);
+
+        let header: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "ES256");
+        assert_eq!(header["kid"], "key-1");
+
+        let claims: serde_json::Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[1]).unwrap()).unwrap();
+        assert_eq!(claims["client_id"], "client-abc");
+        assert_eq!(claims["client_secret"], "sp-key");
+        assert_eq!(claims["aud"], "laserfiche.com");
+        assert_eq!(claims["exp"], 1_700_000_000 + ASSERTION_LIFETIME);
+
+        let secret: Vec<u8> = (1..=32).collect();
+        let verifying_key = VerifyingKey::from(&SigningKey::from_slice(&secret).unwrap());
+        let signature = Signature::from_slice(&URL_SAFE_NO_PAD.decode(parts[2]).unwrap()).unwrap();
+        let signing_input = format!("{}.{}", parts[0], parts[1]);
+        assert!(verifying_key.verify(signing_input.as_bytes(), &signature).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_non_p256_keys() {
+        let mut credentials = sample_credentials();
+        credentials.access_key.jwk.crv = "P-384".to_string();
+        assert!(credentials.client_assertion(0).is_err());
+    }
+
+    #[test]
+    fn test_cloud_urls() {
+        let credentials = sample_credentials().with_scope("repository.Read");
+        assert_eq!(credentials.token_url().unwrap(), "https://signin.laserfiche.com/oauth/Token");
+        assert_eq!(credentials.token_params(), vec![("grant_type", "client_credentials"), ("scope", "repository.Read")]);
+        assert_eq!(
+            credentials.api_server("r-0001a2b3").base_url(),
+            "https://api.laserfiche.com/repository/v1/Repositories/r-0001a2b3"
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let debug = format!("{:?}", sample_credentials());
+        assert!(!debug.contains("sp-key"));
+        assert!(debug.contains("client-abc"));
+    }
+}
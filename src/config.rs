@@ -1,5 +1,6 @@
 use std::env;
 use std::fmt;
+use std::time::Duration;
 
 #[derive(Debug)]
 pub enum ConfigError {
@@ -90,6 +91,267 @@ impl Config {
     }
 }
 
+/// Concurrency limits shared by bulk pipelines, tree transfers, and fan-out helpers
+///
+/// All knobs live here so callers configure parallelism once instead of
+/// passing separate limits to each operation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyConfig {
+    /// Maximum number of requests in flight across the whole operation
+    pub max_in_flight: usize,
+    /// Maximum number of requests in flight against a single host
+    pub per_host_limit: usize,
+    /// Number of pending work items buffered ahead of the workers
+    pub queue_depth: usize,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        ConcurrencyConfig {
+            max_in_flight: 8,
+            per_host_limit: 8,
+            queue_depth: 64,
+        }
+    }
+}
+
+impl ConcurrencyConfig {
+    /// Load overrides from `LF_MAX_IN_FLIGHT`, `LF_PER_HOST_LIMIT` and
+    /// `LF_QUEUE_DEPTH`, falling back to the defaults for unset variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let config = ConcurrencyConfig {
+            max_in_flight: Self::env_usize("LF_MAX_IN_FLIGHT", defaults.max_in_flight)?,
+            per_host_limit: Self::env_usize("LF_PER_HOST_LIMIT", defaults.per_host_limit)?,
+            queue_depth: Self::env_usize("LF_QUEUE_DEPTH", defaults.queue_depth)?,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Reject limits that would stall a pipeline
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_in_flight == 0 {
+            return Err(ConfigError::InvalidValue("max_in_flight must be at least 1".to_string()));
+        }
+        if self.per_host_limit == 0 {
+            return Err(ConfigError::InvalidValue("per_host_limit must be at least 1".to_string()));
+        }
+        if self.queue_depth == 0 {
+            return Err(ConfigError::InvalidValue("queue_depth must be at least 1".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Number of concurrent requests a single-server operation may issue
+    pub fn effective_limit(&self) -> usize {
+        self.max_in_flight.min(self.per_host_limit).max(1)
+    }
+
+    fn env_usize(var_name: &str, default: usize) -> Result<usize, ConfigError> {
+        match env::var(var_name) {
+            Ok(value) => value.trim().parse::<usize>().map_err(|_| {
+                ConfigError::InvalidValue(format!("{} must be a positive integer, got '{}'", var_name, value))
+            }),
+            Err(_) => Ok(default),
+        }
+    }
+}
+
+/// Bounds and tuning for adaptive (AIMD) request throttling
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThrottleConfig {
+    /// Requests per second to start at
+    pub initial_rate: f64,
+    /// Floor the rate never drops below
+    pub min_rate: f64,
+    /// Ceiling the rate never grows past
+    pub max_rate: f64,
+    /// Requests per second regained for each second of successful traffic
+    pub additive_increase: f64,
+    /// Factor applied to the rate on a 429 or 503, between 0 and 1
+    pub multiplicative_decrease: f64,
+    /// Minimum time between two decreases, so one burst of throttled
+    /// responses only halves the rate once
+    pub decrease_cooldown: Duration,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        ThrottleConfig {
+            initial_rate: 20.0,
+            min_rate: 0.5,
+            max_rate: 100.0,
+            additive_increase: 1.0,
+            multiplicative_decrease: 0.5,
+            decrease_cooldown: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ThrottleConfig {
+    /// Reject rates and factors that would stop or never slow a throttle
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !(self.min_rate > 0.0 && self.min_rate.is_finite()) {
+            return Err(ConfigError::InvalidValue("min_rate must be positive".to_string()));
+        }
+        if !(self.max_rate >= self.min_rate && self.max_rate.is_finite()) {
+            return Err(ConfigError::InvalidValue("max_rate must be at least min_rate".to_string()));
+        }
+        if !(self.min_rate..=self.max_rate).contains(&self.initial_rate) {
+            return Err(ConfigError::InvalidValue("initial_rate must be between min_rate and max_rate".to_string()));
+        }
+        if !(self.additive_increase >= 0.0 && self.additive_increase.is_finite()) {
+            return Err(ConfigError::InvalidValue("additive_increase must not be negative".to_string()));
+        }
+        if !(self.multiplicative_decrease > 0.0 && self.multiplicative_decrease < 1.0) {
+            return Err(ConfigError::InvalidValue("multiplicative_decrease must be between 0 and 1".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// How long to keep retrying writes rejected because the entry is locked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockRetryConfig {
+    /// Total time to keep retrying before returning the locked response
+    pub window: Duration,
+    /// Wait before the first retry; doubles after each attempt
+    pub initial_backoff: Duration,
+    /// Cap on the wait between retries
+    pub max_backoff: Duration,
+    /// Server `errorCode`s treated as locked in addition to the built-in checks
+    pub error_codes: Vec<i64>,
+}
+
+impl Default for LockRetryConfig {
+    fn default() -> Self {
+        LockRetryConfig {
+            window: Duration::from_secs(30),
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(5),
+            error_codes: Vec::new(),
+        }
+    }
+}
+
+impl LockRetryConfig {
+    /// Reject backoffs that would spin without waiting
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.initial_backoff.is_zero() {
+            return Err(ConfigError::InvalidValue("initial_backoff must be positive".to_string()));
+        }
+        if self.max_backoff < self.initial_backoff {
+            return Err(ConfigError::InvalidValue("max_backoff must be at least initial_backoff".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Retrying of rate-limited and gateway-failed requests
+///
+/// Applied to every request made through a client configured with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total tries per request, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubles after each attempt
+    pub base_delay: Duration,
+    /// Cap on any wait, including one asked for by `Retry-After`
+    pub max_delay: Duration,
+    /// Wait a random 50-100% of each backoff so clients spread out
+    pub jitter: bool,
+    /// Wait as long as the server's `Retry-After` header asks, when present
+    pub honor_retry_after: bool,
+    /// Response statuses that are retried
+    pub statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            honor_retry_after: true,
+            statuses: vec![429, 502, 503, 504],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Reject policies that never send or would spin without waiting
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_attempts == 0 {
+            return Err(ConfigError::InvalidValue("max_attempts must be at least 1".to_string()));
+        }
+        if self.base_delay.is_zero() {
+            return Err(ConfigError::InvalidValue("base_delay must be positive".to_string()));
+        }
+        if self.max_delay < self.base_delay {
+            return Err(ConfigError::InvalidValue("max_delay must be at least base_delay".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// How a client follows HTTP redirects, e.g. from a load balancer in
+/// front of the API
+///
+/// reqwest drops the `Authorization` header when a redirect leaves the
+/// original host, so a cross-host redirect reaches its target
+/// unauthenticated. `SameHost` follows only redirects that keep the token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectPolicy {
+    /// Return redirect responses as they are
+    Never,
+    /// Follow up to `max` redirects that stay on the original host and
+    /// scheme, keeping the `Authorization` header; stop at any other
+    SameHost { max: usize },
+    /// Follow up to `max` redirects anywhere; the `Authorization` header is
+    /// dropped once a redirect leaves the original host
+    Limited { max: usize },
+}
+
+impl Default for RedirectPolicy {
+    /// reqwest's own default: up to 10 redirects anywhere
+    fn default() -> Self {
+        RedirectPolicy::Limited { max: 10 }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RedirectPolicy {
+    pub(crate) fn to_reqwest(self) -> reqwest::redirect::Policy {
+        match self {
+            RedirectPolicy::Never => reqwest::redirect::Policy::none(),
+            RedirectPolicy::Limited { max } => reqwest::redirect::Policy::limited(max),
+            RedirectPolicy::SameHost { max } => reqwest::redirect::Policy::custom(move |attempt| {
+                if Self::follows_same_host(attempt.previous(), attempt.url(), max) {
+                    attempt.follow()
+                } else {
+                    attempt.stop()
+                }
+            }),
+        }
+    }
+
+    /// Whether a `SameHost` policy follows a redirect to `next`, given the
+    /// URLs requested so far, the original first
+    pub(crate) fn follows_same_host(previous: &[url::Url], next: &url::Url, max: usize) -> bool {
+        let Some(original) = previous.first() else {
+            return false;
+        };
+        previous.len() <= max
+            && next.scheme() == original.scheme()
+            && next.host_str() == original.host_str()
+            && next.port_or_known_default() == original.port_or_known_default()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,4 +467,102 @@ mod tests {
         
         clear_env_vars();
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_concurrency_config_defaults() {
+        let config = ConcurrencyConfig::default();
+        assert!(config.validate().is_ok());
+        assert_eq!(config.effective_limit(), 8);
+    }
+    
+    #[test]
+    fn test_concurrency_config_validation() {
+        let config = ConcurrencyConfig { max_in_flight: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+        
+        let config = ConcurrencyConfig { per_host_limit: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+        
+        let config = ConcurrencyConfig { queue_depth: 0, ..Default::default() };
+        assert!(config.validate().is_err());
+        
+        let config = ConcurrencyConfig { max_in_flight: 16, per_host_limit: 4, queue_depth: 1 };
+        assert!(config.validate().is_ok());
+        assert_eq!(config.effective_limit(), 4);
+    }
+    
+    #[test]
+    fn test_concurrency_config_from_env() {
+        env::remove_var("LF_MAX_IN_FLIGHT");
+        env::remove_var("LF_PER_HOST_LIMIT");
+        env::remove_var("LF_QUEUE_DEPTH");
+        assert_eq!(ConcurrencyConfig::from_env().unwrap(), ConcurrencyConfig::default());
+        
+        env::set_var("LF_MAX_IN_FLIGHT", "32");
+        env::set_var("LF_QUEUE_DEPTH", "256");
+        let config = ConcurrencyConfig::from_env().unwrap();
+        assert_eq!(config.max_in_flight, 32);
+        assert_eq!(config.per_host_limit, 8);
+        assert_eq!(config.queue_depth, 256);
+        
+        env::set_var("LF_PER_HOST_LIMIT", "many");
+        assert!(ConcurrencyConfig::from_env().is_err());
+        
+        env::set_var("LF_PER_HOST_LIMIT", "0");
+        assert!(ConcurrencyConfig::from_env().is_err());
+        
+        env::remove_var("LF_MAX_IN_FLIGHT");
+        env::remove_var("LF_PER_HOST_LIMIT");
+        env::remove_var("LF_QUEUE_DEPTH");
+    }
+
+    #[test]
+    fn test_throttle_config_validation() {
+        assert!(ThrottleConfig::default().validate().is_ok());
+
+        let config = ThrottleConfig { min_rate: 0.0, ..Default::default() };
+        assert!(config.validate().is_err());
+
+        let config = ThrottleConfig { max_rate: 0.1, ..Default::default() };
+        assert!(config.validate().is_err());
+
+        let config = ThrottleConfig { initial_rate: 500.0, ..Default::default() };
+        assert!(config.validate().is_err());
+
+        let config = ThrottleConfig { multiplicative_decrease: 1.0, ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_lock_retry_config_validation() {
+        assert!(LockRetryConfig::default().validate().is_ok());
+
+        let config = LockRetryConfig { initial_backoff: Duration::ZERO, ..Default::default() };
+        assert!(config.validate().is_err());
+
+        let config = LockRetryConfig { max_backoff: Duration::from_millis(1), ..Default::default() };
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_retry_policy_validation() {
+        assert!(RetryPolicy::default().validate().is_ok());
+        assert!(RetryPolicy { max_attempts: 0, ..Default::default() }.validate().is_err());
+        assert!(RetryPolicy { base_delay: Duration::ZERO, ..Default::default() }.validate().is_err());
+        assert!(RetryPolicy { max_delay: Duration::from_millis(1), ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_redirect_policy_same_host() {
+        let url = |s: &str| url::Url::parse(s).unwrap();
+        let original = [url("https://lf.example.com/LFRepositoryAPI/v1/Repositories/r/Entries/1")];
+
+        assert!(RedirectPolicy::follows_same_host(&original, &url("https://lf.example.com/node2/Entries/1"), 3));
+        assert!(!RedirectPolicy::follows_same_host(&original, &url("https://other.example.com/Entries/1"), 3));
+        assert!(!RedirectPolicy::follows_same_host(&original, &url("http://lf.example.com/Entries/1"), 3));
+        assert!(!RedirectPolicy::follows_same_host(&original, &url("https://lf.example.com:8443/Entries/1"), 3));
+
+        let hops = vec![original[0].clone(), url("https://lf.example.com/a"), url("https://lf.example.com/b")];
+        assert!(!RedirectPolicy::follows_same_host(&hops, &url("https://lf.example.com/c"), 2));
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Restricting which hosts and addresses a client may connect to
+//!
+//! An `EgressPolicy` given to `LfClientBuilder::egress_policy` is enforced
+//! at three points: the API server is checked when the client is built,
+//! every redirect is checked before it is followed, and, for async clients,
+//! every host name is checked and its addresses filtered when it is
+//! resolved, so no request the client sends (next links, token refreshes,
+//! Cloud sign-in) can leave the allow-list. reqwest's blocking client takes
+//! no custom resolver, so blocking clients check host names and redirects
+//! but do not filter resolved addresses. Under wasm32 the browser follows
+//! redirects and resolves names itself, so only the API server is checked.
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::config::RedirectPolicy;
+use crate::validation;
+
+#[cfg(not(target_arch = "wasm32"))]
+use hyper::client::connect::dns::Name;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::IpAddr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::SocketAddr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+
+/// A block of IP addresses, e.g. `10.20.0.0/16`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct IpNetwork {
+    address: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.address, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let shift = 32 - u32::from(self.prefix_len.min(32));
+                u32::from(network).checked_shr(shift).unwrap_or(0) == u32::from(ip).checked_shr(shift).unwrap_or(0)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let shift = 128 - u32::from(self.prefix_len.min(128));
+                u128::from(network).checked_shr(shift).unwrap_or(0) == u128::from(ip).checked_shr(shift).unwrap_or(0)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The hosts and addresses a client is allowed to connect to
+///
+/// A URL is allowed when its host is on the host list, or is an IP address
+/// inside an allowed network. When any network is allowed, host names must
+/// also resolve into one; addresses outside every network are dropped
+/// before connecting. A new policy allows nothing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EgressPolicy {
+    hosts: Vec<String>,
+    networks: Vec<IpNetwork>,
+}
+
+impl EgressPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow `host`, e.g. `lf.example.com`, or every subdomain of a domain
+    /// with `*.example.com`
+    pub fn allow_host(mut self, host: impl Into<String>) -> Self {
+        self.hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Allow a single IP address
+    pub fn allow_ip(self, ip: IpAddr) -> Self {
+        let prefix_len = if ip.is_ipv4() { 32 } else { 128 };
+        self.allow_network(ip, prefix_len)
+    }
+
+    /// Allow every address whose first `prefix_len` bits match `address`,
+    /// e.g. `allow_network(Ipv4Addr::new(10, 20, 0, 0).into(), 16)`
+    pub fn allow_network(mut self, address: IpAddr, prefix_len: u8) -> Self {
+        self.networks.push(IpNetwork { address: address.to_canonical(), prefix_len });
+        self
+    }
+
+    /// Whether a connection to `host`, a name or an IP address, is allowed
+    pub fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return self.networks.iter().any(|network| network.contains(ip));
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+            Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.') && sub.len() > 1),
+            None => *allowed == host,
+        })
+    }
+
+    /// Whether `ip`, resolved from an allowed host name, may be connected to
+    pub fn allows_resolved_ip(&self, ip: IpAddr) -> bool {
+        self.networks.is_empty() || self.networks.iter().any(|network| network.contains(ip))
+    }
+
+    /// Fail with `ErrorKind::EgressDenied` unless `url`'s host is allowed
+    ///
+    /// Use this before fetching URLs the API hands back for other hosts,
+    /// such as `ImportResult::document_link`.
+    pub fn check(&self, url: &str) -> validation::Result<()> {
+        let allowed = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(|host| self.allows_host(host)))
+            .unwrap_or(false);
+        if !allowed {
+            return Err(validation::ErrorKind::EgressDenied(url.to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// `redirect` as a reqwest policy that also refuses redirects leaving
+    /// the allow-list
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn redirect_policy(&self, redirect: RedirectPolicy) -> reqwest::redirect::Policy {
+        let policy = self.clone();
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if !attempt.url().host_str().is_some_and(|host| policy.allows_host(host)) {
+                let error = format!("Egress policy does not allow redirect to {}", attempt.url());
+                return attempt.error(error);
+            }
+            match redirect {
+                RedirectPolicy::Never => attempt.stop(),
+                RedirectPolicy::Limited { max } if attempt.previous().len() > max => attempt.error("too many redirects"),
+                RedirectPolicy::Limited { .. } => attempt.follow(),
+                RedirectPolicy::SameHost { max } => {
+                    if RedirectPolicy::follows_same_host(attempt.previous(), attempt.url(), max) {
+                        attempt.follow()
+                    } else {
+                        attempt.stop()
+                    }
+                }
+            }
+        })
+    }
+
+    /// A resolver that refuses disallowed host names and drops disallowed
+    /// addresses
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn resolver(&self) -> Arc<EgressResolver> {
+        Arc::new(EgressResolver { policy: self.clone() })
+    }
+}
+
+/// DNS resolver behind async clients with an `EgressPolicy`
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct EgressResolver {
+    policy: EgressPolicy,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Resolve for EgressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            if !policy.allows_host(&host) {
+                return Err(format!("Egress policy does not allow {}", host).into());
+            }
+            let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| policy.allows_resolved_ip(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("Egress policy allows no address of {}", host).into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn policy() -> EgressPolicy {
+        EgressPolicy::new()
+            .allow_host("lf.example.com")
+            .allow_host("*.laserfiche.com")
+            .allow_network(Ipv4Addr::new(10, 20, 0, 0).into(), 16)
+            .allow_ip(Ipv6Addr::LOCALHOST.into())
+    }
+
+    #[test]
+    fn test_allows_host() {
+        let policy = policy();
+        assert!(policy.allows_host("LF.example.com"));
+        assert!(policy.allows_host("api.laserfiche.com"));
+        assert!(policy.allows_host("signin.laserfiche.com."));
+        assert!(!policy.allows_host("laserfiche.com"));
+        assert!(!policy.allows_host("evillaserfiche.com"));
+        assert!(!policy.allows_host("lf.example.com.evil.net"));
+        assert!(policy.allows_host("10.20.3.4"));
+        assert!(!policy.allows_host("10.21.0.1"));
+        assert!(policy.allows_host("[::1]"));
+        assert!(!EgressPolicy::new().allows_host("lf.example.com"));
+    }
+
+    #[test]
+    fn test_allows_resolved_ip() {
+        assert!(EgressPolicy::new().allow_host("lf.example.com").allows_resolved_ip(Ipv4Addr::new(8, 8, 8, 8).into()));
+
+        let policy = policy();
+        assert!(policy.allows_resolved_ip(Ipv4Addr::new(10, 20, 255, 1).into()));
+        assert!(policy.allows_resolved_ip(Ipv4Addr::new(10, 20, 0, 1).to_ipv6_mapped().into()));
+        assert!(!policy.allows_resolved_ip(Ipv4Addr::new(192, 168, 0, 1).into()));
+    }
+
+    #[test]
+    fn test_check() {
+        let policy = policy();
+        assert!(policy.check("https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo").is_ok());
+
+        let error = policy.check("https://files.example.net/doc/7").unwrap_err();
+        assert!(matches!(error.kind(), validation::ErrorKind::EgressDenied(_)));
+        assert!(policy.check("not a url").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolver_refuses_disallowed_hosts() {
+        let resolver = EgressPolicy::new().allow_host("localhost").allow_network(Ipv4Addr::new(127, 0, 0, 0).into(), 8).resolver();
+
+        let addresses: Vec<SocketAddr> = resolver.resolve("localhost".parse().unwrap()).await.unwrap().collect();
+        assert!(!addresses.is_empty());
+        assert!(addresses.iter().all(|address| address.ip().is_ipv4()));
+
+        assert!(resolver.resolve("lf.example.com".parse().unwrap()).await.is_err());
+    }
+}
@@ -0,0 +1,523 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{impl_into_result, LFAPIError, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{
+    Auth, Entry, FolderId, ImportResultOrError, LFApiServer, LinksOrError, MetadataResultOrError, NewLink,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mime;
+use crate::naming::civil_from_days;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::naming::NamingPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validation;
+
+use mail_parser::{MessageParser, MimeHeaders};
+#[cfg(not(target_arch = "wasm32"))]
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Seconds between 1601-01-01 (the FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+
+// MAPI property ids used in Outlook .msg files
+const PR_SUBJECT: u16 = 0x0037;
+const PR_CLIENT_SUBMIT_TIME: u16 = 0x0039;
+const PR_SENDER_NAME: u16 = 0x0C1A;
+const PR_SENDER_EMAIL_ADDRESS: u16 = 0x0C1F;
+const PR_RECIPIENT_TYPE: u16 = 0x0C15;
+const PR_EMAIL_ADDRESS: u16 = 0x3003;
+const PR_SMTP_ADDRESS: u16 = 0x39FE;
+const PR_DISPLAY_NAME: u16 = 0x3001;
+const PR_BODY: u16 = 0x1000;
+const PR_BODY_HTML: u16 = 0x1013;
+const PR_ATTACH_FILENAME: u16 = 0x3704;
+const PR_ATTACH_LONG_FILENAME: u16 = 0x3707;
+const PR_ATTACH_DATA_BIN: u16 = 0x3701;
+
+/// `PR_RECIPIENT_TYPE` value for primary (To) recipients
+const MAPI_TO: u32 = 1;
+
+/// Length of the `__properties_version1.0` header before the property entries
+const MESSAGE_PROPERTIES_HEADER: usize = 32;
+const CHILD_PROPERTIES_HEADER: usize = 8;
+
+/// Body of a message, imported as the document content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailBody {
+    Text(String),
+    Html(String),
+}
+
+impl EmailBody {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EmailBody::Text(body) | EmailBody::Html(body) => body,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmailBody::Text(_) => "txt",
+            EmailBody::Html(_) => "html",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            EmailBody::Text(_) => "text/plain",
+            EmailBody::Html(_) => "text/html",
+        }
+    }
+}
+
+/// A file attached to a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailAttachment {
+    pub name: Option<String>,
+    pub content: Vec<u8>,
+}
+
+/// Headers, body, and attachments parsed from a .eml or .msg file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailMessage {
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub subject: Option<String>,
+    /// Sent date as RFC 3339
+    pub date: Option<String>,
+    pub body: EmailBody,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+impl EmailMessage {
+    /// Parse a file, as Outlook .msg for a `.msg` extension and MIME otherwise
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read(path)?;
+        let is_msg = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("msg"));
+        if is_msg {
+            Self::parse_msg(&content)
+        } else {
+            Self::parse_eml(&content)
+        }
+    }
+
+    /// Parse an RFC 5322 (.eml) message
+    pub fn parse_eml(content: &[u8]) -> Result<Self> {
+        let message = MessageParser::default()
+            .parse(content)
+            .ok_or("Failed to parse email: not an RFC 5322 message")?;
+
+        let address = |addr: &mail_parser::Addr| {
+            addr.address().or_else(|| addr.name()).map(str::to_string)
+        };
+        let from = message.from().and_then(|from| from.first()).and_then(address);
+        let to = message.to()
+            .map(|to| to.iter().filter_map(address).collect())
+            .unwrap_or_default();
+
+        let body = match (message.body_text(0), message.body_html(0)) {
+            (Some(text), _) => EmailBody::Text(text.into_owned()),
+            (None, Some(html)) => EmailBody::Html(html.into_owned()),
+            (None, None) => EmailBody::Text(String::new()),
+        };
+
+        let attachments = message.attachments()
+            .map(|part| EmailAttachment {
+                name: part.attachment_name().map(str::to_string),
+                content: part.contents().to_vec(),
+            })
+            .collect();
+
+        Ok(EmailMessage {
+            from,
+            to,
+            subject: message.subject().map(str::to_string),
+            date: message.date().map(|date| date.to_rfc3339()),
+            body,
+            attachments,
+        })
+    }
+
+    /// Parse an Outlook (.msg) compound file
+    pub fn parse_msg(content: &[u8]) -> Result<Self> {
+        let mut msg = cfb::CompoundFile::open(Cursor::new(content))
+            .map_err(|e| format!("Failed to parse email: not an Outlook message: {}", e))?;
+
+        let from = read_string(&mut msg, "", PR_SENDER_EMAIL_ADDRESS)
+            .or_else(|| read_string(&mut msg, "", PR_SENDER_NAME));
+
+        let body = match read_string(&mut msg, "", PR_BODY) {
+            Some(text) => EmailBody::Text(text),
+            None => read_binary(&mut msg, "", PR_BODY_HTML)
+                .map(|html| EmailBody::Html(String::from_utf8_lossy(&html).into_owned()))
+                .unwrap_or_else(|| EmailBody::Text(String::new())),
+        };
+
+        let date = read_fixed(&mut msg, "", MESSAGE_PROPERTIES_HEADER, PR_CLIENT_SUBMIT_TIME)
+            .map(filetime_to_rfc3339);
+
+        let mut to = Vec::new();
+        for storage in child_storages(&msg, "__recip_version1.0_") {
+            let recipient_type = read_fixed(&mut msg, &storage, CHILD_PROPERTIES_HEADER, PR_RECIPIENT_TYPE);
+            if recipient_type.is_some_and(|kind| kind as u32 != MAPI_TO) {
+                continue;
+            }
+            let address = read_string(&mut msg, &storage, PR_SMTP_ADDRESS)
+                .or_else(|| read_string(&mut msg, &storage, PR_EMAIL_ADDRESS))
+                .or_else(|| read_string(&mut msg, &storage, PR_DISPLAY_NAME));
+            to.extend(address);
+        }
+
+        let mut attachments = Vec::new();
+        for storage in child_storages(&msg, "__attach_version1.0_") {
+            // Embedded messages have no data stream and are skipped
+            if let Some(content) = read_binary(&mut msg, &storage, PR_ATTACH_DATA_BIN) {
+                let name = read_string(&mut msg, &storage, PR_ATTACH_LONG_FILENAME)
+                    .or_else(|| read_string(&mut msg, &storage, PR_ATTACH_FILENAME));
+                attachments.push(EmailAttachment { name, content });
+            }
+        }
+
+        Ok(EmailMessage {
+            from,
+            to,
+            subject: read_string(&mut msg, "", PR_SUBJECT),
+            date,
+            body,
+            attachments,
+        })
+    }
+
+    /// Field values for the headers named in `fields`, keyed by field name
+    pub fn field_values(&self, fields: &EmailFieldMap) -> BTreeMap<String, Vec<String>> {
+        let mut values = BTreeMap::new();
+        let mappings = [
+            (&fields.from, self.from.iter().cloned().collect::<Vec<_>>()),
+            (&fields.to, self.to.clone()),
+            (&fields.subject, self.subject.iter().cloned().collect()),
+            (&fields.date, self.date.iter().cloned().collect()),
+        ];
+        for (field, header) in mappings {
+            if let Some(field) = field {
+                if !header.is_empty() {
+                    values.insert(field.clone(), header);
+                }
+            }
+        }
+        values
+    }
+}
+
+/// Paths of the storages directly under the root whose names start with `prefix`
+fn child_storages<F: Read + Seek>(msg: &cfb::CompoundFile<F>, prefix: &str) -> Vec<String> {
+    let mut storages: Vec<String> = msg.read_root_storage()
+        .filter(|entry| entry.is_storage() && entry.name().starts_with(prefix))
+        .map(|entry| entry.name().to_string())
+        .collect();
+    storages.sort();
+    storages
+}
+
+fn read_stream<F: Read + Seek>(msg: &mut cfb::CompoundFile<F>, storage: &str, name: &str) -> Option<Vec<u8>> {
+    let path = format!("/{}/{}", storage, name).replace("//", "/");
+    let mut stream = msg.open_stream(path).ok()?;
+    let mut content = Vec::new();
+    stream.read_to_end(&mut content).ok()?;
+    Some(content)
+}
+
+/// A string property, stored as UTF-16 (`001F`) or 8-bit (`001E`)
+fn read_string<F: Read + Seek>(msg: &mut cfb::CompoundFile<F>, storage: &str, property: u16) -> Option<String> {
+    if let Some(raw) = read_stream(msg, storage, &format!("__substg1.0_{:04X}001F", property)) {
+        let units: Vec<u16> = raw.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]])).collect();
+        return Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string());
+    }
+    read_stream(msg, storage, &format!("__substg1.0_{:04X}001E", property))
+        .map(|raw| String::from_utf8_lossy(&raw).trim_end_matches('\0').to_string())
+}
+
+fn read_binary<F: Read + Seek>(msg: &mut cfb::CompoundFile<F>, storage: &str, property: u16) -> Option<Vec<u8>> {
+    read_stream(msg, storage, &format!("__substg1.0_{:04X}0102", property))
+}
+
+/// A fixed-length property from a storage's `__properties_version1.0` table
+fn read_fixed<F: Read + Seek>(
+    msg: &mut cfb::CompoundFile<F>,
+    storage: &str,
+    header_len: usize,
+    property: u16
+) -> Option<u64> {
+    let table = read_stream(msg, storage, "__properties_version1.0")?;
+    table.get(header_len..)?
+        .chunks_exact(16)
+        .find(|entry| u16::from_le_bytes([entry[2], entry[3]]) == property)
+        .map(|entry| u64::from_le_bytes(entry[8..16].try_into().unwrap_or_default()))
+}
+
+/// Format a Windows FILETIME (100ns ticks since 1601) as RFC 3339 UTC
+fn filetime_to_rfc3339(ticks: u64) -> String {
+    let seconds = (ticks / 10_000_000) as i64 - FILETIME_UNIX_OFFSET;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, time / 3600, time % 3600 / 60, time % 60
+    )
+}
+
+/// Field names that message headers are written to; `None` skips a header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmailFieldMap {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    pub date: Option<String>,
+}
+
+/// How `import_email` stores a message
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmailImportOptions {
+    pub fields: EmailFieldMap,
+    /// Import attachments into the same folder, linked from the message
+    /// with this link type; `None` skips attachments
+    pub attachment_link_type: Option<i64>,
+}
+
+/// Entries created for one message
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EmailImport {
+    pub entry_id: i64,
+    pub attachment_ids: Vec<i64>,
+}
+
+pub enum EmailImportOrError {
+    Imported(EmailImport),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(EmailImportOrError::Imported => EmailImport);
+
+/// Document name for a message: its subject (or the file stem) plus the body extension
+#[cfg(not(target_arch = "wasm32"))]
+fn document_name(message: &EmailMessage, file_path: &Path) -> String {
+    let stem = message.subject.as_deref()
+        .filter(|subject| !subject.trim().is_empty())
+        .or_else(|| file_path.file_stem().and_then(|stem| stem.to_str()))
+        .unwrap_or("message");
+    format!("{}.{}", NamingPolicy::sanitize(stem.trim()), message.body.extension())
+}
+
+/// Import an email as a document with header metadata and, optionally,
+/// its attachments as linked entries
+///
+/// The message body becomes the document content. An API error part way
+/// through leaves the entries created so far in place.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local .eml or .msg file
+/// * `folder_id` - Destination folder
+/// * `options` - Header-to-field mapping and attachment handling
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_email(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    folder_id: impl Into<FolderId>,
+    options: &EmailImportOptions
+) -> Result<EmailImportOrError> {
+    let folder_id = validation::validate_entry_id(folder_id.into().as_i64())?;
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let message = EmailMessage::from_file(&validated_path)?;
+
+    let name = validation::validate_file_name(&document_name(&message, &validated_path))?;
+    let body = message.body.as_str().as_bytes().to_vec();
+    let imported = Entry::import_content(
+        api_server.clone(), auth.clone(), body, &name, folder_id, message.body.mime_type()
+    ).await?;
+    let entry_id = match imported {
+        ImportResultOrError::ImportResult(result) => result.operations.entry_create.entry_id,
+        ImportResultOrError::LFAPIError(error) => return Ok(EmailImportOrError::LFAPIError(error)),
+    };
+
+    let fields = message.field_values(&options.fields);
+    if !fields.is_empty() {
+        let payload: serde_json::Map<String, serde_json::Value> = fields.into_iter()
+            .map(|(field, values)| {
+                let values: Vec<serde_json::Value> = values.iter()
+                    .enumerate()
+                    .map(|(i, value)| json!({ "value": value, "position": i + 1 }))
+                    .collect();
+                (field, json!({ "values": values }))
+            })
+            .collect();
+        let result = Entry::update_metadata(api_server.clone(), auth.clone(), entry_id, payload.into()).await?;
+        if let MetadataResultOrError::LFAPIError(error) = result {
+            return Ok(EmailImportOrError::LFAPIError(error));
+        }
+    }
+
+    let mut attachment_ids = Vec::new();
+    if let Some(link_type_id) = options.attachment_link_type {
+        for (index, attachment) in message.attachments.iter().enumerate() {
+            let fallback = format!("attachment-{}", index + 1);
+            let name = NamingPolicy::sanitize(attachment.name.as_deref().unwrap_or(&fallback));
+            let name = validation::validate_file_name(&name)?;
+            let mime_type = mime::detect_mime_type(&name);
+            let imported = Entry::import_content(
+                api_server.clone(), auth.clone(), attachment.content.clone(), &name, folder_id, &mime_type
+            ).await?;
+            match imported {
+                ImportResultOrError::ImportResult(result) => {
+                    attachment_ids.push(result.operations.entry_create.entry_id);
+                }
+                ImportResultOrError::LFAPIError(error) => return Ok(EmailImportOrError::LFAPIError(error)),
+            }
+        }
+
+        if !attachment_ids.is_empty() {
+            let links = attachment_ids.iter()
+                .map(|&target_id| NewLink::new(target_id, link_type_id))
+                .collect();
+            if let LinksOrError::LFAPIError(error) = Entry::set_links(api_server, auth, entry_id, links).await? {
+                return Ok(EmailImportOrError::LFAPIError(error));
+            }
+        }
+    }
+
+    Ok(EmailImportOrError::Imported(EmailImport { entry_id, attachment_ids }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const EML: &str = "From: Ada Lovelace <ada@example.com>\r\n\
+        To: Charles Babbage <charles@example.com>, ops@example.com\r\n\
+        Subject: Invoice 42/7\r\n\
+        Date: Tue, 1 Oct 2024 09:30:00 +0000\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"b1\"\r\n\
+        \r\n\
+        --b1\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        Please find the invoice attached.\r\n\
+        --b1\r\n\
+        Content-Type: application/pdf; name=\"invoice.pdf\"\r\n\
+        Content-Disposition: attachment; filename=\"invoice.pdf\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERi0xLjQ=\r\n\
+        --b1--\r\n";
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn property_entry(property: u16, kind: u16, value: u64) -> Vec<u8> {
+        let mut entry = Vec::new();
+        entry.extend_from_slice(&kind.to_le_bytes());
+        entry.extend_from_slice(&property.to_le_bytes());
+        entry.extend_from_slice(&0u32.to_le_bytes());
+        entry.extend_from_slice(&value.to_le_bytes());
+        entry
+    }
+
+    fn write(msg: &mut cfb::CompoundFile<Cursor<Vec<u8>>>, path: &str, content: &[u8]) {
+        msg.create_stream(path).unwrap().write_all(content).unwrap();
+    }
+
+    fn sample_msg() -> Vec<u8> {
+        let mut msg = cfb::CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        write(&mut msg, "/__substg1.0_0037001F", &utf16("Quarterly report"));
+        write(&mut msg, "/__substg1.0_0C1F001F", &utf16("grace@example.com"));
+        write(&mut msg, "/__substg1.0_1000001F", &utf16("Numbers attached."));
+
+        // 2024-10-01T09:30:00Z as FILETIME
+        let submitted = (1_727_775_000 + FILETIME_UNIX_OFFSET) as u64 * 10_000_000;
+        let mut properties = vec![0u8; MESSAGE_PROPERTIES_HEADER];
+        properties.extend(property_entry(PR_CLIENT_SUBMIT_TIME, 0x0040, submitted));
+        write(&mut msg, "/__properties_version1.0", &properties);
+
+        // One To and one Cc recipient
+        for (index, (kind, address)) in [(1, "alan@example.com"), (2, "cc@example.com")].iter().enumerate() {
+            let storage = format!("/__recip_version1.0_#{:08X}", index);
+            msg.create_storage(&storage).unwrap();
+            let mut properties = vec![0u8; CHILD_PROPERTIES_HEADER];
+            properties.extend(property_entry(PR_RECIPIENT_TYPE, 0x0003, *kind));
+            write(&mut msg, &format!("{}/__properties_version1.0", storage), &properties);
+            write(&mut msg, &format!("{}/__substg1.0_39FE001F", storage), &utf16(address));
+        }
+
+        msg.create_storage("/__attach_version1.0_#00000000").unwrap();
+        write(&mut msg, "/__attach_version1.0_#00000000/__substg1.0_3707001F", &utf16("figures.xlsx"));
+        write(&mut msg, "/__attach_version1.0_#00000000/__substg1.0_37010102", b"PK\x03\x04");
+
+        msg.flush().unwrap();
+        msg.into_inner().into_inner()
+    }
+
+    #[test]
+    fn test_parse_eml() {
+        let message = EmailMessage::parse_eml(EML.as_bytes()).unwrap();
+
+        assert_eq!(message.from.as_deref(), Some("ada@example.com"));
+        assert_eq!(message.to, vec!["charles@example.com", "ops@example.com"]);
+        assert_eq!(message.subject.as_deref(), Some("Invoice 42/7"));
+        assert_eq!(message.date.as_deref(), Some("2024-10-01T09:30:00Z"));
+        assert_eq!(message.body, EmailBody::Text("Please find the invoice attached.".to_string()));
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].name.as_deref(), Some("invoice.pdf"));
+        assert_eq!(message.attachments[0].content, b"%PDF-1.4");
+    }
+
+    #[test]
+    fn test_parse_msg() {
+        let message = EmailMessage::parse_msg(&sample_msg()).unwrap();
+
+        assert_eq!(message.from.as_deref(), Some("grace@example.com"));
+        assert_eq!(message.to, vec!["alan@example.com"]);
+        assert_eq!(message.subject.as_deref(), Some("Quarterly report"));
+        assert_eq!(message.date.as_deref(), Some("2024-10-01T09:30:00Z"));
+        assert_eq!(message.body, EmailBody::Text("Numbers attached.".to_string()));
+        assert_eq!(message.attachments, vec![EmailAttachment {
+            name: Some("figures.xlsx".to_string()),
+            content: b"PK\x03\x04".to_vec(),
+        }]);
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert!(EmailMessage::parse_msg(b"not a compound file").is_err());
+    }
+
+    #[test]
+    fn test_field_values_and_document_name() {
+        let message = EmailMessage::parse_eml(EML.as_bytes()).unwrap();
+        let fields = EmailFieldMap {
+            from: Some("Sender".to_string()),
+            to: Some("Recipients".to_string()),
+            subject: None,
+            date: Some("Sent".to_string()),
+        };
+
+        let values = message.field_values(&fields);
+        assert_eq!(values.len(), 3);
+        assert_eq!(values["Recipients"], vec!["charles@example.com", "ops@example.com"]);
+        assert_eq!(values["Sent"], vec!["2024-10-01T09:30:00Z"]);
+
+        // The slash in the subject is not allowed in an entry name
+        assert_eq!(document_name(&message, Path::new("/mail/1.eml")), "Invoice 42_7.txt");
+    }
+}
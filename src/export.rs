@@ -0,0 +1,700 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::clock::Instant;
+use crate::laserfiche::{Entry, ErrorKind, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{Auth, Error, FolderId, LFApiServer, ListOptions, WrittenOrError};
+use crate::manifest::{csv_field, from_versioned_json, to_versioned_json};
+use crate::naming::NamingPolicy;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::pipeline::{FairQueue, Pipeline};
+use crate::state::{self, StateStore};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures_util::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+/// Columns written by `ExportManifest::to_csv`, in order
+const CSV_HEADER: [&str; 5] = ["entry_id", "repository_path", "target_path", "outcome", "bytes"];
+
+/// What `download_tree` does when a document's file name is already taken
+/// in its target directory, by another document in the export or by a
+/// file already on disk
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// Save as `Name [entry id].ext`, numbered further if that is taken too
+    #[default]
+    AppendEntryId,
+    /// Save as `Name (2).ext`, `Name (3).ext`, ...
+    NumberedSuffix,
+    /// Stop the export with `ErrorKind::FileExists`
+    Fail,
+    /// Leave the existing file and export nothing for the document
+    Skip,
+    /// Replace a file already on disk; documents of the export whose
+    /// names clash with each other are still saved as with `AppendEntryId`
+    Overwrite,
+}
+
+impl CollisionPolicy {
+    /// The name to save `file_name` under, or `None` to skip the document
+    ///
+    /// # Arguments
+    /// * `file_name` - Name the document would be saved under
+    /// * `entry_id` - The document's entry id
+    /// * `directory` - Directory the file goes in, for error messages
+    /// * `taken` - Whether a name is already in use in the directory; for
+    ///   `Overwrite`, only names used by the export itself
+    pub fn resolve(
+        &self,
+        file_name: &str,
+        entry_id: i64,
+        directory: &Path,
+        taken: impl Fn(&str) -> bool
+    ) -> Result<Option<String>> {
+        if !taken(file_name) {
+            return Ok(Some(file_name.to_string()));
+        }
+        let numbered = |base: &str| {
+            (2u64..).map(|n| with_suffix(base, &format!(" ({})", n))).find(|name| !taken(name))
+        };
+        match self {
+            CollisionPolicy::AppendEntryId | CollisionPolicy::Overwrite => {
+                let name = with_suffix(file_name, &format!(" [{}]", entry_id));
+                Ok(if taken(&name) { numbered(&name) } else { Some(name) })
+            }
+            CollisionPolicy::NumberedSuffix => Ok(numbered(file_name)),
+            CollisionPolicy::Fail => {
+                Err(ErrorKind::FileExists(directory.join(file_name).display().to_string()).into())
+            }
+            CollisionPolicy::Skip => Ok(None),
+        }
+    }
+}
+
+/// Insert `suffix` before a file name's extension
+fn with_suffix(file_name: &str, suffix: &str) -> String {
+    let (stem, extension) = split_extension(file_name);
+    format!("{}{}{}", stem, suffix, extension)
+}
+
+/// Split a file name into its stem and extension, the latter including the
+/// dot; a leading dot as in `.profile` does not start an extension
+pub(crate) fn split_extension(file_name: &str) -> (&str, &str) {
+    match file_name.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => file_name.split_at(dot),
+        None => (file_name, ""),
+    }
+}
+
+/// What happened to one document in an export
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportOutcome {
+    /// Saved under its own name
+    Written,
+    /// Saved under a name chosen by the collision policy
+    Renamed,
+    /// Saved over a file already on disk
+    Overwritten,
+    /// Not saved because its name was taken
+    Skipped,
+    /// Not saved because the time budget ran out first
+    Deferred,
+}
+
+impl ExportOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportOutcome::Written => "written",
+            ExportOutcome::Renamed => "renamed",
+            ExportOutcome::Overwritten => "overwritten",
+            ExportOutcome::Skipped => "skipped",
+            ExportOutcome::Deferred => "deferred",
+        }
+    }
+}
+
+/// Record of what an export saved where
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportManifest {
+    pub entries: Vec<ExportedEntry>,
+}
+
+/// One exported document
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportedEntry {
+    pub entry_id: i64,
+    /// The document's path in the repository
+    pub repository_path: String,
+    /// Where the document was saved, for a skipped document the file that
+    /// was left in place, or for a deferred one where it would go
+    pub target_path: String,
+    pub outcome: ExportOutcome,
+    /// Bytes written; 0 when skipped or deferred
+    pub bytes: u64,
+}
+
+impl ExportManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serialize as pretty-printed JSON, tagged with `manifest::FORMAT_VERSION`
+    pub fn to_json(&self) -> Result<String> {
+        to_versioned_json("export", self)
+    }
+
+    /// Parse JSON written by `to_json` of this or an earlier crate version
+    pub fn from_json(json: &str) -> Result<Self> {
+        from_versioned_json("export", json)
+    }
+
+    /// Read a JSON manifest written by `write`, e.g. to resume an export
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Read the manifest saved under `key` by `save_to`, if any
+    pub fn load_from(store: &dyn StateStore, key: &str) -> Result<Option<Self>> {
+        state::load_string(store, key)?.map(|json| Self::from_json(&json)).transpose()
+    }
+
+    /// Save the manifest as JSON under `key`, replacing any saved before
+    pub fn save_to(&self, store: &dyn StateStore, key: &str) -> Result<()> {
+        store.save(key, self.to_json()?.as_bytes())
+    }
+
+    /// Serialize as CSV with a header row
+    pub fn to_csv(&self) -> String {
+        let mut csv = CSV_HEADER.join(",");
+        csv.push_str("\r\n");
+
+        for entry in &self.entries {
+            let row = [
+                entry.entry_id.to_string(),
+                csv_field(&entry.repository_path),
+                csv_field(&entry.target_path),
+                entry.outcome.as_str().to_string(),
+                entry.bytes.to_string(),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push_str("\r\n");
+        }
+
+        csv
+    }
+
+    /// Write the manifest to `path`, as CSV for a `.csv` extension and JSON otherwise
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let contents = if is_csv { self.to_csv() } else { self.to_json()? };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+/// The file name a document is saved under: its entry name with reserved
+/// characters replaced, plus its extension if the name lacks it
+pub fn export_file_name(entry: &Entry) -> String {
+    let mut name = NamingPolicy::sanitize(entry.name.trim());
+    if name.is_empty() {
+        name = entry.id.to_string();
+    }
+    if let Some(extension) = entry.extension.as_deref().filter(|extension| !extension.is_empty()) {
+        let has_extension = name
+            .rsplit_once('.')
+            .is_some_and(|(_, existing)| existing.eq_ignore_ascii_case(extension));
+        if !has_extension {
+            name = format!("{}.{}", name, NamingPolicy::sanitize(extension));
+        }
+    }
+    name
+}
+
+/// The directory name a folder is exported under: its entry name with
+/// reserved characters replaced, or its id if that leaves no usable name
+#[cfg(not(target_arch = "wasm32"))]
+fn export_folder_name(entry: &Entry) -> String {
+    let name = NamingPolicy::sanitize(entry.name.trim());
+    if name.is_empty() || name == "." {
+        entry.id.to_string()
+    } else {
+        name
+    }
+}
+
+/// Names claimed so far in each target directory, compared ignoring case
+/// so the export behaves the same on case-insensitive file systems
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct ClaimedNames(BTreeSet<(PathBuf, String)>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ClaimedNames {
+    fn is_taken(&self, directory: &Path, name: &str) -> bool {
+        self.is_claimed(directory, name) || directory.join(name).exists()
+    }
+
+    fn is_claimed(&self, directory: &Path, name: &str) -> bool {
+        self.0.contains(&(directory.to_path_buf(), name.to_lowercase()))
+    }
+
+    fn claim(&mut self, directory: &Path, name: &str) {
+        self.0.insert((directory.to_path_buf(), name.to_lowercase()));
+    }
+}
+
+/// Download every document below a folder into `target_dir`, recreating
+/// the folder structure and recording each document in `manifest`
+///
+/// Subfolders become directories of the same name, merged with any that
+/// already exist. A document whose file name is taken, or a subfolder whose
+/// name another subfolder or a file already took, is handled per
+/// `collisions`; a skipped subfolder is left out with its contents. No
+/// existing file is overwritten unless that is `CollisionPolicy::Overwrite`. Shortcuts are not followed. On error the
+/// manifest holds every document handled before it.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `folder_id` - Folder to export; its contents go directly in `target_dir`
+/// * `target_dir` - Local directory, created if missing
+/// * `collisions` - How taken file names are handled
+/// * `manifest` - Manifest to append to
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn download_tree(
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: impl Into<FolderId>,
+    target_dir: &Path,
+    collisions: CollisionPolicy,
+    manifest: &mut ExportManifest
+) -> Result<()> {
+    export_tree(api_server, auth, folder_id.into(), target_dir, collisions, None, manifest).await
+}
+
+/// `download_tree`, starting no download once `budget` has passed
+///
+/// The document being downloaded when the budget runs out is finished.
+/// The rest of the tree is still listed, each remaining document recorded
+/// as `ExportOutcome::Deferred` at the path it would have been saved
+/// under, so a later run knows what is left.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `folder_id` - Folder to export; its contents go directly in `target_dir`
+/// * `target_dir` - Local directory, created if missing
+/// * `collisions` - How taken file names are handled
+/// * `budget` - Time from the call after which no download starts
+/// * `manifest` - Manifest to append to
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn download_tree_within_budget(
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: impl Into<FolderId>,
+    target_dir: &Path,
+    collisions: CollisionPolicy,
+    budget: Duration,
+    manifest: &mut ExportManifest
+) -> Result<()> {
+    let deadline = Instant::now().checked_add(budget);
+    export_tree(api_server, auth, folder_id.into(), target_dir, collisions, deadline, manifest).await
+}
+
+/// Download every document below a folder through `pipeline`, several at
+/// a time, taking turns between folders
+///
+/// Like `download_tree`, but the tree is listed first and its documents
+/// queued per folder in a `FairQueue`, so a folder with a handful of
+/// documents finishes early instead of waiting behind one with thousands,
+/// and an interrupted run leaves many folders complete. Downloads run within
+/// the pipeline's limits, throttle, and budget; documents the budget leaves
+/// unstarted are recorded as `ExportOutcome::Deferred`. A failed download
+/// does not stop the others: once they finish, the first failure is
+/// returned with every other document in the manifest.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits, throttle, and budget for the downloads
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `folder_id` - Folder to export; its contents go directly in `target_dir`
+/// * `target_dir` - Local directory, created if missing
+/// * `collisions` - How taken file names are handled
+/// * `manifest` - Manifest to append to
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn download_tree_with_pipeline(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: impl Into<FolderId>,
+    target_dir: &Path,
+    collisions: CollisionPolicy,
+    manifest: &mut ExportManifest
+) -> Result<()> {
+    let folder_id = folder_id.into();
+    let mut plan = TreePlan::new(folder_id, target_dir, collisions)?;
+    let mut queue = FairQueue::new();
+    let mut entries = Entry::walk(api_server.clone(), auth.clone(), folder_id, ListOptions::default());
+    while let Some(entry) = entries.try_next().await? {
+        if let Some(download) = plan.add(entry, manifest)? {
+            queue.push(download.folder_id, download);
+        }
+    }
+
+    let stats = Arc::clone(pipeline.stats());
+    let mut first_error: Option<Error> = None;
+    let mut deferred = Vec::new();
+    pipeline.run_within_budget(queue, move |download: PlannedDownload| {
+        let (api_server, auth, stats) = (api_server.clone(), auth.clone(), Arc::clone(&stats));
+        async move {
+            let started = Instant::now();
+            let exported = download.run(api_server, auth).await?;
+            stats.record_file(exported.bytes, started.elapsed());
+            Ok(exported)
+        }
+    }, |result| match result {
+        Ok(Ok(exported)) => manifest.entries.push(exported),
+        Ok(Err(error)) => {
+            pipeline.stats().record_failure();
+            first_error.get_or_insert(error);
+        }
+        Err(panic) => {
+            first_error.get_or_insert_with(|| panic.to_string().into());
+        }
+    }, |_, download| deferred.push(download.deferred())).await;
+
+    manifest.entries.extend(deferred);
+    match first_error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Entry {
+    /// Export a folder's subtree into `local_dir`, downloading several
+    /// documents at a time, and return what was saved where
+    ///
+    /// `download_tree_with_pipeline` into a new manifest: subfolders become
+    /// directories, at most the pipeline's `max_in_flight` downloads run at
+    /// once, and a progress observer set with `Pipeline::with_progress` is
+    /// told of each document. On failure, use `download_tree_with_pipeline`
+    /// directly to keep the manifest of what was saved.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `folder_id` - Folder to export; its contents go directly in `local_dir`
+    /// * `local_dir` - Local directory, created if missing
+    /// * `collisions` - Whether taken file names are renamed around, skipped, or overwritten
+    /// * `pipeline` - Concurrency limits, throttle, budget, and progress observer
+    pub async fn export_tree(
+        api_server: LFApiServer,
+        auth: Auth,
+        folder_id: impl Into<FolderId>,
+        local_dir: &Path,
+        collisions: CollisionPolicy,
+        pipeline: &Pipeline
+    ) -> Result<ExportManifest> {
+        let mut manifest = ExportManifest::new();
+        download_tree_with_pipeline(pipeline, api_server, auth, folder_id, local_dir, collisions, &mut manifest).await?;
+        Ok(manifest)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn export_tree(
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: FolderId,
+    target_dir: &Path,
+    collisions: CollisionPolicy,
+    deadline: Option<Instant>,
+    manifest: &mut ExportManifest
+) -> Result<()> {
+    let mut plan = TreePlan::new(folder_id, target_dir, collisions)?;
+    let mut entries = Entry::walk(api_server.clone(), auth.clone(), folder_id, ListOptions::default());
+    while let Some(entry) = entries.try_next().await? {
+        let Some(download) = plan.add(entry, manifest)? else {
+            continue;
+        };
+        let exported = match deadline {
+            Some(deadline) if Instant::now() >= deadline => download.deferred(),
+            _ => download.run(api_server.clone(), auth.clone()).await?,
+        };
+        manifest.entries.push(exported);
+    }
+
+    Ok(())
+}
+
+/// The directories and claimed file names of a tree export, deciding what
+/// each listed entry needs
+#[cfg(not(target_arch = "wasm32"))]
+struct TreePlan {
+    directories: BTreeMap<i64, PathBuf>,
+    claimed: ClaimedNames,
+    collisions: CollisionPolicy,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TreePlan {
+    fn new(folder_id: FolderId, target_dir: &Path, collisions: CollisionPolicy) -> Result<Self> {
+        std::fs::create_dir_all(target_dir)?;
+        Ok(TreePlan {
+            directories: BTreeMap::from([(folder_id.as_i64(), target_dir.to_path_buf())]),
+            claimed: ClaimedNames::default(),
+            collisions,
+        })
+    }
+
+    /// Create the directory for a folder, or pick the file a document is
+    /// saved as; a document skipped by the collision policy is recorded in
+    /// `manifest` instead
+    fn add(&mut self, entry: Entry, manifest: &mut ExportManifest) -> Result<Option<PlannedDownload>> {
+        let Some(directory) = self.directories.get(&entry.parent_id).cloned() else {
+            return Ok(None);
+        };
+
+        if entry.is_container {
+            // An existing directory is merged into; only another folder of
+            // this export, or a file, takes the name
+            let folder_name = export_folder_name(&entry);
+            let resolved = self.collisions.resolve(&folder_name, entry.id, &directory, |name| {
+                let path = directory.join(name);
+                self.claimed.is_claimed(&directory, name) || (path.exists() && !path.is_dir())
+            })?;
+            if let Some(name) = resolved {
+                let path = directory.join(&name);
+                std::fs::create_dir_all(&path)?;
+                self.claimed.claim(&directory, &name);
+                self.directories.insert(entry.id, path);
+            }
+            return Ok(None);
+        }
+        if !entry.is_document() {
+            return Ok(None);
+        }
+
+        let file_name = export_file_name(&entry);
+        let overwrite = self.collisions == CollisionPolicy::Overwrite;
+        let resolved = self.collisions.resolve(&file_name, entry.id, &directory, |name| {
+            if overwrite { self.claimed.is_claimed(&directory, name) } else { self.claimed.is_taken(&directory, name) }
+        })?;
+        let Some(name) = resolved else {
+            manifest.entries.push(ExportedEntry {
+                entry_id: entry.id,
+                repository_path: entry.full_path,
+                target_path: directory.join(&file_name).display().to_string(),
+                outcome: ExportOutcome::Skipped,
+                bytes: 0,
+            });
+            return Ok(None);
+        };
+
+        self.claimed.claim(&directory, &name);
+        let path = directory.join(&name);
+        let outcome = if overwrite && path.exists() {
+            ExportOutcome::Overwritten
+        } else if name == file_name {
+            ExportOutcome::Written
+        } else {
+            ExportOutcome::Renamed
+        };
+        Ok(Some(PlannedDownload {
+            entry_id: entry.id,
+            folder_id: entry.parent_id,
+            repository_path: entry.full_path,
+            path,
+            outcome,
+        }))
+    }
+}
+
+/// A document `TreePlan` has picked a file for
+#[cfg(not(target_arch = "wasm32"))]
+struct PlannedDownload {
+    entry_id: i64,
+    folder_id: i64,
+    repository_path: String,
+    path: PathBuf,
+    outcome: ExportOutcome,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PlannedDownload {
+    /// Save the document, removing the partial file if the download fails
+    ///
+    /// A file being overwritten is downloaded beside the old one and
+    /// renamed over it once complete, so a failure leaves the old file.
+    async fn run(self, api_server: LFApiServer, auth: Auth) -> Result<ExportedEntry> {
+        let download_path = match self.outcome {
+            ExportOutcome::Overwritten => {
+                let mut part = self.path.clone().into_os_string();
+                part.push(".part");
+                PathBuf::from(part)
+            }
+            _ => self.path.clone(),
+        };
+        let mut file = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .create_new(self.outcome != ExportOutcome::Overwritten)
+            .open(&download_path)
+            .await?;
+        let bytes = match Entry::export_stream(api_server, auth, self.entry_id, &mut file).await {
+            Ok(WrittenOrError::Written(bytes)) => bytes,
+            Ok(WrittenOrError::LFAPIError(error)) => {
+                let _ = tokio::fs::remove_file(&download_path).await;
+                return Err(ErrorKind::ApiError(Box::new(error)).into());
+            }
+            Err(error) => {
+                let _ = tokio::fs::remove_file(&download_path).await;
+                return Err(error);
+            }
+        };
+        drop(file);
+        if download_path != self.path {
+            tokio::fs::rename(&download_path, &self.path).await?;
+        }
+
+        Ok(ExportedEntry {
+            entry_id: self.entry_id,
+            repository_path: self.repository_path,
+            target_path: self.path.display().to_string(),
+            outcome: self.outcome,
+            bytes,
+        })
+    }
+
+    /// The record of a download the time budget left unstarted
+    fn deferred(self) -> ExportedEntry {
+        ExportedEntry {
+            entry_id: self.entry_id,
+            repository_path: self.repository_path,
+            target_path: self.path.display().to_string(),
+            outcome: ExportOutcome::Deferred,
+            bytes: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::EntryType;
+
+    fn document(id: i64, name: &str, extension: Option<&str>) -> Entry {
+        Entry {
+            id,
+            name: name.to_string(),
+            extension: extension.map(str::to_string),
+            entry_type: EntryType::Document,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_export_file_name() {
+        assert_eq!(export_file_name(&document(7, "Invoice 0001", Some("pdf"))), "Invoice 0001.pdf");
+        assert_eq!(export_file_name(&document(7, "scan.PDF", Some("pdf"))), "scan.PDF");
+        assert_eq!(export_file_name(&document(7, "Q1: A/B", None)), "Q1_ A_B");
+        assert_eq!(export_file_name(&document(7, " ", Some("tif"))), "7.tif");
+    }
+
+    #[test]
+    fn test_collision_policies() {
+        let directory = Path::new("/exports");
+        let taken = ["report.pdf", "report (2).pdf", "report [42].pdf"];
+        let is_taken = |name: &str| taken.iter().any(|taken| taken.eq_ignore_ascii_case(name));
+
+        let resolve = |policy: CollisionPolicy, name: &str| policy.resolve(name, 42, directory, is_taken);
+        assert_eq!(resolve(CollisionPolicy::Fail, "notes.txt").unwrap().as_deref(), Some("notes.txt"));
+        assert_eq!(resolve(CollisionPolicy::NumberedSuffix, "Report.pdf").unwrap().as_deref(), Some("Report (3).pdf"));
+        assert_eq!(resolve(CollisionPolicy::AppendEntryId, "report.pdf").unwrap().as_deref(), Some("report [42] (2).pdf"));
+        assert_eq!(resolve(CollisionPolicy::AppendEntryId, "report (2).pdf").unwrap().as_deref(), Some("report (2) [42].pdf"));
+        assert_eq!(resolve(CollisionPolicy::Skip, "report.pdf").unwrap(), None);
+        assert_eq!(resolve(CollisionPolicy::Overwrite, "notes.txt").unwrap().as_deref(), Some("notes.txt"));
+        assert_eq!(resolve(CollisionPolicy::Overwrite, "report.pdf").unwrap().as_deref(), Some("report [42] (2).pdf"));
+
+        let error = resolve(CollisionPolicy::Fail, "report.pdf").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::FileExists(path) if path.ends_with("report.pdf")));
+        assert_eq!(with_suffix(".profile", " (2)"), ".profile (2)");
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_tree_plan_resolves_folder_collisions() {
+        let folder = |id: i64, name: &str| Entry {
+            id,
+            name: name.to_string(),
+            parent_id: 1,
+            is_container: true,
+            entry_type: EntryType::Folder,
+            ..Default::default()
+        };
+        let target = std::env::temp_dir().join(format!("laserfiche-rs-folder-collisions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&target);
+
+        let mut manifest = ExportManifest::new();
+        let mut plan = TreePlan::new(FolderId(1), &target, CollisionPolicy::NumberedSuffix).unwrap();
+        for entry in [folder(2, "a:b"), folder(3, "a_b"), folder(4, "Reports"), folder(5, "reports"), folder(6, "."), folder(7, " ")] {
+            assert!(plan.add(entry, &mut manifest).unwrap().is_none());
+        }
+        let directory = |id: i64| plan.directories[&id].strip_prefix(&target).unwrap().display().to_string();
+        assert_eq!(directory(2), "a_b");
+        assert_eq!(directory(3), "a_b (2)");
+        assert_eq!(directory(4), "Reports");
+        assert_eq!(directory(5), "reports (2)");
+        assert_eq!((directory(6), directory(7)), ("6".to_string(), "7".to_string()));
+
+        // A rerun merges into the directories already on disk
+        let mut rerun = TreePlan::new(FolderId(1), &target, CollisionPolicy::Skip).unwrap();
+        rerun.add(folder(2, "a:b"), &mut manifest).unwrap();
+        rerun.add(folder(3, "a_b"), &mut manifest).unwrap();
+        assert_eq!(rerun.directories[&2], target.join("a_b"));
+        assert!(!rerun.directories.contains_key(&3));
+
+        let mut failing = TreePlan::new(FolderId(1), &target, CollisionPolicy::Fail).unwrap();
+        failing.add(folder(4, "Reports"), &mut manifest).unwrap();
+        assert!(failing.add(folder(5, "reports"), &mut manifest).is_err());
+        assert!(manifest.entries.is_empty());
+        std::fs::remove_dir_all(&target).unwrap();
+    }
+
+    #[test]
+    fn test_export_manifest_csv() {
+        let mut manifest = ExportManifest::new();
+        manifest.entries.push(ExportedEntry {
+            entry_id: 42,
+            repository_path: "\\Invoices\\report".to_string(),
+            target_path: "/exports/Invoices/report [42].pdf".to_string(),
+            outcome: ExportOutcome::Renamed,
+            bytes: 1024,
+        });
+
+        let csv = manifest.to_csv();
+        assert_eq!(csv.lines().nth(1), Some("42,\\Invoices\\report,/exports/Invoices/report [42].pdf,renamed,1024"));
+        let parsed: ExportManifest = ExportManifest::from_json(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(manifest.to_json().unwrap().contains("\"outcome\": \"renamed\""));
+    }
+}
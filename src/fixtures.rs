@@ -0,0 +1,242 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Realistic, deterministic values for tests, enabled by the `test-utils`
+//! feature
+//!
+//! Every fixture is built from fixed ids, names, and timestamps, so
+//! downstream tests can assert on them without copying this crate's struct
+//! layouts.
+
+use crate::laserfiche::{
+    Auth, Entries, Entry, EntryType, FieldType, FolderId, LFAPIError, LFApiServer, ListOptions, MetadataResult, MetadataResultFieldValue,
+    MetadataResultValue,
+};
+use crate::clock;
+
+/// Creation and modification time of fixture entries, and issue time of
+/// `Auth::fixture`
+pub const FIXTURE_TIME: &str = "2024-01-15T09:30:00Z";
+
+/// `FIXTURE_TIME` as seconds since the Unix epoch, e.g. for `MockClock::at_unix`
+pub const FIXTURE_UNIX: u64 = 1_705_311_000;
+
+/// Folder that fixture documents are filed in
+pub const FIXTURE_FOLDER_ID: i64 = 10;
+
+/// Id of `Entry::fixture`; `Entries::fixture_page` numbers from here
+pub const FIXTURE_DOCUMENT_ID: i64 = 100;
+
+impl LFApiServer {
+    /// A self-hosted server at `lf.example.com` with repository `fixture-repo`
+    pub fn fixture() -> Self {
+        LFApiServer {
+            address: "lf.example.com".to_string(),
+            repository: "fixture-repo".to_string(),
+        }
+    }
+}
+
+impl Auth {
+    /// A one-hour bearer token for `LFApiServer::fixture`, issued at `FIXTURE_TIME`
+    ///
+    /// Pair with `MockClock::at_unix(FIXTURE_UNIX)` to control its expiry;
+    /// use `fixture_fresh` where the system clock decides.
+    pub fn fixture() -> Self {
+        Auth {
+            odata_context: String::new(),
+            access_token: "fixture-access-token".to_string(),
+            expires_in: 3600,
+            token_type: "bearer".to_string(),
+            username: "fixture-user".to_string(),
+            password: "fixture-password".to_string(),
+            timestamp: FIXTURE_UNIX as i64,
+            api_server: LFApiServer::fixture(),
+            cloud_credentials: None,
+        }
+    }
+
+    /// `Auth::fixture` issued now by the system clock
+    pub fn fixture_fresh() -> Self {
+        Auth { timestamp: clock::unix_seconds(clock::system_now()), ..Self::fixture() }
+    }
+}
+
+impl Entry {
+    /// A 24 KiB PDF named `Invoice 0001` with the `Invoice` template, filed
+    /// in folder `FIXTURE_FOLDER_ID`
+    pub fn fixture() -> Self {
+        Entry {
+            id: FIXTURE_DOCUMENT_ID,
+            name: "Invoice 0001".to_string(),
+            parent_id: FIXTURE_FOLDER_ID,
+            full_path: "\\Invoices\\Invoice 0001".to_string(),
+            folder_path: "\\Invoices".to_string(),
+            creator: "fixture-user".to_string(),
+            creation_time: FIXTURE_TIME.to_string(),
+            last_modified_time: FIXTURE_TIME.to_string(),
+            entry_type: EntryType::Document,
+            is_container: false,
+            is_leaf: true,
+            template_name: Some("Invoice".to_string()),
+            template_id: 1,
+            template_field_names: Some(vec!["Invoice Number".to_string(), "Vendor".to_string()]),
+            volume_name: "DEFAULT".to_string(),
+            row_number: 1,
+            fields: None,
+            elec_document_size: Some(24_576),
+            extension: Some("pdf".to_string()),
+        }
+    }
+
+    /// The `\Invoices` folder, id `FIXTURE_FOLDER_ID`, below the root
+    pub fn fixture_folder() -> Self {
+        Entry {
+            id: FIXTURE_FOLDER_ID,
+            name: "Invoices".to_string(),
+            parent_id: 1,
+            full_path: "\\Invoices".to_string(),
+            folder_path: "\\".to_string(),
+            creator: "fixture-user".to_string(),
+            creation_time: FIXTURE_TIME.to_string(),
+            last_modified_time: FIXTURE_TIME.to_string(),
+            entry_type: EntryType::Folder,
+            is_container: true,
+            is_leaf: false,
+            template_name: None,
+            template_id: 0,
+            template_field_names: None,
+            volume_name: "DEFAULT".to_string(),
+            row_number: 1,
+            fields: None,
+            elec_document_size: None,
+            extension: None,
+        }
+    }
+}
+
+impl Entries {
+    /// One page of `n` fixture documents numbered `Invoice 0001`, `Invoice 0002`, ...
+    pub fn fixture_page(n: usize) -> Self {
+        let value = (0..n as i64)
+            .map(|i| Entry {
+                id: FIXTURE_DOCUMENT_ID + i,
+                name: format!("Invoice {:04}", i + 1),
+                full_path: format!("\\Invoices\\Invoice {:04}", i + 1),
+                row_number: i + 1,
+                ..Entry::fixture()
+            })
+            .collect();
+        Entries { value, odata_next_link: None, odata_count: Some(n as i64) }
+    }
+
+    /// Point this page at a next page of the fixture folder's children,
+    /// starting after `skip` entries, on `api_server`
+    pub fn with_next_link(mut self, api_server: &LFApiServer, skip: u32) -> Self {
+        let url = Entry::build_list_url(api_server, FolderId(FIXTURE_FOLDER_ID), &ListOptions::new().skip(skip))
+            .expect("fixture folder url is valid");
+        self.odata_next_link = Some(url);
+        self
+    }
+}
+
+impl MetadataResult {
+    /// Single-valued `String` fields, numbered from field id 1
+    pub fn fixture(fields: &[(&str, &str)]) -> Self {
+        let value = fields
+            .iter()
+            .zip(1..)
+            .map(|((name, value), field_id)| MetadataResultValue {
+                field_name: name.to_string(),
+                field_type: FieldType::String,
+                group_id: None,
+                field_id,
+                is_multi_value: false,
+                is_required: false,
+                values: vec![MetadataResultFieldValue { value: Some(value.to_string()), position: 1 }],
+            })
+            .collect();
+        MetadataResult { value }
+    }
+}
+
+impl LFAPIError {
+    /// A problem-details error as the Repository API returns it
+    pub fn fixture(status: u16, title: &str) -> Self {
+        LFAPIError {
+            type_field: Some(format!("https://httpstatuses.io/{}", status)),
+            title: Some(title.to_string()),
+            status: Some(status as i64),
+            detail: None,
+            instance: None,
+            operation_id: Some("fixture-operation".to_string()),
+            error_source: Some("Api".to_string()),
+            error_code: None,
+            trace_id: Some("fixture-trace".to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::fixture(404, "Entry not found.")
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::fixture(401, "Access token is invalid or expired.")
+    }
+
+    pub fn forbidden() -> Self {
+        Self::fixture(403, "Access denied.")
+    }
+
+    pub fn conflict() -> Self {
+        Self::fixture(409, "An object with the same name already exists.")
+    }
+
+    pub fn rate_limited() -> Self {
+        Self::fixture(429, "Too many requests.")
+    }
+
+    pub fn server_error() -> Self {
+        Self::fixture(500, "Internal server error.")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{Clock, MockClock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_fixture_page() {
+        let page = Entries::fixture_page(3);
+        let ids: Vec<i64> = page.value.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![100, 101, 102]);
+        assert_eq!(page.value[2].name, "Invoice 0003");
+        assert!(page.next_link().is_none());
+
+        let page = page.with_next_link(&LFApiServer::fixture(), 3);
+        let next_link = page.next_link().unwrap();
+        assert!(next_link.as_str().ends_with("/Entries/10/Laserfiche.Repository.Folder/children?$skip=3"));
+        assert!(Entry::build_next_link_url(&LFApiServer::fixture(), &next_link).is_ok());
+    }
+
+    #[test]
+    fn test_fixture_errors() {
+        assert!(LFAPIError::not_found().is_not_found());
+        assert!(LFAPIError::forbidden().is_forbidden());
+        assert!(LFAPIError::rate_limited().is_rate_limited());
+        assert_eq!(LFAPIError::conflict().to_string(), "An object with the same name already exists.");
+    }
+
+    #[test]
+    fn test_fixture_auth() {
+        let clock = MockClock::at_unix(FIXTURE_UNIX);
+        assert!(!Auth::fixture().is_expired_at(clock.now(), Duration::from_secs(30)));
+        clock.advance(Duration::from_secs(3600));
+        assert!(Auth::fixture().is_expired_at(clock.now(), Duration::from_secs(30)));
+        assert!(!Auth::fixture_fresh().is_expired(Duration::from_secs(30)));
+    }
+}
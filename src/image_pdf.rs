@@ -0,0 +1,395 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::Result;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{Auth, Entry, FolderId, ImportResultOrError, LFApiServer};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{mime, validation};
+
+use lopdf::{Dictionary, Document, Object, Stream};
+use std::io::Cursor;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use tiff::decoder::ifd::Value;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::{CompressionMethod, Tag};
+use tiff::ColorType;
+
+/// Resolution assumed when a scan doesn't record one
+const DEFAULT_DPI: f64 = 72.0;
+
+/// Scan formats `image_to_pdf` can convert
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Tiff,
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// Recognise a scan by its leading bytes
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+            Some(ImageFormat::Tiff)
+        } else {
+            None
+        }
+    }
+}
+
+/// One page's image XObject, ready to place on a page
+struct PageImage {
+    stream: Stream,
+    width: u32,
+    height: u32,
+    dpi: (f64, f64),
+}
+
+fn tiff_error(error: tiff::TiffError) -> crate::laserfiche::Error {
+    format!("TIFF error: {}", error).into()
+}
+
+/// Convert a TIFF or JPEG scan to PDF, one page per image
+///
+/// JPEG data is embedded as-is. TIFF pages are decoded and re-compressed,
+/// except single-strip CCITT Group 4 pages which are embedded as-is.
+/// Page sizes follow the image resolution, or 72 dpi when none is recorded.
+pub fn image_to_pdf(bytes: &[u8]) -> Result<Vec<u8>> {
+    let images = match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Jpeg) => vec![jpeg_image(bytes)?],
+        Some(ImageFormat::Tiff) => tiff_images(bytes)?,
+        None => return Err("Not a TIFF or JPEG image".into()),
+    };
+    build_pdf(images)
+}
+
+fn image_stream(width: u32, height: u32, color_space: &str, bits: u8, content: Vec<u8>) -> Stream {
+    let mut dict = Dictionary::new();
+    dict.set("Type", Object::Name(b"XObject".to_vec()));
+    dict.set("Subtype", Object::Name(b"Image".to_vec()));
+    dict.set("Width", width as i64);
+    dict.set("Height", height as i64);
+    dict.set("ColorSpace", Object::Name(color_space.as_bytes().to_vec()));
+    dict.set("BitsPerComponent", bits as i64);
+    Stream::new(dict, content)
+}
+
+/// Embed a JPEG unchanged, reading its size from the frame header
+fn jpeg_image(bytes: &[u8]) -> Result<PageImage> {
+    let mut dpi = (DEFAULT_DPI, DEFAULT_DPI);
+    let mut adobe = false;
+    let mut position = 2;
+
+    while position + 4 <= bytes.len() {
+        if bytes[position] != 0xFF {
+            break;
+        }
+        let marker = bytes[position + 1];
+        let length = u16::from_be_bytes([bytes[position + 2], bytes[position + 3]]) as usize;
+        let segment = bytes.get(position + 4..position + 2 + length).unwrap_or_default();
+
+        match marker {
+            // JFIF header: units, then horizontal and vertical density
+            0xE0 if segment.len() >= 12 && segment.starts_with(b"JFIF\0") => {
+                let x = u16::from_be_bytes([segment[8], segment[9]]) as f64;
+                let y = u16::from_be_bytes([segment[10], segment[11]]) as f64;
+                match segment[7] {
+                    1 if x > 0.0 && y > 0.0 => dpi = (x, y),
+                    2 if x > 0.0 && y > 0.0 => dpi = (x * 2.54, y * 2.54),
+                    _ => {}
+                }
+            }
+            0xEE if segment.starts_with(b"Adobe") => adobe = true,
+            // Start of frame, excluding DHT, JPG and DAC
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) && segment.len() >= 6 => {
+                let height = u16::from_be_bytes([segment[1], segment[2]]) as u32;
+                let width = u16::from_be_bytes([segment[3], segment[4]]) as u32;
+                let (color_space, decode) = match segment[5] {
+                    1 => ("DeviceGray", None),
+                    3 => ("DeviceRGB", None),
+                    // Adobe writes CMYK JPEGs inverted
+                    4 if adobe => ("DeviceCMYK", Some(vec![1, 0, 1, 0, 1, 0, 1, 0])),
+                    4 => ("DeviceCMYK", None),
+                    components => return Err(format!("Unsupported JPEG with {} components", components).into()),
+                };
+                if width == 0 || height == 0 {
+                    return Err("Unsupported JPEG without frame dimensions".into());
+                }
+
+                let mut stream = image_stream(width, height, color_space, segment[0], bytes.to_vec());
+                stream.dict.set("Filter", Object::Name(b"DCTDecode".to_vec()));
+                if let Some(decode) = decode {
+                    stream.dict.set("Decode", decode.into_iter().map(Object::Integer).collect::<Vec<_>>());
+                }
+                // Already compressed; recompressing only costs time
+                stream.allows_compression = false;
+                return Ok(PageImage { stream, width, height, dpi });
+            }
+            _ => {}
+        }
+        position += 2 + length;
+    }
+    Err("Invalid JPEG: no frame header".into())
+}
+
+/// Every page of a TIFF, as images
+fn tiff_images(bytes: &[u8]) -> Result<Vec<PageImage>> {
+    let mut decoder = Decoder::new(Cursor::new(bytes)).map_err(tiff_error)?;
+    let mut images = Vec::new();
+    loop {
+        images.push(tiff_page(&mut decoder, bytes)?);
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image().map_err(tiff_error)?;
+    }
+    Ok(images)
+}
+
+fn tiff_dpi(decoder: &mut Decoder<Cursor<&[u8]>>) -> (f64, f64) {
+    let mut resolution = |tag| match decoder.find_tag(tag) {
+        Ok(Some(Value::Rational(n, d))) if n > 0 && d > 0 => Some(n as f64 / d as f64),
+        Ok(Some(Value::RationalBig(n, d))) if n > 0 && d > 0 => Some(n as f64 / d as f64),
+        _ => None,
+    };
+    let (x, y) = (resolution(Tag::XResolution), resolution(Tag::YResolution));
+    let scale = match decoder.find_tag_unsigned::<u16>(Tag::ResolutionUnit) {
+        Ok(Some(3)) => 2.54,
+        Ok(Some(1)) => return (DEFAULT_DPI, DEFAULT_DPI),
+        _ => 1.0,
+    };
+    match (x, y) {
+        (Some(x), Some(y)) => (x * scale, y * scale),
+        (Some(x), None) => (x * scale, x * scale),
+        _ => (DEFAULT_DPI, DEFAULT_DPI),
+    }
+}
+
+fn tiff_page(decoder: &mut Decoder<Cursor<&[u8]>>, bytes: &[u8]) -> Result<PageImage> {
+    let (width, height) = decoder.dimensions().map_err(tiff_error)?;
+    let dpi = tiff_dpi(decoder);
+    let compression = decoder.find_tag_unsigned::<u16>(Tag::Compression).map_err(tiff_error)?;
+
+    if compression == Some(CompressionMethod::Fax4.to_u16()) {
+        let stream = group4_stream(decoder, bytes, width, height)?;
+        return Ok(PageImage { stream, width, height, dpi });
+    }
+
+    let color_type = decoder.colortype().map_err(tiff_error)?;
+    let pixels = decoder.read_image().map_err(tiff_error)?;
+    let (color_space, bits, content) = match (color_type, pixels) {
+        (ColorType::Gray(bits @ (1 | 2 | 4 | 8)), DecodingResult::U8(data)) => ("DeviceGray", bits, data),
+        (ColorType::RGB(8), DecodingResult::U8(data)) => ("DeviceRGB", 8, data),
+        (ColorType::CMYK(8), DecodingResult::U8(data)) => ("DeviceCMYK", 8, data),
+        (ColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ("DeviceRGB", 8, data.chunks_exact(4).flat_map(|pixel| pixel[..3].to_vec()).collect())
+        }
+        (ColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ("DeviceGray", 8, data.chunks_exact(2).map(|pixel| pixel[0]).collect())
+        }
+        (ColorType::Gray(16), DecodingResult::U16(data)) => ("DeviceGray", 16, big_endian(&data)),
+        (ColorType::RGB(16), DecodingResult::U16(data)) => ("DeviceRGB", 16, big_endian(&data)),
+        (color_type, _) => return Err(format!("Unsupported TIFF color type {:?}", color_type).into()),
+    };
+
+    let mut stream = image_stream(width, height, color_space, bits, content);
+    stream.compress().map_err(|e| format!("PDF error: {}", e))?;
+    Ok(PageImage { stream, width, height, dpi })
+}
+
+fn big_endian(samples: &[u16]) -> Vec<u8> {
+    samples.iter().flat_map(|sample| sample.to_be_bytes()).collect()
+}
+
+/// Embed a single-strip CCITT Group 4 page without decoding it
+fn group4_stream(decoder: &mut Decoder<Cursor<&[u8]>>, bytes: &[u8], width: u32, height: u32) -> Result<Stream> {
+    let offsets = decoder.get_tag_u64_vec(Tag::StripOffsets).map_err(tiff_error)?;
+    let counts = decoder.get_tag_u64_vec(Tag::StripByteCounts).map_err(tiff_error)?;
+    let (&[offset], &[count]) = (offsets.as_slice(), counts.as_slice()) else {
+        return Err("Unsupported TIFF: Group 4 image split into several strips".into());
+    };
+    let mut data = usize::try_from(offset).ok()
+        .zip(usize::try_from(count).ok())
+        .and_then(|(offset, count)| bytes.get(offset..offset.checked_add(count)?))
+        .ok_or("Invalid TIFF: strip lies outside the file")?
+        .to_vec();
+    // FillOrder 2 stores the least significant bit first
+    if decoder.find_tag_unsigned::<u16>(Tag::FillOrder).map_err(tiff_error)? == Some(2) {
+        data.iter_mut().for_each(|byte| *byte = byte.reverse_bits());
+    }
+    let black_is_zero = decoder.find_tag_unsigned::<u16>(Tag::PhotometricInterpretation).map_err(tiff_error)? == Some(1);
+
+    let mut params = Dictionary::new();
+    params.set("K", -1);
+    params.set("Columns", width as i64);
+    params.set("Rows", height as i64);
+    params.set("BlackIs1", black_is_zero);
+
+    let mut stream = image_stream(width, height, "DeviceGray", 1, data);
+    stream.dict.set("Filter", Object::Name(b"CCITTFaxDecode".to_vec()));
+    stream.dict.set("DecodeParms", params);
+    stream.allows_compression = false;
+    Ok(stream)
+}
+
+/// A PDF with each image filling its own page
+fn build_pdf(images: Vec<PageImage>) -> Result<Vec<u8>> {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let mut kids = Vec::with_capacity(images.len());
+
+    for image in images {
+        let page_width = image.width as f64 * 72.0 / image.dpi.0;
+        let page_height = image.height as f64 * 72.0 / image.dpi.1;
+        let image_id = document.add_object(image.stream);
+
+        let mut xobjects = Dictionary::new();
+        xobjects.set("Im0", image_id);
+        let mut resources = Dictionary::new();
+        resources.set("XObject", xobjects);
+
+        let content = format!("q {:.4} 0 0 {:.4} 0 0 cm /Im0 Do Q", page_width, page_height);
+        let content_id = document.add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+
+        let mut page = Dictionary::new();
+        page.set("Type", Object::Name(b"Page".to_vec()));
+        page.set("Parent", pages_id);
+        page.set("MediaBox", vec![0.into(), 0.into(), Object::Real(page_width as f32), Object::Real(page_height as f32)]);
+        page.set("Resources", resources);
+        page.set("Contents", content_id);
+        kids.push(Object::Reference(document.add_object(page)));
+    }
+
+    let mut pages = Dictionary::new();
+    pages.set("Type", Object::Name(b"Pages".to_vec()));
+    pages.set("Count", kids.len() as i64);
+    pages.set("Kids", kids);
+    document.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", pages_id);
+    let catalog_id = document.add_object(catalog);
+    document.trailer.set("Root", catalog_id);
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(|e| format!("PDF error: {}", e))?;
+    Ok(bytes)
+}
+
+/// Import a file, converting TIFF and JPEG scans to PDF first
+///
+/// Converted scans are imported as `{stem}.pdf` from `file_name`; any other
+/// file is imported unchanged, like `Entry::import`.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local file to import
+/// * `file_name` - Name for the imported document
+/// * `root_id` - Destination folder
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_as_pdf(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    file_name: String,
+    root_id: impl Into<FolderId>
+) -> Result<ImportResultOrError> {
+    let root_id = validation::validate_entry_id(root_id.into().as_i64())?;
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let validated_name = validation::validate_file_name(&file_name)?;
+    let content = std::fs::read(&validated_path)?;
+
+    if ImageFormat::detect(&content).is_none() {
+        let mime_type = mime::detect_mime_type_with_content(&validated_name, &content);
+        return Entry::import_content(api_server, auth, content, &validated_name, root_id, &mime_type).await;
+    }
+
+    let stem = Path::new(&validated_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(&validated_name);
+    let pdf_name = validation::validate_file_name(&format!("{}.pdf", stem))?;
+    let pdf = image_to_pdf(&content)?;
+    Entry::import_content(api_server, auth, pdf, &pdf_name, root_id, "application/pdf").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiff::encoder::{colortype, Rational, TiffEncoder};
+    use tiff::tags::ResolutionUnit;
+
+    fn sample_tiff(pages: u32) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        for _ in 0..pages {
+            let mut image = encoder.new_image::<colortype::Gray8>(300, 600).unwrap();
+            image.resolution(ResolutionUnit::Inch, Rational { n: 300, d: 1 });
+            image.write_data(&vec![128u8; 300 * 600]).unwrap();
+        }
+        bytes.into_inner()
+    }
+
+    /// Headers of a 200x100 dpi RGB JPEG; the scan data is never decoded
+    fn sample_jpeg() -> Vec<u8> {
+        let mut bytes = vec![0xFF, 0xD8];
+        bytes.extend([0xFF, 0xE0, 0x00, 0x10]);
+        bytes.extend(b"JFIF\0");
+        bytes.extend([1, 1, 1, 0x00, 0xC8, 0x00, 0x64, 0, 0]);
+        bytes.extend([0xFF, 0xC0, 0x00, 0x11, 8, 0x00, 0x64, 0x00, 0xC8, 3]);
+        bytes.extend([1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1]);
+        bytes.extend([0xFF, 0xD9]);
+        bytes
+    }
+
+    fn media_boxes(pdf: &[u8]) -> Vec<(f32, f32)> {
+        let document = Document::load_mem(pdf).unwrap();
+        document.get_pages().values()
+            .map(|&page_id| {
+                let media_box = document.get_dictionary(page_id).unwrap()
+                    .get(b"MediaBox").unwrap()
+                    .as_array().unwrap();
+                (media_box[2].as_float().unwrap(), media_box[3].as_float().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(ImageFormat::detect(&sample_tiff(1)), Some(ImageFormat::Tiff));
+        assert_eq!(ImageFormat::detect(&sample_jpeg()), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::detect(b"%PDF-1.5"), None);
+    }
+
+    #[test]
+    fn test_tiff_pages_sized_by_resolution() {
+        let pdf = image_to_pdf(&sample_tiff(2)).unwrap();
+        assert_eq!(media_boxes(&pdf), vec![(72.0, 144.0), (72.0, 144.0)]);
+    }
+
+    #[test]
+    fn test_jpeg_embedded_unchanged() {
+        let jpeg = sample_jpeg();
+        let pdf = image_to_pdf(&jpeg).unwrap();
+        assert_eq!(media_boxes(&pdf), vec![(72.0, 72.0)]);
+
+        let document = Document::load_mem(&pdf).unwrap();
+        let image = document.objects.values()
+            .filter_map(|object| object.as_stream().ok())
+            .find(|stream| stream.dict.get(b"Filter").and_then(Object::as_name).ok() == Some(b"DCTDecode"))
+            .unwrap();
+        assert_eq!(image.content, jpeg);
+        assert_eq!(image.dict.get(b"ColorSpace").unwrap().as_name().unwrap(), b"DeviceRGB");
+    }
+
+    #[test]
+    fn test_rejects_other_content() {
+        assert!(image_to_pdf(b"plain text").is_err());
+        assert!(image_to_pdf(&[0xFF, 0xD8, 0xFF, 0xD9]).is_err());
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::{self, Instant};
+use crate::config::ConcurrencyConfig;
+use crate::inspect::{self, ContentInspector, ContentRejected};
+use crate::laserfiche::{
+    Auth, Entry, ErrorKind, FolderId, ImportRequest, ImportResult, ImportResultOrError, LFAPIError, LFApiServer, Result,
+};
+use crate::outbox;
+use crate::pipeline::Pipeline;
+use crate::stats::{TransferStats, TransferStatsSnapshot};
+use crate::throttle;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One file for `BulkImporter` to import
+#[derive(Debug, Clone)]
+pub struct ImportJob {
+    pub path: PathBuf,
+    pub folder_id: FolderId,
+    /// Template, field values, and tags for the new document
+    pub request: ImportRequest,
+    /// Name for the document; the file's name when unset
+    pub file_name: Option<String>,
+}
+
+impl ImportJob {
+    pub fn new(path: impl Into<PathBuf>, folder_id: impl Into<FolderId>) -> Self {
+        ImportJob { path: path.into(), folder_id: folder_id.into(), request: ImportRequest::new(), file_name: None }
+    }
+
+    /// Assign `request`'s template, fields, and tags on import
+    pub fn request(mut self, request: ImportRequest) -> Self {
+        self.request = request;
+        self
+    }
+
+    /// Import under `file_name` instead of the file's own name
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    /// The name the document is imported under
+    pub fn document_name(&self) -> String {
+        self.file_name.clone().unwrap_or_else(|| {
+            self.path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+        })
+    }
+}
+
+impl<P: Into<PathBuf>> From<(P, FolderId, ImportRequest)> for ImportJob {
+    fn from((path, folder_id, request): (P, FolderId, ImportRequest)) -> Self {
+        ImportJob::new(path, folder_id).request(request)
+    }
+}
+
+/// A file imported by `BulkImporter::run`
+#[derive(Debug, Clone)]
+pub struct ImportSuccess {
+    pub path: PathBuf,
+    pub result: ImportResult,
+    /// Attempts made, including the successful one
+    pub attempts: u32,
+}
+
+/// A file `BulkImporter::run` could not import
+#[derive(Debug, Clone)]
+pub struct ImportFailure {
+    pub path: PathBuf,
+    pub folder_id: FolderId,
+    pub error: String,
+    /// Attempts made before giving up; 0 if the inspector rejected the file
+    pub attempts: u32,
+    /// Why the importer's `ContentInspector` vetoed the file, which was
+    /// then never uploaded
+    pub rejected: Option<ContentRejected>,
+}
+
+/// Consolidated result of `BulkImporter::run`
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    pub imported: Vec<ImportSuccess>,
+    pub failed: Vec<ImportFailure>,
+    /// Jobs not started because the pipeline's budget ran out
+    pub deferred: Vec<ImportJob>,
+    /// Counters for the run; retries count repeated attempts
+    pub stats: TransferStatsSnapshot,
+}
+
+impl ImportReport {
+    /// True when every job was imported
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.deferred.is_empty()
+    }
+}
+
+/// Imports many files concurrently, retrying transient failures
+///
+/// Jobs run through a `Pipeline`, so at most its `max_in_flight` uploads
+/// are in flight at once. A job that fails because the server could not be
+/// reached, is busy (5xx or 429), or has the target locked is retried with
+/// a growing delay; other refusals fail the job at once.
+#[derive(Clone)]
+pub struct BulkImporter {
+    pipeline: Pipeline,
+    max_attempts: u32,
+    retry_delay: Duration,
+    inspector: Option<Arc<dyn ContentInspector>>,
+}
+
+impl std::fmt::Debug for BulkImporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BulkImporter")
+            .field("pipeline", &self.pipeline)
+            .field("max_attempts", &self.max_attempts)
+            .field("retry_delay", &self.retry_delay)
+            .field("inspector", &self.inspector.is_some())
+            .finish()
+    }
+}
+
+impl Default for BulkImporter {
+    fn default() -> Self {
+        Self::with_pipeline(Pipeline::default())
+    }
+}
+
+impl BulkImporter {
+    /// Import with at most `concurrency` uploads in flight
+    pub fn new(concurrency: usize) -> Self {
+        let config = ConcurrencyConfig { max_in_flight: concurrency, ..Default::default() };
+        Self::with_pipeline(Pipeline::new(config))
+    }
+
+    /// Import through `pipeline`, e.g. one with a throttle, budget, or
+    /// progress observer
+    pub fn with_pipeline(pipeline: Pipeline) -> Self {
+        BulkImporter { pipeline, max_attempts: 3, retry_delay: Duration::from_millis(500), inspector: None }
+    }
+
+    /// Try each job up to `max_attempts` times in all (default 3)
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Wait `retry_delay` times the attempt number before each retry
+    /// (default 500 ms)
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Scan each file with `inspector` before uploading it; files it
+    /// rejects are listed in `ImportReport::failed` with `rejected` set
+    pub fn inspector(mut self, inspector: Arc<dyn ContentInspector>) -> Self {
+        self.inspector = Some(inspector);
+        self
+    }
+
+    /// The pipeline jobs run through
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// Import every job, in any order
+    ///
+    /// Every job ends up in exactly one of the report's lists; with a
+    /// pipeline budget, those not started in time are listed as `deferred`.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `jobs` - Files to import, e.g. `ImportJob`s or `(path, folder, request)` tuples
+    pub async fn run<I>(&self, api_server: LFApiServer, auth: Auth, jobs: I) -> ImportReport
+    where
+        I: IntoIterator,
+        I::Item: Into<ImportJob>,
+        I::IntoIter: Send + 'static,
+    {
+        let mut report = ImportReport::default();
+        let jobs: Vec<ImportJob> = jobs.into_iter().map(Into::into).collect();
+        let paths: Vec<(PathBuf, FolderId)> = jobs.iter().map(|job| (job.path.clone(), job.folder_id)).collect();
+
+        let stats = Arc::clone(self.pipeline.stats());
+        let (max_attempts, retry_delay) = (self.max_attempts, self.retry_delay);
+        let inspector = self.inspector.clone();
+        let mut deferred = Vec::new();
+        self.pipeline.run_within_budget(jobs, move |job| {
+            let api_server = api_server.clone();
+            let auth = auth.clone();
+            let stats = Arc::clone(&stats);
+            let inspector = inspector.clone();
+            async move {
+                if let Some(inspector) = inspector {
+                    inspect_one(inspector.as_ref(), &job).await?;
+                }
+                import_one(api_server, auth, job, max_attempts, retry_delay, &stats).await
+            }
+        }, |outcome| match outcome {
+            Ok(Ok(success)) => report.imported.push(success),
+            Ok(Err(failure)) => {
+                self.pipeline.stats().record_failure();
+                report.failed.push(failure);
+            }
+            Err(panic) => {
+                let (path, folder_id) = paths[panic.index].clone();
+                report.failed.push(ImportFailure { path, folder_id, error: panic.to_string(), attempts: 1, rejected: None });
+            }
+        }, |_, job| deferred.push(job)).await;
+
+        report.deferred = deferred;
+        report.stats = self.pipeline.stats().snapshot();
+        report
+    }
+}
+
+async fn import_one(
+    api_server: LFApiServer,
+    auth: Auth,
+    job: ImportJob,
+    max_attempts: u32,
+    retry_delay: Duration,
+    stats: &TransferStats
+) -> std::result::Result<ImportSuccess, ImportFailure> {
+    let started = Instant::now();
+    let size = std::fs::metadata(&job.path).map_or(0, |metadata| metadata.len());
+    let fail = |error: String, attempts| {
+        ImportFailure { path: job.path.clone(), folder_id: job.folder_id, error, attempts, rejected: None }
+    };
+
+    let mut attempt = 1;
+    loop {
+        // Boxed so the large upload future stays off the worker's stack
+        let outcome = Box::pin(import_attempt(api_server.clone(), auth.clone(), &job)).await;
+
+        let transient = match outcome {
+            Ok(ImportResultOrError::ImportResult(result)) => {
+                stats.record_file(size, started.elapsed());
+                return Ok(ImportSuccess { path: job.path, result, attempts: attempt });
+            }
+            Ok(ImportResultOrError::LFAPIError(error)) if is_transient_refusal(&error) => error.to_string(),
+            Ok(ImportResultOrError::LFAPIError(error)) => return Err(fail(describe(&error), attempt)),
+            Err(error) if outbox::is_unreachable(&error) => error.to_string(),
+            Err(error) => return Err(fail(error.to_string(), attempt)),
+        };
+
+        if attempt >= max_attempts {
+            return Err(fail(transient, attempt));
+        }
+        stats.record_retry();
+        clock::sleep(retry_delay * attempt).await;
+        attempt += 1;
+        throttle::acquire_scoped().await;
+    }
+}
+
+/// Fail `job` without uploading it if `inspector` rejects its content
+async fn inspect_one(inspector: &dyn ContentInspector, job: &ImportJob) -> std::result::Result<(), ImportFailure> {
+    inspect::check_file(inspector, &job.document_name(), &job.path).await.map_err(|error| {
+        let rejected = match error.kind() {
+            ErrorKind::ContentRejected(_, reason) => Some(ContentRejected::new(reason.clone())),
+            _ => None,
+        };
+        ImportFailure { path: job.path.clone(), folder_id: job.folder_id, error: error.to_string(), attempts: 0, rejected }
+    })
+}
+
+async fn import_attempt(api_server: LFApiServer, auth: Auth, job: &ImportJob) -> Result<ImportResultOrError> {
+    let file_path = path_string(&job.path)?;
+    Entry::import_with_request(api_server, auth, file_path, job.document_name(), job.folder_id, &job.request).await
+}
+
+fn path_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("'{}' is not valid UTF-8", path.display()).into())
+}
+
+/// Refusals the server may not repeat: overload, rate limiting, or a lock
+fn is_transient_refusal(error: &LFAPIError) -> bool {
+    error.is_server_error() || error.is_rate_limited() || error.is_entry_locked()
+}
+
+fn describe(error: &LFAPIError) -> String {
+    error.detail.clone()
+        .or_else(|| error.title.clone())
+        .unwrap_or_else(|| format!("API error (status {:?})", error.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_job_document_name() {
+        let job = ImportJob::new("/scans/2024/0001.pdf", FolderId(42));
+        assert_eq!(job.document_name(), "0001.pdf");
+        assert_eq!(job.file_name("Invoice 0001.pdf").document_name(), "Invoice 0001.pdf");
+
+        let job: ImportJob = ("/scans/0002.pdf", FolderId(42), ImportRequest::new().template("Invoice")).into();
+        assert_eq!(job.folder_id, FolderId(42));
+        assert_eq!(job.request, ImportRequest::new().template("Invoice"));
+    }
+
+    #[test]
+    fn test_transient_refusals() {
+        assert!(is_transient_refusal(&LFAPIError { status: Some(503), ..Default::default() }));
+        assert!(is_transient_refusal(&LFAPIError { status: Some(429), ..Default::default() }));
+        assert!(is_transient_refusal(&LFAPIError { status: Some(423), ..Default::default() }));
+        assert!(!is_transient_refusal(&LFAPIError { status: Some(409), ..Default::default() }));
+        assert!(!is_transient_refusal(&LFAPIError { status: Some(404), ..Default::default() }));
+    }
+}
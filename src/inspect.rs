@@ -0,0 +1,159 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{
+    Auth, Entry, ErrorKind, FolderId, ImportResultOrError, LFApiServer, Result,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mime;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validation;
+
+use futures_util::future::BoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Cursor;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use tokio::io::AsyncRead;
+
+/// Why an inspector refused to let content be uploaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentRejected {
+    pub reason: String,
+}
+
+impl ContentRejected {
+    pub fn new(reason: impl Into<String>) -> Self {
+        ContentRejected { reason: reason.into() }
+    }
+}
+
+/// Pre-upload hook that can veto an import, e.g. after an ICAP or ClamAV scan
+///
+/// The inspector reads its own copy of the content; the upload is unaffected
+/// by how much of it is consumed. Inspectors that cannot reach their scanner
+/// should reject rather than accept.
+pub trait ContentInspector: Send + Sync {
+    fn inspect<'a>(
+        &'a self,
+        file_name: &'a str,
+        content: &'a mut (dyn AsyncRead + Unpin + Send)
+    ) -> BoxFuture<'a, std::result::Result<(), ContentRejected>>;
+}
+
+/// Import a file after `inspector` approves its content
+///
+/// A veto fails with `ErrorKind::ContentRejected` and nothing is uploaded.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local file to import
+/// * `file_name` - Name for the new document
+/// * `root_id` - Destination folder
+/// * `inspector` - Hook that may veto the upload
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_inspected(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    file_name: String,
+    root_id: impl Into<FolderId>,
+    inspector: &dyn ContentInspector
+) -> Result<ImportResultOrError> {
+    let root_id = root_id.into().as_i64();
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let validated_name = validation::validate_file_name(&file_name)?;
+    let validated_root_id = validation::validate_entry_id(root_id)?;
+
+    let file_content = tokio::fs::read(&validated_path).await?;
+    check(inspector, &validated_name, &file_content).await?;
+
+    let mime_type = mime::detect_mime_type(&validated_name);
+    Entry::import_content(api_server, auth, file_content, &validated_name, validated_root_id, &mime_type).await
+}
+
+/// Run `inspector` over the file at `path`, streamed from disk
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn check_file(inspector: &dyn ContentInspector, file_name: &str, path: &Path) -> Result<()> {
+    let mut file = tokio::fs::File::open(path).await?;
+    inspector.inspect(file_name, &mut file).await
+        .map_err(|rejected| ErrorKind::ContentRejected(file_name.to_string(), rejected.reason).into())
+}
+
+/// Run `inspector` over a tee of `content`
+#[cfg(not(target_arch = "wasm32"))]
+async fn check(inspector: &dyn ContentInspector, file_name: &str, content: &[u8]) -> Result<()> {
+    let mut tee = Cursor::new(content);
+    inspector.inspect(file_name, &mut tee).await
+        .map_err(|rejected| ErrorKind::ContentRejected(file_name.to_string(), rejected.reason).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    /// Rejects content containing the EICAR test signature
+    struct SignatureScanner;
+
+    impl ContentInspector for SignatureScanner {
+        fn inspect<'a>(
+            &'a self,
+            _file_name: &'a str,
+            content: &'a mut (dyn AsyncRead + Unpin + Send)
+        ) -> BoxFuture<'a, std::result::Result<(), ContentRejected>> {
+            Box::pin(async move {
+                let mut buffer = Vec::new();
+                content.read_to_end(&mut buffer).await
+                    .map_err(|e| ContentRejected::new(format!("scan failed: {}", e)))?;
+                if buffer.windows(5).any(|window| window == b"EICAR") {
+                    return Err(ContentRejected::new("EICAR test signature found"));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clean_content_passes() {
+        assert!(check(&SignatureScanner, "clean.txt", b"quarterly report").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_content_is_typed() {
+        let error = check(&SignatureScanner, "infected.txt", b"X5O!P%@AP EICAR-STANDARD").await.unwrap_err();
+
+        match error.kind() {
+            ErrorKind::ContentRejected(file_name, reason) => {
+                assert_eq!(file_name, "infected.txt");
+                assert_eq!(reason, "EICAR test signature found");
+            }
+            other => panic!("expected ContentRejected, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_inspected_rejects_before_upload() {
+        let path = std::env::temp_dir().join(format!("lf_inspect_{}.txt", std::process::id()));
+        std::fs::write(&path, b"EICAR").unwrap();
+
+        let api_server = LFApiServer {
+            address: "test.laserfiche.com".to_string(),
+            repository: "test-repo".to_string(),
+        };
+        let result = import_inspected(
+            api_server,
+            Auth::default(),
+            path.to_string_lossy().to_string(),
+            "infected.txt".to_string(),
+            1,
+            &SignatureScanner
+        ).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result.map(|_| ()).unwrap_err().kind(), ErrorKind::ContentRejected(..)));
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! SHA-256 checksums of content as it is imported and exported, so archival
+//! pipelines can show that what reached the repository, and what came back
+//! out of it, is byte-for-byte what they hold
+
+use crate::laserfiche::{
+    impl_into_result, Auth, DocumentId, EdocReaderOrError, Entry, ErrorKind, ImportResult,
+    LFAPIError, LFApiServer, Result,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{FolderId, ImportResultOrError};
+use crate::manifest;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mime;
+use crate::validation;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Size and lowercase hex SHA-256 of content that was transferred
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checksum {
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+pub enum ChecksumOrError {
+    Checksum(Checksum),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(ChecksumOrError::Checksum => Checksum);
+
+/// An import and the checksum of the content it sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksummedImport {
+    pub result: ImportResult,
+    pub checksum: Checksum,
+}
+
+pub enum ChecksummedImportOrError {
+    ChecksummedImport(ChecksummedImport),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(ChecksummedImportOrError::ChecksummedImport => ChecksummedImport);
+
+/// Import a file, hashing its content as it is streamed to the server
+///
+/// Keep the returned checksum, e.g. in an `ImportManifest`, and pass it to
+/// `verify` later to prove the stored document still matches.
+///
+/// The file is hashed once before the upload too. If what was sent differs,
+/// because the file changed meanwhile, this fails with
+/// `ErrorKind::IntegrityError` naming the document that was created, so it
+/// can be deleted or re-imported.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local file to import
+/// * `file_name` - Name for the new document
+/// * `root_id` - Destination folder
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_checksummed(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    file_name: String,
+    root_id: impl Into<FolderId>
+) -> Result<ChecksummedImportOrError> {
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let before = hash_file(&validated_path).await?;
+    let file = tokio::fs::File::open(&validated_path).await?;
+    let mime_type = mime::detect_mime_type_of_file(&file_name, &validated_path)?;
+    let hasher = SharedHasher::default();
+    let reader = HashingReader { inner: file, hasher: hasher.clone() };
+
+    match Entry::import_reader(api_server, auth, reader, before.bytes, file_name, root_id, Some(&mime_type)).await? {
+        ImportResultOrError::ImportResult(result) => {
+            let checksum = hasher.finish();
+            check(result.operations.entry_create.entry_id, &before.sha256, &checksum)?;
+            Ok(ChecksummedImportOrError::ChecksummedImport(ChecksummedImport { result, checksum }))
+        }
+        ImportResultOrError::LFAPIError(error) => Ok(ChecksummedImportOrError::LFAPIError(error)),
+    }
+}
+
+/// Download a document's content into `writer`, hashing it on the way
+///
+/// The content is checked against `expected` when given, and against the
+/// SHA-256 the server reports in a `Repr-Digest` or `Digest` header when it
+/// sends one. A mismatch fails with `ErrorKind::IntegrityError`; by then
+/// the content has been written, so discard it.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Document to download
+/// * `writer` - Destination for the content
+/// * `expected` - Lowercase or uppercase hex SHA-256 the content must have
+pub async fn export_checksummed<W: AsyncWrite + Unpin + ?Sized>(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<DocumentId>,
+    writer: &mut W,
+    expected: Option<&str>
+) -> Result<ChecksumOrError> {
+    let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+    let mut reader = match Entry::open(api_server, auth, entry_id).await? {
+        EdocReaderOrError::Reader(reader) => reader,
+        EdocReaderOrError::LFAPIError(error) => return Ok(ChecksumOrError::LFAPIError(error)),
+    };
+    let reported = reader.reported_sha256().map(str::to_string);
+
+    let hasher = SharedHasher::default();
+    let mut hashing = HashingWriter { inner: writer, hasher: hasher.clone() };
+    tokio::io::copy(&mut reader, &mut hashing).await?;
+    hashing.flush().await?;
+    let checksum = hasher.finish();
+
+    for expected in expected.into_iter().chain(reported.as_deref()) {
+        check(entry_id, expected, &checksum)?;
+    }
+    Ok(ChecksumOrError::Checksum(checksum))
+}
+
+/// Download a document and check its content against `expected` without
+/// keeping it, e.g. in a periodic fixity audit
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Document to check
+/// * `expected` - Hex SHA-256 recorded when the document was imported
+pub async fn verify(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<DocumentId>,
+    expected: &str
+) -> Result<ChecksumOrError> {
+    export_checksummed(api_server, auth, entry_id, &mut tokio::io::sink(), Some(expected)).await
+}
+
+/// Checksum of a local file's current content
+#[cfg(not(target_arch = "wasm32"))]
+async fn hash_file(path: &std::path::Path) -> Result<Checksum> {
+    let hasher = SharedHasher::default();
+    let mut reader = HashingReader { inner: tokio::fs::File::open(path).await?, hasher: hasher.clone() };
+    tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+    Ok(hasher.finish())
+}
+
+fn check(entry_id: i64, expected: &str, checksum: &Checksum) -> Result<()> {
+    if !expected.eq_ignore_ascii_case(&checksum.sha256) {
+        return Err(ErrorKind::IntegrityError(entry_id, expected.to_lowercase(), checksum.sha256.clone()).into());
+    }
+    Ok(())
+}
+
+/// Lowercase hex SHA-256 from a `Repr-Digest: sha-256=:<base64>:` or
+/// `Digest: SHA-256=<base64>` response header, if the server sent one
+pub(crate) fn reported_sha256(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    ["repr-digest", "digest"].iter()
+        .filter_map(|name| headers.get(*name)?.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|digest| {
+            let (algorithm, value) = digest.trim().split_once('=')?;
+            if !algorithm.eq_ignore_ascii_case("sha-256") {
+                return None;
+            }
+            let decoded = STANDARD.decode(value.trim_matches(':')).ok()?;
+            (decoded.len() == 32).then(|| manifest::to_hex(&decoded))
+        })
+}
+
+/// Running hash shared between a wrapped stream and the caller waiting
+/// for its result
+#[derive(Clone, Default)]
+struct SharedHasher(Arc<Mutex<(Sha256, u64)>>);
+
+impl SharedHasher {
+    fn update(&self, bytes: &[u8]) {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        state.0.update(bytes);
+        state.1 += bytes.len() as u64;
+    }
+
+    fn finish(&self) -> Checksum {
+        let state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        Checksum { bytes: state.1, sha256: manifest::to_hex(&state.0.clone().finalize()) }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct HashingReader<R> {
+    inner: R,
+    hasher: SharedHasher,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<R: AsyncRead + Unpin> AsyncRead for HashingReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        self.hasher.update(&buf.filled()[before..]);
+        poll
+    }
+}
+
+struct HashingWriter<'a, W: ?Sized> {
+    inner: &'a mut W,
+    hasher: SharedHasher,
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> AsyncWrite for HashingWriter<'_, W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut *self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            self.hasher.update(&buf[..written]);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SHA-256 of "abc"
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+    #[test]
+    fn test_reported_sha256() {
+        let base64 = STANDARD.encode(Sha256::digest(b"abc"));
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("repr-digest", format!("sha-512=:AAAA:, sha-256=:{}:", base64).parse().unwrap());
+        assert_eq!(reported_sha256(&headers).as_deref(), Some(ABC_SHA256));
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("digest", format!("SHA-256={}", base64).parse().unwrap());
+        assert_eq!(reported_sha256(&headers).as_deref(), Some(ABC_SHA256));
+
+        headers.insert("digest", "MD5=kAFQmDzST7DWlj99KOF/cg==".parse().unwrap());
+        assert_eq!(reported_sha256(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_hash_file() {
+        let file = std::env::temp_dir().join(format!("lf_hash_file_{}.txt", std::process::id()));
+        std::fs::write(&file, b"abc").unwrap();
+        let checksum = hash_file(&file).await;
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(checksum.unwrap(), Checksum { bytes: 3, sha256: ABC_SHA256.to_string() });
+    }
+
+    #[tokio::test]
+    async fn test_hashing_writer_and_check() {
+        let hasher = SharedHasher::default();
+        let mut content = Vec::new();
+        let mut writer = HashingWriter { inner: &mut content, hasher: hasher.clone() };
+        writer.write_all(b"ab").await.unwrap();
+        writer.write_all(b"c").await.unwrap();
+        let checksum = hasher.finish();
+        assert_eq!(checksum, Checksum { bytes: 3, sha256: ABC_SHA256.to_string() });
+
+        assert!(check(7, &ABC_SHA256.to_uppercase(), &checksum).is_ok());
+        match check(7, "00", &checksum) {
+            Err(crate::laserfiche::Error(ErrorKind::IntegrityError(7, expected, actual), _)) => {
+                assert_eq!((expected.as_str(), actual.as_str()), ("00", ABC_SHA256));
+            }
+            other => panic!("expected IntegrityError, got {:?}", other),
+        }
+    }
+}
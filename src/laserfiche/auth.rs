@@ -0,0 +1,535 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Tokens: signing in, refreshing, and sharing a token between tasks
+
+use super::{http_client, RequestUrl, UrlError};
+use super::error::{ErrorKind, LFAPIError, Result};
+use super::types::LFApiServer;
+use crate::clock::{self, Clock, SystemClock};
+use crate::cloud::CloudCredentials;
+use crate::retry::RetryingSend;
+use crate::validation;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+use super::blocking;
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub enum AuthOrError {
+    Auth(Auth),
+    LFAPIError(LFAPIError),
+}
+
+/// Default allowance for clock skew between this host and the token server
+pub const DEFAULT_TOKEN_LEEWAY: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Auth {
+    #[serde(rename = "@odata.context", default, skip_serializing_if = "String::is_empty")]
+    pub odata_context: String,
+    #[serde(rename = "access_token")]
+    pub access_token: String,
+    #[serde(rename = "expires_in")]
+    pub expires_in: i64,
+    #[serde(rename = "token_type")]
+    pub token_type: String,
+    #[serde(skip)]
+    pub username: String,
+    #[serde(skip)]
+    pub password: String,
+    #[serde(skip)]
+    pub timestamp: i64,
+    #[serde(skip)]
+    pub api_server: LFApiServer,
+    /// Set for Laserfiche Cloud tokens, which refresh with these instead of
+    /// `username`/`password`
+    #[serde(skip)]
+    pub cloud_credentials: Option<CloudCredentials>,
+}
+impl Auth {
+    #[allow(clippy::new_ret_no_self)]
+    pub async fn new(api_server: LFApiServer, username: String, password: String) -> Result<AuthOrError> {
+        Self::authenticate(api_server, username, password).await
+    }
+
+    pub async fn refresh(&self) -> Result<AuthOrError> {
+        if let Some(credentials) = &self.cloud_credentials {
+            return Self::new_cloud(credentials.clone(), self.api_server.repository.clone()).await;
+        }
+        Self::authenticate(
+            self.api_server.clone(),
+            self.username.clone(),
+            self.password.clone()
+        ).await
+    }
+
+    async fn authenticate(api_server: LFApiServer, username: String, password: String) -> Result<AuthOrError> {
+        // Validate server address and repository name
+        let validated_address = validation::validate_server_address(&api_server.address)?;
+        let validated_repository = validation::validate_repository_name(&api_server.repository)?;
+        
+        let validated_server = LFApiServer {
+            address: validated_address,
+            repository: validated_repository,
+        };
+        
+        let token_url = Self::build_token_url(&validated_server)?;
+        let auth_params = Self::build_auth_params(&username, &password);
+        
+        let response = http_client()
+            .post(token_url)
+            .form(&auth_params)
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(AuthOrError::LFAPIError(error));
+        }
+
+        let mut auth = response.json::<Self>().await?;
+        auth.username = username;
+        auth.password = password;
+        auth.api_server = validated_server;
+        auth.timestamp = Self::current_timestamp();
+        
+        Ok(AuthOrError::Auth(auth))
+    }
+
+    /// When the token expires, computed from the local issue time and `expires_in`
+    pub fn expires_at(&self) -> SystemTime {
+        let issued_at = UNIX_EPOCH + Duration::from_secs(self.timestamp.max(0) as u64);
+        issued_at + Duration::from_secs(self.expires_in.max(0) as u64)
+    }
+
+    /// Time left before the token should be treated as expired
+    ///
+    /// `leeway` is subtracted to absorb clock skew and request latency, so a
+    /// token is refreshed slightly before the server would reject it.
+    pub fn remaining(&self, leeway: Duration) -> Duration {
+        self.remaining_at(clock::system_now(), leeway)
+    }
+
+    /// Same as `remaining`, evaluated at `now` instead of the system clock
+    pub fn remaining_at(&self, now: SystemTime, leeway: Duration) -> Duration {
+        let deadline = self.expires_at()
+            .checked_sub(leeway)
+            .unwrap_or(UNIX_EPOCH);
+
+        // A clock that moved backwards past the issue time still counts the
+        // full lifetime rather than extending it.
+        let lifetime = Duration::from_secs(self.expires_in.max(0) as u64).saturating_sub(leeway);
+        deadline.duration_since(now)
+            .map(|left| left.min(lifetime))
+            .unwrap_or(Duration::ZERO)
+    }
+
+    /// Whether the token is expired (or within `leeway` of expiring)
+    pub fn is_expired(&self, leeway: Duration) -> bool {
+        self.remaining(leeway).is_zero()
+    }
+
+    /// Same as `is_expired`, evaluated at `now` instead of the system clock
+    pub fn is_expired_at(&self, now: SystemTime, leeway: Duration) -> bool {
+        self.remaining_at(now, leeway).is_zero()
+    }
+
+    fn build_token_url(api_server: &LFApiServer) -> std::result::Result<String, UrlError> {
+        RequestUrl::token(api_server)?.build()
+    }
+
+    fn build_auth_params<'a>(username: &'a str, password: &'a str) -> Vec<(&'static str, &'a str)> {
+        vec![
+            ("grant_type", "password"),
+            ("username", username),
+            ("password", password),
+        ]
+    }
+
+    pub(super) fn current_timestamp() -> i64 {
+        clock::unix_seconds(SystemClock.now())
+    }
+}
+
+/// Token shared between tasks that refreshes itself before it expires
+///
+/// Clones share one token. `token()` hands out the current `Auth`, first
+/// re-authenticating when it is within the leeway of expiring; concurrent
+/// callers wait on a single refresh instead of each starting their own.
+///
+/// Expiry is judged by a `Clock`, the system clock unless `with_clock` is
+/// given another; refreshed tokens are stamped with that clock's time.
+#[derive(Debug, Clone)]
+pub struct SharedAuth {
+    auth: Arc<RwLock<Auth>>,
+    refreshing: Arc<tokio::sync::Mutex<()>>,
+    leeway: Duration,
+    clock: Arc<dyn Clock>,
+}
+
+impl SharedAuth {
+    pub fn new(auth: Auth) -> Self {
+        Self::with_leeway(auth, DEFAULT_TOKEN_LEEWAY)
+    }
+
+    /// Refresh once fewer than `leeway` remain on the token
+    pub fn with_leeway(auth: Auth, leeway: Duration) -> Self {
+        SharedAuth {
+            auth: Arc::new(RwLock::new(auth)),
+            refreshing: Arc::new(tokio::sync::Mutex::new(())),
+            leeway,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Judge expiry by `clock`; clones made afterwards share it
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn leeway(&self) -> Duration {
+        self.leeway
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// The stored token, without checking its expiry
+    pub fn current(&self) -> Auth {
+        self.auth.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the stored token, e.g. after an external refresh
+    pub fn replace(&self, auth: Auth) {
+        *self.auth.write().unwrap_or_else(|e| e.into_inner()) = auth;
+    }
+
+    /// A token valid for at least `leeway`, refreshing first if needed
+    ///
+    /// Fails with `ErrorKind::TokenRefreshFailed` when the server refuses
+    /// the stored credentials.
+    pub async fn token(&self) -> Result<Auth> {
+        if let Some(auth) = self.fresh() {
+            return Ok(auth);
+        }
+
+        let _refreshing = self.refreshing.lock().await;
+        // Another caller may have refreshed while we waited
+        if let Some(auth) = self.fresh() {
+            return Ok(auth);
+        }
+
+        match self.current().refresh().await? {
+            AuthOrError::Auth(auth) => Ok(self.store(auth)),
+            AuthOrError::LFAPIError(error) => Err(ErrorKind::TokenRefreshFailed(refresh_failure(&error)).into()),
+        }
+    }
+
+    /// Re-authenticate now regardless of expiry, storing the token on success
+    pub async fn refresh(&self) -> Result<AuthOrError> {
+        let _refreshing = self.refreshing.lock().await;
+        Ok(match self.current().refresh().await? {
+            AuthOrError::Auth(auth) => AuthOrError::Auth(self.store(auth)),
+            error => error,
+        })
+    }
+
+    /// Blocking version of `token`; must not be called from an async context
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    pub fn token_blocking(&self) -> blocking::Result<Auth> {
+        if let Some(auth) = self.fresh() {
+            return Ok(auth);
+        }
+
+        let _refreshing = self.refreshing.blocking_lock();
+        if let Some(auth) = self.fresh() {
+            return Ok(auth);
+        }
+
+        match self.current().refresh_blocking()? {
+            AuthOrError::Auth(auth) => Ok(self.store(auth)),
+            AuthOrError::LFAPIError(error) => {
+                Err(blocking::ErrorKind::TokenRefreshFailed(refresh_failure(&error)).into())
+            }
+        }
+    }
+
+    /// Blocking version of `refresh`; must not be called from an async context
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    pub fn refresh_blocking(&self) -> blocking::Result<AuthOrError> {
+        let _refreshing = self.refreshing.blocking_lock();
+        Ok(match self.current().refresh_blocking()? {
+            AuthOrError::Auth(auth) => AuthOrError::Auth(self.store(auth)),
+            error => error,
+        })
+    }
+
+    fn fresh(&self) -> Option<Auth> {
+        let auth = self.auth.read().unwrap_or_else(|e| e.into_inner());
+        (!auth.is_expired_at(self.clock.now(), self.leeway)).then(|| auth.clone())
+    }
+
+    /// Stamp a newly issued token with this clock's time and store it
+    fn store(&self, mut auth: Auth) -> Auth {
+        auth.timestamp = clock::unix_seconds(self.clock.now());
+        self.replace(auth.clone());
+        auth
+    }
+}
+
+impl From<Auth> for SharedAuth {
+    fn from(auth: Auth) -> Self {
+        SharedAuth::new(auth)
+    }
+}
+
+/// Best available description of a refused token request
+fn refresh_failure(error: &LFAPIError) -> String {
+    error.detail.clone()
+        .or_else(|| error.title.clone())
+        .unwrap_or_else(|| format!("status {}", error.status.unwrap_or_default()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::tests::mock_auth;
+
+    #[test]
+    fn test_auth_struct_fields() {
+        let auth = mock_auth();
+        assert_eq!(auth.token_type, "Bearer");
+        assert_eq!(auth.access_token, "test-token-12345");
+        assert_eq!(auth.timestamp, 1234567890);
+        assert_eq!(auth.username, "test-user");
+        assert_eq!(auth.password, "test-pass");
+        assert_eq!(auth.expires_in, 3600);
+    }
+
+    #[test]
+    fn test_auth_or_error_enum() {
+        let auth = mock_auth();
+        let auth_result = AuthOrError::Auth(auth.clone());
+        
+        match auth_result {
+            AuthOrError::Auth(a) => assert_eq!(a.access_token, "test-token-12345"),
+            AuthOrError::LFAPIError(_) => panic!("Expected Auth variant"),
+        }
+
+        let error = LFAPIError {
+            status: Some(401),
+            title: Some("Unauthorized".to_string()),
+            ..Default::default()
+        };
+        let error_result = AuthOrError::LFAPIError(error);
+        
+        match error_result {
+            AuthOrError::Auth(_) => panic!("Expected LFAPIError variant"),
+            AuthOrError::LFAPIError(e) => assert_eq!(e.status, Some(401)),
+        }
+    }
+
+    #[test]
+    fn test_timestamp_year_2038_boundary() {
+        // Year 2038 problem occurs at 2^31 - 1 seconds (January 19, 2038 03:14:07 UTC)
+        let year_2038_timestamp: u64 = 2_147_483_647;
+        let result: std::result::Result<i64, _> = year_2038_timestamp.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 2_147_483_647);
+        
+        // One second after the 2038 boundary (still within i64 range)
+        let after_2038: u64 = 2_147_483_648;
+        let result: std::result::Result<i64, _> = after_2038.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 2_147_483_648);
+    }
+
+    #[test]
+    fn test_timestamp_max_i64_value() {
+        // Test at exactly i64::MAX
+        let max_i64_as_u64: u64 = i64::MAX as u64;
+        let result: std::result::Result<i64, _> = max_i64_as_u64.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), i64::MAX);
+        
+        // Test overflow scenario - one more than i64::MAX
+        let overflow: u64 = (i64::MAX as u64) + 1;
+        let result: std::result::Result<i64, _> = overflow.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_timestamp_overflow_handling() {
+        // Test that our implementation handles overflow gracefully
+        let max_u64 = u64::MAX;
+        let result: i64 = max_u64.try_into().unwrap_or(i64::MAX);
+        assert_eq!(result, i64::MAX);
+        
+        // Test with a value that would overflow
+        let overflow_value: u64 = (i64::MAX as u64) + 1000;
+        let result: i64 = overflow_value.try_into().unwrap_or(i64::MAX);
+        assert_eq!(result, i64::MAX);
+    }
+
+    #[test]
+    fn test_current_timestamp_safe_conversion() {
+        // Test that current_timestamp returns a valid i64
+        let timestamp = Auth::current_timestamp();
+        assert!(timestamp > 0);
+        
+        // Verify it's approximately the current time (within reasonable bounds)
+        let now_secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        
+        // Check that the timestamp is reasonable (between year 2020 and 2100)
+        let year_2020: i64 = 1577836800; // January 1, 2020
+        let year_2100: i64 = 4102444800; // January 1, 2100
+        assert!(timestamp >= year_2020);
+        assert!(timestamp <= year_2100);
+        
+        // The current timestamp should be close to now
+        assert!((timestamp as u64) <= now_secs + 1);
+    }
+
+    #[test]
+    fn test_future_dates_handling() {
+        // Test with year 2050 timestamp
+        let year_2050: u64 = 2524608000; // January 1, 2050
+        let result: std::result::Result<i64, _> = year_2050.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 2524608000);
+        
+        // Test with year 2100 timestamp
+        let year_2100: u64 = 4102444800; // January 1, 2100
+        let result: std::result::Result<i64, _> = year_2100.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 4102444800);
+        
+        // Test with year 2200 timestamp (well within i64 range)
+        let year_2200: u64 = 7258118400; // January 1, 2200
+        let result: std::result::Result<i64, _> = year_2200.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 7258118400);
+    }
+
+    #[test]
+    fn test_edge_case_zero_timestamp() {
+        // Test Unix epoch (0)
+        let epoch: u64 = 0;
+        let result: std::result::Result<i64, _> = epoch.try_into();
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_auth_expires_at() {
+        let auth = mock_auth();
+        assert_eq!(auth.expires_at(), UNIX_EPOCH + Duration::from_secs(1234567890 + 3600));
+
+        let negative = Auth { expires_in: -10, ..mock_auth() };
+        assert_eq!(negative.expires_at(), UNIX_EPOCH + Duration::from_secs(1234567890));
+    }
+
+    #[test]
+    fn test_auth_remaining_at() {
+        let auth = mock_auth();
+        let issued = UNIX_EPOCH + Duration::from_secs(1234567890);
+
+        // Fresh token: full lifetime minus leeway
+        assert_eq!(auth.remaining_at(issued, Duration::ZERO), Duration::from_secs(3600));
+        assert_eq!(auth.remaining_at(issued, Duration::from_secs(30)), Duration::from_secs(3570));
+
+        // Part way through
+        let later = issued + Duration::from_secs(600);
+        assert_eq!(auth.remaining_at(later, Duration::from_secs(30)), Duration::from_secs(2970));
+
+        // Inside the leeway window and past expiry
+        let nearly = issued + Duration::from_secs(3590);
+        assert_eq!(auth.remaining_at(nearly, Duration::from_secs(30)), Duration::ZERO);
+        let expired = issued + Duration::from_secs(7200);
+        assert_eq!(auth.remaining_at(expired, Duration::ZERO), Duration::ZERO);
+
+        // Clock moved backwards before the issue time: never more than the lifetime
+        let skewed = issued - Duration::from_secs(900);
+        assert_eq!(auth.remaining_at(skewed, Duration::from_secs(30)), Duration::from_secs(3570));
+
+        // Leeway larger than the lifetime
+        assert_eq!(auth.remaining_at(issued, Duration::from_secs(7200)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_auth_is_expired() {
+        let mut auth = mock_auth();
+        auth.timestamp = Auth::current_timestamp();
+        assert!(!auth.is_expired(DEFAULT_TOKEN_LEEWAY));
+
+        auth.timestamp = 1234567890;
+        assert!(auth.is_expired(DEFAULT_TOKEN_LEEWAY));
+    }
+
+    #[tokio::test]
+    async fn test_shared_auth_returns_fresh_token() {
+        let mut auth = mock_auth();
+        auth.timestamp = Auth::current_timestamp();
+        let shared = SharedAuth::new(auth);
+        let clone = shared.clone();
+
+        assert_eq!(shared.token().await.unwrap().access_token, clone.current().access_token);
+
+        let replaced = Auth { access_token: "rotated".to_string(), ..shared.current() };
+        shared.replace(replaced);
+        assert_eq!(clone.token().await.unwrap().access_token, "rotated");
+    }
+
+    #[tokio::test]
+    async fn test_shared_auth_refreshes_expired_token() {
+        // An expired token against an invalid server fails in validation,
+        // proving a refresh was attempted without touching the network
+        let auth = Auth { api_server: LFApiServer::default(), ..mock_auth() };
+        let shared = SharedAuth::new(auth);
+
+        assert!(shared.token().await.is_err());
+        assert_eq!(shared.current().access_token, mock_auth().access_token);
+    }
+
+    #[tokio::test]
+    async fn test_shared_auth_follows_clock() {
+        // Issued at 1234567890 with a one-hour lifetime
+        let clock = crate::clock::MockClock::at_unix(1234567890);
+        let auth = Auth { api_server: LFApiServer::default(), ..mock_auth() };
+        let shared = SharedAuth::new(auth).with_clock(Arc::new(clock.clone()));
+
+        assert!(shared.token().await.is_ok());
+        clock.advance(Duration::from_secs(3600 - 31));
+        assert!(shared.token().await.is_ok());
+
+        // Inside the leeway a refresh is attempted, which fails validation
+        clock.advance(Duration::from_secs(2));
+        assert!(shared.token().await.is_err());
+    }
+
+    #[test]
+    fn test_auth_timestamp_field() {
+        // Create an Auth instance and verify timestamp is set correctly
+        let mut auth = mock_auth();
+        
+        // Set timestamp to a known value
+        auth.timestamp = 1234567890;
+        assert_eq!(auth.timestamp, 1234567890);
+        
+        // Test setting to max value
+        auth.timestamp = i64::MAX;
+        assert_eq!(auth.timestamp, i64::MAX);
+        
+        // Verify current_timestamp is within valid range
+        auth.timestamp = Auth::current_timestamp();
+        assert!(auth.timestamp > 0);
+    }
+}
@@ -2,19 +2,18 @@
 // Developed by Caleb Mitchell Smith (PixelCoda)
 // Licensed under GPLv3....see LICENSE file.
 
+use crate::retry::RetryingSend;
+use crate::server_info::ServerInfo;
 use crate::validation;
-use crate::laserfiche::{
-    LFApiServer, LFAPIError, AuthOrError, Auth as AsyncAuth,
-    EntryOrError, ImportResultOrError,
-    Entry, Entries, EntriesOrError, MetadataResult, MetadataResultOrError,
-    ImportResult, BitsOrError, LFObject, DeletedObject
-};
-
-use serde_json::json;
-use std::io::Cursor;
+use crate::laserfiche::{RequestUrl, UrlError, PageCursor, DestroyEntry, names_filter, record_named_child, write_atomically, NAMES_PER_FILTER};
+
+pub use crate::laserfiche::types::*;
+
+use std::collections::BTreeMap;
+
 use error_chain::error_chain;
-use std::time::{SystemTime, UNIX_EPOCH};
-use std::convert::TryInto;
+use std::cell::RefCell;
+use once_cell::sync::Lazy;
 
 error_chain! {
     foreign_links {
@@ -22,10 +21,80 @@ error_chain! {
         IOError(std::io::Error);
         ValidationError(validation::Error);
     }
+
+    errors {
+        TokenRefreshFailed(detail: String) {
+            description("Token refresh failed")
+            display("Token refresh failed: {}", detail)
+        }
+        ApiError(error: Box<LFAPIError>) {
+            description("API error")
+            display("API error: {}", error)
+        }
+        EntryLocked(entry_id: i64) {
+            description("Entry locked")
+            display("Entry {} is locked", entry_id)
+        }
+        UnsupportedByServer(feature: String, required: String, supported: String) {
+            description("Feature not supported by the server")
+            display("{} needs Repository API {}, but the server supports up to {}", feature, required, supported)
+        }
+        NotRoutedForVersion(route: String, version: String) {
+            description("Route not available in the requested API version")
+            display("'{}' is not yet routed for Repository API {}; send it from a v1 client", route, version)
+        }
+        FileExists(path: String) {
+            description("File already exists")
+            display("'{}' already exists", path)
+        }
+    }
+}
+
+impl From<UrlError> for Error {
+    fn from(error: UrlError) -> Self {
+        match error {
+            UrlError::Invalid(error) => error.into(),
+            UrlError::NotRouted { route, version } => ErrorKind::NotRoutedForVersion(route, version.to_string()).into(),
+        }
+    }
 }
 
-/// Blocking version of Auth - reuses types from async module
-pub type Auth = AsyncAuth;
+/// Process-wide blocking client, shared for connection and TLS session reuse
+static SHARED_HTTP_CLIENT: Lazy<reqwest::blocking::Client> = Lazy::new(reqwest::blocking::Client::new);
+
+thread_local! {
+    static SCOPED_HTTP_CLIENT: RefCell<Option<reqwest::blocking::Client>> = const { RefCell::new(None) };
+}
+
+/// The pooled blocking client used when no other client is scoped
+pub(crate) fn shared_http_client() -> reqwest::blocking::Client {
+    SHARED_HTTP_CLIENT.clone()
+}
+
+/// HTTP client for the current request: the one scoped by `with_http_client`
+/// (e.g. a blocking `LfClient`'s), or the shared pooled client
+pub(crate) fn http_client() -> reqwest::blocking::Client {
+    SCOPED_HTTP_CLIENT
+        .with(|scoped| scoped.borrow().clone())
+        .unwrap_or_else(shared_http_client)
+}
+
+/// Run `f` with `client` serving every API request it makes on this thread
+pub(crate) fn with_http_client<T>(client: &reqwest::blocking::Client, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED_HTTP_CLIENT.with(|scoped| scoped.replace(Some(client.clone())));
+    let _restore = RestoreClient(previous);
+    f()
+}
+
+/// Puts back the outer scope's client, even if the scoped call panics
+struct RestoreClient(Option<reqwest::blocking::Client>);
+
+impl Drop for RestoreClient {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        SCOPED_HTTP_CLIENT.with(|scoped| *scoped.borrow_mut() = previous);
+    }
+}
 
 impl Auth {
     /// Synchronous authentication
@@ -35,6 +104,9 @@ impl Auth {
 
     /// Synchronous token refresh
     pub fn refresh_blocking(&self) -> Result<AuthOrError> {
+        if let Some(credentials) = &self.cloud_credentials {
+            return Self::new_cloud_blocking(credentials.clone(), self.api_server.repository.clone());
+        }
         Self::authenticate_blocking(
             self.api_server.clone(),
             self.username.clone(),
@@ -48,15 +120,11 @@ impl Auth {
         let validated_repository = validation::validate_repository_name(&api_server.repository)?;
         
         let validated_server = LFApiServer {
-            address: validated_address.clone(),
-            repository: validated_repository.clone(),
+            address: validated_address,
+            repository: validated_repository,
         };
         
-        let token_url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Token",
-            validated_address,
-            validated_repository
-        );
+        let token_url = RequestUrl::token(&validated_server)?.build()?;
         
         let auth_params = vec![
             ("grant_type", "password"),
@@ -64,10 +132,10 @@ impl Auth {
             ("password", password.as_str()),
         ];
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .post(token_url)
             .form(&auth_params)
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::OK {
             let error = response.json::<LFAPIError>()?;
@@ -78,43 +146,90 @@ impl Auth {
         auth.username = username;
         auth.password = password;
         auth.api_server = validated_server;
-        auth.timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_else(|_| std::time::Duration::from_secs(0))
-            .as_secs()
-            .try_into()
-            .unwrap_or(i64::MAX);
+        auth.timestamp = Auth::current_timestamp();
         
         Ok(AuthOrError::Auth(auth))
     }
 }
 
-/// Helper function to detect MIME type based on file extension
-fn detect_mime_type(file_name: &str) -> String {
-    let extension = file_name
-        .rsplit('.')
-        .next()
-        .unwrap_or("")
-        .to_lowercase();
-        
-    match extension.as_str() {
-        "pdf" => "application/pdf",
-        "png" => "image/png",
-        "jpg" | "jpeg" => "image/jpeg",
-        "gif" => "image/gif",
-        "tiff" | "tif" => "image/tiff",
-        "doc" => "application/msword",
-        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
-        "xls" => "application/vnd.ms-excel",
-        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-        "txt" => "text/plain",
-        "xml" => "application/xml",
-        "json" => "application/json",
-        _ => "application/octet-stream"
-    }.to_string()
+fn handle_entries_response(response: reqwest::blocking::Response) -> Result<EntriesOrError> {
+    if response.status() != reqwest::StatusCode::OK {
+        let error = response.json::<LFAPIError>()?;
+        return Ok(EntriesOrError::LFAPIError(error));
+    }
+
+    let entries = response.json::<Entries>()?;
+    Ok(EntriesOrError::Entries(entries))
+}
+
+/// Blocking version of `paginate`: every entry of a paged collection,
+/// fetching each page only once the previous one is used up
+pub(crate) fn paginate_blocking<F>(mut fetch: F) -> impl Iterator<Item = Result<Entry>>
+where
+    F: FnMut(Option<NextLink>) -> Result<EntriesOrError>,
+{
+    let mut cursor = PageCursor::First;
+    let mut page = Vec::new().into_iter();
+    std::iter::from_fn(move || loop {
+        if let Some(entry) = page.next() {
+            return Some(Ok(entry));
+        }
+        let next_link = match std::mem::replace(&mut cursor, PageCursor::Done) {
+            PageCursor::First => None,
+            PageCursor::Next(next_link) => Some(next_link),
+            PageCursor::Done => return None,
+        };
+        match fetch(next_link) {
+            Ok(EntriesOrError::Entries(entries)) => {
+                cursor = PageCursor::after(&entries);
+                page = entries.value.into_iter();
+            }
+            Ok(EntriesOrError::LFAPIError(error)) => return Some(Err(ErrorKind::ApiError(Box::new(error)).into())),
+            Err(error) => return Some(Err(error)),
+        }
+    })
 }
 
 /// Blocking API methods for Entry operations
+impl ServerInfo {
+    /// Blocking version of detect
+    pub fn detect_blocking(api_server: LFApiServer, auth: Auth) -> Result<Self> {
+        let url = RequestUrl::entry(&api_server, 1)?.query("$select", "id").build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()?;
+
+        if response.status().is_client_error() || response.status().is_server_error() {
+            let error = response.json::<LFAPIError>()?;
+            return Err(ErrorKind::ApiError(Box::new(error)).into());
+        }
+
+        Ok(Self::from_headers(response.headers()))
+    }
+}
+
+impl AuditReasons {
+    /// Blocking version of list
+    pub fn list_blocking(api_server: LFApiServer, auth: Auth) -> Result<AuditReasonsOrError> {
+        let url = RequestUrl::repository(&api_server)?.segment("AuditReasons").build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>()?;
+            return Ok(AuditReasonsOrError::LFAPIError(error));
+        }
+
+        let reasons = response.json::<AuditReasons>()?;
+        Ok(AuditReasonsOrError::AuditReasons(reasons))
+    }
+}
+
 impl Entry {
     /// Blocking version of import
     pub fn import_blocking(
@@ -122,47 +237,65 @@ impl Entry {
         auth: Auth,
         file_path: String,
         file_name: String,
-        root_id: i64
+        root_id: impl Into<FolderId>
+    ) -> Result<ImportResultOrError> {
+        Self::import_with_request_blocking(api_server, auth, file_path, file_name, root_id, &ImportRequest::default())
+    }
+
+    /// Blocking version of import_with_mime_type
+    pub fn import_with_mime_type_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        mime_type: &str
+    ) -> Result<ImportResultOrError> {
+        let request = ImportRequest::default().mime_type(mime_type);
+        Self::import_with_request_blocking(api_server, auth, file_path, file_name, root_id, &request)
+    }
+
+    /// Blocking version of import_with_request
+    pub fn import_with_request_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        request: &ImportRequest
     ) -> Result<ImportResultOrError> {
         // Validate inputs
         let validated_path = validation::validate_file_path(&file_path)?;
         let validated_name = validation::validate_file_name(&file_name)?;
-        let validated_root_id = validation::validate_entry_id(root_id)?;
-        
+        let validated_root_id = validation::validate_entry_id(root_id.into().as_i64())?;
+
         let file_content = std::fs::read(&validated_path)?;
         
         // Validate file size
         validation::validate_file_size(file_content.len() as u64)?;
-        
-        // Detect MIME type from file extension
-        let mime_type = detect_mime_type(&validated_name);
+        let mime_type = request.resolve_mime_type(&validated_name, &file_content)?;
         
         let file_part = reqwest::blocking::multipart::Part::bytes(file_content)
             .file_name(validated_name.clone())
-            .mime_str(&mime_type)
-            .unwrap_or_else(|_| reqwest::blocking::multipart::Part::bytes(vec![]));
+            .mime_str(&mime_type)?;
 
-        let request_part = reqwest::blocking::multipart::Part::text("{}")
-            .mime_str("application/json")
-            .unwrap_or_else(|_| reqwest::blocking::multipart::Part::text("{}"));
+        let request_part = reqwest::blocking::multipart::Part::text(request.to_json().to_string())
+            .mime_str("application/json")?;
 
         let form = reqwest::blocking::multipart::Form::new()
             .part("electronicDocument", file_part)
             .part("request", request_part);
 
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}/{}?autoRename=true",
-            api_server.address,
-            api_server.repository,
-            validated_root_id,
-            validated_name
-        );
+        let url = RequestUrl::entry(&api_server, validated_root_id)?
+            .segment(&validated_name)
+            .query("autoRename", "true")
+            .build()?;
 
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .post(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
             .multipart(form)
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::CREATED {
             let error = response.json::<LFAPIError>()?;
@@ -177,19 +310,15 @@ impl Entry {
     pub fn get_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        root_id: i64
+        root_id: impl Into<EntryId>
     ) -> Result<EntryOrError> {
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}",
-            api_server.address,
-            api_server.repository,
-            root_id
-        );
+        let root_id = root_id.into().as_i64();
+        let url = RequestUrl::entry(&api_server, root_id)?.build()?;
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .get(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::OK {
             let error = response.json::<LFAPIError>()?;
@@ -204,85 +333,181 @@ impl Entry {
     pub fn list_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        root_id: i64
+        root_id: impl Into<FolderId>
     ) -> Result<EntriesOrError> {
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}/Laserfiche.Repository.Folder/children",
-            api_server.address,
-            api_server.repository,
-            root_id
-        );
+        Self::list_with_options_blocking(api_server, auth, root_id, &ListOptions::default())
+    }
+
+    /// Blocking version of list_with_options
+    pub fn list_with_options_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>,
+        options: &ListOptions
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_list_url(&api_server, root_id.into(), options)?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()?;
+
+        handle_entries_response(response)
+    }
+
+    /// Blocking version of list_all, as an iterator
+    pub fn list_all_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>
+    ) -> impl Iterator<Item = Result<Entry>> {
+        let folder_id = root_id.into();
+        // Pages are fetched after this call returns, so keep the caller's client
+        let http = http_client();
+        paginate_blocking(move |next_link| with_http_client(&http, || match next_link {
+            None => Self::list_blocking(api_server.clone(), auth.clone(), folder_id),
+            Some(next_link) => Self::list_custom_blocking(api_server.clone(), auth.clone(), next_link),
+        }))
+    }
+
+    /// Blocking version of find_children_by_names
+    pub fn find_children_by_names_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        folder_id: impl Into<FolderId>,
+        names: &[&str]
+    ) -> Result<BTreeMap<String, EntryId>> {
+        let folder_id = folder_id.into();
+        let mut found = BTreeMap::new();
+        for names in names.chunks(NAMES_PER_FILTER) {
+            let options = names_filter(names);
+            let children = paginate_blocking(|next_link| match next_link {
+                None => Self::list_with_options_blocking(api_server.clone(), auth.clone(), folder_id, &options),
+                Some(next_link) => Self::list_custom_blocking(api_server.clone(), auth.clone(), next_link),
+            });
+            for child in children {
+                record_named_child(names, &child?, &mut found);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Blocking version of list_custom
+    pub fn list_custom_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        next_link: NextLink
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_next_link_url(&api_server, &next_link)?;
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .get(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
-            .send()?;
+            .send_retrying()?;
 
-        if response.status() != reqwest::StatusCode::OK {
-            let error = response.json::<LFAPIError>()?;
-            return Ok(EntriesOrError::LFAPIError(error));
+        handle_entries_response(response)
+    }
+
+    /// Blocking version of search
+    pub fn search_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        search_query: String,
+        options: SearchOptions
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_search_url(&api_server, &search_query, &options)?;
+
+        let mut request = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token));
+        if let Some(prefer) = options.prefer() {
+            request = request.header("Prefer", prefer);
         }
 
-        let entries = response.json::<Entries>()?;
-        Ok(EntriesOrError::Entries(entries))
+        handle_entries_response(request.send_retrying()?)
     }
 
     /// Blocking version of export
     pub fn export_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        entry_id: i64,
+        entry_id: impl Into<DocumentId>,
         file_path: &str
     ) -> Result<BitsOrError> {
+        Self::export_with_options_blocking(api_server, auth, entry_id, file_path, &ExportOptions::default())
+    }
+
+    /// Blocking version of export_with_options
+    pub fn export_with_options_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<BitsOrError> {
+        let entry_id = entry_id.into().as_i64();
         // Validate inputs
         let validated_id = validation::validate_entry_id(entry_id)?;
         let validated_path = validation::validate_file_path(file_path)?;
-        
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}/Laserfiche.Repository.Document/edoc",
-            api_server.address,
-            api_server.repository,
-            validated_id
-        );
-        
-        let response = reqwest::blocking::Client::new()
+        if !options.overwrites() && validated_path.exists() {
+            return Err(ErrorKind::FileExists(validated_path.display().to_string()).into());
+        }
+
+        let bytes = match Self::export_bytes_blocking(api_server, auth, validated_id)? {
+            BitsOrError::Bits(bytes) => bytes,
+            error => return Ok(error),
+        };
+        write_atomically(&validated_path, &bytes, options.overwrites()).map_err(|error| match error.kind() {
+            std::io::ErrorKind::AlreadyExists => Error::from(ErrorKind::FileExists(validated_path.display().to_string())),
+            _ => error.into(),
+        })?;
+
+        Ok(BitsOrError::Bits(bytes))
+    }
+
+    /// Blocking version of export_bytes
+    pub fn export_bytes_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>
+    ) -> Result<BitsOrError> {
+        let validated_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let url = RequestUrl::entry(&api_server, validated_id)?
+            .segment("Laserfiche.Repository.Document")
+            .segment("edoc")
+            .build()?;
+
+        let response = http_client()
             .get(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::OK {
             let error = response.json::<LFAPIError>()?;
             return Ok(BitsOrError::LFAPIError(error));
         }
 
-        let bytes = response.bytes()?;
-        let mut file = std::fs::File::create(&validated_path)?;
-        let mut cursor = Cursor::new(&bytes);
-        std::io::copy(&mut cursor, &mut file)?;
-        
-        Ok(BitsOrError::Bits(bytes.to_vec()))
+        Ok(BitsOrError::Bits(response.bytes()?.to_vec()))
     }
 
     /// Blocking version of get_metadata
     pub fn get_metadata_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        entry_id: i64
+        entry_id: impl Into<EntryId>
     ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into().as_i64();
         // Validate entry ID
         let validated_id = validation::validate_entry_id(entry_id)?;
         
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}/fields",
-            api_server.address,
-            api_server.repository,
-            validated_id
-        );
+        let url = RequestUrl::entry(&api_server, validated_id)?
+            .segment("fields")
+            .build()?;
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .get(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::OK {
             let error = response.json::<LFAPIError>()?;
@@ -297,25 +522,23 @@ impl Entry {
     pub fn update_metadata_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        entry_id: i64,
+        entry_id: impl Into<EntryId>,
         metadata: serde_json::Value
     ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into().as_i64();
         // Validate inputs
         let validated_id = validation::validate_entry_id(entry_id)?;
         let validated_metadata = validation::validate_metadata_json(&metadata)?;
         
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}/fields",
-            api_server.address,
-            api_server.repository,
-            validated_id
-        );
+        let url = RequestUrl::entry(&api_server, validated_id)?
+            .segment("fields")
+            .build()?;
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .put(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
             .json(&validated_metadata)
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::OK {
             let error = response.json::<LFAPIError>()?;
@@ -330,26 +553,29 @@ impl Entry {
     pub fn delete_blocking(
         api_server: LFApiServer,
         auth: Auth,
-        root_id: i64,
+        root_id: impl Into<EntryId>,
         comment: String
     ) -> Result<LFObject> {
-        let params = json!({
-            "auditReasonId": 0,
-            "comment": comment
-        });
-
-        let url = format!(
-            "https://{}/LFRepositoryAPI/v1/Repositories/{}/Entries/{}",
-            api_server.address,
-            api_server.repository,
-            root_id
-        );
+        Self::delete_with_options_blocking(api_server, auth, root_id, &DeleteOptions::new().comment(comment))
+    }
+
+    /// Blocking version of delete_with_options
+    pub fn delete_with_options_blocking(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<EntryId>,
+        options: &DeleteOptions
+    ) -> Result<LFObject> {
+        let root_id = root_id.into().as_i64();
+        let params = DestroyEntry::from(options);
+
+        let url = RequestUrl::entry(&api_server, root_id)?.build()?;
         
-        let response = reqwest::blocking::Client::new()
+        let response = http_client()
             .delete(url)
             .header("Authorization", format!("Bearer {}", auth.access_token))
             .json(&params)
-            .send()?;
+            .send_retrying()?;
 
         if response.status() != reqwest::StatusCode::CREATED {
             let error = response.json::<LFAPIError>()?;
@@ -359,4 +585,4 @@ impl Entry {
         let deleted = response.json::<DeletedObject>()?;
         Ok(LFObject::DeletedObject(deleted))
     }
-}
\ No newline at end of file
+}
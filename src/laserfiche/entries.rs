@@ -0,0 +1,2161 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Entry calls: get, list, walk, create, import, export, copy, move, and delete
+
+use super::{box_stream, http_client, with_http_client, ApiHelper, BoxStream, MaybeSend, RequestUrl, UrlError, SCOPED_HTTP_CLIENT};
+use super::auth::Auth;
+use super::error::{Error, ErrorKind, LFAPIError, Result};
+use super::metadata::fields_json;
+use super::types::{
+    BitsOrError, DeletedObject, DocumentId, EdocDeletedOrError, EdocInfoOrError, EdocReaderOrError, Entries, EntriesOrError, Entry, EntryId, EntryOrError,
+    EntryType, FolderId, ImportResult, ImportResultOrError, LFApiServer, LFObject, NextLink, WrittenOrError,
+};
+use crate::mime;
+use crate::progress::TransferProgress;
+use crate::retry::RetryingSend;
+use crate::validation;
+
+use futures_util::stream::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncReadExt;
+use tokio_util::io::StreamReader;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct DestroyEntry {
+    audit_reason_id: i64,
+    comment: String,
+}
+
+impl From<&DeleteOptions> for DestroyEntry {
+    fn from(options: &DeleteOptions) -> Self {
+        DestroyEntry {
+            audit_reason_id: options.audit_reason_id.unwrap_or(0),
+            comment: options.comment.clone(),
+        }
+    }
+}
+
+/// Audit reason and comment for `Entry::delete_with_options`, built with
+/// chained setters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeleteOptions {
+    audit_reason_id: Option<i64>,
+    comment: String,
+}
+
+impl DeleteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cite an audit reason, one of the `delete_entry` ids from
+    /// `AuditReasons::list`; without one the server records none
+    pub fn audit_reason(mut self, audit_reason_id: i64) -> Self {
+        self.audit_reason_id = Some(audit_reason_id);
+        self
+    }
+
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = comment.into();
+        self
+    }
+}
+
+/// How `Entry::export_with_options` saves the downloaded file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportOptions {
+    overwrite: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions { overwrite: true }
+    }
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace a file already at the destination (the default); when
+    /// false, the export fails with `ErrorKind::FileExists` instead
+    pub fn overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn overwrites(&self) -> bool {
+        self.overwrite
+    }
+}
+
+/// Write `bytes` to `path` through a temporary file in the same directory
+/// that is renamed into place once complete, so a failed or interrupted
+/// write never leaves a truncated file at `path`
+///
+/// Without `overwrite`, the file is linked into place instead, which fails
+/// with `io::ErrorKind::AlreadyExists` if something is already at `path`.
+/// On filesystems without hard links, such as FAT or some network shares,
+/// the name is claimed with an empty file that is then renamed over.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn write_atomically(path: &std::path::Path, bytes: &[u8], overwrite: bool) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT_TEMP: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = path.file_name().ok_or(std::io::ErrorKind::InvalidInput)?;
+    let temp_name = format!(
+        ".{}.{}-{}.part",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        NEXT_TEMP.fetch_add(1, Ordering::Relaxed)
+    );
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|_| {
+            if overwrite {
+                std::fs::rename(&temp_path, path)
+            } else {
+                match std::fs::hard_link(&temp_path, path) {
+                    Ok(()) => std::fs::remove_file(&temp_path),
+                    Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(e),
+                    Err(_) => claim_and_rename(&temp_path, path),
+                }
+            }
+        });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
+
+/// Move `temp_path` to `path` only if nothing is there yet, without hard links
+#[cfg(not(target_arch = "wasm32"))]
+fn claim_and_rename(temp_path: &std::path::Path, path: &std::path::Path) -> std::io::Result<()> {
+    std::fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+    std::fs::rename(temp_path, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(path);
+    })
+}
+
+/// An audit reason configured in the repository
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditReason {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Audit reasons the repository accepts, by operation
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuditReasons {
+    pub delete_entry: Vec<AuditReason>,
+    pub export_document: Vec<AuditReason>,
+}
+
+impl AuditReasons {
+    /// Delete reason named `name`, ignoring case
+    pub fn delete_reason(&self, name: &str) -> Option<&AuditReason> {
+        self.delete_entry.iter().find(|reason| reason.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Fetch the audit reasons configured in the repository
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn list(api_server: LFApiServer, auth: Auth) -> Result<AuditReasonsOrError> {
+        let url = RequestUrl::repository(&api_server)?.segment("AuditReasons").build()?;
+
+        let request = http_client().get(url);
+
+        match ApiHelper::execute_request::<AuditReasons>(request, &auth.access_token, reqwest::StatusCode::OK).await? {
+            Ok(reasons) => Ok(AuditReasonsOrError::AuditReasons(reasons)),
+            Err(error) => Ok(AuditReasonsOrError::LFAPIError(error)),
+        }
+    }
+}
+
+pub enum AuditReasonsOrError {
+    AuditReasons(AuditReasons),
+    LFAPIError(LFAPIError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct PatchedEntry {
+    parent_id: Option<i64>,
+    name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct NewEntry {
+    entry_type: EntryType,
+    name: String,
+    volume_name: String,
+}
+
+/// Position of a pager in a paged collection
+pub(crate) enum PageCursor {
+    First,
+    Next(NextLink),
+    Done,
+}
+
+impl PageCursor {
+    /// The cursor following `entries`
+    pub(crate) fn after(entries: &Entries) -> Self {
+        entries.next_link().map_or(PageCursor::Done, PageCursor::Next)
+    }
+}
+
+/// Every entry of a paged collection, fetching each page only once the
+/// previous one is used up
+///
+/// `fetch` is given `None` for the first page and the next link after that.
+/// An API error ends the stream with `ErrorKind::ApiError`.
+pub(crate) fn paginate<F, Fut>(fetch: F) -> BoxStream<'static, Result<Entry>>
+where
+    F: FnMut(Option<NextLink>) -> Fut + MaybeSend + 'static,
+    Fut: std::future::Future<Output = Result<EntriesOrError>> + MaybeSend + 'static,
+{
+    let entries = futures_util::stream::try_unfold((PageCursor::First, fetch), |(cursor, mut fetch)| async move {
+        let next_link = match cursor {
+            PageCursor::First => None,
+            PageCursor::Next(next_link) => Some(next_link),
+            PageCursor::Done => return Ok(None),
+        };
+        match fetch(next_link).await? {
+            EntriesOrError::Entries(entries) => {
+                let cursor = PageCursor::after(&entries);
+                Ok(Some((entries.value, (cursor, fetch))))
+            }
+            EntriesOrError::LFAPIError(error) => Err(Error::from(ErrorKind::ApiError(Box::new(error)))),
+        }
+    })
+    .map_ok(|page| futures_util::stream::iter(page.into_iter().map(Ok)))
+    .try_flatten();
+    box_stream(entries)
+}
+
+/// Most child names `Entry::find_children_by_names` puts in one `$filter`,
+/// keeping request URLs well under common length limits
+pub const NAMES_PER_FILTER: usize = 20;
+
+/// List options matching children named any of `names`
+pub(crate) fn names_filter(names: &[&str]) -> ListOptions {
+    let filter = names
+        .iter()
+        .map(|name| format!("name eq '{}'", name.replace('\'', "''")))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    ListOptions::new().select("id,name").filter(filter)
+}
+
+/// Record `child` under each of `names` it answers to
+pub(crate) fn record_named_child(names: &[&str], child: &Entry, found: &mut BTreeMap<String, EntryId>) {
+    for name in names.iter().filter(|name| name.eq_ignore_ascii_case(&child.name)) {
+        found.insert(name.to_string(), child.entry_id());
+    }
+}
+
+/// Entries below `root`, breadth first, with each folder's children
+/// streamed from `list`
+///
+/// Shortcuts are not descended into. The stream ends after the first error.
+pub(crate) fn walk_tree<F>(root: FolderId, list: F) -> BoxStream<'static, Result<Entry>>
+where
+    F: FnMut(FolderId) -> BoxStream<'static, Result<Entry>> + MaybeSend + 'static,
+{
+    let pending = std::collections::VecDeque::from([root]);
+    let listing: Option<BoxStream<'static, Result<Entry>>> = None;
+    let entries = futures_util::stream::unfold(Some((pending, listing, list)), |state| async move {
+        let (mut pending, mut listing, mut list) = state?;
+        loop {
+            if let Some(children) = listing.as_mut() {
+                match children.next().await {
+                    Some(Ok(entry)) => {
+                        if let Some(folder_id) = entry.folder_id().filter(|_| !entry.is_shortcut()) {
+                            pending.push_back(folder_id);
+                        }
+                        return Some((Ok(entry), Some((pending, listing, list))));
+                    }
+                    Some(Err(error)) => return Some((Err(error), None)),
+                    // This folder is done; move on to the next
+                    None => {}
+                }
+            }
+            let folder_id = pending.pop_front()?;
+            listing = Some(list(folder_id));
+        }
+    });
+    box_stream(entries)
+}
+
+/// A document's content as its headers describe it, from `Entry::edoc_head`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdocInfo {
+    pub content_type: Option<String>,
+    /// Size of the content in bytes
+    pub content_length: Option<u64>,
+    /// Changes whenever the content does, for comparing against a copy
+    /// already downloaded
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl EdocInfo {
+    pub(crate) fn from_headers(headers: &reqwest::header::HeaderMap) -> Self {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        EdocInfo {
+            content_type: header(reqwest::header::CONTENT_TYPE).map(str::to_string),
+            content_length: header(reqwest::header::CONTENT_LENGTH).and_then(|value| value.parse().ok()),
+            etag: header(reqwest::header::ETAG).map(str::to_string),
+            last_modified: header(reqwest::header::LAST_MODIFIED).and_then(|value| httpdate::parse_http_date(value).ok()),
+        }
+    }
+}
+
+/// Where `Entry::export_resume` keeps the ETag of an incomplete download
+#[cfg(not(target_arch = "wasm32"))]
+fn resume_etag_path(path: &std::path::Path) -> std::path::PathBuf {
+    let mut etag_path = path.as_os_str().to_owned();
+    etag_path.push(".etag");
+    etag_path.into()
+}
+
+/// The first byte and total size from a `Content-Range` header, e.g.
+/// `bytes 100-199/1000` or `bytes */1000`
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_content_range(value: &str) -> (Option<u64>, Option<u64>) {
+    let Some(range) = value.strip_prefix("bytes ") else {
+        return (None, None);
+    };
+    let (span, total) = range.split_once('/').unwrap_or((range, "*"));
+    let start = span.split_once('-').and_then(|(start, _)| start.parse().ok());
+    (start, total.parse().ok())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn remove_if_exists(path: &std::path::Path) -> std::io::Result<()> {
+    match tokio::fs::remove_file(path).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Streaming handle on a document's electronic content
+///
+/// Reads the response body as it arrives, so large documents can be piped
+/// into parsers or scanners without buffering them or writing temp files.
+pub struct EdocReader {
+    inner: StreamReader<BoxStream<'static, std::io::Result<bytes::Bytes>>, bytes::Bytes>,
+    content_length: Option<u64>,
+    content_type: Option<String>,
+    reported_sha256: Option<String>,
+}
+
+impl EdocReader {
+    pub(crate) fn from_response(response: reqwest::Response) -> Self {
+        let content_length = response.content_length();
+        let content_type = response.headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let reported_sha256 = crate::integrity::reported_sha256(response.headers());
+        let stream = box_stream(response.bytes_stream().map_err(std::io::Error::other));
+
+        EdocReader {
+            inner: StreamReader::new(stream),
+            content_length,
+            content_type,
+            reported_sha256,
+        }
+    }
+
+    /// Hex SHA-256 of the content, when the server reports one in a
+    /// `Repr-Digest` or `Digest` header
+    pub fn reported_sha256(&self) -> Option<&str> {
+        self.reported_sha256.as_deref()
+    }
+
+    /// Size of the content in bytes, when the server reports it
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    /// MIME type reported by the server
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+}
+
+impl AsyncRead for EdocReader {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl std::fmt::Debug for EdocReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EdocReader")
+            .field("content_length", &self.content_length)
+            .field("content_type", &self.content_type)
+            .finish_non_exhaustive()
+    }
+}
+
+/// OData and listing options for `Entry::list_with_options`, built with
+/// chained setters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListOptions {
+    select: Option<String>,
+    order_by: Option<String>,
+    filter: Option<String>,
+    skip: Option<u32>,
+    top: Option<u32>,
+    count: bool,
+    group_by_entry_type: Option<bool>,
+    fields: Vec<String>,
+    format_field_values: Option<bool>,
+}
+
+impl ListOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// OData `$select`, e.g. `"id,name,entryType"`
+    pub fn select(mut self, select: impl Into<String>) -> Self {
+        self.select = Some(select.into());
+        self
+    }
+
+    /// OData `$orderby`, e.g. `"name asc"`
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.order_by = Some(order_by.into());
+        self
+    }
+
+    /// OData `$filter`, e.g. `"entryType eq 'Document'"`
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// OData `$skip`: number of children to skip
+    pub fn skip(mut self, skip: u32) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// OData `$top`: maximum number of children to return
+    pub fn top(mut self, top: u32) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// OData `$count`: include the total in `Entries::odata_count`
+    pub fn count(mut self, count: bool) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// List folders before documents
+    pub fn group_by_entry_type(mut self, group: bool) -> Self {
+        self.group_by_entry_type = Some(group);
+        self
+    }
+
+    /// Include a template field's values in each entry; repeat for more fields
+    pub fn field(mut self, field_name: impl Into<String>) -> Self {
+        self.fields.push(field_name.into());
+        self
+    }
+
+    /// Return field values formatted for display rather than raw
+    pub fn format_field_values(mut self, format: bool) -> Self {
+        self.format_field_values = Some(format);
+        self
+    }
+
+    /// Add `properties` to a `$select` projection that lacks them; without a
+    /// projection every property is already returned
+    pub(crate) fn selecting(mut self, properties: &[&str]) -> Self {
+        if let Some(select) = &mut self.select {
+            for property in properties {
+                if !select.split(',').any(|selected| selected.trim() == *property) {
+                    select.push(',');
+                    select.push_str(property);
+                }
+            }
+        }
+        self
+    }
+
+    pub(crate) fn apply(&self, url: RequestUrl) -> RequestUrl {
+        let url = url.query_opt("groupByEntryType", self.group_by_entry_type);
+        let url = self.fields.iter().fold(url, |url, field| url.query("fields", field));
+        url.query_opt("formatFieldValues", self.format_field_values)
+            .query_opt("$select", self.select.as_deref())
+            .query_opt("$orderby", self.order_by.as_deref())
+            .query_opt("$filter", self.filter.as_deref())
+            .query_opt("$skip", self.skip)
+            .query_opt("$top", self.top)
+            .query_opt("$count", self.count.then_some(true))
+    }
+}
+
+/// Template, field values, tags, and volume sent with an import, so one
+/// call can fully index a document; built with chained setters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportRequest {
+    template: Option<String>,
+    fields: BTreeMap<String, Vec<String>>,
+    tags: Vec<String>,
+    volume: Option<String>,
+    mime_type: Option<String>,
+}
+
+impl ImportRequest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Template to assign; fields must belong to it
+    pub fn template(mut self, template_name: impl Into<String>) -> Self {
+        self.template = Some(template_name.into());
+        self
+    }
+
+    /// Add a value to a field; repeat for each value of a multi-value field
+    pub fn field(mut self, field_name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.entry(field_name.into()).or_default().push(value.into());
+        self
+    }
+
+    /// Set all values of a field, replacing any added before
+    pub fn field_values(mut self, field_name: impl Into<String>, values: Vec<String>) -> Self {
+        self.fields.insert(field_name.into(), values);
+        self
+    }
+
+    /// Tag to assign, by name
+    pub fn tag(mut self, tag_name: impl Into<String>) -> Self {
+        self.tags.push(tag_name.into());
+        self
+    }
+
+    /// Volume to store the electronic document in
+    pub fn volume(mut self, volume_name: impl Into<String>) -> Self {
+        self.volume = Some(volume_name.into());
+        self
+    }
+
+    /// Content type to upload the document as, instead of the one detected
+    /// from its name and content
+    pub fn mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// The validated `mime_type` override, or the type detected for
+    /// `file_name` and `content`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn resolve_mime_type(&self, file_name: &str, content: &[u8]) -> validation::Result<String> {
+        match &self.mime_type {
+            Some(mime_type) => validation::validate_mime_type(mime_type),
+            None => Ok(mime::detect_mime_type_with_content(file_name, content)),
+        }
+    }
+
+    /// The `request` part of the import form
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        let mut request = serde_json::Map::new();
+        if let Some(template) = &self.template {
+            request.insert("template".to_string(), json!(template));
+        }
+        let mut metadata = serde_json::Map::new();
+        if !self.fields.is_empty() {
+            metadata.insert("fields".to_string(), fields_json(&self.fields));
+        }
+        if !self.tags.is_empty() {
+            metadata.insert("tags".to_string(), json!(self.tags));
+        }
+        if !metadata.is_empty() {
+            request.insert("metadata".to_string(), serde_json::Value::Object(metadata));
+        }
+        if let Some(volume) = &self.volume {
+            request.insert("volumeName".to_string(), json!(volume));
+        }
+        serde_json::Value::Object(request)
+    }
+}
+
+impl Entry {
+    /// Import a document into Laserfiche repository
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `file_path` - Path to the file to import
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>
+    ) -> Result<ImportResultOrError> {
+        let root_id = root_id.into().as_i64();
+        // Validate inputs
+        let validated_path = validation::validate_file_path(&file_path)?;
+        let validated_name = validation::validate_file_name(&file_name)?;
+        let validated_root_id = validation::validate_entry_id(root_id)?;
+        
+        let file_content = std::fs::read(&validated_path)?;
+        let mime_type = mime::detect_mime_type_with_content(&validated_name, &file_content);
+        
+        Self::import_content(api_server, auth, file_content, &validated_name, validated_root_id, &mime_type).await
+    }
+
+    /// Import a document, sending `mime_type` instead of the one detected
+    /// from its extension
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `file_path` - Path to the file to import
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    /// * `mime_type` - Content type of the upload, e.g. `application/vnd.ms-outlook`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_mime_type(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        mime_type: &str
+    ) -> Result<ImportResultOrError> {
+        let root_id = root_id.into().as_i64();
+        let validated_path = validation::validate_file_path(&file_path)?;
+        let validated_name = validation::validate_file_name(&file_name)?;
+        let validated_root_id = validation::validate_entry_id(root_id)?;
+        let validated_mime = validation::validate_mime_type(mime_type)?;
+
+        let file_content = std::fs::read(&validated_path)?;
+
+        Self::import_content(api_server, auth, file_content, &validated_name, validated_root_id, &validated_mime).await
+    }
+
+    /// Import a document and assign its template, field values, and tags
+    /// in the same request
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `file_path` - Path to the file to import
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    /// * `request` - Metadata to set on the new document
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_request(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        request: &ImportRequest
+    ) -> Result<ImportResultOrError> {
+        let root_id = root_id.into().as_i64();
+        let validated_path = validation::validate_file_path(&file_path)?;
+        let validated_name = validation::validate_file_name(&file_name)?;
+        let validated_root_id = validation::validate_entry_id(root_id)?;
+
+        let file_content = std::fs::read(&validated_path)?;
+        validation::validate_file_size(file_content.len() as u64)?;
+        let mime_type = request.resolve_mime_type(&validated_name, &file_content)?;
+
+        let file_part = reqwest::multipart::Part::bytes(file_content);
+        Self::upload(api_server, auth, file_part, &validated_name, validated_root_id, &mime_type, request).await
+    }
+
+    /// Import a document streamed from `reader`, without reading it into
+    /// memory or needing it on disk
+    ///
+    /// `length` must be the exact number of bytes `reader` yields; it is sent
+    /// as the part's length and checked against the upload size limit.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `reader` - Source of the document content
+    /// * `length` - Content length in bytes
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    /// * `mime_type` - Content type of the upload, or `None` to detect it
+    ///   from `file_name` and the first bytes `reader` yields
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_reader<R>(
+        api_server: LFApiServer,
+        auth: Auth,
+        reader: R,
+        length: u64,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResultOrError>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        let root_id = root_id.into().as_i64();
+        let validated_name = validation::validate_file_name(&file_name)?;
+        let validated_root_id = validation::validate_entry_id(root_id)?;
+        validation::validate_file_size(length)?;
+
+        // Bytes read for sniffing are sent ahead of the rest of the reader
+        let mut reader = Box::pin(reader);
+        let mut head = Vec::new();
+        let mime_type = match mime_type {
+            Some(mime_type) => validation::validate_mime_type(mime_type)?,
+            None => {
+                (&mut reader).take(mime::SNIFF_LEN as u64).read_to_end(&mut head).await?;
+                mime::detect_mime_type_with_content(&validated_name, &head)
+            }
+        };
+
+        let reader = std::io::Cursor::new(head).chain(reader);
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader));
+        let file_part = reqwest::multipart::Part::stream_with_length(body, length);
+        Self::upload(api_server, auth, file_part, &validated_name, validated_root_id, &mime_type, &ImportRequest::default()).await
+    }
+
+    /// Import a local file, streaming it from disk and reporting the bytes
+    /// sent to `progress`
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `file_path` - Path to the file to import
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    /// * `progress` - Receives a `TransferUpdate` as each chunk is sent
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_progress(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_path: String,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        progress: &TransferProgress
+    ) -> Result<ImportResultOrError> {
+        let validated_path = validation::validate_file_path(&file_path)?;
+        let file = tokio::fs::File::open(&validated_path).await?;
+        let length = file.metadata().await?.len();
+        let mime_type = mime::detect_mime_type_of_file(&file_name, &validated_path)?;
+        let reader = progress.reader(file, Some(length));
+        Self::import_reader(api_server, auth, reader, length, file_name, root_id, Some(&mime_type)).await
+    }
+
+    /// Import a document from content already in memory, e.g. a file picked
+    /// in a browser, without needing it on disk
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `content` - The document content
+    /// * `file_name` - Name for the document in repository
+    /// * `root_id` - Parent folder ID
+    /// * `mime_type` - Content type of the upload, or `None` to detect it from `file_name`
+    pub async fn import_bytes(
+        api_server: LFApiServer,
+        auth: Auth,
+        content: Vec<u8>,
+        file_name: String,
+        root_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResultOrError> {
+        let root_id = root_id.into().as_i64();
+        let validated_name = validation::validate_file_name(&file_name)?;
+        let validated_root_id = validation::validate_entry_id(root_id)?;
+        let mime_type = match mime_type {
+            Some(mime_type) => validation::validate_mime_type(mime_type)?,
+            None => mime::detect_mime_type_with_content(&validated_name, &content),
+        };
+
+        Self::import_content(api_server, auth, content, &validated_name, validated_root_id, &mime_type).await
+    }
+
+    /// Upload already-read content as a new document; shared by the import variants
+    pub(crate) async fn import_content(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_content: Vec<u8>,
+        validated_name: &str,
+        validated_root_id: i64,
+        mime_type: &str
+    ) -> Result<ImportResultOrError> {
+        // Validate file size
+        validation::validate_file_size(file_content.len() as u64)?;
+
+        let file_part = reqwest::multipart::Part::bytes(file_content);
+        Self::upload(api_server, auth, file_part, validated_name, validated_root_id, mime_type, &ImportRequest::default()).await
+    }
+
+    /// Post `file_part` as the document content of an import
+    async fn upload(
+        api_server: LFApiServer,
+        auth: Auth,
+        file_part: reqwest::multipart::Part,
+        validated_name: &str,
+        validated_root_id: i64,
+        mime_type: &str,
+        request: &ImportRequest
+    ) -> Result<ImportResultOrError> {
+        let form = Self::build_import_form(file_part, validated_name, mime_type, request)?;
+        let import_url = Self::build_import_url(&api_server, validated_root_id, validated_name)?;
+        
+        let response = http_client()
+            .post(import_url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .multipart(form)
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::CREATED {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(ImportResultOrError::LFAPIError(error));
+        }
+
+        let result = response.json::<ImportResult>().await?;
+        Ok(ImportResultOrError::ImportResult(result))
+    }
+
+    fn build_import_form(
+        file_part: reqwest::multipart::Part,
+        file_name: &str,
+        mime_type: &str,
+        request: &ImportRequest
+    ) -> Result<reqwest::multipart::Form> {
+        let file_part = file_part
+            .file_name(file_name.to_string())
+            .mime_str(mime_type)?;
+
+        let request_part = reqwest::multipart::Part::text(request.to_json().to_string())
+            .mime_str("application/json")?;
+
+        Ok(reqwest::multipart::Form::new()
+            .part("electronicDocument", file_part)
+            .part("request", request_part))
+    }
+
+    pub(crate) fn build_import_url(api_server: &LFApiServer, root_id: i64, file_name: &str) -> std::result::Result<String, UrlError> {
+        RequestUrl::entry(api_server, root_id)?
+            .segment(file_name)
+            .query("autoRename", "true")
+            .build()
+    }
+    
+    /// Create a new folder in the repository
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `folder_name` - Name for the new folder
+    /// * `volume_name` - Volume name
+    /// * `root_id` - Parent folder ID
+    pub async fn new_path(
+        api_server: LFApiServer,
+        auth: Auth,
+        folder_name: String,
+        volume_name: String,
+        root_id: impl Into<FolderId>
+    ) -> Result<EntryOrError> {
+        let root_id = root_id.into().as_i64();
+        let params = NewEntry {
+            entry_type: EntryType::Folder,
+            name: folder_name,
+            volume_name,
+        };
+
+        let url = RequestUrl::children(&api_server, root_id)?.build()?;
+
+        let response = http_client()
+            .post(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&params)
+            .send_retrying()
+            .await?;
+
+        Self::handle_entry_response(response, reqwest::StatusCode::CREATED).await
+    }
+
+    /// Get the folder at `path`, creating it and any missing parents first,
+    /// like `mkdir -p`
+    ///
+    /// Segments are separated by `\\` or `/` and resolved from the root
+    /// folder; empty segments are skipped, so `\\` is the root itself. A
+    /// folder another client creates between the lookup and the create is
+    /// found again and used rather than failing the walk.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `path` - Folder path, e.g. `\\Projects\\2024\\Invoices`
+    /// * `volume_name` - Volume for folders that have to be created
+    pub async fn ensure_path(
+        api_server: LFApiServer,
+        auth: Auth,
+        path: &str,
+        volume_name: String
+    ) -> Result<EntryOrError> {
+        Self::ensure_path_under(api_server, auth, FolderId::ROOT, path, volume_name).await
+    }
+
+    /// `ensure_path`, resolving `path` from `parent_id` instead of the root
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `parent_id` - Folder the path starts from; an empty path is this folder
+    /// * `path` - Folder path below `parent_id`, e.g. `2024\\Invoices`
+    /// * `volume_name` - Volume for folders that have to be created
+    pub async fn ensure_path_under(
+        api_server: LFApiServer,
+        auth: Auth,
+        parent_id: impl Into<FolderId>,
+        path: &str,
+        volume_name: String
+    ) -> Result<EntryOrError> {
+        let segments = path
+            .split(['\\', '/'])
+            .filter(|segment| !segment.is_empty())
+            .map(validation::validate_file_name)
+            .collect::<validation::Result<Vec<_>>>()?;
+
+        let mut folder_id = parent_id.into();
+        let mut created = None;
+        for segment in &segments {
+            if let Some(id) = Self::find_child(&api_server, &auth, folder_id, segment).await? {
+                folder_id = FolderId(id.as_i64());
+                created = None;
+                continue;
+            }
+
+            match Self::new_path(api_server.clone(), auth.clone(), segment.clone(), volume_name.clone(), folder_id).await? {
+                EntryOrError::Entry(entry) => {
+                    folder_id = FolderId(entry.id);
+                    created = Some(entry);
+                }
+                // Lost a race with another client creating the same folder
+                EntryOrError::LFAPIError(error) if error.is_conflict() => {
+                    match Self::find_child(&api_server, &auth, folder_id, segment).await? {
+                        Some(id) => folder_id = FolderId(id.as_i64()),
+                        None => return Ok(EntryOrError::LFAPIError(error)),
+                    }
+                    created = None;
+                }
+                error => return Ok(error),
+            }
+        }
+
+        if let Some(entry) = created {
+            return Ok(EntryOrError::Entry(entry));
+        }
+        match Self::get(api_server, auth, folder_id).await? {
+            EntryOrError::Entry(entry) if entry.folder_id().is_none() => {
+                Err(format!("'{}' is not a folder", entry.full_path).into())
+            }
+            found => Ok(found),
+        }
+    }
+
+    async fn find_child(api_server: &LFApiServer, auth: &Auth, folder_id: FolderId, name: &str) -> Result<Option<EntryId>> {
+        let mut found = Self::find_children_by_names(api_server.clone(), auth.clone(), folder_id, &[name]).await?;
+        Ok(found.remove(name))
+    }
+
+    async fn handle_entry_response(
+        response: reqwest::Response,
+        expected_status: reqwest::StatusCode
+    ) -> Result<EntryOrError> {
+        if response.status() != expected_status {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(EntryOrError::LFAPIError(error));
+        }
+        
+        let entry = response.json::<Entry>().await?;
+        Ok(EntryOrError::Entry(entry))
+    }
+
+    /// Describe a document's content from its headers, without downloading it
+    ///
+    /// A HEAD response has no body, so a refusal is reported with only its
+    /// status and reason.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Document to describe
+    pub async fn edoc_head(api_server: LFApiServer, auth: Auth, root_id: impl Into<DocumentId>) -> Result<EdocInfoOrError> {
+        let root_id = root_id.into().as_i64();
+        // Validate entry ID
+        let validated_id = validation::validate_entry_id(root_id)?;
+        let url = Self::build_edoc_url(&api_server, validated_id)?;
+
+        let response = http_client()
+            .head(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        let status = response.status();
+        if status != reqwest::StatusCode::OK {
+            return Ok(EdocInfoOrError::LFAPIError(Self::status_error(status)));
+        }
+
+        Ok(EdocInfoOrError::Info(EdocInfo::from_headers(response.headers())))
+    }
+
+    /// Export/download a document from the repository
+    ///
+    /// The file is written to a temporary file next to `file_path` and
+    /// renamed into place once complete, replacing any file already there.
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document entry ID
+    /// * `file_path` - Path to save the exported file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        file_path: &str
+    ) -> Result<BitsOrError> {
+        Self::export_with_options(api_server, auth, entry_id, file_path, &ExportOptions::default()).await
+    }
+
+    /// Export a document, choosing whether an existing file is replaced
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document entry ID
+    /// * `file_path` - Path to save the exported file
+    /// * `options` - Whether to overwrite an existing file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_with_options(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<BitsOrError> {
+        let entry_id = entry_id.into().as_i64();
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        let validated_path = validation::validate_file_path(file_path)?;
+        if !options.overwrite && validated_path.exists() {
+            return Err(ErrorKind::FileExists(validated_path.display().to_string()).into());
+        }
+
+        let bytes = match Self::export_bytes(api_server, auth, validated_id).await? {
+            BitsOrError::Bits(bytes) => bytes,
+            error => return Ok(error),
+        };
+        Self::save_to_file(&bytes, &validated_path, options.overwrite)?;
+
+        Ok(BitsOrError::Bits(bytes))
+    }
+
+    /// Download a document's content into memory without writing a file,
+    /// e.g. to hand it on to object storage or an HTTP response
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to download
+    pub async fn export_bytes(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>
+    ) -> Result<BitsOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let mut content = Vec::new();
+        match Self::export_stream(api_server, auth, entry_id, &mut content).await? {
+            WrittenOrError::Written(_) => Ok(BitsOrError::Bits(content)),
+            WrittenOrError::LFAPIError(error) => Ok(BitsOrError::LFAPIError(error)),
+        }
+    }
+
+    /// Open a document's content for streaming reads
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to read
+    pub async fn open(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>
+    ) -> Result<EdocReaderOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let url = Self::build_edoc_url(&api_server, entry_id)?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(EdocReaderOrError::LFAPIError(error));
+        }
+
+        Ok(EdocReaderOrError::Reader(EdocReader::from_response(response)))
+    }
+
+    /// Download a document's content into `writer` without buffering it
+    ///
+    /// Unlike `export`, memory use stays flat regardless of document size,
+    /// and the destination can be any `AsyncWrite` (a file, socket, or
+    /// archive entry). `writer` is flushed but not shut down.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to download
+    /// * `writer` - Destination for the content
+    pub async fn export_stream<W: AsyncWrite + Unpin + ?Sized>(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        writer: &mut W
+    ) -> Result<WrittenOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        match Self::open(api_server, auth, entry_id).await? {
+            EdocReaderOrError::Reader(reader) => Ok(WrittenOrError::Written(Self::write_edoc(reader, writer).await?)),
+            EdocReaderOrError::LFAPIError(error) => Ok(WrittenOrError::LFAPIError(error)),
+        }
+    }
+
+    /// Download a document's content into `writer` like `export_stream`,
+    /// reporting the bytes received to `progress`
+    ///
+    /// The total in each update is the response's `Content-Length`, when
+    /// the server sends one.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to download
+    /// * `writer` - Destination for the content
+    /// * `progress` - Receives a `TransferUpdate` as each chunk is written
+    pub async fn export_stream_with_progress<W: AsyncWrite + Unpin + ?Sized>(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        writer: &mut W,
+        progress: &TransferProgress
+    ) -> Result<WrittenOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        match Self::open(api_server, auth, entry_id).await? {
+            EdocReaderOrError::Reader(reader) => {
+                let mut writer = progress.writer(writer, reader.content_length());
+                Ok(WrittenOrError::Written(Self::write_edoc(reader, &mut writer).await?))
+            }
+            EdocReaderOrError::LFAPIError(error) => Ok(WrittenOrError::LFAPIError(error)),
+        }
+    }
+
+    /// Download bytes `start..=end` of a document's content, or from `start`
+    /// to the end when `end` is `None`
+    ///
+    /// Sends an HTTP `Range` request; a server that ignores it and returns
+    /// the whole document still yields only the requested bytes.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to download
+    /// * `start` - Offset of the first byte
+    /// * `end` - Offset of the last byte, inclusive
+    pub async fn export_range(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        start: u64,
+        end: Option<u64>
+    ) -> Result<BitsOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let range = match end {
+            Some(end) if end < start => return Err(format!("Invalid byte range {}-{}", start, end).into()),
+            Some(end) => format!("bytes={}-{}", start, end),
+            None => format!("bytes={}-", start),
+        };
+        let url = Self::build_edoc_url(&api_server, entry_id)?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .header(reqwest::header::RANGE, range)
+            .send_retrying()
+            .await?;
+
+        match response.status() {
+            reqwest::StatusCode::PARTIAL_CONTENT => Ok(BitsOrError::Bits(response.bytes().await?.to_vec())),
+            reqwest::StatusCode::OK => {
+                let content = response.bytes().await?;
+                let len = content.len() as u64;
+                let to = end.map_or(len, |end| end.saturating_add(1).min(len));
+                let from = start.min(to);
+                Ok(BitsOrError::Bits(content[from as usize..to as usize].to_vec()))
+            }
+            status @ reqwest::StatusCode::RANGE_NOT_SATISFIABLE => Ok(BitsOrError::LFAPIError(Self::status_error(status))),
+            _ => Ok(BitsOrError::LFAPIError(response.json::<LFAPIError>().await?)),
+        }
+    }
+
+    /// Download a document into `partial_file`, continuing from where an
+    /// interrupted download of the same version stopped
+    ///
+    /// While the download is incomplete, the document's ETag is kept beside
+    /// the file in `<partial_file>.etag`. A resume sends it as `If-Range`,
+    /// so if the document has changed since, the server returns the new
+    /// version in full and the file is rewritten instead of mixing versions.
+    /// Without a saved ETag the download starts over. The `.etag` file is
+    /// removed once the download completes.
+    ///
+    /// Returns the bytes written by this call.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to download
+    /// * `partial_file` - Path of the file being downloaded
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_resume(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        partial_file: &str
+    ) -> Result<WrittenOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let path = validation::validate_file_path(partial_file)?;
+        let etag_path = resume_etag_path(&path);
+        let offset = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata.len(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(error) => return Err(error.into()),
+        };
+        // A weak ETag cannot be used with If-Range
+        let etag = tokio::fs::read_to_string(&etag_path).await.ok()
+            .filter(|etag| offset > 0 && !etag.is_empty() && !etag.starts_with("W/"));
+        let url = Self::build_edoc_url(&api_server, entry_id)?;
+
+        let mut request = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token));
+        if let Some(etag) = &etag {
+            request = request
+                .header(reqwest::header::RANGE, format!("bytes={}-", offset))
+                .header(reqwest::header::IF_RANGE, etag.as_str());
+        }
+        let response = request.send_retrying().await?;
+
+        let headers = response.headers();
+        let content_range = headers.get(reqwest::header::CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_content_range)
+            .unwrap_or_default();
+        let response_etag = headers.get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let append = match response.status() {
+            reqwest::StatusCode::PARTIAL_CONTENT => {
+                let same_version = response_etag.is_none() || response_etag == etag;
+                if content_range.0 != Some(offset) || !same_version {
+                    return Err(format!("Download of entry {} did not resume from byte {}", entry_id, offset).into());
+                }
+                true
+            }
+            reqwest::StatusCode::OK => false,
+            status @ reqwest::StatusCode::RANGE_NOT_SATISFIABLE => {
+                // The file already holds the whole document
+                if content_range.1 == Some(offset) {
+                    remove_if_exists(&etag_path).await?;
+                    return Ok(WrittenOrError::Written(0));
+                }
+                return Ok(WrittenOrError::LFAPIError(Self::status_error(status)));
+            }
+            _ => return Ok(WrittenOrError::LFAPIError(response.json::<LFAPIError>().await?)),
+        };
+
+        let mut file = if append {
+            tokio::fs::OpenOptions::new().append(true).open(&path).await?
+        } else {
+            match &response_etag {
+                Some(etag) => tokio::fs::write(&etag_path, etag).await?,
+                None => remove_if_exists(&etag_path).await?,
+            }
+            tokio::fs::File::create(&path).await?
+        };
+        // An error here leaves the file and its ETag in place for the next resume
+        let written = Self::write_edoc(EdocReader::from_response(response), &mut file).await?;
+        remove_if_exists(&etag_path).await?;
+        Ok(WrittenOrError::Written(written))
+    }
+
+    /// Replace a document's electronic content with content in memory,
+    /// keeping its id, metadata, and links
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to update
+    /// * `content` - The new content
+    /// * `mime_type` - Content type of the new content
+    pub async fn set_edoc_bytes(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        content: Vec<u8>,
+        mime_type: &str
+    ) -> Result<WrittenOrError> {
+        let length = content.len() as u64;
+        validation::validate_file_size(length)?;
+        Self::put_edoc(api_server, auth, entry_id, reqwest::Body::from(content), length, mime_type).await
+    }
+
+    /// Replace a document's electronic content with content streamed from
+    /// `reader`, without reading it into memory
+    ///
+    /// `length` must be the exact number of bytes `reader` yields. A
+    /// streamed body cannot be replayed, so the upload is not retried.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document to update
+    /// * `reader` - Source of the new content
+    /// * `length` - Content length in bytes
+    /// * `mime_type` - Content type of the new content
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn set_edoc<R>(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        reader: R,
+        length: u64,
+        mime_type: &str
+    ) -> Result<WrittenOrError>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        validation::validate_file_size(length)?;
+        let body = reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader));
+        Self::put_edoc(api_server, auth, entry_id, body, length, mime_type).await
+    }
+
+    /// PUT `body` as the document's content; shared by the `set_edoc` variants
+    async fn put_edoc(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>,
+        body: reqwest::Body,
+        length: u64,
+        mime_type: &str
+    ) -> Result<WrittenOrError> {
+        let validated_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let mime_type = validation::validate_mime_type(mime_type)?;
+        let url = Self::build_edoc_url(&api_server, validated_id)?;
+
+        let response = http_client()
+            .put(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .header(reqwest::header::CONTENT_TYPE, mime_type)
+            .header(reqwest::header::CONTENT_LENGTH, length)
+            .body(body)
+            .send_retrying()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(WrittenOrError::LFAPIError(error));
+        }
+
+        Ok(WrittenOrError::Written(length))
+    }
+
+    /// Remove a document's electronic content, leaving the entry, its
+    /// metadata, and any pages in place
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Document whose content is removed
+    pub async fn delete_edoc(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<DocumentId>
+    ) -> Result<EdocDeletedOrError> {
+        let validated_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        let url = Self::build_edoc_url(&api_server, validated_id)?;
+
+        let response = http_client()
+            .delete(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(EdocDeletedOrError::LFAPIError(error));
+        }
+
+        Ok(EdocDeletedOrError::Deleted(DocumentId(validated_id)))
+    }
+
+    fn build_edoc_url(api_server: &LFApiServer, entry_id: i64) -> std::result::Result<String, UrlError> {
+        RequestUrl::entry(api_server, entry_id)?
+            .segment("Laserfiche.Repository.Document")
+            .segment("edoc")
+            .build()
+    }
+
+    /// An error for a response without a JSON body, e.g. to a HEAD or a
+    /// `Range` request
+    fn status_error(status: reqwest::StatusCode) -> LFAPIError {
+        LFAPIError {
+            title: status.canonical_reason().map(str::to_string),
+            status: Some(i64::from(status.as_u16())),
+            ..Default::default()
+        }
+    }
+
+    async fn write_edoc<W: AsyncWrite + Unpin + ?Sized>(mut reader: EdocReader, writer: &mut W) -> Result<u64> {
+        let written = tokio::io::copy(&mut reader, writer).await?;
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_to_file(bytes: &[u8], file_path: &std::path::Path, overwrite: bool) -> Result<()> {
+        write_atomically(file_path, bytes, overwrite).map_err(|error| match error.kind() {
+            std::io::ErrorKind::AlreadyExists => ErrorKind::FileExists(file_path.display().to_string()).into(),
+            _ => error.into(),
+        })
+    }
+
+    /// Get entry information by ID
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Entry ID
+    pub async fn get(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<EntryId>
+    ) -> Result<EntryOrError> {
+        let root_id = root_id.into().as_i64();
+        let validated_id = validation::validate_entry_id(root_id)?;
+        let url = RequestUrl::entry(&api_server, validated_id)?.build()?;
+        
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        Self::handle_entry_response(response, reqwest::StatusCode::OK).await
+    }
+
+    /// Delete an entry from the repository
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Entry ID to delete
+    /// * `comment` - Audit comment for deletion
+    pub async fn delete(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<EntryId>,
+        comment: String
+    ) -> Result<LFObject> {
+        Self::delete_with_options(api_server, auth, root_id, &DeleteOptions::new().comment(comment)).await
+    }
+
+    /// Delete an entry citing an audit reason
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Entry ID to delete
+    /// * `options` - Audit reason and comment for the deletion
+    pub async fn delete_with_options(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<EntryId>,
+        options: &DeleteOptions
+    ) -> Result<LFObject> {
+        let root_id = root_id.into().as_i64();
+        // Validate entry ID
+        let validated_id = validation::validate_entry_id(root_id)?;
+        
+        let params = DestroyEntry::from(options);
+
+        let url = RequestUrl::entry(&api_server, validated_id)?.build()?;
+        
+        let response = http_client()
+            .delete(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&params)
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::CREATED {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(LFObject::LFAPIError(error));
+        }
+
+        let deleted = response.json::<DeletedObject>().await?;
+        Ok(LFObject::DeletedObject(deleted))
+    }
+
+    /// Move or rename an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Entry ID to move/rename
+    /// * `parent_id` - New parent folder ID (for moving)
+    /// * `new_name` - New name (for renaming)
+    pub async fn patch(api_server: LFApiServer, auth: Auth, root_id: impl Into<EntryId>, parent_id: Option<FolderId>, new_name: Option<String>) -> Result<LFObject> {
+        let root_id = root_id.into().as_i64();
+        let parent_id = parent_id.map(|id| id.as_i64());
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(root_id)?;
+        let validated_parent_id = if let Some(pid) = parent_id {
+            Some(validation::validate_entry_id(pid)?)
+        } else {
+            None
+        };
+        let validated_name = if let Some(name) = &new_name {
+            Some(validation::validate_file_name(name)?)
+        } else {
+            None
+        };
+        
+        let params = PatchedEntry {
+            parent_id: validated_parent_id,
+            name: validated_name.clone(),
+        };   
+
+        let request = http_client()
+        .patch(RequestUrl::entry(&api_server, validated_id)?.build()?)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .json(&params)
+        .send_retrying().await;
+
+        match request{
+            Ok(req) => {
+
+                if req.status() != reqwest::StatusCode::OK{
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(LFObject::LFAPIError(json));
+                }
+
+                let json = req.json::<Self>().await?;
+            
+                Ok(LFObject::Entry(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// List child entries of a folder
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Folder entry ID
+    pub async fn list(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>
+    ) -> Result<EntriesOrError> {
+        Self::list_with_options(api_server, auth, root_id, &ListOptions::default()).await
+    }
+
+    /// List child entries of a folder with OData paging, ordering,
+    /// filtering, and field options
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Folder entry ID
+    /// * `options` - Query options for the listing
+    pub async fn list_with_options(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>,
+        options: &ListOptions
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_list_url(&api_server, root_id.into(), options)?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        Self::handle_entries_response(response).await
+    }
+
+    /// Every child entry of a folder, following `@odata.nextLink` as the
+    /// stream is read
+    ///
+    /// Pages are fetched lazily; an API error on any page ends the stream
+    /// with `ErrorKind::ApiError`.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Folder entry ID
+    pub fn list_all(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>
+    ) -> BoxStream<'static, Result<Entry>> {
+        Self::list_all_with_options(api_server, auth, root_id, ListOptions::default())
+    }
+
+    /// Every child of a folder listed with `options`, following next links
+    /// as the stream is read
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Folder entry ID
+    /// * `options` - Query options for the first page; next links carry them on
+    pub fn list_all_with_options(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>,
+        options: ListOptions
+    ) -> BoxStream<'static, Result<Entry>> {
+        let folder_id = root_id.into();
+        // Pages are fetched after this call returns, so keep the caller's client
+        let http = http_client();
+        paginate(move |next_link| {
+            let (api_server, auth, http, options) = (api_server.clone(), auth.clone(), http.clone(), options.clone());
+            with_http_client(http, async move {
+                match next_link {
+                    None => Self::list_with_options(api_server, auth, folder_id, &options).await,
+                    Some(next_link) => Self::list_custom(api_server, auth, next_link).await,
+                }
+            })
+        })
+    }
+
+    /// Every entry below a folder, breadth first, listing each subfolder
+    /// with `options` as the stream is read
+    ///
+    /// A `$select` projection is widened to include the properties needed to
+    /// find subfolders. Shortcuts are not followed. The stream ends after
+    /// the first error.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `root_id` - Folder entry ID to start from; not itself yielded
+    /// * `options` - Query options for each folder listing
+    pub fn walk(
+        api_server: LFApiServer,
+        auth: Auth,
+        root_id: impl Into<FolderId>,
+        options: ListOptions
+    ) -> BoxStream<'static, Result<Entry>> {
+        let options = options.selecting(&["id", "entryType", "isContainer"]);
+        let http = http_client();
+        walk_tree(root_id.into(), move |folder_id| {
+            SCOPED_HTTP_CLIENT.sync_scope(http.clone(), || {
+                Self::list_all_with_options(api_server.clone(), auth.clone(), folder_id, options.clone())
+            })
+        })
+    }
+
+    /// Ids of a folder's children named `names`, keyed by the name as given
+    ///
+    /// Names match ignoring case, as the repository compares them; names
+    /// with no such child are left out. Children are looked up
+    /// `NAMES_PER_FILTER` names per request with an OData `$filter`, so the
+    /// folder is never listed in full.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `folder_id` - Folder to look in
+    /// * `names` - Child names to look up
+    pub async fn find_children_by_names(
+        api_server: LFApiServer,
+        auth: Auth,
+        folder_id: impl Into<FolderId>,
+        names: &[&str]
+    ) -> Result<BTreeMap<String, EntryId>> {
+        let folder_id = folder_id.into();
+        let mut found = BTreeMap::new();
+        for names in names.chunks(NAMES_PER_FILTER) {
+            let mut children = Self::list_all_with_options(api_server.clone(), auth.clone(), folder_id, names_filter(names));
+            while let Some(child) = children.try_next().await? {
+                record_named_child(names, &child, &mut found);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Children URL shared by the async and blocking `list`
+    pub(crate) fn build_list_url(
+        api_server: &LFApiServer,
+        folder_id: FolderId,
+        options: &ListOptions
+    ) -> std::result::Result<String, UrlError> {
+        let url = RequestUrl::children(api_server, folder_id.as_i64())?;
+
+        options.apply(url).build()
+    }
+
+    pub(crate) async fn handle_entries_response(
+        response: reqwest::Response
+    ) -> Result<EntriesOrError> {
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(EntriesOrError::LFAPIError(error));
+        }
+        
+        let entries = response.json::<Entries>().await?;
+        Ok(EntriesOrError::Entries(entries))
+    }
+
+    /// Fetch the next page of a previous list or search
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `next_link` - Cursor taken from a previous response via `Entries::next_link()`
+    pub async fn list_custom(
+        api_server: LFApiServer,
+        auth: Auth,
+        next_link: NextLink
+    ) -> Result<EntriesOrError> {
+        // Refuse to send the token anywhere but the configured repository
+        let url = Self::build_next_link_url(&api_server, &next_link)?;
+
+        let request = http_client().get(url);
+
+        match ApiHelper::execute_request::<Entries>(request, &auth.access_token, reqwest::StatusCode::OK).await? {
+            Ok(entries) => Ok(EntriesOrError::Entries(entries)),
+            Err(error) => Ok(EntriesOrError::LFAPIError(error)),
+        }
+    }
+
+    /// Next-page URL shared by the async and blocking `list_custom`
+    pub(crate) fn build_next_link_url(
+        api_server: &LFApiServer,
+        next_link: &NextLink
+    ) -> std::result::Result<String, UrlError> {
+        RequestUrl::next_link(api_server, next_link)?.build()
+    }
+
+    /// Copy an entry to a new location
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID to copy
+    /// * `target_folder_id` - Destination folder ID
+    /// * `new_name` - Optional new name for the copy
+    pub async fn copy(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        target_folder_id: impl Into<FolderId>,
+        new_name: Option<String>
+    ) -> Result<EntryOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let target_folder_id = target_folder_id.into().as_i64();
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        let validated_target_id = validation::validate_entry_id(target_folder_id)?;
+        let validated_name = if let Some(name) = &new_name {
+            Some(validation::validate_file_name(name)?)
+        } else {
+            None
+        };
+        
+        let mut params = json!({
+            "targetId": validated_target_id
+        });
+        
+        if let Some(name) = validated_name {
+            params["name"] = json!(name);
+        }
+
+        let request = http_client()
+            .post(RequestUrl::entry(&api_server, validated_id)?.segment("Copy").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&params)
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::CREATED {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(EntryOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Self>().await?;
+                Ok(EntryOrError::Entry(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::laserfiche::tests::{mock_api_server, mock_auth};
+
+    #[test]
+    fn test_write_atomically() {
+        let directory = std::env::temp_dir().join(format!("laserfiche-rs-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("scan.pdf");
+
+        write_atomically(&path, b"first", true).unwrap();
+        write_atomically(&path, b"second", true).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        let error = write_atomically(&path, b"third", false).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        // A failed write leaves neither the destination nor a temporary file
+        let missing = directory.join("missing").join("scan.pdf");
+        assert!(write_atomically(&missing, b"lost", true).is_err());
+        let names: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(names, vec![std::ffi::OsString::from("scan.pdf")]);
+
+        // The fallback for filesystems without hard links refuses to replace too
+        let temp_path = directory.join(".scan.pdf.part");
+        std::fs::write(&temp_path, b"third").unwrap();
+        let error = claim_and_rename(&temp_path, &path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+        let fresh = directory.join("fresh.pdf");
+        claim_and_rename(&temp_path, &fresh).unwrap();
+        assert_eq!(std::fs::read(&fresh).unwrap(), b"third");
+        assert!(!temp_path.exists());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_edoc_reader_streams_body() {
+        let response = http::Response::builder()
+            .header("content-type", "application/pdf")
+            .body("%PDF-1.7 body")
+            .unwrap();
+        let mut reader = EdocReader::from_response(reqwest::Response::from(response));
+        assert_eq!(reader.content_type(), Some("application/pdf"));
+
+        let mut content = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut content).await.unwrap();
+        assert_eq!(content, "%PDF-1.7 body");
+    }
+
+    #[tokio::test]
+    async fn test_write_edoc_copies_to_writer() {
+        let body: Vec<u8> = (0..=255u8).cycle().take(200_000).collect();
+        let response = http::Response::builder().body(body.clone()).unwrap();
+        let reader = EdocReader::from_response(reqwest::Response::from(response));
+
+        let mut out = Vec::new();
+        let written = Entry::write_edoc(reader, &mut out).await.unwrap();
+        assert_eq!(written, body.len() as u64);
+        assert_eq!(out, body);
+    }
+
+    fn page(names: &[&str], next_link: Option<&str>) -> EntriesOrError {
+        EntriesOrError::Entries(Entries {
+            value: names.iter().map(|name| Entry { name: name.to_string(), ..Default::default() }).collect(),
+            odata_next_link: next_link.map(str::to_string),
+            odata_count: None,
+        })
+    }
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(parse_content_range("bytes 100-199/1000"), (Some(100), Some(1000)));
+        assert_eq!(parse_content_range("bytes */1000"), (None, Some(1000)));
+        assert_eq!(parse_content_range("bytes 0-9/*"), (Some(0), None));
+        assert_eq!(parse_content_range("items 0-9/10"), (None, None));
+        assert_eq!(resume_etag_path(std::path::Path::new("/tmp/scan.tiff")), std::path::Path::new("/tmp/scan.tiff.etag"));
+    }
+
+    #[tokio::test]
+    async fn test_paginate_follows_next_links() {
+        let requested = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&requested);
+        let entries = paginate(move |next_link: Option<NextLink>| {
+            let link = next_link.map(|link| link.as_str().to_string());
+            log.lock().unwrap().push(link.clone());
+            async move {
+                Ok(match link.as_deref() {
+                    None => page(&["a", "b"], Some("https://example.com/page2")),
+                    Some("https://example.com/page2") => page(&[], Some("https://example.com/page3")),
+                    _ => page(&["c"], None),
+                })
+            }
+        });
+
+        let names: Vec<String> = entries.map_ok(|entry| entry.name).try_collect().await.unwrap();
+        assert_eq!(names, vec!["a", "b", "c"]);
+        assert_eq!(requested.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_paginate_stops_at_api_error() {
+        let mut entries = paginate(|next_link: Option<NextLink>| async move {
+            Ok(match next_link {
+                None => page(&["a"], Some("https://example.com/page2")),
+                Some(_) => EntriesOrError::LFAPIError(LFAPIError { status: Some(500), ..Default::default() }),
+            })
+        });
+
+        assert_eq!(entries.next().await.unwrap().unwrap().name, "a");
+        let error = entries.next().await.unwrap().unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::ApiError(_)));
+        assert!(error.to_string().contains("HTTP 500"));
+    }
+
+    #[tokio::test]
+    async fn test_walk_tree_is_breadth_first() {
+        fn child(id: i64, entry_type: &str) -> Result<Entry> {
+            Ok(Entry {
+                id,
+                entry_type: entry_type.into(),
+                is_container: entry_type != "Document",
+                ..Default::default()
+            })
+        }
+        let entries = walk_tree(FolderId(1), |folder_id| {
+            let children = match folder_id.as_i64() {
+                1 => vec![child(2, "Folder"), child(3, "Document"), child(4, "Shortcut"), child(6, "Folder")],
+                2 => vec![child(5, "Document")],
+                6 => vec![Err("listing failed".into())],
+                other => panic!("folder {} should not be listed", other),
+            };
+            box_stream(futures_util::stream::iter(children))
+        });
+
+        let results: Vec<Result<Entry>> = entries.collect().await;
+        let ids: Vec<i64> = results.iter().filter_map(|entry| entry.as_ref().ok()).map(|entry| entry.id).collect();
+        assert_eq!(ids, vec![2, 3, 4, 6, 5]);
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn test_list_options_selecting() {
+        let options = ListOptions::new().select("name, creator").selecting(&["id", "name"]);
+        assert_eq!(options.select.as_deref(), Some("name, creator,id"));
+        assert_eq!(ListOptions::new().selecting(&["id"]), ListOptions::new());
+    }
+
+    #[test]
+    fn test_import_request_json() {
+        assert_eq!(ImportRequest::new().to_json(), json!({}));
+
+        let request = ImportRequest::new()
+            .template("Invoices")
+            .field("Vendor", "Acme")
+            .field("Line Items", "1")
+            .field("Line Items", "2")
+            .tag("Needs Review")
+            .volume("DEFAULT");
+        assert_eq!(request.to_json(), json!({
+            "template": "Invoices",
+            "metadata": {
+                "fields": {
+                    "Line Items": { "values": [{ "value": "1", "position": 1 }, { "value": "2", "position": 2 }] },
+                    "Vendor": { "values": [{ "value": "Acme", "position": 1 }] }
+                },
+                "tags": ["Needs Review"]
+            },
+            "volumeName": "DEFAULT"
+        }));
+
+        let replaced = request.field_values("Line Items", vec!["3".to_string()]);
+        assert_eq!(replaced.to_json()["metadata"]["fields"]["Line Items"]["values"], json!([{ "value": "3", "position": 1 }]));
+    }
+
+    #[tokio::test]
+    async fn test_import_reader_validates_before_upload() {
+        let oversized = Entry::import_reader(
+            mock_api_server(), mock_auth(), tokio::io::empty(), u64::MAX,
+            "scan.pdf".to_string(), 1, None
+        ).await;
+        assert!(oversized.is_err());
+
+        let bad_mime = Entry::import_reader(
+            mock_api_server(), mock_auth(), tokio::io::empty(), 0,
+            "scan.pdf".to_string(), 1, Some("not a mime type")
+        ).await;
+        assert!(bad_mime.is_err());
+    }
+
+    #[test]
+    fn test_detect_mime_type() {
+        assert_eq!(mime::detect_mime_type("test.pdf"), "application/pdf");
+        assert_eq!(mime::detect_mime_type("test.jpg"), "image/jpeg");
+        assert_eq!(mime::detect_mime_type("test.jpeg"), "image/jpeg");
+        assert_eq!(mime::detect_mime_type("test.png"), "image/png");
+        assert_eq!(mime::detect_mime_type("test.gif"), "image/gif");
+        assert_eq!(mime::detect_mime_type("test.tif"), "image/tiff");
+        assert_eq!(mime::detect_mime_type("test.tiff"), "image/tiff");
+        assert_eq!(mime::detect_mime_type("test.txt"), "text/plain");
+        assert_eq!(mime::detect_mime_type("test.csv"), "text/csv");
+        assert_eq!(mime::detect_mime_type("test.xml"), "application/xml");
+        assert_eq!(mime::detect_mime_type("test.json"), "application/json");
+        assert_eq!(mime::detect_mime_type("test.doc"), "application/msword");
+        assert_eq!(mime::detect_mime_type("test.docx"), "application/vnd.openxmlformats-officedocument.wordprocessingml.document");
+        assert_eq!(mime::detect_mime_type("test.xls"), "application/vnd.ms-excel");
+        assert_eq!(mime::detect_mime_type("test.xlsx"), "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet");
+        assert_eq!(mime::detect_mime_type("test.ppt"), "application/vnd.ms-powerpoint");
+        assert_eq!(mime::detect_mime_type("test.pptx"), "application/vnd.openxmlformats-officedocument.presentationml.presentation");
+        assert_eq!(mime::detect_mime_type("test.unknown"), "application/octet-stream");
+        assert_eq!(mime::detect_mime_type("test"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_detect_mime_type_case_insensitive() {
+        assert_eq!(mime::detect_mime_type("TEST.PDF"), "application/pdf");
+        assert_eq!(mime::detect_mime_type("Test.Pdf"), "application/pdf");
+        assert_eq!(mime::detect_mime_type("test.PDF"), "application/pdf");
+    }
+
+    #[test]
+    fn test_list_url() {
+        let api_server = mock_api_server();
+        let url = Entry::build_list_url(&api_server, FolderId(1), &ListOptions::new()).unwrap();
+        assert_eq!(url, "https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo/Entries/1/Laserfiche.Repository.Folder/children");
+        assert!(Entry::build_list_url(&api_server, FolderId(0), &ListOptions::new()).is_err());
+    }
+
+    #[test]
+    fn test_list_options() {
+        let options = ListOptions::new()
+            .group_by_entry_type(true)
+            .field("Vendor")
+            .field("Invoice Date")
+            .format_field_values(false)
+            .order_by("name asc")
+            .skip(100)
+            .top(50)
+            .count(true);
+        let url = Entry::build_list_url(&mock_api_server(), FolderId(1), &options).unwrap();
+        assert_eq!(
+            url,
+            "https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo/Entries/1/Laserfiche.Repository.Folder/children\
+             ?groupByEntryType=true&fields=Vendor&fields=Invoice%20Date&formatFieldValues=false&$orderby=name%20asc&$skip=100&$top=50&$count=true"
+        );
+    }
+
+    #[test]
+    fn test_names_filter() {
+        let options = names_filter(&["Invoices", "O'Brien"]);
+        assert_eq!(options.filter.as_deref(), Some("name eq 'Invoices' or name eq 'O''Brien'"));
+        assert_eq!(options.select.as_deref(), Some("id,name"));
+
+        let mut found = BTreeMap::new();
+        let child = Entry { id: 9, name: "INVOICES".to_string(), ..Default::default() };
+        record_named_child(&["invoices", "Receipts"], &child, &mut found);
+        assert_eq!(found, BTreeMap::from([("invoices".to_string(), EntryId(9))]));
+    }
+
+    #[test]
+    fn test_patched_entry_struct() {
+        // Test PatchedEntry instead of non-existent DeleteParameters
+        let patched = PatchedEntry {
+            parent_id: Some(10),
+            name: Some("renamed-document.pdf".to_string()),
+        };
+
+        assert_eq!(patched.parent_id, Some(10));
+        assert_eq!(patched.name, Some("renamed-document.pdf".to_string()));
+    }
+
+    #[test]
+    fn test_patched_entry_serialization() {
+        let patched = PatchedEntry {
+            parent_id: Some(10),
+            name: Some("renamed-document.pdf".to_string()),
+        };
+
+        let json = serde_json::to_string(&patched).unwrap();
+        assert!(json.contains("\"parentId\":10"));
+        assert!(json.contains("\"name\":\"renamed-document.pdf\""));
+    }
+
+    #[test]
+    fn test_delete_options_serialization() {
+        let json = serde_json::to_value(DestroyEntry::from(&DeleteOptions::new().comment("cleanup"))).unwrap();
+        assert_eq!(json, serde_json::json!({"auditReasonId": 0, "comment": "cleanup"}));
+
+        let json = serde_json::to_value(DestroyEntry::from(&DeleteOptions::new().audit_reason(7))).unwrap();
+        assert_eq!(json["auditReasonId"], 7);
+    }
+
+    #[test]
+    fn test_audit_reasons_parsing() {
+        let reasons: AuditReasons = serde_json::from_str(r#"{
+            "deleteEntry": [{"id": 7, "name": "Retention expired"}],
+            "exportDocument": [{"id": 9, "name": "Legal hold"}]
+        }"#).unwrap();
+        assert_eq!(reasons.delete_reason("retention EXPIRED").map(|reason| reason.id), Some(7));
+        assert!(reasons.delete_reason("Legal hold").is_none());
+        assert_eq!(reasons.export_document[0].id, 9);
+    }
+}
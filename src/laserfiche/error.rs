@@ -0,0 +1,195 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Errors of the async API, and the error body the server sends
+
+use serde::{Deserialize, Serialize};
+use crate::validation;
+use error_chain::error_chain;
+
+error_chain! {
+    foreign_links {
+        HttpRequest(reqwest::Error);
+        IOError(std::io::Error);
+        ValidationError(validation::Error);
+    }
+
+    errors {
+        ContentRejected(file_name: String, reason: String) {
+            description("Content rejected by inspection")
+            display("Import of '{}' rejected by content inspection: {}", file_name, reason)
+        }
+        TokenRefreshFailed(detail: String) {
+            description("Token refresh failed")
+            display("Token refresh failed: {}", detail)
+        }
+        ApiError(error: Box<LFAPIError>) {
+            description("API error")
+            display("API error: {}", error)
+        }
+        EntryLocked(entry_id: i64) {
+            description("Entry locked")
+            display("Entry {} is locked", entry_id)
+        }
+        UnsupportedByServer(feature: String, required: String, supported: String) {
+            description("Feature not supported by the server")
+            display("{} needs Repository API {}, but the server supports up to {}", feature, required, supported)
+        }
+        NotRoutedForVersion(route: String, version: String) {
+            description("Route not available in the requested API version")
+            display("'{}' is not yet routed for Repository API {}; send it from a v1 client", route, version)
+        }
+        FileExists(path: String) {
+            description("File already exists")
+            display("'{}' already exists", path)
+        }
+        IntegrityError(entry_id: i64, expected: String, actual: String) {
+            description("Content checksum mismatch")
+            display("Content of entry {} has SHA-256 {}, expected {}", entry_id, actual, expected)
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LFAPIError {
+    #[serde(rename = "type")]
+    pub type_field: Option<String>,
+    pub title: Option<String>,
+    pub status: Option<i64>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    pub operation_id: Option<String>,
+    pub error_source: Option<String>,
+    pub error_code: Option<i64>,
+    pub trace_id: Option<String>,
+    pub additional_prop1: Option<String>,
+    pub additional_prop2: Option<String>,
+    pub additional_prop3: Option<String>,
+}
+
+impl LFAPIError {
+    /// HTTP status of the response, if the server reported a valid one
+    pub fn status_code(&self) -> Option<u16> {
+        self.status.and_then(|status| u16::try_from(status).ok())
+    }
+
+    /// 404: the entry, template, or other resource does not exist
+    pub fn is_not_found(&self) -> bool {
+        self.status == Some(404)
+    }
+
+    /// 401: the token is missing, invalid, or expired
+    pub fn is_unauthorized(&self) -> bool {
+        self.status == Some(401)
+    }
+
+    /// 403: the user may not perform this operation
+    pub fn is_forbidden(&self) -> bool {
+        self.status == Some(403)
+    }
+
+    /// 409: the request conflicts with the entry's state, e.g. a name
+    /// already taken in the target folder
+    pub fn is_conflict(&self) -> bool {
+        self.status == Some(409)
+    }
+
+    /// 429: the server asked for fewer requests
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == Some(429)
+    }
+
+    /// 5xx: the server failed, so the request may succeed if retried
+    pub fn is_server_error(&self) -> bool {
+        self.status_code().is_some_and(|status| (500..600).contains(&status))
+    }
+
+    /// Whether the request failed because the entry is locked or busy,
+    /// e.g. while the server is still processing an import
+    pub fn is_entry_locked(&self) -> bool {
+        if self.status == Some(423) {
+            return true;
+        }
+        [&self.title, &self.detail].into_iter().flatten().any(|text| {
+            let text = text.to_lowercase();
+            text.contains("locked") || text.contains("in use") || text.contains("busy")
+        })
+    }
+}
+
+/// The title, or else the detail or status, of the error
+impl std::fmt::Display for LFAPIError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.title, &self.detail, self.status) {
+            (Some(title), _, _) => f.write_str(title),
+            (None, Some(detail), _) => f.write_str(detail),
+            (None, None, Some(status)) => write!(f, "HTTP {}", status),
+            (None, None, None) => f.write_str("unknown error"),
+        }
+    }
+}
+
+impl std::error::Error for LFAPIError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_classification() {
+        let conflict = LFAPIError { status: Some(409), ..Default::default() };
+        let not_found = LFAPIError { status: Some(404), ..Default::default() };
+
+        assert!(conflict.is_conflict());
+        assert!(!not_found.is_conflict());
+        assert!(!LFAPIError::default().is_conflict());
+        assert!(not_found.is_not_found());
+        assert_eq!(not_found.status_code(), Some(404));
+        assert!(LFAPIError { status: Some(401), ..Default::default() }.is_unauthorized());
+        assert!(LFAPIError { status: Some(503), ..Default::default() }.is_server_error());
+        assert!(!not_found.is_server_error());
+        assert_eq!(LFAPIError { status: Some(-1), ..Default::default() }.status_code(), None);
+    }
+
+    #[test]
+    fn test_api_error_display() {
+        let error = LFAPIError {
+            title: Some("Entry not found.".to_string()),
+            detail: Some("Entry 42 does not exist.".to_string()),
+            status: Some(404),
+            ..Default::default()
+        };
+        assert_eq!(error.to_string(), "Entry not found.");
+        assert_eq!(LFAPIError { title: None, ..error.clone() }.to_string(), "Entry 42 does not exist.");
+        assert_eq!(LFAPIError { status: Some(500), ..Default::default() }.to_string(), "HTTP 500");
+        assert_eq!(LFAPIError::default().to_string(), "unknown error");
+
+        // Usable wherever a boxed error is expected
+        let boxed: Box<dyn std::error::Error> = Box::new(error);
+        assert_eq!(boxed.to_string(), "Entry not found.");
+    }
+
+    #[test]
+    fn test_lfapi_error() {
+        let error = LFAPIError {
+            type_field: Some("NotFoundError".to_string()),
+            title: Some("Not Found".to_string()),
+            status: Some(404),
+            detail: Some("The requested entry does not exist".to_string()),
+            instance: Some("/api/entries/999".to_string()),
+            operation_id: Some("op-123".to_string()),
+            error_source: Some("Repository".to_string()),
+            error_code: Some(1001),
+            trace_id: Some("trace-123".to_string()),
+            additional_prop1: None,
+            additional_prop2: None,
+            additional_prop3: None,
+        };
+
+        assert_eq!(error.status, Some(404));
+        assert_eq!(error.title, Some("Not Found".to_string()));
+        assert_eq!(error.error_code, Some(1001));
+    }
+}
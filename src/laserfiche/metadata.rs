@@ -0,0 +1,625 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Field values, templates, tags, and links of an entry
+
+use super::{http_client, RequestUrl};
+use super::auth::Auth;
+use super::error::{LFAPIError, Result};
+use super::types::{
+    Entry, EntryId, EntryOrError, Field, Fields, FullEntry, FullEntryOrError, LFApiServer, LFObject, Links, LinksOrError,
+    MetadataResult, MetadataResultOrError, MetadataValue, NewLink, Tags, TagsOrError, Template, TemplateOrError,
+};
+use crate::retry::RetryingSend;
+use crate::validation;
+
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Field values for `Entry::update_metadata`, set through typed setters so
+/// the payload always has the `{"Field": {"values": [{"value", "position"}]}}`
+/// shape the API expects
+///
+/// Setting a field again replaces its earlier values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldValuesBuilder {
+    fields: BTreeMap<String, Vec<String>>,
+}
+
+impl FieldValuesBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a text field
+    pub fn text(self, field_name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.multi_value(field_name, [value.into()])
+    }
+
+    /// Set a number field
+    pub fn number(self, field_name: impl Into<String>, value: f64) -> Self {
+        self.multi_value(field_name, [value.to_string()])
+    }
+
+    /// Set a date field to the UTC day `at` falls on, as `YYYY-MM-DD`
+    pub fn date(self, field_name: impl Into<String>, at: SystemTime) -> Self {
+        let secs = at.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0);
+        let (year, month, day) = crate::naming::civil_from_days((secs / 86_400) as i64);
+        self.multi_value(field_name, [format!("{:04}-{:02}-{:02}", year, month, day)])
+    }
+
+    /// Set every value of a multi-value field, in order
+    pub fn multi_value<I, S>(mut self, field_name: impl Into<String>, values: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.fields.insert(field_name.into(), values.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// The payload for `Entry::update_metadata`
+    pub fn build(&self) -> serde_json::Value {
+        fields_json(&self.fields)
+    }
+}
+
+impl From<FieldValuesBuilder> for serde_json::Value {
+    fn from(builder: FieldValuesBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// Field values keyed by name, each numbered from position 1
+pub(super) fn fields_json(fields: &BTreeMap<String, Vec<String>>) -> serde_json::Value {
+    let fields: serde_json::Map<String, serde_json::Value> = fields.iter()
+        .map(|(name, values)| {
+            let values: Vec<MetadataValue> = values.iter()
+                .enumerate()
+                .map(|(index, value)| MetadataValue { value: value.clone(), position: index as i64 + 1 })
+                .collect();
+            (name.clone(), json!({ "values": values }))
+        })
+        .collect();
+    serde_json::Value::Object(fields)
+}
+
+/// `current`'s links from `source_id`, plus `link` unless an identical
+/// link is already among them
+fn outgoing_with(current: &Links, source_id: EntryId, link: NewLink) -> Vec<NewLink> {
+    let mut links: Vec<NewLink> = current.value
+        .iter()
+        .filter(|existing| existing.source_id == source_id.as_i64())
+        .map(NewLink::from)
+        .collect();
+    if !links.iter().any(|existing| existing.target_id == link.target_id && existing.link_type_id == link.link_type_id) {
+        links.push(link);
+    }
+    links
+}
+
+impl Entry {
+    /// Update metadata/field values for an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID to update
+    /// * `metadata` - JSON object containing field values
+    pub async fn update_metadata(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        metadata: serde_json::Value
+    ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into().as_i64();
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        let validated_metadata = validation::validate_metadata_json(&metadata)?;
+        
+        let url = RequestUrl::entry(&api_server, validated_id)?
+            .segment("fields")
+            .build()?;
+        
+        let response = http_client()
+            .put(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&validated_metadata)
+            .send_retrying()
+            .await?;
+
+        Self::handle_metadata_response(response).await
+    }
+
+    /// Get metadata/field values for an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn get_metadata(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<MetadataResultOrError> {
+        let entry_id = entry_id.into().as_i64();
+        // Validate entry ID
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        
+        let url = RequestUrl::entry(&api_server, validated_id)?
+            .segment("fields")
+            .build()?;
+        
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        Self::handle_metadata_response(response).await
+    }
+
+    async fn handle_metadata_response(
+        response: reqwest::Response
+    ) -> Result<MetadataResultOrError> {
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(MetadataResultOrError::LFAPIError(error));
+        }
+        
+        let metadata = response.json::<MetadataResult>().await?;
+        Ok(MetadataResultOrError::Metadata(metadata))
+    }
+
+    pub async fn get_field(api_server: LFApiServer, auth: Auth, root_id: impl Into<EntryId>, field_id: i64) -> Result<LFObject> {
+        let root_id = root_id.into().as_i64();
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(root_id)?;
+        let validated_field_id = validation::validate_entry_id(field_id)?;
+
+        let request = http_client()
+        .get(RequestUrl::entry(&api_server, validated_id)?.segment("fields").segment(&validated_field_id.to_string()).build()?)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying().await;
+
+        match request{
+            Ok(req) => {
+
+                if req.status() != reqwest::StatusCode::OK{
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(LFObject::LFAPIError(json));
+                }
+
+                let json = req.json::<Field>().await?;
+            
+                Ok(LFObject::Field(json))
+            },
+            Err(err) => Err(err.into())
+        }
+
+    }
+
+    pub async fn get_fields(api_server: LFApiServer, auth: Auth, root_id: impl Into<EntryId>) -> Result<LFObject> {
+        let root_id = root_id.into().as_i64();
+        // Validate entry ID
+        let validated_id = validation::validate_entry_id(root_id)?;
+
+        let request = http_client()
+        .get(RequestUrl::entry(&api_server, validated_id)?.segment("fields").build()?)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying().await;
+
+        match request{
+            Ok(req) => {
+
+                if req.status() != reqwest::StatusCode::OK{
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(LFObject::LFAPIError(json));
+                }
+
+                let json = req.json::<Fields>().await?;
+            
+                Ok(LFObject::Fields(json))
+            },
+            Err(err) => Err(err.into())
+        }
+
+    }
+
+    /// Get the template associated with an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn get_template(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<TemplateOrError> {
+        let entry_id = entry_id.into().as_i64();
+        // Validate entry ID
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        
+        let request = http_client()
+            .get(RequestUrl::entry(&api_server, validated_id)?.segment("template").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(TemplateOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Template>().await?;
+                Ok(TemplateOrError::Template(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Assign a template to an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    /// * `template_name` - Name of the template to assign
+    pub async fn set_template(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        template_name: String
+    ) -> Result<EntryOrError> {
+        let entry_id = entry_id.into().as_i64();
+        // Validate inputs
+        let validated_id = validation::validate_entry_id(entry_id)?;
+        let validated_template_name = validation::validate_field_name(&template_name)?;
+        
+        let params = json!({
+            "templateName": validated_template_name
+        });
+
+        let request = http_client()
+            .put(RequestUrl::entry(&api_server, validated_id)?.segment("template").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&params)
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(EntryOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Self>().await?;
+                Ok(EntryOrError::Entry(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Remove template from an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn remove_template(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<EntryOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let request = http_client()
+            .delete(RequestUrl::entry(&api_server, entry_id)?.segment("template").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(EntryOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Self>().await?;
+                Ok(EntryOrError::Entry(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Get tags assigned to an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn get_tags(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<TagsOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let request = http_client()
+            .get(RequestUrl::entry(&api_server, entry_id)?.segment("tags").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(TagsOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Tags>().await?;
+                Ok(TagsOrError::Tags(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Assign tags to an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    /// * `tag_ids` - List of tag IDs to assign
+    pub async fn set_tags(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        tag_ids: Vec<i64>
+    ) -> Result<TagsOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let params = json!({
+            "tags": tag_ids
+        });
+
+        let request = http_client()
+            .put(RequestUrl::entry(&api_server, entry_id)?.segment("tags").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&params)
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(TagsOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Tags>().await?;
+                Ok(TagsOrError::Tags(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Get links associated with an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn get_links(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<LinksOrError> {
+        let entry_id = entry_id.into().as_i64();
+        let request = http_client()
+            .get(RequestUrl::entry(&api_server, entry_id)?.segment("links").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(LinksOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Links>().await?;
+                Ok(LinksOrError::Links(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Get an entry with its field values, tags, and links, fetched
+    /// concurrently, e.g. for a detail view
+    ///
+    /// If more than one request is refused, the first error in that order
+    /// is returned.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry ID
+    pub async fn get_full(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<FullEntryOrError> {
+        let entry_id = entry_id.into();
+        let (entry, metadata, tags, links) = tokio::join!(
+            Self::get(api_server.clone(), auth.clone(), entry_id),
+            Self::get_metadata(api_server.clone(), auth.clone(), entry_id),
+            Self::get_tags(api_server.clone(), auth.clone(), entry_id),
+            Self::get_links(api_server, auth, entry_id),
+        );
+
+        let entry = match entry? {
+            EntryOrError::Entry(entry) => entry,
+            EntryOrError::LFAPIError(error) => return Ok(FullEntryOrError::LFAPIError(error)),
+        };
+        let metadata = match metadata? {
+            MetadataResultOrError::Metadata(metadata) => metadata,
+            MetadataResultOrError::LFAPIError(error) => return Ok(FullEntryOrError::LFAPIError(error)),
+        };
+        let tags = match tags? {
+            TagsOrError::Tags(tags) => tags,
+            TagsOrError::LFAPIError(error) => return Ok(FullEntryOrError::LFAPIError(error)),
+        };
+        let links = match links? {
+            LinksOrError::Links(links) => links,
+            LinksOrError::LFAPIError(error) => return Ok(FullEntryOrError::LFAPIError(error)),
+        };
+
+        Ok(FullEntryOrError::Full(FullEntry { entry, metadata, tags, links }))
+    }
+
+    /// Replace the links of an entry
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Source entry ID
+    /// * `links` - Links to assign, from this entry to each target
+    pub async fn set_links(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        links: Vec<NewLink>
+    ) -> Result<LinksOrError> {
+        let entry_id = validation::validate_entry_id(entry_id.into().as_i64())?;
+        for link in &links {
+            validation::validate_entry_id(link.target_id)?;
+        }
+
+        let request = http_client()
+            .put(RequestUrl::entry(&api_server, entry_id)?.segment("links").build()?)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .json(&links)
+            .send_retrying().await;
+
+        match request {
+            Ok(req) => {
+                if req.status() != reqwest::StatusCode::OK {
+                    let json = req.json::<LFAPIError>().await?;
+                    return Ok(LinksOrError::LFAPIError(json));
+                }
+
+                let json = req.json::<Links>().await?;
+                Ok(LinksOrError::Links(json))
+            },
+            Err(err) => Err(err.into())
+        }
+    }
+
+    /// Link an entry to another, keeping its existing links
+    ///
+    /// The links endpoint replaces an entry's outgoing links as a whole, so
+    /// they are read and written back with the new link added.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `source_id` - Entry the link starts from
+    /// * `target_id` - Entry the link points to
+    /// * `link_type_id` - Link definition id, from `LinkDefinitions::list`
+    /// * `description` - Optional description of the link
+    pub async fn add_link(
+        api_server: LFApiServer,
+        auth: Auth,
+        source_id: impl Into<EntryId>,
+        target_id: impl Into<EntryId>,
+        link_type_id: i64,
+        description: Option<String>
+    ) -> Result<LinksOrError> {
+        let source_id = source_id.into();
+        let mut link = NewLink::new(target_id, link_type_id);
+        link.description = description;
+
+        let current = match Self::get_links(api_server.clone(), auth.clone(), source_id).await? {
+            LinksOrError::Links(links) => links,
+            error => return Ok(error),
+        };
+        Self::set_links(api_server, auth, source_id, outgoing_with(&current, source_id, link)).await
+    }
+
+    /// Remove every link from an entry to other entries
+    ///
+    /// Links from other entries to this one belong to those entries and
+    /// are kept.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Source entry ID
+    pub async fn delete_links(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>
+    ) -> Result<LinksOrError> {
+        Self::set_links(api_server, auth, entry_id, Vec::new()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::types::Link;
+    use std::time::Duration;
+
+    #[test]
+    fn test_field_values_builder() {
+        let fields = FieldValuesBuilder::new()
+            .text("Vendor", "Acme")
+            .number("Amount", 1250.5)
+            .number("Quantity", 3.0)
+            .date("Invoice Date", UNIX_EPOCH + Duration::from_secs(1_706_659_200 + 3_600))
+            .multi_value("Line Items", ["A-1", "B-2"]);
+        assert_eq!(serde_json::Value::from(fields.clone()), json!({
+            "Amount": { "values": [{ "value": "1250.5", "position": 1 }] },
+            "Invoice Date": { "values": [{ "value": "2024-01-31", "position": 1 }] },
+            "Line Items": { "values": [{ "value": "A-1", "position": 1 }, { "value": "B-2", "position": 2 }] },
+            "Quantity": { "values": [{ "value": "3", "position": 1 }] },
+            "Vendor": { "values": [{ "value": "Acme", "position": 1 }] }
+        }));
+
+        let replaced = fields.text("Vendor", "Globex").build();
+        assert_eq!(replaced["Vendor"]["values"], json!([{ "value": "Globex", "position": 1 }]));
+        assert!(validation::validate_metadata_json(&replaced).is_ok());
+    }
+
+    #[test]
+    fn test_outgoing_with_new_link() {
+        let current = Links {
+            value: vec![
+                Link { id: 1, source_id: 10, target_id: 20, link_type: "Invoice".to_string(), link_type_id: 4, description: None },
+                Link { id: 2, source_id: 30, target_id: 10, link_type: "Invoice".to_string(), link_type_id: 4, description: None },
+            ],
+        };
+
+        let links = outgoing_with(&current, EntryId(10), NewLink::new(EntryId(21), 4).description("PO 7781"));
+        assert_eq!(links, vec![NewLink::new(EntryId(20), 4), NewLink::new(EntryId(21), 4).description("PO 7781")]);
+
+        // An existing link is not added twice
+        assert_eq!(outgoing_with(&current, EntryId(10), NewLink::new(EntryId(20), 4)).len(), 1);
+
+        let json = serde_json::to_value(&links).unwrap();
+        assert_eq!(json[0], serde_json::json!({"targetId": 20, "linkTypeId": 4}));
+        assert_eq!(json[1]["description"], "PO 7781");
+    }
+
+    #[test]
+    fn test_metadata_value() {
+        let metadata = MetadataValue {
+            value: "test-value".to_string(),
+            position: 1,
+        };
+
+        assert_eq!(metadata.value, "test-value");
+        assert_eq!(metadata.position, 1);
+    }
+}
@@ -0,0 +1,14 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! The `laserfiche` items most callers use, for `use laserfiche::prelude::*;`
+
+pub use super::auth::{Auth, SharedAuth};
+pub use super::entries::{DeleteOptions, ExportOptions, ImportRequest, ListOptions};
+pub use super::error::LFAPIError;
+pub use super::metadata::FieldValuesBuilder;
+pub use super::search::SearchOptions;
+pub use super::types::{
+    DocumentId, Entries, Entry, EntryId, EntryType, FieldType, FolderId, ImportResult, LFApiServer, MetadataResult, NewLink, NextLink,
+};
@@ -0,0 +1,200 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Repository searches
+
+use super::{http_client, with_http_client, ApiHelper, BoxStream, RequestUrl, UrlError};
+use super::auth::Auth;
+use super::entries::paginate;
+use super::error::Result;
+use super::types::{Entries, EntriesOrError, Entry, LFApiServer, NextLink};
+use crate::retry::RetryingSend;
+
+
+/// OData options for `Entry::search`, built with chained setters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    order_by: Option<String>,
+    select: Option<String>,
+    filter: Option<String>,
+    skip: Option<u32>,
+    top: Option<u32>,
+    page_size: Option<u32>,
+}
+
+impl SearchOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// OData `$orderby`, e.g. `"name asc"`
+    pub fn order_by(mut self, order_by: impl Into<String>) -> Self {
+        self.order_by = Some(order_by.into());
+        self
+    }
+
+    /// OData `$select`, e.g. `"id,name,entryType"`
+    pub fn select(mut self, select: impl Into<String>) -> Self {
+        self.select = Some(select.into());
+        self
+    }
+
+    /// OData `$filter`, e.g. `"entryType eq 'Document'"`
+    pub fn filter(mut self, filter: impl Into<String>) -> Self {
+        self.filter = Some(filter.into());
+        self
+    }
+
+    /// OData `$skip`: number of results to skip
+    pub fn skip(mut self, skip: u32) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    /// OData `$top`: maximum number of results to return
+    pub fn top(mut self, top: u32) -> Self {
+        self.top = Some(top);
+        self
+    }
+
+    /// Results per page, requested with `Prefer: maxpagesize=N`; the server
+    /// may return fewer
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    fn apply(&self, url: RequestUrl) -> RequestUrl {
+        url.query_opt("$orderby", self.order_by.as_deref())
+            .query_opt("$select", self.select.as_deref())
+            .query_opt("$filter", self.filter.as_deref())
+            .query_opt("$skip", self.skip)
+            .query_opt("$top", self.top)
+    }
+
+    /// Value of the `Prefer` header, if a page size was set
+    pub(crate) fn prefer(&self) -> Option<String> {
+        self.page_size.map(|page_size| format!("maxpagesize={}", page_size))
+    }
+}
+
+impl Entry {
+    /// Search for entries using OData query parameters
+    /// 
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `search_query` - Search query string
+    /// * `options` - OData ordering, projection, filtering and paging
+    pub async fn search(
+        api_server: LFApiServer, 
+        auth: Auth, 
+        search_query: String,
+        options: SearchOptions
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_search_url(&api_server, &search_query, &options)?;
+
+        let mut request = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token));
+        if let Some(prefer) = options.prefer() {
+            request = request.header("Prefer", prefer);
+        }
+
+        Self::handle_entries_response(request.send_retrying().await?).await
+    }
+
+    /// Every result of a search, following `@odata.nextLink` as the stream
+    /// is read
+    ///
+    /// Pages are fetched lazily, each asking for `options`' page size, so
+    /// result sets of any size can be exported without a paging loop. An
+    /// API error on any page ends the stream with `ErrorKind::ApiError`.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `search_query` - Search query string
+    /// * `options` - OData options and page size; next links carry the OData options on
+    pub fn search_stream(
+        api_server: LFApiServer,
+        auth: Auth,
+        search_query: String,
+        options: SearchOptions
+    ) -> BoxStream<'static, Result<Entry>> {
+        // Pages are fetched after this call returns, so keep the caller's client
+        let http = http_client();
+        paginate(move |next_link| {
+            let (api_server, auth, http, search_query, options) =
+                (api_server.clone(), auth.clone(), http.clone(), search_query.clone(), options.clone());
+            with_http_client(http, async move {
+                match next_link {
+                    None => Self::search(api_server, auth, search_query, options).await,
+                    Some(next_link) => Self::search_next(api_server, auth, next_link, &options).await,
+                }
+            })
+        })
+    }
+
+    /// `list_custom`, asking for the same page size as the first page
+    pub(crate) async fn search_next(
+        api_server: LFApiServer,
+        auth: Auth,
+        next_link: NextLink,
+        options: &SearchOptions
+    ) -> Result<EntriesOrError> {
+        let url = Self::build_next_link_url(&api_server, &next_link)?;
+
+        let mut request = http_client().get(url);
+        if let Some(prefer) = options.prefer() {
+            request = request.header("Prefer", prefer);
+        }
+
+        match ApiHelper::execute_request::<Entries>(request, &auth.access_token, reqwest::StatusCode::OK).await? {
+            Ok(entries) => Ok(EntriesOrError::Entries(entries)),
+            Err(error) => Ok(EntriesOrError::LFAPIError(error)),
+        }
+    }
+
+    pub(crate) fn build_search_url(
+        api_server: &LFApiServer,
+        search_query: &str,
+        options: &SearchOptions
+    ) -> std::result::Result<String, UrlError> {
+        let url = RequestUrl::repository(api_server)?
+            .segment("Entries")
+            .segment("Search")
+            .query("q", search_query);
+
+        options.apply(url).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::tests::mock_api_server;
+
+    #[test]
+    fn test_search_options_builder() {
+        let api_server = mock_api_server();
+
+        let url = Entry::build_search_url(&api_server, "invoice", &SearchOptions::new()).unwrap();
+        assert_eq!(url, "https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo/Entries/Search?q=invoice");
+
+        let options = SearchOptions::new()
+            .top(50)
+            .filter("entryType eq 'Document'")
+            .select("id,name");
+        let url = Entry::build_search_url(&api_server, "invoice", &options).unwrap();
+        assert_eq!(url, "https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo/Entries/Search?q=invoice&$select=id%2Cname&$filter=entryType%20eq%20%27Document%27&$top=50");
+
+        // Page size goes in the Prefer header, not the query
+        let options = SearchOptions::new().page_size(500);
+        let url = Entry::build_search_url(&api_server, "invoice", &options).unwrap();
+        assert!(url.ends_with("/Entries/Search?q=invoice"));
+        assert_eq!(options.prefer().as_deref(), Some("maxpagesize=500"));
+        assert_eq!(SearchOptions::new().prefer(), None);
+    }
+}
@@ -0,0 +1,984 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Wire types shared by the async API in `laserfiche` and the blocking API
+//! in `laserfiche::blocking`
+//!
+//! Both modules re-export everything here, so e.g. `laserfiche::Entry` and
+//! `laserfiche::blocking::Entry` are one type and values pass freely between
+//! sync and async code. Only the transports, and the `Error` types their
+//! failures are reported in, differ between the two.
+
+use super::RedactedUrl;
+use crate::server_info::ApiVersion;
+use crate::validation;
+
+use serde::{Deserialize, Serialize};
+
+pub use super::auth::{Auth, AuthOrError};
+pub use super::entries::{
+    AuditReason, AuditReasons, AuditReasonsOrError, DeleteOptions, EdocInfo, EdocReader, ExportOptions, ImportRequest, ListOptions,
+};
+pub use super::error::LFAPIError;
+pub use super::metadata::FieldValuesBuilder;
+pub use super::search::SearchOptions;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LFApiServer {
+    pub address: String,
+    pub repository: String,
+}
+
+impl LFApiServer {
+    /// Repository `repository_id` on Laserfiche Cloud in `domain`, e.g.
+    /// `laserfiche.com` from an access key
+    pub fn cloud(domain: &str, repository_id: &str) -> Self {
+        LFApiServer {
+            address: format!("api.{}", domain),
+            repository: repository_id.to_string(),
+        }
+    }
+
+    /// Base URL of the repository API, e.g.
+    /// `https://{address}/LFRepositoryAPI/v1/Repositories/{repository}`, or
+    /// `https://api.laserfiche.com/repository/v1/Repositories/{repository}`
+    /// on Laserfiche Cloud
+    pub fn base_url(&self) -> String {
+        self.base_url_for(ApiVersion::V1)
+    }
+
+    /// Base URL of the repository API routes for `version`, e.g.
+    /// `https://{address}/LFRepositoryAPI/v2/Repositories/{repository}`
+    pub fn base_url_for(&self, version: ApiVersion) -> String {
+        format!("{}://{}/{}/{}/Repositories/{}",
+            validation::API_SCHEME,
+            self.address,
+            validation::api_base_path(&self.address),
+            version.path_segment(),
+            self.repository)
+    }
+
+    /// Display adapter for logs, with any embedded credentials masked
+    pub fn redacted(&self) -> RedactedUrl {
+        RedactedUrl::new(&self.base_url())
+    }
+}
+
+impl std::fmt::Display for LFApiServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.base_url())
+    }
+}
+
+macro_rules! entry_id_newtype {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[serde(transparent)]
+        pub struct $name(pub i64);
+
+        impl $name {
+            /// The raw repository id
+            pub fn as_i64(self) -> i64 {
+                self.0
+            }
+        }
+
+        impl From<i64> for $name {
+            fn from(id: i64) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+entry_id_newtype!(
+    /// Id of any repository entry (document, folder, or shortcut)
+    EntryId
+);
+entry_id_newtype!(
+    /// Id of an entry known to be a folder
+    FolderId
+);
+entry_id_newtype!(
+    /// Id of an entry known to be a document
+    DocumentId
+);
+
+impl FolderId {
+    /// The repository's root folder, `\\`
+    pub const ROOT: FolderId = FolderId(1);
+}
+
+impl From<FolderId> for EntryId {
+    fn from(id: FolderId) -> Self {
+        EntryId(id.0)
+    }
+}
+
+impl From<DocumentId> for EntryId {
+    fn from(id: DocumentId) -> Self {
+        EntryId(id.0)
+    }
+}
+
+/// An enum of the string values the API documents for a property, keeping
+/// any other value in `Unknown` so new server versions still deserialize
+macro_rules! string_enum {
+    ($(#[$meta:meta])* $name:ident { $($(#[$variant_meta:meta])* $variant:ident => $value:literal),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+        #[serde(from = "String", into = "String")]
+        #[non_exhaustive]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value this crate does not know, as the server sent it
+            Unknown(String),
+        }
+
+        impl $name {
+            /// The value as the API spells it
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl Default for $name {
+            /// `Unknown("")`, for properties the server left out
+            fn default() -> Self {
+                $name::Unknown(String::new())
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                $(if value.eq_ignore_ascii_case($value) {
+                    return $name::$variant;
+                })*
+                $name::Unknown(value.to_string())
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(value: String) -> Self {
+                $name::from(value.as_str())
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(value: $name) -> Self {
+                value.as_str().to_string()
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.as_str() == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.as_str() == *other
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+    };
+}
+
+string_enum!(
+    /// Kind of repository entry, from `entryType`
+    EntryType {
+        Folder => "Folder",
+        Document => "Document",
+        Shortcut => "Shortcut",
+        RecordSeries => "RecordSeries",
+    }
+);
+
+string_enum!(
+    /// Data type of a template field, from `fieldType`
+    FieldType {
+        String => "String",
+        /// Multi-line text
+        Blob => "Blob",
+        Number => "Number",
+        ShortInteger => "ShortInteger",
+        LongInteger => "LongInteger",
+        Date => "Date",
+        DateTime => "DateTime",
+        Time => "Time",
+        /// One of a fixed list of values
+        List => "List",
+    }
+);
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Fields {
+    pub value: Vec<Field>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.count", default, skip_serializing_if = "Option::is_none")]
+    pub odata_count: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Field {
+    pub field_name: String,
+    pub values: Vec<FieldValue>,
+    pub field_type: FieldType,
+    pub field_id: i64,
+    pub is_multi_value: bool,
+    pub is_required: bool,
+    pub has_more_values: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FieldValue {
+    pub value: Option<String>,
+    /// 1-based position among a multi-value field's values
+    pub position: i64,
+    pub additional_prop1: Option<String>,
+    pub additional_prop2: Option<String>,
+    pub additional_prop3: Option<String>,
+}
+
+pub enum EntryOrError {
+    Entry(Entry),
+    LFAPIError(LFAPIError),
+}
+
+pub enum ImportResultOrError {
+    ImportResult(ImportResult),
+    LFAPIError(LFAPIError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Entries {
+    pub value: Vec<Entry>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.count", default, skip_serializing_if = "Option::is_none")]
+    pub odata_count: Option<i64>,
+}
+
+impl Entries {
+    /// Cursor for the next page of this collection, if the server returned one
+    pub fn next_link(&self) -> Option<NextLink> {
+        self.odata_next_link.clone().map(NextLink)
+    }
+}
+
+/// Cursor to the next page of a collection response
+///
+/// Only obtainable from a previous response (see `Entries::next_link`), so the
+/// bearer token is never attached to an arbitrary caller-supplied URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextLink(pub(crate) String);
+
+impl NextLink {
+    /// The raw `@odata.nextLink` URL
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for NextLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", RedactedUrl::new(&self.0))
+    }
+}
+
+pub enum MetadataResultOrError {
+    Metadata(MetadataResult),
+    LFAPIError(LFAPIError),
+}
+
+pub enum BitsOrError {
+    Bits(Vec<u8>),
+    LFAPIError(LFAPIError),
+}
+
+/// Outcome of `Entry::export_stream`: the number of bytes written
+pub enum WrittenOrError {
+    Written(u64),
+    LFAPIError(LFAPIError),
+}
+
+/// Outcome of `Entry::delete_edoc`: the document left without content
+pub enum EdocDeletedOrError {
+    Deleted(DocumentId),
+    LFAPIError(LFAPIError),
+}
+
+pub enum EdocInfoOrError {
+    Info(EdocInfo),
+    LFAPIError(LFAPIError),
+}
+
+pub enum EdocReaderOrError {
+    Reader(EdocReader),
+    LFAPIError(LFAPIError),
+}
+
+pub enum EntriesOrError {
+    Entries(Entries),
+    LFAPIError(LFAPIError),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedObject {
+    /// Operation token of the delete; follow it with `tasks::Task`
+    pub token: String,
+}
+
+pub enum LFObject {
+    Fields(Fields),
+    Field(Field),
+    Entry(Entry),
+    Entries(Entries),
+    DeletedObject(DeletedObject),
+    LFAPIError(LFAPIError),
+}
+
+/// Template information for an entry
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub field_count: i64,
+}
+
+pub enum TemplateOrError {
+    Template(Template),
+    LFAPIError(LFAPIError),
+}
+
+/// Tags associated with an entry
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Tags {
+    pub value: Vec<Tag>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_secure: bool,
+    pub watermark_text: Option<String>,
+}
+
+pub enum TagsOrError {
+    Tags(Tags),
+    LFAPIError(LFAPIError),
+}
+
+/// Links associated with an entry
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Links {
+    pub value: Vec<Link>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Link {
+    pub id: i64,
+    pub source_id: i64,
+    pub target_id: i64,
+    pub link_type: String,
+    /// Link definition id, as `NewLink::link_type_id` takes it
+    #[serde(default)]
+    pub link_type_id: i64,
+    pub description: Option<String>,
+}
+
+pub enum LinksOrError {
+    Links(Links),
+    LFAPIError(LFAPIError),
+}
+
+/// An entry together with its field values, tags, and links, from
+/// `Entry::get_full`
+#[derive(Debug, Clone, Default)]
+pub struct FullEntry {
+    pub entry: Entry,
+    pub metadata: MetadataResult,
+    pub tags: Tags,
+    pub links: Links,
+}
+
+pub enum FullEntryOrError {
+    Full(FullEntry),
+    LFAPIError(LFAPIError),
+}
+
+/// Implement `into_result` for `XOrError` enums, turning the success variant
+/// into `Ok` and the API error into `Err(LfError::Api)`
+macro_rules! impl_into_result {
+    ($($name:ident::$variant:ident => $value:ty),* $(,)?) => {
+        $(impl $name {
+            /// `Ok` with the value, or `Err` with `LfError::Api`
+            pub fn into_result(self) -> $crate::v2::Result<$value> {
+                match self {
+                    $name::$variant(value) => Ok(value),
+                    $name::LFAPIError(error) => Err(error.into()),
+                }
+            }
+        }
+
+        impl From<$name> for std::result::Result<$value, $crate::laserfiche::LFAPIError> {
+            /// `Ok` with the value, or `Err` with the server's error body
+            fn from(value: $name) -> Self {
+                match value {
+                    $name::$variant(value) => Ok(value),
+                    $name::LFAPIError(error) => Err(error),
+                }
+            }
+        })*
+    };
+}
+pub(crate) use impl_into_result;
+
+impl_into_result!(
+    AuthOrError::Auth => Auth,
+    EntryOrError::Entry => Entry,
+    EntriesOrError::Entries => Entries,
+    ImportResultOrError::ImportResult => ImportResult,
+    MetadataResultOrError::Metadata => MetadataResult,
+    BitsOrError::Bits => Vec<u8>,
+    WrittenOrError::Written => u64,
+    EdocReaderOrError::Reader => EdocReader,
+    EdocInfoOrError::Info => EdocInfo,
+    EdocDeletedOrError::Deleted => DocumentId,
+    TemplateOrError::Template => Template,
+    TagsOrError::Tags => Tags,
+    LinksOrError::Links => Links,
+    FullEntryOrError::Full => FullEntry,
+    AuditReasonsOrError::AuditReasons => AuditReasons,
+);
+
+impl LFObject {
+    /// `Ok` with this object unless it is an API error
+    pub fn into_result(self) -> crate::v2::Result<LFObject> {
+        match self {
+            LFObject::LFAPIError(error) => Err(error.into()),
+            object => Ok(object),
+        }
+    }
+}
+
+impl From<LFObject> for std::result::Result<LFObject, LFAPIError> {
+    /// `Ok` with the object, or `Err` with the server's error body
+    fn from(object: LFObject) -> Self {
+        match object {
+            LFObject::LFAPIError(error) => Err(error),
+            object => Ok(object),
+        }
+    }
+}
+
+/// A link to create with `Entry::set_links` or `Entry::add_link`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NewLink {
+    pub target_id: i64,
+    /// Id of a link definition from `LinkDefinitions::list`
+    pub link_type_id: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl NewLink {
+    pub fn new(target_id: impl Into<EntryId>, link_type_id: i64) -> Self {
+        NewLink { target_id: target_id.into().as_i64(), link_type_id, description: None }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+}
+
+impl From<&Link> for NewLink {
+    /// The link as it would be assigned again from its source
+    fn from(link: &Link) -> Self {
+        NewLink { target_id: link.target_id, link_type_id: link.link_type_id, description: link.description.clone() }
+    }
+}
+
+/// Represents a Laserfiche repository entry (document or folder)
+///
+/// Properties the server leaves out, e.g. under a `$select` projection,
+/// take their default values.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Entry {
+    pub id: i64,
+    pub name: String,
+    pub parent_id: i64,
+    pub full_path: String,
+    pub folder_path: String,
+    pub creator: String,
+    pub creation_time: String,
+    pub last_modified_time: String,
+    pub entry_type: EntryType,
+    pub is_container: bool,
+    pub is_leaf: bool,
+    pub template_name: Option<String>,
+    pub template_id: i64,
+    pub template_field_names: Option<Vec<String>>,
+    pub volume_name: String,
+    pub row_number: i64,
+    pub fields: Option<Vec<Field>>,
+    /// Size in bytes of a document's electronic content
+    pub elec_document_size: Option<i64>,
+    /// File extension of a document's electronic content, without the dot
+    pub extension: Option<String>,
+}
+impl Entry {
+    /// This entry's id
+    pub fn entry_id(&self) -> EntryId {
+        EntryId(self.id)
+    }
+
+    /// Id of the folder containing this entry
+    pub fn parent_folder_id(&self) -> FolderId {
+        FolderId(self.parent_id)
+    }
+
+    /// This entry's id as a folder id, if it is a container
+    pub fn folder_id(&self) -> Option<FolderId> {
+        self.is_container.then_some(FolderId(self.id))
+    }
+
+    /// This entry's id as a document id, if it is not a container
+    pub fn document_id(&self) -> Option<DocumentId> {
+        (!self.is_container).then_some(DocumentId(self.id))
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.entry_type == EntryType::Folder
+    }
+
+    pub fn is_document(&self) -> bool {
+        self.entry_type == EntryType::Document
+    }
+
+    pub fn is_shortcut(&self) -> bool {
+        self.entry_type == EntryType::Shortcut
+    }
+
+    pub fn is_record_series(&self) -> bool {
+        self.entry_type == EntryType::RecordSeries
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataValue {
+    pub value: String,
+    pub position: i64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub operations: Operations,
+    pub document_link: String,
+}
+
+impl ImportResult {
+    /// The entry `document_link` points to, once the link is checked to
+    /// be on `api_server` and in its repository
+    pub fn linked_entry_id(&self, api_server: &LFApiServer) -> validation::Result<EntryId> {
+        let link = validation::validate_next_link(&self.document_link, &api_server.address, &api_server.repository)?;
+        let entry_id = url::Url::parse(&link)
+            .ok()
+            .and_then(|url| {
+                let segments: Vec<String> = url.path_segments()?.map(str::to_string).collect();
+                segments.windows(2)
+                    .find(|pair| pair[0].eq_ignore_ascii_case("Entries"))
+                    .and_then(|pair| pair[1].parse::<i64>().ok())
+            })
+            .ok_or_else(|| validation::ErrorKind::InvalidUrl(self.document_link.clone()))?;
+        Ok(EntryId(validation::validate_entry_id(entry_id)?))
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Operations {
+    pub entry_create: EntryCreate,
+    pub set_edoc: SetEdoc,
+    pub set_template: Option<SetTemplate>,
+    pub set_fields: Option<SetFields>,
+    pub set_tags: Option<SetTags>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryCreate {
+    pub entry_id: i64,
+    pub exceptions: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetEdoc {
+    pub exceptions: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTemplate {
+    pub template: String,
+    pub exceptions: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetFields {
+    pub field_count: i64,
+    pub exceptions: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetTags {
+    pub assigned_tags: Vec<String>,
+    pub exceptions: Vec<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataResult {
+    pub value: Vec<MetadataResultValue>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataResultValue {
+    pub field_name: String,
+    pub field_type: FieldType,
+    pub group_id: Option<i64>,
+    pub field_id: i64,
+    pub is_multi_value: bool,
+    pub is_required: bool,
+    pub values: Vec<MetadataResultFieldValue>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetadataResultFieldValue {
+    pub value: Option<String>,
+    pub position: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::tests::mock_api_server;
+
+    #[test]
+    fn test_entry_from_projection() {
+        let entry: Entry = serde_json::from_str(r#"{"id": 7, "name": "scan.tif", "elecDocumentSize": 2048}"#).unwrap();
+        assert_eq!(entry.id, 7);
+        assert_eq!(entry.elec_document_size, Some(2048));
+        assert!(entry.creator.is_empty());
+    }
+
+    #[test]
+    fn test_lfapiserver_creation() {
+        let server = LFApiServer {
+            address: "example.laserfiche.com".to_string(),
+            repository: "my-repo".to_string(),
+        };
+        assert_eq!(server.address, "example.laserfiche.com");
+        assert_eq!(server.repository, "my-repo");
+    }
+
+    #[test]
+    fn test_lfapiserver_base_url_and_display() {
+        let server = mock_api_server();
+        assert_eq!(server.base_url(), "https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo");
+        assert_eq!(server.base_url_for(ApiVersion::V2), "https://test.laserfiche.com/LFRepositoryAPI/v2/Repositories/test-repo");
+        assert_eq!(server.to_string(), server.base_url());
+        assert_eq!(server.redacted().as_str(), server.base_url());
+    }
+
+    #[test]
+    fn test_entry_struct() {
+        let entry = Entry {
+            id: 123,
+            name: "test-document.pdf".to_string(),
+            parent_id: 1,
+            full_path: "/root/test-document.pdf".to_string(),
+            folder_path: "/root".to_string(),
+            creator: "john.doe".to_string(),
+            creation_time: "2024-01-01T00:00:00Z".to_string(),
+            last_modified_time: "2024-01-02T00:00:00Z".to_string(),
+            entry_type: EntryType::Document,
+            is_container: false,
+            is_leaf: true,
+            volume_name: "Volume1".to_string(),
+            row_number: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(entry.id, 123);
+        assert_eq!(entry.name, "test-document.pdf");
+        assert_eq!(entry.parent_id, 1);
+        assert!(!entry.is_container);
+        assert!(entry.is_leaf);
+    }
+
+    #[test]
+    fn test_entries_collection() {
+        let entry1 = Entry {
+            id: 1,
+            name: "doc1.pdf".to_string(),
+            ..Default::default()
+        };
+        
+        let entry2 = Entry {
+            id: 2,
+            name: "doc2.pdf".to_string(),
+            ..Default::default()
+        };
+
+        let entries = Entries {
+            value: vec![entry1, entry2],
+            odata_next_link: Some("https://api.laserfiche.com/next".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(entries.value.len(), 2);
+        assert_eq!(entries.value[0].id, 1);
+        assert_eq!(entries.value[1].id, 2);
+        assert!(entries.odata_next_link.is_some());
+    }
+
+    #[test]
+    fn test_entries_next_link() {
+        let mut entries = Entries::default();
+        assert!(entries.next_link().is_none());
+
+        entries.odata_next_link = Some("https://test.laserfiche.com/LFRepositoryAPI/v1/Repositories/test-repo/Entries/1/Laserfiche.Repository.Folder/children?$skip=100".to_string());
+        let next_link = entries.next_link().expect("next link should be present");
+        assert_eq!(next_link.as_str(), entries.odata_next_link.as_deref().unwrap());
+
+        let api_server = mock_api_server();
+        assert!(validation::validate_next_link(next_link.as_str(), &api_server.address, &api_server.repository).is_ok());
+    }
+
+    #[test]
+    fn test_id_newtypes() {
+        assert_eq!(EntryId::from(5).as_i64(), 5);
+        assert_eq!(i64::from(FolderId(7)), 7);
+        assert_eq!(EntryId::from(FolderId(7)), EntryId(7));
+        assert_eq!(EntryId::from(DocumentId(9)), EntryId(9));
+        assert_eq!(DocumentId(42).to_string(), "42");
+
+        // Ids serialize as bare integers
+        assert_eq!(serde_json::to_string(&FolderId(12)).unwrap(), "12");
+        assert_eq!(serde_json::from_str::<DocumentId>("34").unwrap(), DocumentId(34));
+    }
+
+    #[test]
+    fn test_entry_and_field_types() {
+        let entry: Entry = serde_json::from_str(r#"{"id": 5, "entryType": "RecordSeries", "isContainer": true}"#).unwrap();
+        assert_eq!(entry.entry_type, EntryType::RecordSeries);
+        assert!(entry.is_record_series() && !entry.is_folder());
+        assert_eq!(serde_json::to_value(&entry).unwrap()["entryType"], "RecordSeries");
+
+        let entry: Entry = serde_json::from_str(r#"{"id": 6, "entryType": "Portfolio"}"#).unwrap();
+        assert_eq!(entry.entry_type, EntryType::Unknown("Portfolio".to_string()));
+        assert_eq!(entry.entry_type, "Portfolio");
+        assert_eq!(Entry::default().entry_type.as_str(), "");
+
+        let field: MetadataResultValue = serde_json::from_str(r#"{"fieldName": "Due", "fieldType": "DateTime",
+            "isMultiValue": false, "isRequired": false, "fieldId": 1, "values": []}"#).unwrap();
+        assert_eq!(field.field_type, FieldType::DateTime);
+        assert_eq!(FieldType::from("longinteger"), FieldType::LongInteger);
+        assert_eq!(FieldType::List.to_string(), "List");
+    }
+
+    #[test]
+    fn test_entry_typed_ids() {
+        let folder = Entry {
+            id: 10,
+            parent_id: 1,
+            is_container: true,
+            ..Default::default()
+        };
+        assert_eq!(folder.entry_id(), EntryId(10));
+        assert_eq!(folder.parent_folder_id(), FolderId(1));
+        assert_eq!(folder.folder_id(), Some(FolderId(10)));
+        assert_eq!(folder.document_id(), None);
+
+        let document = Entry {
+            id: 11,
+            parent_id: 10,
+            is_container: false,
+            ..Default::default()
+        };
+        assert_eq!(document.folder_id(), None);
+        assert_eq!(document.document_id(), Some(DocumentId(11)));
+    }
+
+    #[test]
+    fn test_import_result() {
+        let import_result = ImportResult {
+            operations: Operations {
+                entry_create: EntryCreate {
+                    entry_id: 123,
+                    exceptions: vec![],
+                },
+                set_edoc: SetEdoc {
+                    exceptions: vec![],
+                },
+                set_template: None,
+                set_fields: None,
+                set_tags: None,
+            },
+            document_link: "https://api.laserfiche.com/entries/123".to_string(),
+        };
+
+        assert_eq!(import_result.operations.entry_create.entry_id, 123);
+        assert!(import_result.operations.entry_create.exceptions.is_empty());
+        assert_eq!(import_result.document_link, "https://api.laserfiche.com/entries/123");
+    }
+
+    #[test]
+    fn test_import_result_linked_entry_id() {
+        let api_server = LFApiServer { address: "lf.example.com".to_string(), repository: "repo".to_string() };
+        let linked = |link: &str| ImportResult { document_link: link.to_string(), ..Default::default() }.linked_entry_id(&api_server);
+
+        assert_eq!(linked("https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/Entries/123").unwrap(), EntryId(123));
+
+        let error = linked("https://evil.example.com/LFRepositoryAPI/v1/Repositories/repo/Entries/123").unwrap_err();
+        assert!(matches!(error.kind(), validation::ErrorKind::UntrustedUrl(_)));
+        assert!(linked("https://lf.example.com/LFRepositoryAPI/v1/Repositories/other/Entries/123").is_err());
+        assert!(linked("https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/Entries/latest").is_err());
+        assert!(linked("").is_err());
+    }
+
+    #[test]
+    fn test_entry_or_error_enum() {
+        let entry = Entry {
+            id: 123,
+            name: "test.pdf".to_string(),
+            ..Default::default()
+        };
+        let entry_result = EntryOrError::Entry(entry);
+        
+        match entry_result {
+            EntryOrError::Entry(e) => assert_eq!(e.id, 123),
+            EntryOrError::LFAPIError(_) => panic!("Expected Entry variant"),
+        }
+    }
+
+    #[test]
+    fn test_entries_or_error_enum() {
+        let entries = Entries {
+            value: vec![],
+            ..Default::default()
+        };
+        let entries_result = EntriesOrError::Entries(entries);
+        
+        match entries_result {
+            EntriesOrError::Entries(e) => assert_eq!(e.value.len(), 0),
+            EntriesOrError::LFAPIError(_) => panic!("Expected Entries variant"),
+        }
+    }
+
+    #[test]
+    fn test_or_error_into_api_result() {
+        let not_found = || LFAPIError { status: Some(404), ..Default::default() };
+
+        let result: std::result::Result<Entry, LFAPIError> = EntryOrError::LFAPIError(not_found()).into();
+        assert!(result.is_err_and(|error| error.is_not_found()));
+        let result: std::result::Result<Tags, LFAPIError> = TagsOrError::Tags(Tags { value: Vec::new() }).into();
+        assert!(result.is_ok());
+
+        let result: std::result::Result<LFObject, LFAPIError> = LFObject::Entry(Entry { id: 7, ..Default::default() }).into();
+        assert!(matches!(result, Ok(LFObject::Entry(entry)) if entry.id == 7));
+        let result: std::result::Result<LFObject, LFAPIError> = LFObject::LFAPIError(not_found()).into();
+        assert!(result.is_err_and(|error| error.is_not_found()));
+    }
+
+    #[test]
+    fn test_import_result_or_error_enum() {
+        let import = ImportResult {
+            operations: Operations {
+                entry_create: EntryCreate {
+                    entry_id: 456,
+                    exceptions: vec![],
+                },
+                set_edoc: SetEdoc {
+                    exceptions: vec![],
+                },
+                set_template: None,
+                set_fields: None,
+                set_tags: None,
+            },
+            document_link: "https://test.com/456".to_string(),
+        };
+        let import_result = ImportResultOrError::ImportResult(import);
+        
+        match import_result {
+            ImportResultOrError::ImportResult(i) => {
+                assert_eq!(i.operations.entry_create.entry_id, 456)
+            },
+            ImportResultOrError::LFAPIError(_) => panic!("Expected ImportResult variant"),
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    fn test_blocking_and_async_share_types() {
+        use crate::laserfiche::{self, blocking};
+
+        fn list_async(entries: laserfiche::Entries) -> laserfiche::EntriesOrError {
+            laserfiche::EntriesOrError::Entries(entries)
+        }
+
+        let entries = blocking::Entries { value: vec![blocking::Entry { id: 7, ..Default::default() }], ..Default::default() };
+        let blocking::EntriesOrError::Entries(entries) = list_async(entries) else { panic!("entries became an error") };
+        let auth: laserfiche::Auth = blocking::Auth::default();
+        assert_eq!(entries.value[0].id, 7);
+        assert!(auth.access_token.is_empty());
+    }
+}
@@ -1,3 +1,71 @@
+//! Rust client for the Laserfiche Repository API
+//!
+//! Most applications need only `use laserfiche_rs::prelude::*;`. Beyond it:
+//!
+//! - `client` - `LfClient`, which keeps its token fresh, and its builder
+//! - `v2` - the client API returning `Result<T, LfError>`
+//! - `types` - the request and response types the API exchanges
+//! - `ops` - search, tasks, watches, reports, checksums, the offline outbox, and state stores
+//! - `bulk` - pipelines, blueprints, moves, tagging, folder sync, and manifests
+//! - `laserfiche` - the underlying static calls, async and `blocking`
+
 pub mod laserfiche;
 pub mod validation;
 pub mod config;
+pub mod pipeline;
+pub mod snapshot;
+pub mod blueprint;
+pub mod naming;
+pub mod moves;
+pub mod tagging;
+pub mod normalize;
+pub mod migration;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod importer;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sync;
+pub mod reports;
+pub mod preflight;
+pub mod search;
+pub mod server_info;
+pub mod tasks;
+pub mod watch;
+pub mod outbox;
+pub mod state;
+pub mod tag_definitions;
+pub mod repositories;
+pub mod pages;
+pub mod link_definitions;
+pub mod throttle;
+pub mod stats;
+pub mod progress;
+pub mod client;
+pub mod manifest;
+pub mod export;
+pub mod inspect;
+pub mod integrity;
+pub mod mime;
+pub mod cloud;
+pub mod retry;
+pub mod slow_requests;
+pub mod clock;
+pub mod egress;
+pub mod messages;
+pub mod repository;
+pub mod v2;
+pub mod prelude;
+pub mod types;
+pub mod ops;
+pub mod bulk;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+#[cfg(feature = "email")]
+pub mod email;
+#[cfg(feature = "image-pdf")]
+pub mod image_pdf;
+#[cfg(feature = "chrono")]
+pub mod timestamps;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod fixtures;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+pub mod testing;
@@ -0,0 +1,138 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{http_client, impl_into_result, Auth, LFAPIError, LFApiServer, NextLink, RequestUrl, Result};
+use crate::retry::RetryingSend;
+
+use serde::{Deserialize, Serialize};
+
+/// A kind of link entries can be related by, e.g. invoice and purchase order
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LinkDefinition {
+    pub link_type_id: i64,
+    /// Label of the entry a link starts from, e.g. `Invoice`
+    pub source_label: String,
+    /// Label of the entry a link points to, e.g. `Purchase Order`
+    pub target_label: String,
+    pub link_type_description: Option<String>,
+}
+
+/// One page of the repository's link definitions
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkDefinitionsPage {
+    pub value: Vec<LinkDefinition>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.count", default, skip_serializing_if = "Option::is_none")]
+    pub odata_count: Option<i64>,
+}
+
+impl LinkDefinitionsPage {
+    /// Cursor for the next page, if the server returned one
+    pub fn next_link(&self) -> Option<NextLink> {
+        self.odata_next_link.clone().map(NextLink)
+    }
+
+    /// The definition whose source and target labels are `source_label`
+    /// and `target_label`, ignoring case
+    pub fn find(&self, source_label: &str, target_label: &str) -> Option<&LinkDefinition> {
+        self.value.iter().find(|definition| {
+            definition.source_label.eq_ignore_ascii_case(source_label)
+                && definition.target_label.eq_ignore_ascii_case(target_label)
+        })
+    }
+}
+
+pub enum LinkDefinitionsOrError {
+    LinkDefinitions(LinkDefinitionsPage),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(LinkDefinitionsOrError::LinkDefinitions => LinkDefinitionsPage);
+
+/// Link types defined in the repository, for the ids `Entry::add_link` takes
+pub struct LinkDefinitions;
+
+impl LinkDefinitions {
+    /// Fetch the first page of link definitions
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn list(api_server: LFApiServer, auth: Auth) -> Result<LinkDefinitionsOrError> {
+        let url = RequestUrl::repository(&api_server)?.segment("LinkDefinitions").build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Fetch the page of link definitions a previous page links to
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `next_link` - From `LinkDefinitionsPage::next_link`
+    pub async fn list_next(api_server: LFApiServer, auth: Auth, next_link: NextLink) -> Result<LinkDefinitionsOrError> {
+        let url = RequestUrl::next_link(&api_server, &next_link)?.build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Every link definition, in one page
+    ///
+    /// Repositories define few link types, so all pages are read up front.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn list_all(api_server: LFApiServer, auth: Auth) -> Result<LinkDefinitionsOrError> {
+        let mut all = match Self::list(api_server.clone(), auth.clone()).await? {
+            LinkDefinitionsOrError::LinkDefinitions(page) => page,
+            error => return Ok(error),
+        };
+        while let Some(next_link) = all.next_link() {
+            match Self::list_next(api_server.clone(), auth.clone(), next_link).await? {
+                LinkDefinitionsOrError::LinkDefinitions(page) => {
+                    all.value.extend(page.value);
+                    all.odata_next_link = page.odata_next_link;
+                }
+                error => return Ok(error),
+            }
+        }
+        Ok(LinkDefinitionsOrError::LinkDefinitions(all))
+    }
+
+    async fn fetch_page(url: String, auth: &Auth) -> Result<LinkDefinitionsOrError> {
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(LinkDefinitionsOrError::LFAPIError(error));
+        }
+
+        let page = response.json::<LinkDefinitionsPage>().await?;
+        Ok(LinkDefinitionsOrError::LinkDefinitions(page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_definitions_page() {
+        let page: LinkDefinitionsPage = serde_json::from_str(r#"{
+            "value": [
+                {"linkTypeId": 1, "sourceLabel": "Parent", "targetLabel": "Child", "linkTypeDescription": "Hierarchy"},
+                {"linkTypeId": 4, "sourceLabel": "Invoice", "targetLabel": "Purchase Order"}
+            ]
+        }"#).unwrap();
+        assert!(page.next_link().is_none());
+        assert_eq!(page.find("invoice", "purchase order").map(|definition| definition.link_type_id), Some(4));
+        assert!(page.find("Purchase Order", "Invoice").is_none());
+    }
+}
@@ -2,11 +2,14 @@
 // Developed by Caleb Mitchell Smith (PixelCoda)
 // Licensed under GPLv3....see LICENSE file.
 
+#[cfg(not(target_arch = "wasm32"))]
 use laserfiche_rs::{laserfiche, config};
+#[cfg(not(target_arch = "wasm32"))]
 use std::process;
 use log::debug;
 
 /// Helper trait for safe array access with logging
+#[allow(dead_code)]
 trait SafeArrayAccess<T> {
     fn safe_get(&self, index: usize, context: &str) -> Option<&T>;
     fn safe_first(&self, context: &str) -> Option<&T>;
@@ -35,6 +38,12 @@ impl<T> SafeArrayAccess<T> for Vec<T> {
     }
 }
 
+/// The demo reads its settings from the environment and blocks on a tokio
+/// runtime, neither of which a browser has; wasm32 builds use the library
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(not(target_arch = "wasm32"))]
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load configuration from environment variables with proper validation
@@ -77,150 +86,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
-    // Run example tests (uncomment as needed)
-    // test_token_refresh(&auth).await?;
-    // test_file_import(&api_server, &auth).await?;
-    // test_list_entries(&api_server, &auth).await?;
-    // test_export_file(&api_server, &auth).await?;
-    
-    println!("\nAll tests completed successfully!");
-    Ok(())
-}
-
-#[allow(dead_code)]
-async fn test_token_refresh(auth: &laserfiche::Auth) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\nTesting token refresh...");
-    
-    let refresh_result = auth.refresh().await?;
-    
-    match refresh_result {
-        laserfiche::AuthOrError::Auth(refreshed_auth) => {
-            println!("Token refreshed successfully: {:?}", refreshed_auth);
-        },
-        laserfiche::AuthOrError::LFAPIError(error) => {
-            eprintln!("Token refresh failed: {:?}", error);
-            return Err(format!("Token refresh failed: {:?}", error).into());
-        }
-    }
-    Ok(())
-}
-
-#[allow(dead_code)]
-async fn test_file_import(api_server: &laserfiche::LFApiServer, auth: &laserfiche::Auth) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\nTesting file import...");
-    
-    let import_result = laserfiche::Entry::import(
-        api_server.clone(),
-        auth.clone(),
-        "incoming".to_string(),
-        "test2.tiff".to_string(),
-        1  // Parent folder ID
-    ).await?;
-    
-    match import_result {
-        laserfiche::ImportResultOrError::ImportResult(result) => {
-            println!("File imported successfully: {:?}", result);
-        },
-        laserfiche::ImportResultOrError::LFAPIError(error) => {
-            eprintln!("File import failed: {:?}", error);
-            return Err(format!("File import failed: {:?}", error).into());
-        }
-    }
-    Ok(())
-}
-
-#[allow(dead_code)]
-async fn test_list_entries(api_server: &laserfiche::LFApiServer, auth: &laserfiche::Auth) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\nListing entries...");
-    
-    let entries_result = laserfiche::Entry::list(
-        api_server.clone(),
-        auth.clone(),
-        1  // Folder ID
-    ).await?;
-    
-    match entries_result {
-        laserfiche::EntriesOrError::Entries(entries) => {
-            println!("Found {} entries", entries.value.len());
-            
-            // Get metadata for each entry
-            for entry in entries.value {
-                if let Err(e) = get_entry_metadata(api_server, auth, entry.id).await {
-                    eprintln!("Failed to get metadata for entry {}: {}", entry.id, e);
-                }
-            }
-        },
-        laserfiche::EntriesOrError::LFAPIError(error) => {
-            eprintln!("Failed to list entries: {:?}", error);
-            return Err(format!("Failed to list entries: {:?}", error).into());
-        }
-    }
-    Ok(())
-}
-
-async fn get_entry_metadata(
-    api_server: &laserfiche::LFApiServer,
-    auth: &laserfiche::Auth,
-    entry_id: i64
-) -> Result<(), Box<dyn std::error::Error>> {
-    let metadata_result = laserfiche::Entry::get_metadata(
-        api_server.clone(),
-        auth.clone(),
-        entry_id
-    ).await?;
-    
-    match metadata_result {
-        laserfiche::MetadataResultOrError::Metadata(metadata) => {
-            // Use the safe access helper method
-            match metadata.value.safe_first(&format!("metadata for entry {}", entry_id)) {
-                Some(first_field) => {
-                    println!("Entry {} metadata:", entry_id);
-                    println!("  Field name: {:?}", first_field.field_name);
-                    
-                    // Safe array access for values using helper method
-                    match first_field.values.safe_first(&format!("values for field '{}'", first_field.field_name)) {
-                        Some(first_value) => {
-                            println!("  First value: {:?}", first_value.value);
-                        },
-                        None => {
-                            println!("  Field has no values");
-                        }
-                    }
-                    println!("  Total fields: {}", metadata.value.len());
-                },
-                None => {
-                    debug!("Entry {} has no metadata fields", entry_id);
-                }
-            }
-        },
-        laserfiche::MetadataResultOrError::LFAPIError(error) => {
-            eprintln!("Failed to get metadata for entry {}: {:?}", entry_id, error);
-            return Err(format!("Failed to get metadata for entry {}: {:?}", entry_id, error).into());
-        }
-    }
-    Ok(())
-}
-
-#[allow(dead_code)]
-async fn test_export_file(api_server: &laserfiche::LFApiServer, auth: &laserfiche::Auth) -> Result<(), Box<dyn std::error::Error>> {
-    println!("\nExporting file...");
-    
-    let export_result = laserfiche::Entry::export(
-        api_server.clone(),
-        auth.clone(),
-        3740,  // Entry ID to export
-        "export_test.png"
-    ).await?;
-    
-    match export_result {
-        laserfiche::BitsOrError::Bits(data) => {
-            println!("File exported successfully: {} bytes", data.len());
-        },
-        laserfiche::BitsOrError::LFAPIError(error) => {
-            eprintln!("Export failed: {:?}", error);
-            return Err(format!("Export failed: {:?}", error).into());
-        }
-    }
+    println!("\nSee examples/ for bulk import, folder sync, search export, and hot-folder");
+    println!("workflows: cargo run --example bulk_import --features testing");
     Ok(())
 }
 
@@ -0,0 +1,357 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{ImportResult, Result};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{Auth, Entry, FolderId, ImportResultOrError, LFApiServer};
+use crate::state::{self, StateStore};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validation;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+#[cfg(not(target_arch = "wasm32"))]
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Columns written by `ImportManifest::to_csv`, in order
+const CSV_HEADER: [&str; 6] = ["source_path", "entry_id", "document_link", "sha256", "template", "metadata"];
+
+/// Version of the JSON written by `ImportManifest` and `ExportManifest`
+///
+/// Raised whenever a field is renamed, removed, or changes meaning, with a
+/// step added to `migrate`, so manifests kept by long-running jobs still
+/// load after an upgrade. Files without a version predate it and are
+/// format 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// JSON key holding a manifest's format version
+const FORMAT_VERSION_KEY: &str = "format_version";
+
+/// A manifest serialized with its format version alongside its fields
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    format_version: u32,
+    #[serde(flatten)]
+    manifest: &'a T,
+}
+
+/// Record of what an import created, for reconciling downstream systems
+/// with Laserfiche entry ids
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// One imported file
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub source_path: String,
+    pub entry_id: i64,
+    pub document_link: String,
+    /// Lowercase hex SHA-256 of the uploaded content
+    pub sha256: String,
+    pub template: Option<String>,
+    /// Field values applied at import, by field name
+    #[serde(default)]
+    pub metadata: BTreeMap<String, Vec<String>>,
+}
+
+impl ImportManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the outcome of one successful import
+    pub fn record(
+        &mut self,
+        source_path: &str,
+        result: &ImportResult,
+        sha256: String,
+        template: Option<String>,
+        metadata: BTreeMap<String, Vec<String>>
+    ) {
+        self.entries.push(ManifestEntry {
+            source_path: source_path.to_string(),
+            entry_id: result.operations.entry_create.entry_id,
+            document_link: result.document_link.clone(),
+            sha256,
+            template,
+            metadata,
+        });
+    }
+
+    /// Serialize as pretty-printed JSON, tagged with `FORMAT_VERSION`
+    pub fn to_json(&self) -> Result<String> {
+        to_versioned_json("import", self)
+    }
+
+    /// Parse JSON written by `to_json` of this or an earlier crate version
+    pub fn from_json(json: &str) -> Result<Self> {
+        from_versioned_json("import", json)
+    }
+
+    /// Read a JSON manifest written by `write`, e.g. to resume a job
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Read the manifest saved under `key` by `save_to`, if any
+    pub fn load_from(store: &dyn StateStore, key: &str) -> Result<Option<Self>> {
+        state::load_string(store, key)?.map(|json| Self::from_json(&json)).transpose()
+    }
+
+    /// Save the manifest as JSON under `key`, replacing any saved before
+    pub fn save_to(&self, store: &dyn StateStore, key: &str) -> Result<()> {
+        store.save(key, self.to_json()?.as_bytes())
+    }
+
+    /// Serialize as CSV with a header row; metadata is a JSON object column
+    pub fn to_csv(&self) -> Result<String> {
+        let mut csv = CSV_HEADER.join(",");
+        csv.push_str("\r\n");
+
+        for entry in &self.entries {
+            let metadata = serde_json::to_string(&entry.metadata)
+                .map_err(|e| format!("Failed to serialize manifest metadata: {}", e))?;
+            let row = [
+                csv_field(&entry.source_path),
+                entry.entry_id.to_string(),
+                csv_field(&entry.document_link),
+                entry.sha256.clone(),
+                csv_field(entry.template.as_deref().unwrap_or("")),
+                csv_field(&metadata),
+            ];
+            csv.push_str(&row.join(","));
+            csv.push_str("\r\n");
+        }
+
+        Ok(csv)
+    }
+
+    /// Write the manifest to `path`, as CSV for a `.csv` extension and JSON otherwise
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let is_csv = path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+        let contents = if is_csv { self.to_csv()? } else { self.to_json()? };
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+}
+
+pub(crate) fn to_versioned_json<T: Serialize>(kind: &str, manifest: &T) -> Result<String> {
+    serde_json::to_string_pretty(&Versioned { format_version: FORMAT_VERSION, manifest })
+        .map_err(|e| format!("Failed to serialize {} manifest: {}", kind, e).into())
+}
+
+/// Parse a manifest, migrating it from its format version to `FORMAT_VERSION`
+///
+/// Fails for a format newer than this crate writes rather than dropping
+/// fields it does not know.
+pub(crate) fn from_versioned_json<T: DeserializeOwned>(kind: &str, json: &str) -> Result<T> {
+    let mut value: Value = serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse {} manifest: {}", kind, e))?;
+
+    let version = match value.as_object_mut().and_then(|fields| fields.remove(FORMAT_VERSION_KEY)) {
+        None => 0,
+        Some(version) => version.as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| format!("Invalid {} manifest format version: {}", kind, version))?,
+    };
+    if version > FORMAT_VERSION {
+        return Err(format!(
+            "The {} manifest is format {}, newer than the {} this version reads",
+            kind, version, FORMAT_VERSION
+        ).into());
+    }
+    for from in version..FORMAT_VERSION {
+        value = migrate(from, value);
+    }
+
+    serde_json::from_value(value)
+        .map_err(|e| format!("Failed to parse {} manifest: {}", kind, e).into())
+}
+
+/// Upgrade manifest JSON from format `from` to `from + 1`
+fn migrate(from: u32, value: Value) -> Value {
+    match from {
+        // Format 1 only added `format_version`
+        0 => value,
+        _ => unreachable!("no manifest format after {}", FORMAT_VERSION),
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote, or line break
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Lowercase hex SHA-256 of a file, read in chunks
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Import a file and record it in `manifest` on success
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local file to import
+/// * `file_name` - Name for the new document
+/// * `root_id` - Destination folder
+/// * `manifest` - Manifest to append to
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_with_manifest(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    file_name: String,
+    root_id: impl Into<FolderId>,
+    manifest: &mut ImportManifest
+) -> Result<ImportResultOrError> {
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let sha256 = sha256_file(&validated_path)?;
+
+    let result = Entry::import(api_server, auth, file_path.clone(), file_name, root_id).await?;
+    if let ImportResultOrError::ImportResult(import) = &result {
+        manifest.record(&file_path, import, sha256, None, BTreeMap::new());
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{EntryCreate, Operations};
+
+    fn sample_result(entry_id: i64) -> ImportResult {
+        ImportResult {
+            operations: Operations {
+                entry_create: EntryCreate { entry_id, exceptions: Vec::new() },
+                ..Default::default()
+            },
+            document_link: format!("https://example.com/docs/{}", entry_id),
+        }
+    }
+
+    fn sample_manifest() -> ImportManifest {
+        let mut manifest = ImportManifest::new();
+        let mut metadata = BTreeMap::new();
+        metadata.insert("Vendor".to_string(), vec!["Acme, Inc.".to_string()]);
+        manifest.record("/scans/a.pdf", &sample_result(41), "ab".repeat(32), Some("Invoice".to_string()), metadata);
+        manifest.record("/scans/b \"draft\".pdf", &sample_result(42), "cd".repeat(32), None, BTreeMap::new());
+        manifest
+    }
+
+    #[test]
+    fn test_manifest_json_round_trip() {
+        let manifest = sample_manifest();
+        let json = manifest.to_json().unwrap();
+        let parsed: ImportManifest = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed, manifest);
+        assert_eq!(parsed.entries[0].entry_id, 41);
+        assert_eq!(parsed.entries[0].document_link, "https://example.com/docs/41");
+    }
+
+    #[test]
+    fn test_manifest_format_version() {
+        let manifest = sample_manifest();
+        let json: Value = serde_json::from_str(&manifest.to_json().unwrap()).unwrap();
+        assert_eq!(json["format_version"], FORMAT_VERSION);
+        assert_eq!(ImportManifest::from_json(&manifest.to_json().unwrap()).unwrap(), manifest);
+
+        // Written before manifests were versioned
+        let unversioned = r#"{"entries": [{"source_path": "/scans/a.pdf", "entry_id": 41,
+            "document_link": "https://example.com/docs/41", "sha256": "ab", "template": null}]}"#;
+        let migrated = ImportManifest::from_json(unversioned).unwrap();
+        assert_eq!(migrated.entries[0].entry_id, 41);
+        assert!(migrated.entries[0].metadata.is_empty());
+
+        let newer = format!(r#"{{"format_version": {}, "entries": []}}"#, FORMAT_VERSION + 1);
+        let error = ImportManifest::from_json(&newer).unwrap_err();
+        assert!(error.to_string().contains("newer"));
+        assert!(ImportManifest::from_json(r#"{"format_version": "one", "entries": []}"#).is_err());
+    }
+
+    #[test]
+    fn test_manifest_state_store() {
+        let store = crate::state::MemoryStore::new();
+        assert_eq!(ImportManifest::load_from(&store, "import.json").unwrap(), None);
+
+        let manifest = sample_manifest();
+        manifest.save_to(&store, "import.json").unwrap();
+        assert_eq!(ImportManifest::load_from(&store, "import.json").unwrap(), Some(manifest));
+    }
+
+    #[test]
+    fn test_manifest_csv() {
+        let csv = sample_manifest().to_csv().unwrap();
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+
+        assert_eq!(lines[0], "source_path,entry_id,document_link,sha256,template,metadata");
+        assert!(lines[1].starts_with("/scans/a.pdf,41,https://example.com/docs/41,"));
+        assert!(lines[1].ends_with(",Invoice,\"{\"\"Vendor\"\":[\"\"Acme, Inc.\"\"]}\""));
+        assert!(lines[2].starts_with("\"/scans/b \"\"draft\"\".pdf\",42,"));
+    }
+
+    #[test]
+    fn test_sha256_file() {
+        let path = std::env::temp_dir().join(format!("lf_manifest_sha_{}.txt", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+
+        let digest = sha256_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(digest, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    #[test]
+    fn test_write_picks_format_from_extension() {
+        let manifest = sample_manifest();
+        let dir = std::env::temp_dir();
+        let csv_path = dir.join(format!("lf_manifest_{}.csv", std::process::id()));
+        let json_path = dir.join(format!("lf_manifest_{}.json", std::process::id()));
+
+        manifest.write(&csv_path).unwrap();
+        manifest.write(&json_path).unwrap();
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        let json = std::fs::read_to_string(&json_path).unwrap();
+        std::fs::remove_file(&csv_path).unwrap();
+        std::fs::remove_file(&json_path).unwrap();
+
+        assert!(csv.starts_with("source_path,"));
+        assert!(json.trim_start().starts_with('{'));
+    }
+}
@@ -0,0 +1,263 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Localized rendering of this crate's error messages
+//!
+//! Errors still `Display` in English. Applications that show errors to end
+//! users render them with `Localize::localized` instead, through a
+//! `MessageProvider` such as a `MessageCatalog` of translated templates;
+//! any message the provider does not translate falls back to English.
+
+use crate::laserfiche::{self, LFAPIError};
+use crate::v2::LfError;
+use crate::validation;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// One of the fixed messages this crate's errors are rendered from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKey {
+    InvalidEntryId,
+    InvalidFilePath,
+    PathTraversalAttempt,
+    InvalidRepositoryName,
+    InvalidUrl,
+    InsecureUrl,
+    InvalidFieldName,
+    InvalidFieldValue,
+    SqlInjectionAttempt,
+    ScriptInjectionAttempt,
+    FileSizeTooLarge,
+    InvalidFileName,
+    UntrustedUrl,
+    InvalidMimeType,
+    ContentRejected,
+    TokenRefreshFailed,
+    ApiError,
+    EntryLocked,
+    UnsupportedByServer,
+    NotRoutedForVersion,
+    EgressDenied,
+    FileExists,
+    IntegrityError,
+}
+
+/// A message to render: its key and the values it refers to, in the order
+/// documented on `MessageCatalog`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub key: MessageKey,
+    pub args: Vec<String>,
+}
+
+impl Message {
+    fn new(key: MessageKey, args: &[&dyn fmt::Display]) -> Self {
+        Message { key, args: args.iter().map(|arg| arg.to_string()).collect() }
+    }
+}
+
+/// Source of translated messages
+pub trait MessageProvider: fmt::Debug + Send + Sync {
+    /// The message in the provider's language, or `None` to fall back to English
+    fn render(&self, message: &Message) -> Option<String>;
+}
+
+/// Message templates keyed by `MessageKey`, with `{0}`, `{1}`, ... standing
+/// for the message's arguments
+///
+/// Arguments by key: `InvalidEntryId` the id; `FileSizeTooLarge` the size
+/// and the limit; `ContentRejected` the file name and reason;
+/// `TokenRefreshFailed` the detail; `ApiError` the server's message and
+/// HTTP status; `EntryLocked` the entry id; `UnsupportedByServer` the
+/// feature, the version it needs, and the newest the server supports;
+/// `NotRoutedForVersion` the route and the version requests are scoped to;
+/// `IntegrityError` the entry id and the expected and actual SHA-256; every
+/// other key the rejected input. A catalog deserializes from a JSON object such as
+/// `{"InvalidEntryId": "Identifiant d'entrée invalide : {0}"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct MessageCatalog {
+    templates: HashMap<MessageKey, String>,
+}
+
+impl MessageCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Translate `key` with `template`
+    pub fn with(mut self, key: MessageKey, template: impl Into<String>) -> Self {
+        self.templates.insert(key, template.into());
+        self
+    }
+
+    pub fn insert(&mut self, key: MessageKey, template: impl Into<String>) {
+        self.templates.insert(key, template.into());
+    }
+}
+
+impl MessageProvider for MessageCatalog {
+    fn render(&self, message: &Message) -> Option<String> {
+        let template = self.templates.get(&message.key)?;
+        Some(message.args.iter().enumerate().fold(template.clone(), |text, (i, arg)| {
+            text.replace(&format!("{{{}}}", i), arg)
+        }))
+    }
+}
+
+/// An error that can be rendered through a `MessageProvider`
+pub trait Localize: fmt::Display {
+    /// The fixed message behind this error, if it has one
+    fn message(&self) -> Option<Message>;
+
+    /// This error in the provider's language, or in English if the provider
+    /// has no translation
+    fn localized(&self, provider: &dyn MessageProvider) -> String {
+        self.message()
+            .and_then(|message| provider.render(&message))
+            .unwrap_or_else(|| self.to_string())
+    }
+}
+
+impl Localize for validation::Error {
+    fn message(&self) -> Option<Message> {
+        use validation::ErrorKind::*;
+
+        let message = match self.kind() {
+            InvalidEntryId(id) => Message::new(MessageKey::InvalidEntryId, &[id]),
+            InvalidFilePath(path) => Message::new(MessageKey::InvalidFilePath, &[path]),
+            PathTraversalAttempt(path) => Message::new(MessageKey::PathTraversalAttempt, &[path]),
+            InvalidRepositoryName(name) => Message::new(MessageKey::InvalidRepositoryName, &[name]),
+            InvalidUrl(url) => Message::new(MessageKey::InvalidUrl, &[url]),
+            InsecureUrl(url) => Message::new(MessageKey::InsecureUrl, &[url]),
+            InvalidFieldName(name) => Message::new(MessageKey::InvalidFieldName, &[name]),
+            InvalidFieldValue(value) => Message::new(MessageKey::InvalidFieldValue, &[value]),
+            SqlInjectionAttempt(input) => Message::new(MessageKey::SqlInjectionAttempt, &[input]),
+            ScriptInjectionAttempt(input) => Message::new(MessageKey::ScriptInjectionAttempt, &[input]),
+            FileSizeTooLarge(size, max) => Message::new(MessageKey::FileSizeTooLarge, &[size, max]),
+            InvalidFileName(name) => Message::new(MessageKey::InvalidFileName, &[name]),
+            UntrustedUrl(url) => Message::new(MessageKey::UntrustedUrl, &[url]),
+            InvalidMimeType(mime_type) => Message::new(MessageKey::InvalidMimeType, &[mime_type]),
+            EgressDenied(url) => Message::new(MessageKey::EgressDenied, &[url]),
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+impl Localize for LFAPIError {
+    fn message(&self) -> Option<Message> {
+        let status = self.status.map(|status| status.to_string()).unwrap_or_default();
+        Some(Message::new(MessageKey::ApiError, &[self, &status]))
+    }
+}
+
+impl Localize for laserfiche::Error {
+    fn message(&self) -> Option<Message> {
+        match self.kind() {
+            laserfiche::ErrorKind::ValidationError(error) => error.message(),
+            laserfiche::ErrorKind::ContentRejected(file_name, reason) => {
+                Some(Message::new(MessageKey::ContentRejected, &[file_name, reason]))
+            }
+            laserfiche::ErrorKind::TokenRefreshFailed(detail) => Some(Message::new(MessageKey::TokenRefreshFailed, &[detail])),
+            laserfiche::ErrorKind::ApiError(error) => error.message(),
+            laserfiche::ErrorKind::EntryLocked(entry_id) => Some(Message::new(MessageKey::EntryLocked, &[entry_id])),
+            laserfiche::ErrorKind::UnsupportedByServer(feature, required, supported) => {
+                Some(Message::new(MessageKey::UnsupportedByServer, &[feature, required, supported]))
+            }
+            laserfiche::ErrorKind::NotRoutedForVersion(route, version) => {
+                Some(Message::new(MessageKey::NotRoutedForVersion, &[route, version]))
+            }
+            laserfiche::ErrorKind::FileExists(path) => Some(Message::new(MessageKey::FileExists, &[path])),
+            laserfiche::ErrorKind::IntegrityError(entry_id, expected, actual) => {
+                Some(Message::new(MessageKey::IntegrityError, &[entry_id, expected, actual]))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl Localize for laserfiche::blocking::Error {
+    fn message(&self) -> Option<Message> {
+        use laserfiche::blocking::ErrorKind;
+
+        match self.kind() {
+            ErrorKind::ValidationError(error) => error.message(),
+            ErrorKind::TokenRefreshFailed(detail) => Some(Message::new(MessageKey::TokenRefreshFailed, &[detail])),
+            ErrorKind::ApiError(error) => error.message(),
+            ErrorKind::EntryLocked(entry_id) => Some(Message::new(MessageKey::EntryLocked, &[entry_id])),
+            ErrorKind::UnsupportedByServer(feature, required, supported) => {
+                Some(Message::new(MessageKey::UnsupportedByServer, &[feature, required, supported]))
+            }
+            ErrorKind::NotRoutedForVersion(route, version) => {
+                Some(Message::new(MessageKey::NotRoutedForVersion, &[route, version]))
+            }
+            ErrorKind::FileExists(path) => Some(Message::new(MessageKey::FileExists, &[path])),
+            _ => None,
+        }
+    }
+}
+
+impl Localize for LfError {
+    fn message(&self) -> Option<Message> {
+        match self {
+            LfError::Api(error) => error.message(),
+            LfError::Request(error) => error.message(),
+            #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+            LfError::BlockingRequest(error) => error.message(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn french() -> MessageCatalog {
+        MessageCatalog::new()
+            .with(MessageKey::InvalidEntryId, "Identifiant d'entrée invalide : {0}")
+            .with(MessageKey::FileSizeTooLarge, "Le fichier fait {0} octets ; le maximum est {1}")
+            .with(MessageKey::ApiError, "Erreur du serveur ({1}) : {0}")
+    }
+
+    #[test]
+    fn test_localized_validation_errors() {
+        let catalog = french();
+
+        let error = validation::validate_entry_id(-1).unwrap_err();
+        assert_eq!(error.localized(&catalog), "Identifiant d'entrée invalide : -1");
+
+        let error = validation::validate_file_size(validation::MAX_FILE_SIZE + 1).unwrap_err();
+        assert_eq!(error.localized(&catalog), "Le fichier fait 104857601 octets ; le maximum est 104857600");
+
+        // Untranslated messages fall back to English
+        let error = validation::validate_repository_name("").unwrap_err();
+        assert_eq!(error.localized(&catalog), error.to_string());
+    }
+
+    #[test]
+    fn test_localized_client_errors() {
+        let catalog = french();
+        let api_error = LFAPIError { title: Some("Entrée introuvable.".to_string()), status: Some(404), ..Default::default() };
+
+        let error: laserfiche::Error = laserfiche::ErrorKind::ApiError(Box::new(api_error.clone())).into();
+        assert_eq!(error.localized(&catalog), "Erreur du serveur (404) : Entrée introuvable.");
+
+        let error = LfError::from(api_error);
+        assert_eq!(error.localized(&catalog), "Erreur du serveur (404) : Entrée introuvable.");
+
+        let error: laserfiche::Error = validation::validate_entry_id(0).unwrap_err().into();
+        assert_eq!(error.localized(&catalog), "Identifiant d'entrée invalide : 0");
+    }
+
+    #[test]
+    fn test_catalog_from_json() {
+        let catalog: MessageCatalog = serde_json::from_str(r#"{"EntryLocked": "Eintrag {0} ist gesperrt"}"#).unwrap();
+        let error: laserfiche::Error = laserfiche::ErrorKind::EntryLocked(42).into();
+        assert_eq!(error.localized(&catalog), "Eintrag 42 ist gesperrt");
+    }
+}
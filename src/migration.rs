@@ -0,0 +1,384 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+use crate::laserfiche::{
+    Auth, Entry, EntryId, EntryOrError, LFApiServer, MetadataResult, MetadataResultOrError, Result,
+};
+use crate::normalize::{Case, Scope, Transform};
+use crate::pipeline::Pipeline;
+use crate::stats::TransferStatsSnapshot;
+use crate::throttle;
+use crate::validation;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+/// Old-to-new template and field mappings for `migrate_templates`
+///
+/// Migrations are written in TOML, so they can be reviewed like any other
+/// config:
+///
+/// ```toml
+/// [[templates]]
+/// from = "Legacy Invoice"
+/// to = "Invoice"
+/// carry_unmapped = true
+///
+/// [[templates.fields]]
+/// from = "Vendor Name"
+/// to = "Vendor"
+/// transforms = ["trim", { case = "upper" }]
+///
+/// [[templates.fields]]
+/// to = "Source"
+/// value = "2019 archive"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateMigration {
+    #[serde(default)]
+    pub templates: Vec<TemplateMapping>,
+}
+
+/// Entries with template `from` move to template `to`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateMapping {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub fields: Vec<FieldMapping>,
+    /// Copy fields no mapping reads under their own names; otherwise they
+    /// are left behind with the old template
+    #[serde(default)]
+    pub carry_unmapped: bool,
+}
+
+/// Where one field of the new template gets its values
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldMapping {
+    /// Field of the old template to read; defaults to `to`
+    #[serde(default)]
+    pub from: Option<String>,
+    pub to: String,
+    /// A fixed value to write instead of reading a field
+    #[serde(default)]
+    pub value: Option<String>,
+    /// Applied to each value in order
+    #[serde(default)]
+    pub transforms: Vec<TransformSpec>,
+}
+
+/// A `normalize::Transform` as a migration file spells it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransformSpec {
+    Trim,
+    /// `upper`, `lower`, or `title`
+    Case(String),
+    Replace { pattern: String, replacement: String },
+    Date { from: Vec<String>, to: String },
+}
+
+impl TransformSpec {
+    pub fn to_transform(&self) -> Result<Transform> {
+        match self {
+            TransformSpec::Trim => Ok(Transform::Trim),
+            TransformSpec::Case(case) => match case.as_str() {
+                "upper" => Ok(Transform::Case(Case::Upper)),
+                "lower" => Ok(Transform::Case(Case::Lower)),
+                "title" => Ok(Transform::Case(Case::Title)),
+                other => Err(format!("Unknown case '{}'; expected upper, lower, or title", other).into()),
+            },
+            TransformSpec::Replace { pattern, replacement } => Transform::replace(pattern, replacement.clone()),
+            TransformSpec::Date { from, to } => {
+                let from: Vec<&str> = from.iter().map(String::as_str).collect();
+                Transform::date(&from, to)
+            }
+        }
+    }
+}
+
+impl TemplateMigration {
+    /// Parse a TOML migration
+    pub fn from_toml(source: &str) -> Result<Self> {
+        let migration: TemplateMigration = toml::from_str(source)
+            .map_err(|e| format!("Invalid template migration: {}", e))?;
+        migration.validate()?;
+        Ok(migration)
+    }
+
+    /// Check every name and transform before anything is changed
+    pub fn validate(&self) -> Result<()> {
+        let mut sources = HashSet::new();
+        for template in &self.templates {
+            validation::validate_field_name(&template.from)?;
+            validation::validate_field_name(&template.to)?;
+            if !sources.insert(template.from.as_str()) {
+                return Err(format!("Template '{}' is mapped more than once", template.from).into());
+            }
+            for field in &template.fields {
+                validation::validate_field_name(&field.to)?;
+                if let Some(from) = &field.from {
+                    validation::validate_field_name(from)?;
+                }
+                if let Some(value) = &field.value {
+                    validation::validate_field_value(value)?;
+                }
+                for transform in &field.transforms {
+                    transform.to_transform()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The mapping for entries with template `template_name`, if any
+    pub fn mapping_for(&self, template_name: &str) -> Option<&TemplateMapping> {
+        self.templates.iter().find(|template| template.from == template_name)
+    }
+}
+
+impl TemplateMapping {
+    /// Field values for `Entry::update_metadata` once the entry has the new
+    /// template, read from its values under the old one
+    pub fn fields_payload(&self, metadata: &MetadataResult) -> Result<serde_json::Value> {
+        let old_values: BTreeMap<&str, Vec<String>> = metadata.value.iter()
+            .map(|field| {
+                let values = field.values.iter().map(|value| value.value.clone().unwrap_or_default()).collect();
+                (field.field_name.as_str(), values)
+            })
+            .collect();
+
+        let mut payload = serde_json::Map::new();
+        let mut read = HashSet::new();
+        for field in &self.fields {
+            let values = match &field.value {
+                Some(value) => vec![value.clone()],
+                None => {
+                    let from = field.from.as_deref().unwrap_or(&field.to);
+                    read.insert(from);
+                    match old_values.get(from) {
+                        Some(values) => values.clone(),
+                        None => continue,
+                    }
+                }
+            };
+            let transforms = field.transforms.iter().map(TransformSpec::to_transform).collect::<Result<Vec<_>>>()?;
+            let values = values.into_iter()
+                .map(|value| transforms.iter().fold(value, |value, transform| transform.apply(&value)));
+            payload.insert(field.to.clone(), values_json(values));
+        }
+
+        if self.carry_unmapped {
+            for (name, values) in &old_values {
+                if !read.contains(name) && !payload.contains_key(*name) {
+                    payload.insert(name.to_string(), values_json(values.iter().cloned()));
+                }
+            }
+        }
+
+        Ok(serde_json::Value::Object(payload))
+    }
+}
+
+fn values_json(values: impl Iterator<Item = String>) -> serde_json::Value {
+    let values: Vec<serde_json::Value> = values.enumerate()
+        .map(|(i, value)| json!({ "value": value, "position": i + 1 }))
+        .collect();
+    json!({ "values": values })
+}
+
+/// An entry that could not be migrated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationFailure {
+    pub entry_id: EntryId,
+    pub error: String,
+}
+
+/// Consolidated result of `migrate_templates`
+#[derive(Debug, Clone, Default)]
+pub struct MigrationReport {
+    /// Entries moved to their new template, or that would be on a dry run
+    pub migrated: Vec<EntryId>,
+    /// Entries whose template no mapping covers
+    pub skipped: Vec<EntryId>,
+    pub failed: Vec<MigrationFailure>,
+    /// Entries a pipeline budget left unstarted
+    pub deferred: Vec<EntryId>,
+    /// True if nothing was written back
+    pub dry_run: bool,
+    /// Why listing the scope stopped early, if it did; entries found before
+    /// then are still processed
+    pub scope_error: Option<String>,
+    pub stats: TransferStatsSnapshot,
+}
+
+impl MigrationReport {
+    /// True when the whole scope was read and every entry was processed
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.deferred.is_empty() && self.scope_error.is_none()
+    }
+}
+
+enum MigrationOutcome {
+    Migrated(EntryId),
+    Skipped(EntryId),
+    Failed(MigrationFailure),
+}
+
+/// Move every entry in `scope` whose template `migration` maps to its new
+/// template, carrying field values across as the mapping describes
+///
+/// Each entry gets the new template first, then its mapped field values.
+/// With `dry_run`, metadata is still read and every mapping evaluated, so a
+/// bad transform shows up as a failure, but nothing is written. Entries run
+/// through `pipeline` while later pages are read; an entry whose task panics
+/// is listed as failed, and entries not started within the pipeline's budget
+/// as deferred, untouched.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits, throttle, budget, and stats for the run
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `scope` - Folder or search whose entries are migrated
+/// * `migration` - Template and field mappings
+/// * `dry_run` - Evaluate the mappings without writing anything
+pub async fn migrate_templates(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    scope: Scope,
+    migration: &TemplateMigration,
+    dry_run: bool
+) -> MigrationReport {
+    let mut report = MigrationReport { dry_run, ..Default::default() };
+
+    let worker = {
+        let (api_server, auth) = (api_server.clone(), auth.clone());
+        let migration = Arc::new(migration.clone());
+        let stats = Arc::clone(pipeline.stats());
+        move |entry: Entry| {
+            let (api_server, auth, migration) = (api_server.clone(), auth.clone(), Arc::clone(&migration));
+            let stats = Arc::clone(&stats);
+            async move {
+                let entry_id = EntryId(entry.id);
+                let Some(mapping) = entry.template_name.as_deref().and_then(|name| migration.mapping_for(name)) else {
+                    return MigrationOutcome::Skipped(entry_id);
+                };
+                let started = Instant::now();
+                match migrate_entry(api_server, auth, entry_id, mapping, dry_run).await {
+                    Ok(outcome) => {
+                        if !matches!(outcome, MigrationOutcome::Failed(_)) {
+                            stats.record_file(0, started.elapsed());
+                        }
+                        outcome
+                    }
+                    Err(error) => MigrationOutcome::Failed(MigrationFailure { entry_id, error: error.to_string() }),
+                }
+            }
+        }
+    };
+
+    let mut deferred = Vec::new();
+    let scope_error = scope.run(pipeline, api_server, auth, worker, |outcome| match outcome {
+        Ok(MigrationOutcome::Migrated(entry_id)) => report.migrated.push(entry_id),
+        Ok(MigrationOutcome::Skipped(entry_id)) => report.skipped.push(entry_id),
+        Ok(MigrationOutcome::Failed(failure)) => {
+            pipeline.stats().record_failure();
+            report.failed.push(failure);
+        }
+        Err((entry_id, panic)) => report.failed.push(MigrationFailure { entry_id, error: panic.to_string() }),
+    }, |entry_id| deferred.push(entry_id)).await;
+
+    report.deferred = deferred;
+    report.scope_error = scope_error;
+    report.stats = pipeline.stats().snapshot();
+    report
+}
+
+async fn migrate_entry(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: EntryId,
+    mapping: &TemplateMapping,
+    dry_run: bool
+) -> Result<MigrationOutcome> {
+    let metadata = match Entry::get_metadata(api_server.clone(), auth.clone(), entry_id).await? {
+        MetadataResultOrError::Metadata(metadata) => metadata,
+        MetadataResultOrError::LFAPIError(error) => return Ok(MigrationOutcome::Failed(MigrationFailure { entry_id, error: error.to_string() })),
+    };
+    let payload = mapping.fields_payload(&metadata)?;
+    if dry_run {
+        return Ok(MigrationOutcome::Migrated(entry_id));
+    }
+
+    throttle::acquire_scoped().await;
+    if let EntryOrError::LFAPIError(error) = Entry::set_template(api_server.clone(), auth.clone(), entry_id, mapping.to.clone()).await? {
+        return Ok(MigrationOutcome::Failed(MigrationFailure { entry_id, error: error.to_string() }));
+    }
+
+    if payload.as_object().is_some_and(|fields| !fields.is_empty()) {
+        throttle::acquire_scoped().await;
+        if let MetadataResultOrError::LFAPIError(error) = Entry::update_metadata(api_server, auth, entry_id, payload).await? {
+            return Ok(MigrationOutcome::Failed(MigrationFailure { entry_id, error: error.to_string() }));
+        }
+    }
+
+    Ok(MigrationOutcome::Migrated(entry_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATION: &str = r#"
+        [[templates]]
+        from = "Legacy Invoice"
+        to = "Invoice"
+        carry_unmapped = true
+
+        [[templates.fields]]
+        from = "Vendor Name"
+        to = "Vendor"
+        transforms = ["trim", { case = "upper" }]
+
+        [[templates.fields]]
+        from = "Invoice Date"
+        to = "Invoice Date"
+        transforms = [{ date = { from = ["M/D/YYYY"], to = "YYYY-MM-DD" } }]
+
+        [[templates.fields]]
+        to = "Source"
+        value = "2019 archive"
+    "#;
+
+    #[test]
+    fn test_parse_migration() {
+        let migration = TemplateMigration::from_toml(MIGRATION).unwrap();
+        let mapping = migration.mapping_for("Legacy Invoice").unwrap();
+        assert_eq!(mapping.to, "Invoice");
+        assert_eq!(mapping.fields[0].transforms, vec![TransformSpec::Trim, TransformSpec::Case("upper".to_string())]);
+        assert!(migration.mapping_for("Invoice").is_none());
+
+        assert!(TemplateMigration::from_toml(&MIGRATION.replace("\"upper\"", "\"shouting\"")).is_err());
+        let twice = format!("{}\n[[templates]]\nfrom = \"Legacy Invoice\"\nto = \"Bill\"\n", MIGRATION);
+        assert!(TemplateMigration::from_toml(&twice).is_err());
+    }
+
+    #[test]
+    fn test_fields_payload() {
+        let migration = TemplateMigration::from_toml(MIGRATION).unwrap();
+        let metadata = MetadataResult::fixture(&[("Vendor Name", " acme corp "), ("Invoice Date", "3/7/2024"), ("Notes", "paid")]);
+
+        let payload = migration.templates[0].fields_payload(&metadata).unwrap();
+        assert_eq!(payload, json!({
+            "Vendor": { "values": [{ "value": "ACME CORP", "position": 1 }] },
+            "Invoice Date": { "values": [{ "value": "2024-03-07", "position": 1 }] },
+            "Source": { "values": [{ "value": "2019 archive", "position": 1 }] },
+            "Notes": { "values": [{ "value": "paid", "position": 1 }] }
+        }));
+    }
+}
@@ -0,0 +1,265 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::validation;
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Read;
+use std::sync::RwLock;
+
+/// Sent when no mapping matches a file's extension and its content is not
+/// recognised either
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Bytes from the start of the content that `sniff` looks at
+pub const SNIFF_LEN: usize = 512;
+
+/// Extensions recognised out of the box
+const BUILT_IN: [(&str, &str); 17] = [
+    ("pdf", "application/pdf"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("tiff", "image/tiff"),
+    ("tif", "image/tiff"),
+    ("doc", "application/msword"),
+    ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+    ("xls", "application/vnd.ms-excel"),
+    ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+    ("ppt", "application/vnd.ms-powerpoint"),
+    ("pptx", "application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+    ("txt", "text/plain"),
+    ("csv", "text/csv"),
+    ("xml", "application/xml"),
+    ("json", "application/json"),
+];
+
+/// Table consulted by every import that isn't given an explicit MIME type
+static GLOBAL_TABLE: Lazy<RwLock<MimeTable>> = Lazy::new(|| RwLock::new(MimeTable::new()));
+
+/// Extension to MIME type mappings used to label uploaded content
+///
+/// Starts with the built-in mappings; `insert` adds or replaces entries, e.g.
+/// for `.msg`, `.eml`, `.dwg`, or `.heic` files from document capture.
+/// Extensions are matched case-insensitively and without the leading dot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeTable {
+    mappings: BTreeMap<String, String>,
+}
+
+impl Default for MimeTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MimeTable {
+    /// A table holding the built-in mappings
+    pub fn new() -> Self {
+        let mappings = BUILT_IN.iter()
+            .map(|(extension, mime_type)| (extension.to_string(), mime_type.to_string()))
+            .collect();
+        MimeTable { mappings }
+    }
+
+    /// A table with no mappings at all
+    pub fn empty() -> Self {
+        MimeTable { mappings: BTreeMap::new() }
+    }
+
+    /// Map `extension` (with or without a leading dot) to `mime_type`
+    pub fn insert(&mut self, extension: &str, mime_type: &str) -> validation::Result<()> {
+        let mime_type = validation::validate_mime_type(mime_type)?;
+        self.mappings.insert(normalize_extension(extension), mime_type);
+        Ok(())
+    }
+
+    /// Builder-style `insert`
+    pub fn with(mut self, extension: &str, mime_type: &str) -> validation::Result<Self> {
+        self.insert(extension, mime_type)?;
+        Ok(self)
+    }
+
+    /// Drop the mapping for `extension`, returning its MIME type
+    pub fn remove(&mut self, extension: &str) -> Option<String> {
+        self.mappings.remove(&normalize_extension(extension))
+    }
+
+    /// MIME type mapped to `extension`, if any
+    pub fn get(&self, extension: &str) -> Option<&str> {
+        self.mappings.get(&normalize_extension(extension)).map(String::as_str)
+    }
+
+    /// MIME type for `file_name` by its extension, or `DEFAULT_MIME_TYPE`
+    pub fn detect(&self, file_name: &str) -> String {
+        self.by_extension(file_name).unwrap_or(DEFAULT_MIME_TYPE).to_string()
+    }
+
+    /// MIME type for `file_name` by its extension, falling back to `sniff`
+    /// on the start of `content` when the extension is missing or unknown
+    pub fn detect_with_content(&self, file_name: &str, content: &[u8]) -> String {
+        self.by_extension(file_name)
+            .or_else(|| sniff(content))
+            .unwrap_or(DEFAULT_MIME_TYPE)
+            .to_string()
+    }
+
+    fn by_extension(&self, file_name: &str) -> Option<&str> {
+        file_name.rsplit_once('.').and_then(|(_, extension)| self.get(extension))
+    }
+}
+
+/// MIME type recognised from the magic bytes that start `content`, for the
+/// formats document capture produces most
+///
+/// Office Open XML files are told apart by the part names near the start
+/// of the zip; older Office formats share one container and are not
+/// recognised.
+pub fn sniff(content: &[u8]) -> Option<&'static str> {
+    let head = &content[..content.len().min(SNIFF_LEN)];
+    let contains = |needle: &[u8]| head.windows(needle.len()).any(|window| window == needle);
+    let mime_type = match head {
+        [b'%', b'P', b'D', b'F', b'-', ..] => "application/pdf",
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'I', b'I', 0x2a, 0x00, ..] | [b'M', b'M', 0x00, 0x2a, ..] => "image/tiff",
+        [b'B', b'M', ..] if head.len() >= 14 => "image/bmp",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [b'{', b'\\', b'r', b't', b'f', ..] => "application/rtf",
+        [b'P', b'K', 0x03, 0x04, ..] if contains(b"word/") => {
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+        }
+        [b'P', b'K', 0x03, 0x04, ..] if contains(b"xl/") => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        [b'P', b'K', 0x03, 0x04, ..] if contains(b"ppt/") => {
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation"
+        }
+        [b'P', b'K', 0x03, 0x04, ..] => "application/zip",
+        _ if head.starts_with(b"<?xml") => "application/xml",
+        _ => return None,
+    };
+    Some(mime_type)
+}
+
+fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_lowercase()
+}
+
+/// Add or replace a mapping in the process-wide table used by imports
+///
+/// # Arguments
+/// * `extension` - File extension, with or without the leading dot
+/// * `mime_type` - MIME type to send for it, e.g. `application/vnd.ms-outlook`
+pub fn register_extension(extension: &str, mime_type: &str) -> validation::Result<()> {
+    GLOBAL_TABLE.write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(extension, mime_type)
+}
+
+/// Replace the process-wide table used by imports
+pub fn set_global_table(table: MimeTable) {
+    *GLOBAL_TABLE.write().unwrap_or_else(|e| e.into_inner()) = table;
+}
+
+/// A copy of the process-wide table
+pub fn global_table() -> MimeTable {
+    GLOBAL_TABLE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// MIME type for `file_name` from the process-wide table
+pub fn detect_mime_type(file_name: &str) -> String {
+    GLOBAL_TABLE.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .detect(file_name)
+}
+
+/// MIME type for `file_name` from the process-wide table, or sniffed from
+/// `content` when its extension is missing or unknown
+pub fn detect_mime_type_with_content(file_name: &str, content: &[u8]) -> String {
+    GLOBAL_TABLE.read()
+        .unwrap_or_else(|e| e.into_inner())
+        .detect_with_content(file_name, content)
+}
+
+/// `detect_mime_type_with_content` for a file on disk, reading no more than
+/// its first `SNIFF_LEN` bytes
+#[cfg(not(target_arch = "wasm32"))]
+pub fn detect_mime_type_of_file(file_name: &str, path: &std::path::Path) -> std::io::Result<String> {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    std::fs::File::open(path)?.take(SNIFF_LEN as u64).read_to_end(&mut head)?;
+    Ok(detect_mime_type_with_content(file_name, &head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_mappings() {
+        let table = MimeTable::new();
+        assert_eq!(table.detect("scan.PDF"), "application/pdf");
+        assert_eq!(table.detect("archive.tar.gz"), DEFAULT_MIME_TYPE);
+        assert_eq!(table.detect("README"), DEFAULT_MIME_TYPE);
+        assert_eq!(table.detect("message.msg"), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_custom_mappings() {
+        let mut table = MimeTable::new()
+            .with(".msg", "application/vnd.ms-outlook").unwrap()
+            .with("HEIC", "image/heic").unwrap();
+        table.insert("txt", "text/x-log").unwrap();
+
+        assert_eq!(table.detect("message.msg"), "application/vnd.ms-outlook");
+        assert_eq!(table.detect("IMG_0001.heic"), "image/heic");
+        assert_eq!(table.detect("notes.txt"), "text/x-log");
+
+        assert_eq!(table.remove(".TXT").as_deref(), Some("text/x-log"));
+        assert_eq!(table.detect("notes.txt"), DEFAULT_MIME_TYPE);
+        assert!(table.insert("dwg", "not a mime type").is_err());
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"%PDF-1.7\n%\xe2\xe3"), Some("application/pdf"));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0"), Some("image/png"));
+        assert_eq!(sniff(b"\xff\xd8\xff\xe0"), Some("image/jpeg"));
+        assert_eq!(sniff(b"II*\0\x08\0\0\0"), Some("image/tiff"));
+        assert_eq!(sniff(b"MM\0*\0\0\0\x08"), Some("image/tiff"));
+        assert_eq!(sniff(b"GIF89a"), Some("image/gif"));
+        assert_eq!(sniff(b"PK\x03\x04\x14\0\0\0word/document.xml"),
+            Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"));
+        assert_eq!(sniff(b"PK\x03\x04\x14\0\0\0xl/workbook.xml"),
+            Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"));
+        assert_eq!(sniff(b"PK\x03\x04\x14\0\0\0data.bin"), Some("application/zip"));
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?>"), Some("application/xml"));
+        assert_eq!(sniff(b"plain words"), None);
+        assert_eq!(sniff(b""), None);
+    }
+
+    #[test]
+    fn test_detect_with_content() {
+        let table = MimeTable::new();
+        // The extension wins when it is known
+        assert_eq!(table.detect_with_content("notes.txt", b"%PDF-1.7"), "text/plain");
+        assert_eq!(table.detect_with_content("SCAN0001", b"%PDF-1.7"), "application/pdf");
+        assert_eq!(table.detect_with_content("scan.dat", b"II*\0"), "image/tiff");
+        assert_eq!(table.detect_with_content("scan.dat", b"??"), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_empty_table() {
+        assert_eq!(MimeTable::empty().detect("scan.pdf"), DEFAULT_MIME_TYPE);
+    }
+
+    #[test]
+    fn test_register_extension() {
+        register_extension("dwg", "image/vnd.dwg").unwrap();
+        assert_eq!(detect_mime_type("plan.DWG"), "image/vnd.dwg");
+        assert_eq!(global_table().get("dwg"), Some("image/vnd.dwg"));
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::{self, Instant};
+use crate::laserfiche::{
+    Auth, Entry, EntryId, EntryOrError, FolderId, LFAPIError, LFApiServer, LFObject, Result,
+};
+use crate::export;
+use crate::naming::NamingPolicy;
+use crate::pipeline::Pipeline;
+use crate::stats::{TransferStats, TransferStatsSnapshot};
+use crate::throttle;
+use crate::validation;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// One move and/or rename in a bulk operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveRequest {
+    pub entry_id: EntryId,
+    pub target_parent: Option<FolderId>,
+    pub new_name: Option<String>,
+}
+
+/// What to do when a move collides with an existing name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave the entry in place and list it in the report
+    Report,
+    /// Retry with names rendered from `policy`, which may reference
+    /// `{Name}` (the intended name without its extension) and `{Attempt}`
+    /// (1, 2, ...); the extension is put back after the rendered name
+    Rename {
+        policy: NamingPolicy,
+        max_attempts: u32,
+    },
+}
+
+impl ConflictPolicy {
+    /// Retry as `"{Name} ({Attempt})"` up to `max_attempts` times, so
+    /// `report.pdf` becomes `report (1).pdf`
+    pub fn numbered(max_attempts: u32) -> Self {
+        ConflictPolicy::Rename {
+            policy: NamingPolicy::parse("{Name} ({Attempt})").expect("built-in naming policy is valid"),
+            max_attempts,
+        }
+    }
+}
+
+/// A move that could not be completed because the name was taken
+#[derive(Debug, Clone)]
+pub struct MoveConflict {
+    pub request: MoveRequest,
+    /// Last name tried
+    pub name: String,
+    /// Number of attempts made, including the first
+    pub attempts: u32,
+    pub error: LFAPIError,
+}
+
+/// A move that failed for a reason other than a name conflict
+#[derive(Debug, Clone)]
+pub struct MoveFailure {
+    pub request: MoveRequest,
+    pub error: String,
+}
+
+/// Consolidated result of `move_entries`
+#[derive(Debug, Clone, Default)]
+pub struct MoveReport {
+    pub moved: Vec<Entry>,
+    pub conflicts: Vec<MoveConflict>,
+    pub failed: Vec<MoveFailure>,
+    /// Requests not started because the pipeline's budget ran out
+    pub deferred: Vec<MoveRequest>,
+    /// Counters for the run; retries count conflict renames
+    pub stats: TransferStatsSnapshot,
+}
+
+impl MoveReport {
+    /// True when every request was moved
+    pub fn is_complete(&self) -> bool {
+        self.conflicts.is_empty() && self.failed.is_empty() && self.deferred.is_empty()
+    }
+}
+
+enum MoveOutcome {
+    Moved(Entry),
+    Conflict(MoveConflict),
+    Failed(MoveFailure),
+}
+
+/// Move and/or rename many entries, resolving name conflicts per `conflicts`
+///
+/// Requests run concurrently within the pipeline's limits. Every request
+/// ends up in exactly one of the report's lists; with a pipeline budget,
+/// those not started in time are listed as `deferred` for a later run.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits for the bulk operation
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `moves` - Entries to move or rename
+/// * `conflicts` - How name collisions are handled
+pub async fn move_entries(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    moves: Vec<MoveRequest>,
+    conflicts: ConflictPolicy
+) -> MoveReport {
+    let mut report = MoveReport::default();
+
+    let stats = Arc::clone(pipeline.stats());
+    let requests = moves.clone();
+    let mut deferred = Vec::new();
+    pipeline.run_within_budget(moves, move |request| {
+        let api_server = api_server.clone();
+        let auth = auth.clone();
+        let conflicts = conflicts.clone();
+        let stats = Arc::clone(&stats);
+        async move {
+            match move_one(api_server, auth, request.clone(), &conflicts, &stats).await {
+                Ok(outcome) => outcome,
+                Err(error) => MoveOutcome::Failed(MoveFailure { request, error: error.to_string() }),
+            }
+        }
+    }, |outcome| match outcome {
+        Ok(MoveOutcome::Moved(entry)) => report.moved.push(entry),
+        Ok(MoveOutcome::Conflict(conflict)) => {
+            pipeline.stats().record_failure();
+            report.conflicts.push(conflict);
+        }
+        Ok(MoveOutcome::Failed(failure)) => {
+            pipeline.stats().record_failure();
+            report.failed.push(failure);
+        }
+        Err(panic) => report.failed.push(MoveFailure {
+            request: requests[panic.index].clone(),
+            error: panic.to_string(),
+        }),
+    }, |_, request| deferred.push(request)).await;
+
+    report.deferred = deferred;
+    report.stats = pipeline.stats().snapshot();
+    report
+}
+
+async fn move_one(
+    api_server: LFApiServer,
+    auth: Auth,
+    request: MoveRequest,
+    conflicts: &ConflictPolicy,
+    stats: &TransferStats
+) -> Result<MoveOutcome> {
+    let started = Instant::now();
+    let first = Entry::patch(
+        api_server.clone(),
+        auth.clone(),
+        request.entry_id,
+        request.target_parent,
+        request.new_name.clone()
+    ).await?;
+
+    let error = match first {
+        LFObject::Entry(entry) => {
+            stats.record_file(0, started.elapsed());
+            return Ok(MoveOutcome::Moved(entry));
+        }
+        LFObject::LFAPIError(error) if error.is_conflict() => error,
+        LFObject::LFAPIError(error) => {
+            return Ok(MoveOutcome::Failed(MoveFailure { request, error: describe(&error) }));
+        }
+        _ => return Err("Unexpected response to entry patch".into()),
+    };
+
+    // The name that collided: the requested one, or the entry's current name
+    let base_name = match &request.new_name {
+        Some(name) => name.clone(),
+        None => match Entry::get(api_server.clone(), auth.clone(), request.entry_id).await? {
+            EntryOrError::Entry(entry) => entry.name,
+            EntryOrError::LFAPIError(error) => {
+                return Ok(MoveOutcome::Failed(MoveFailure { request, error: describe(&error) }));
+            }
+        },
+    };
+
+    let (policy, max_attempts) = match conflicts {
+        ConflictPolicy::Report => {
+            return Ok(MoveOutcome::Conflict(MoveConflict { request, name: base_name, attempts: 1, error }));
+        }
+        ConflictPolicy::Rename { policy, max_attempts } => (policy, *max_attempts),
+    };
+
+    let mut last_error = error;
+    let mut last_name = base_name.clone();
+    let mut attempts = 1;
+    for attempt in 1..=max_attempts {
+        last_name = retry_name(policy, &base_name, attempt)?;
+        attempts += 1;
+        stats.record_retry();
+
+        throttle::acquire_scoped().await;
+        let retried = Entry::patch(
+            api_server.clone(),
+            auth.clone(),
+            request.entry_id,
+            request.target_parent,
+            Some(last_name.clone())
+        ).await?;
+
+        match retried {
+            LFObject::Entry(entry) => {
+                stats.record_file(0, started.elapsed());
+                return Ok(MoveOutcome::Moved(entry));
+            }
+            LFObject::LFAPIError(error) if error.is_conflict() => last_error = error,
+            LFObject::LFAPIError(error) => {
+                return Ok(MoveOutcome::Failed(MoveFailure { request, error: describe(&error) }));
+            }
+            _ => return Err("Unexpected response to entry patch".into()),
+        }
+    }
+
+    Ok(MoveOutcome::Conflict(MoveConflict { request, name: last_name, attempts, error: last_error }))
+}
+
+/// Render the name for retry `attempt` of an entry originally named `name`,
+/// keeping its extension last
+fn retry_name(policy: &NamingPolicy, name: &str, attempt: u32) -> Result<String> {
+    let (stem, extension) = export::split_extension(name);
+    let mut fields = BTreeMap::new();
+    fields.insert("Name".to_string(), stem.to_string());
+    fields.insert("Attempt".to_string(), attempt.to_string());
+    let rendered = policy.render(&fields, clock::system_now())?;
+    Ok(validation::validate_file_name(&format!("{}{}", rendered, extension))?)
+}
+
+fn describe(error: &LFAPIError) -> String {
+    error.detail.clone()
+        .or_else(|| error.title.clone())
+        .unwrap_or_else(|| format!("API error (status {:?})", error.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbered_retry_names() {
+        let ConflictPolicy::Rename { policy, max_attempts } = ConflictPolicy::numbered(3) else {
+            panic!("numbered() should build a rename policy");
+        };
+
+        assert_eq!(max_attempts, 3);
+        assert_eq!(retry_name(&policy, "Invoice", 1).unwrap(), "Invoice (1)");
+        assert_eq!(retry_name(&policy, "Invoice", 2).unwrap(), "Invoice (2)");
+        assert_eq!(retry_name(&policy, "report.pdf", 2).unwrap(), "report (2).pdf");
+        assert_eq!(retry_name(&policy, ".profile", 1).unwrap(), ".profile (1)");
+    }
+
+    #[test]
+    fn test_describe_prefers_detail() {
+        let error = LFAPIError {
+            title: Some("Conflict".to_string()),
+            detail: Some("An entry named 'Invoice' already exists".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(describe(&error), "An entry named 'Invoice' already exists");
+
+        let error = LFAPIError { status: Some(500), ..Default::default() };
+        assert_eq!(describe(&error), "API error (status Some(500))");
+    }
+
+    #[test]
+    fn test_report_is_complete() {
+        let mut report = MoveReport::default();
+        assert!(report.is_complete());
+
+        report.deferred.push(MoveRequest { entry_id: EntryId(6), target_parent: None, new_name: None });
+        assert!(!report.is_complete());
+        report.deferred.clear();
+
+        report.failed.push(MoveFailure {
+            request: MoveRequest { entry_id: EntryId(5), target_parent: None, new_name: None },
+            error: "boom".to_string(),
+        });
+        assert!(!report.is_complete());
+    }
+}
@@ -0,0 +1,350 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::laserfiche::{
+    Auth, Entry, EntryId, LFApiServer, LFObject, MetadataResult, MetadataResultOrError, Result,
+};
+use crate::validation;
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Characters replaced in substituted values so they cannot split or break a name
+const RESERVED_NAME_CHARS: [char; 9] = ['\\', '/', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Renders entry names from field values and dates
+///
+/// Placeholders in braces are either field names (`{Invoice Number}`) or
+/// date patterns built from `yyyy`, `yy`, `MM`, `dd`, `HH`, `mm`, and `ss`
+/// separated by `-`, `_`, `.`, or spaces (`{yyyy-MM-dd}`). Dates are UTC.
+/// Use `{{` and `}}` for literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamingPolicy {
+    template: String,
+    parts: Vec<NamePart>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NamePart {
+    Literal(String),
+    Field(String),
+    Date(Vec<DateToken>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateToken {
+    Year,
+    ShortYear,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    Separator(char),
+}
+
+impl NamingPolicy {
+    /// Parse a template such as `"{Invoice Number}_{Vendor}_{yyyy-MM-dd}"`
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => {
+                                return Err(format!("Unclosed placeholder in naming policy: {}", template).into());
+                            }
+                            Some(c) => placeholder.push(c),
+                        }
+                    }
+
+                    let placeholder = placeholder.trim();
+                    if placeholder.is_empty() {
+                        return Err(format!("Empty placeholder in naming policy: {}", template).into());
+                    }
+
+                    if !literal.is_empty() {
+                        parts.push(NamePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(match Self::parse_date(placeholder)? {
+                        Some(tokens) => NamePart::Date(tokens),
+                        None => NamePart::Field(validation::validate_field_name(placeholder)?),
+                    });
+                }
+                '}' => {
+                    return Err(format!("Unmatched '}}' in naming policy: {}", template).into());
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(NamePart::Literal(literal));
+        }
+
+        Ok(NamingPolicy { template: template.to_string(), parts })
+    }
+
+    /// The template this policy was parsed from
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Field names referenced by the template, in order of appearance
+    pub fn field_names(&self) -> Vec<&str> {
+        self.parts
+            .iter()
+            .filter_map(|part| match part {
+                NamePart::Field(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Render a name from `fields`, using `at` for date placeholders
+    ///
+    /// Fails if a referenced field is missing or blank, or if the result is
+    /// not a valid entry name.
+    pub fn render(&self, fields: &BTreeMap<String, String>, at: SystemTime) -> Result<String> {
+        let mut name = String::new();
+
+        for part in &self.parts {
+            match part {
+                NamePart::Literal(text) => name.push_str(text),
+                NamePart::Field(field) => {
+                    let value = fields.get(field)
+                        .map(|value| value.trim())
+                        .filter(|value| !value.is_empty())
+                        .ok_or_else(|| format!("Naming policy field '{}' has no value", field))?;
+                    name.push_str(&Self::sanitize(value));
+                }
+                NamePart::Date(tokens) => name.push_str(&Self::format_date(tokens, at)),
+            }
+        }
+
+        Ok(validation::validate_file_name(name.trim())?)
+    }
+
+    /// Render a name from an entry's metadata; multi-value fields use their first value
+    pub fn render_metadata(&self, metadata: &MetadataResult, at: SystemTime) -> Result<String> {
+        let fields = metadata.value
+            .iter()
+            .filter_map(|field| {
+                field.values
+                    .iter()
+                    .find_map(|v| v.value.clone())
+                    .map(|value| (field.field_name.clone(), value))
+            })
+            .collect();
+        self.render(&fields, at)
+    }
+
+    pub(crate) fn sanitize(value: &str) -> String {
+        value
+            .chars()
+            .map(|c| if RESERVED_NAME_CHARS.contains(&c) || c.is_control() { '_' } else { c })
+            .collect::<String>()
+            .replace("..", "_")
+    }
+
+    /// Date tokens for `placeholder`, or `None` if it names a field
+    fn parse_date(placeholder: &str) -> Result<Option<Vec<DateToken>>> {
+        let is_date = placeholder.chars().any(|c| "yMdHms".contains(c))
+            && placeholder.chars().all(|c| "yMdHms-_. ".contains(c));
+        if !is_date {
+            return Ok(None);
+        }
+
+        let mut tokens = Vec::new();
+        let chars: Vec<char> = placeholder.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let run = chars[i..].iter().take_while(|&&next| next == c).count();
+            let token = match (c, run) {
+                ('y', 4) => DateToken::Year,
+                ('y', 2) => DateToken::ShortYear,
+                ('M', 2) => DateToken::Month,
+                ('d', 2) => DateToken::Day,
+                ('H', 2) => DateToken::Hour,
+                ('m', 2) => DateToken::Minute,
+                ('s', 2) => DateToken::Second,
+                ('-' | '_' | '.' | ' ', 1) => DateToken::Separator(c),
+                _ => return Err(format!("Invalid date pattern in naming policy: {{{}}}", placeholder).into()),
+            };
+            tokens.push(token);
+            i += run;
+        }
+
+        Ok(Some(tokens))
+    }
+
+    fn format_date(tokens: &[DateToken], at: SystemTime) -> String {
+        let secs = at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+        let time_of_day = secs % 86_400;
+
+        tokens
+            .iter()
+            .map(|token| match token {
+                DateToken::Year => format!("{:04}", year),
+                DateToken::ShortYear => format!("{:02}", year % 100),
+                DateToken::Month => format!("{:02}", month),
+                DateToken::Day => format!("{:02}", day),
+                DateToken::Hour => format!("{:02}", time_of_day / 3600),
+                DateToken::Minute => format!("{:02}", time_of_day % 3600 / 60),
+                DateToken::Second => format!("{:02}", time_of_day % 60),
+                DateToken::Separator(c) => c.to_string(),
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for NamingPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+/// Convert days since 1970-01-01 to a (year, month, day) Gregorian date
+pub(crate) fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Convert a Gregorian date to days since 1970-01-01
+pub(crate) fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = i64::from((month + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Rename an entry using a naming policy rendered from its own metadata
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Entry to rename
+/// * `policy` - Naming policy to render
+pub async fn rename_with_policy(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<EntryId>,
+    policy: &NamingPolicy
+) -> Result<LFObject> {
+    let entry_id = entry_id.into();
+
+    let metadata = match Entry::get_metadata(api_server.clone(), auth.clone(), entry_id).await? {
+        MetadataResultOrError::Metadata(metadata) => metadata,
+        MetadataResultOrError::LFAPIError(error) => return Ok(LFObject::LFAPIError(error)),
+    };
+
+    let new_name = policy.render_metadata(&metadata, clock::system_now())?;
+    Entry::patch(api_server, auth, entry_id, None, Some(new_name)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{MetadataResultFieldValue, MetadataResultValue};
+    use std::time::Duration;
+
+    // 2024-03-05 14:07:09 UTC
+    fn sample_time() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_709_647_629)
+    }
+
+    fn fields(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render_fields_and_date() {
+        let policy = NamingPolicy::parse("{Invoice Number}_{Vendor}_{yyyy-MM-dd}").unwrap();
+        let name = policy.render(&fields(&[("Invoice Number", "INV-001"), ("Vendor", "Acme")]), sample_time()).unwrap();
+
+        assert_eq!(name, "INV-001_Acme_2024-03-05");
+        assert_eq!(policy.field_names(), vec!["Invoice Number", "Vendor"]);
+    }
+
+    #[test]
+    fn test_render_time_tokens() {
+        let policy = NamingPolicy::parse("scan {yyMMdd HH.mm.ss}").unwrap();
+        assert_eq!(policy.render(&BTreeMap::new(), sample_time()).unwrap(), "scan 240305 14.07.09");
+    }
+
+    #[test]
+    fn test_render_sanitizes_values() {
+        let policy = NamingPolicy::parse("{Vendor} {{draft}}").unwrap();
+        let name = policy.render(&fields(&[("Vendor", "A/B: ../Co")]), sample_time()).unwrap();
+        assert_eq!(name, "A_B_ __Co {draft}");
+    }
+
+    #[test]
+    fn test_render_missing_field() {
+        let policy = NamingPolicy::parse("{Invoice Number}").unwrap();
+        assert!(policy.render(&BTreeMap::new(), sample_time()).is_err());
+        assert!(policy.render(&fields(&[("Invoice Number", "  ")]), sample_time()).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(NamingPolicy::parse("{Vendor").is_err());
+        assert!(NamingPolicy::parse("Vendor}").is_err());
+        assert!(NamingPolicy::parse("{}").is_err());
+        assert!(NamingPolicy::parse("{yyy}").is_err());
+    }
+
+    #[test]
+    fn test_render_metadata() {
+        let metadata = MetadataResult {
+            value: vec![MetadataResultValue {
+                field_name: "Vendor".to_string(),
+                values: vec![MetadataResultFieldValue { value: Some("Acme".to_string()), position: 1 }],
+                ..Default::default()
+            }],
+        };
+        let policy = NamingPolicy::parse("{Vendor}-{yyyy}").unwrap();
+        assert_eq!(policy.render_metadata(&metadata, sample_time()).unwrap(), "Acme-2024");
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        for days in [-1, 0, 59, 19_782, 2_932_896] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+}
@@ -0,0 +1,610 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+use crate::laserfiche::{
+    Auth, BoxStream, Entry, EntryId, FolderId, LFApiServer, MaybeSend, MetadataResult, MetadataResultOrError,
+    Result, SearchOptions,
+};
+use crate::pipeline::{Pipeline, WorkerPanic};
+use crate::stats::TransferStatsSnapshot;
+use crate::throttle;
+
+use futures_util::stream::StreamExt;
+use regex::Regex;
+use serde_json::json;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+/// Letter case applied by `Transform::Case`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    /// First letter of each word upper case, the rest lower case
+    Title,
+}
+
+/// Calendar date layout such as `MM/DD/YYYY`
+///
+/// `YYYY` is a four-digit year, `MM` and `DD` two-digit month and day, and
+/// `M` and `D` a month or day of one or two digits. Anything else must
+/// appear literally.
+#[derive(Debug, Clone)]
+pub struct DateFormat {
+    layout: String,
+    parser: Regex,
+    order: Vec<DatePart>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DatePart {
+    Year,
+    Month,
+    Day,
+}
+
+impl DateFormat {
+    pub fn parse(layout: &str) -> Result<Self> {
+        let mut pattern = String::from("^");
+        let mut order = Vec::new();
+        let mut rest = layout;
+        while !rest.is_empty() {
+            let (part, digits, len) = if rest.starts_with("YYYY") {
+                (DatePart::Year, r"(\d{4})", 4)
+            } else if rest.starts_with("MM") {
+                (DatePart::Month, r"(\d{2})", 2)
+            } else if rest.starts_with("DD") {
+                (DatePart::Day, r"(\d{2})", 2)
+            } else if rest.starts_with('M') {
+                (DatePart::Month, r"(\d{1,2})", 1)
+            } else if rest.starts_with('D') {
+                (DatePart::Day, r"(\d{1,2})", 1)
+            } else {
+                let literal = rest.chars().next().unwrap_or_default();
+                pattern.push_str(&regex::escape(&literal.to_string()));
+                rest = &rest[literal.len_utf8()..];
+                continue;
+            };
+            if order.contains(&part) {
+                return Err(format!("Date format '{}' repeats a year, month, or day", layout).into());
+            }
+            order.push(part);
+            pattern.push_str(digits);
+            rest = &rest[len..];
+        }
+        if order.len() != 3 {
+            return Err(format!("Date format '{}' needs a year, month, and day", layout).into());
+        }
+        pattern.push('$');
+
+        let parser = Regex::new(&pattern)
+            .map_err(|e| format!("Invalid date format '{}': {}", layout, e))?;
+        Ok(DateFormat { layout: layout.to_string(), parser, order })
+    }
+
+    /// Year, month, and day of `value`, if it is a valid date in this layout
+    fn read(&self, value: &str) -> Option<(u32, u32, u32)> {
+        let captures = self.parser.captures(value)?;
+        let (mut year, mut month, mut day) = (0, 0, 0);
+        for (i, part) in self.order.iter().enumerate() {
+            let number = captures.get(i + 1)?.as_str().parse().ok()?;
+            match part {
+                DatePart::Year => year = number,
+                DatePart::Month => month = number,
+                DatePart::Day => day = number,
+            }
+        }
+        ((1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day)).then_some((year, month, day))
+    }
+
+    fn write(&self, (year, month, day): (u32, u32, u32)) -> String {
+        let mut out = String::new();
+        let mut rest = self.layout.as_str();
+        while !rest.is_empty() {
+            let (text, len) = if rest.starts_with("YYYY") {
+                (format!("{:04}", year), 4)
+            } else if rest.starts_with("MM") {
+                (format!("{:02}", month), 2)
+            } else if rest.starts_with("DD") {
+                (format!("{:02}", day), 2)
+            } else if rest.starts_with('M') {
+                (month.to_string(), 1)
+            } else if rest.starts_with('D') {
+                (day.to_string(), 1)
+            } else {
+                let literal = rest.chars().next().unwrap_or_default();
+                (literal.to_string(), literal.len_utf8())
+            };
+            out.push_str(&text);
+            rest = &rest[len..];
+        }
+        out
+    }
+}
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// One change applied to a field value
+#[derive(Debug, Clone)]
+pub enum Transform {
+    /// Remove leading and trailing whitespace
+    Trim,
+    Case(Case),
+    /// Rewrite dates matching any of `from` in the `to` layout; other values
+    /// are left as they are
+    Date { from: Vec<DateFormat>, to: DateFormat },
+    /// Replace every match of `pattern`; `replacement` may use `$1` etc.
+    Replace { pattern: Regex, replacement: String },
+}
+
+impl Transform {
+    /// `Transform::Date` from layout strings
+    pub fn date(from: &[&str], to: &str) -> Result<Self> {
+        Ok(Transform::Date {
+            from: from.iter().map(|layout| DateFormat::parse(layout)).collect::<Result<_>>()?,
+            to: DateFormat::parse(to)?,
+        })
+    }
+
+    /// `Transform::Replace` from a regex pattern string
+    pub fn replace(pattern: &str, replacement: impl Into<String>) -> Result<Self> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e))?;
+        Ok(Transform::Replace { pattern, replacement: replacement.into() })
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Transform::Trim => value.trim().to_string(),
+            Transform::Case(Case::Upper) => value.to_uppercase(),
+            Transform::Case(Case::Lower) => value.to_lowercase(),
+            Transform::Case(Case::Title) => title_case(value),
+            Transform::Date { from, to } => from.iter()
+                .find_map(|format| format.read(value))
+                .map(|date| to.write(date))
+                .unwrap_or_else(|| value.to_string()),
+            Transform::Replace { pattern, replacement } => pattern.replace_all(value, replacement.as_str()).into_owned(),
+        }
+    }
+}
+
+fn title_case(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut word_start = true;
+    for c in value.chars() {
+        if word_start {
+            out.extend(c.to_uppercase());
+        } else {
+            out.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+    out
+}
+
+/// Transforms to run over field values, in the order they were added
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    rules: Vec<(Option<String>, Transform)>,
+}
+
+impl Normalizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply `transform` to the values of the field named `field_name`
+    pub fn field(mut self, field_name: impl Into<String>, transform: Transform) -> Self {
+        self.rules.push((Some(field_name.into()), transform));
+        self
+    }
+
+    /// Apply `transform` to the values of every field
+    pub fn all_fields(mut self, transform: Transform) -> Self {
+        self.rules.push((None, transform));
+        self
+    }
+
+    /// Run the transforms for `field_name` over `value`
+    pub fn normalize_value(&self, field_name: &str, value: &str) -> String {
+        self.rules.iter()
+            .filter(|(field, _)| field.as_deref().is_none_or(|field| field == field_name))
+            .fold(value.to_string(), |value, (_, transform)| transform.apply(&value))
+    }
+
+    /// Field values for `Entry::update_metadata` with every value
+    /// normalized, or `None` if nothing would change
+    ///
+    /// The payload lists every field, changed or not, since the update
+    /// replaces the entry's field values as a whole.
+    pub fn fields_payload(&self, metadata: &MetadataResult) -> Option<serde_json::Value> {
+        let mut changed = false;
+        let mut payload = serde_json::Map::new();
+
+        for field in &metadata.value {
+            let values: Vec<serde_json::Value> = field.values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| {
+                    let before = value.value.clone().unwrap_or_default();
+                    let after = self.normalize_value(&field.field_name, &before);
+                    changed |= after != before;
+                    json!({ "value": after, "position": i + 1 })
+                })
+                .collect();
+            payload.insert(field.field_name.clone(), json!({ "values": values }));
+        }
+
+        changed.then_some(serde_json::Value::Object(payload))
+    }
+
+    /// The values of `metadata` that normalizing would change
+    pub fn changes(&self, entry_id: EntryId, metadata: &MetadataResult) -> Vec<FieldChange> {
+        metadata.value.iter()
+            .flat_map(|field| field.values.iter().enumerate().map(move |(i, value)| (field, i + 1, value)))
+            .filter_map(|(field, position, value)| {
+                let before = value.value.clone().unwrap_or_default();
+                let after = self.normalize_value(&field.field_name, &before);
+                (after != before).then(|| FieldChange { entry_id, field_name: field.field_name.clone(), position, before, after })
+            })
+            .collect()
+    }
+}
+
+/// One field value a pass rewrote, or would rewrite on a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub entry_id: EntryId,
+    pub field_name: String,
+    /// Position of the value in a multi-value field, from 1
+    pub position: usize,
+    pub before: String,
+    pub after: String,
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}[{}]: {:?} -> {:?}", self.entry_id, self.field_name, self.position, self.before, self.after)
+    }
+}
+
+/// A regex search-and-replace over one field, for `replace_field_value`
+#[derive(Debug, Clone)]
+pub struct FieldReplace {
+    normalizer: Normalizer,
+    dry_run: bool,
+}
+
+impl FieldReplace {
+    /// Replace every match of `pattern` in values of `field_name`;
+    /// `replacement` may use `$1` etc.
+    pub fn new(field_name: impl Into<String>, pattern: &str, replacement: impl Into<String>) -> Result<Self> {
+        let transform = Transform::replace(pattern, replacement)?;
+        Ok(FieldReplace { normalizer: Normalizer::new().field(field_name, transform), dry_run: false })
+    }
+
+    /// Only report what would change, without writing anything back
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+}
+
+/// Entries a normalization pass runs over
+#[derive(Debug, Clone)]
+pub enum Scope {
+    /// Direct children of a folder
+    Folder(FolderId),
+    /// Every hit of a repository search
+    Search { query: String, options: SearchOptions },
+}
+
+impl Scope {
+    pub(crate) fn entries(&self, api_server: LFApiServer, auth: Auth) -> BoxStream<'static, Result<Entry>> {
+        match self.clone() {
+            Scope::Folder(folder_id) => Entry::list_all(api_server, auth, folder_id),
+            Scope::Search { query, options } => Entry::search_stream(api_server, auth, query, options),
+        }
+    }
+
+    /// Run `worker` on `pipeline` over every entry in the scope, reading
+    /// pages while earlier entries are processed
+    ///
+    /// `on_result` gets each worker's result, or the id of the entry whose
+    /// worker panicked; `on_deferred` gets the entries a pipeline budget left
+    /// unstarted. A failed page ends the listing, and why is returned; the
+    /// entries found before then are still processed.
+    pub(crate) async fn run<W, Fut, R, C, D>(
+        &self,
+        pipeline: &Pipeline,
+        api_server: LFApiServer,
+        auth: Auth,
+        worker: W,
+        mut on_result: C,
+        mut on_deferred: D
+    ) -> Option<String>
+    where
+        W: Fn(Entry) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(std::result::Result<R, (EntryId, WorkerPanic)>),
+        D: FnMut(EntryId),
+    {
+        let mut scope_error = None;
+        // Ids in listing order, to name the entry behind a `WorkerPanic`
+        let read = Mutex::new(Vec::new());
+        let entries = self.entries(api_server, auth)
+            .take_while(|entry| {
+                if let Err(error) = entry {
+                    scope_error = Some(error.to_string());
+                }
+                std::future::ready(entry.is_ok())
+            })
+            .filter_map(|entry| std::future::ready(entry.ok()))
+            .inspect(|entry| read.lock().unwrap_or_else(|e| e.into_inner()).push(EntryId(entry.id)));
+
+        pipeline.run_stream_within_budget(entries, worker, |result| {
+            on_result(result.map_err(|panic| {
+                let entry_id = read.lock().unwrap_or_else(|e| e.into_inner())[panic.index];
+                (entry_id, panic)
+            }))
+        }, |_, entry| on_deferred(EntryId(entry.id))).await;
+        scope_error
+    }
+}
+
+/// An entry whose field values could not be normalized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizeFailure {
+    pub entry_id: EntryId,
+    pub error: String,
+}
+
+/// Consolidated result of `normalize_scope` and `replace_field_value`
+#[derive(Debug, Clone, Default)]
+pub struct NormalizeReport {
+    /// Entries whose field values were rewritten, or would be on a dry run
+    pub normalized: Vec<EntryId>,
+    /// Every value rewritten, or that would be on a dry run
+    pub changes: Vec<FieldChange>,
+    /// True if nothing was written back
+    pub dry_run: bool,
+    /// Entries whose values were already normal
+    pub unchanged: Vec<EntryId>,
+    pub failed: Vec<NormalizeFailure>,
+    /// Entries a pipeline budget left unstarted
+    pub deferred: Vec<EntryId>,
+    /// Why listing the scope stopped early, if it did; entries found before
+    /// then are still processed
+    pub scope_error: Option<String>,
+    pub stats: TransferStatsSnapshot,
+}
+
+impl NormalizeReport {
+    /// True when the whole scope was read and every entry was processed
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.deferred.is_empty() && self.scope_error.is_none()
+    }
+
+    /// One line per changed value, e.g. `1234 Vendor[1]: "Acme Crop" -> "Acme Corp"`,
+    /// for review before running without `dry_run`
+    pub fn preview(&self) -> String {
+        self.changes.iter().map(|change| format!("{}\n", change)).collect()
+    }
+}
+
+enum NormalizeOutcome {
+    Normalized(EntryId, Vec<FieldChange>),
+    Unchanged(EntryId),
+    Failed(NormalizeFailure),
+}
+
+/// Rewrite the field values of every entry in `scope` with `normalizer`
+///
+/// Entries are read page by page while earlier ones are being updated, with
+/// at most the pipeline's effective limit in flight. Only entries whose
+/// values change are written back. An entry whose task panics is listed as
+/// failed, and entries not started within the pipeline's budget as deferred.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits, throttle, budget, and stats for the run
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `scope` - Folder or search whose entries are normalized
+/// * `normalizer` - Transforms to apply
+pub async fn normalize_scope(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    scope: Scope,
+    normalizer: &Normalizer
+) -> NormalizeReport {
+    run_scope(pipeline, api_server, auth, scope, normalizer, false).await
+}
+
+/// Search-and-replace the values of one field across `scope`, e.g. to fix a
+/// vendor name misspelled on thousands of invoices
+///
+/// With `FieldReplace::dry_run`, nothing is written and the report lists
+/// what would change; `NormalizeReport::preview` renders it for review.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits, throttle, budget, and stats for the run
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `scope` - Folder or search whose entries are searched
+/// * `replace` - Field, pattern, and replacement
+pub async fn replace_field_value(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    scope: Scope,
+    replace: &FieldReplace
+) -> NormalizeReport {
+    run_scope(pipeline, api_server, auth, scope, &replace.normalizer, replace.dry_run).await
+}
+
+async fn run_scope(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    scope: Scope,
+    normalizer: &Normalizer,
+    dry_run: bool
+) -> NormalizeReport {
+    let mut report = NormalizeReport { dry_run, ..Default::default() };
+
+    let worker = {
+        let (api_server, auth) = (api_server.clone(), auth.clone());
+        let normalizer = Arc::new(normalizer.clone());
+        let stats = Arc::clone(pipeline.stats());
+        move |entry: Entry| {
+            let (api_server, auth, normalizer) = (api_server.clone(), auth.clone(), Arc::clone(&normalizer));
+            let stats = Arc::clone(&stats);
+            async move {
+                let entry_id = EntryId(entry.id);
+                let started = Instant::now();
+                match normalize_entry(api_server, auth, entry_id, &normalizer, dry_run).await {
+                    Ok(outcome) => {
+                        if !matches!(outcome, NormalizeOutcome::Failed(_)) {
+                            stats.record_file(0, started.elapsed());
+                        }
+                        outcome
+                    }
+                    Err(error) => NormalizeOutcome::Failed(NormalizeFailure { entry_id, error: error.to_string() }),
+                }
+            }
+        }
+    };
+
+    let mut deferred = Vec::new();
+    let scope_error = scope.run(pipeline, api_server, auth, worker, |outcome| match outcome {
+        Ok(NormalizeOutcome::Normalized(entry_id, changes)) => {
+            report.normalized.push(entry_id);
+            report.changes.extend(changes);
+        }
+        Ok(NormalizeOutcome::Unchanged(entry_id)) => report.unchanged.push(entry_id),
+        Ok(NormalizeOutcome::Failed(failure)) => {
+            pipeline.stats().record_failure();
+            report.failed.push(failure);
+        }
+        Err((entry_id, panic)) => report.failed.push(NormalizeFailure { entry_id, error: panic.to_string() }),
+    }, |entry_id| deferred.push(entry_id)).await;
+
+    report.deferred = deferred;
+    report.scope_error = scope_error;
+    report.stats = pipeline.stats().snapshot();
+    report
+}
+
+async fn normalize_entry(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: EntryId,
+    normalizer: &Normalizer,
+    dry_run: bool
+) -> Result<NormalizeOutcome> {
+    let metadata = match Entry::get_metadata(api_server.clone(), auth.clone(), entry_id).await? {
+        MetadataResultOrError::Metadata(metadata) => metadata,
+        MetadataResultOrError::LFAPIError(error) => return Ok(NormalizeOutcome::Failed(NormalizeFailure { entry_id, error: error.to_string() })),
+    };
+
+    let changes = normalizer.changes(entry_id, &metadata);
+    if dry_run && !changes.is_empty() {
+        return Ok(NormalizeOutcome::Normalized(entry_id, changes));
+    }
+    let Some(payload) = normalizer.fields_payload(&metadata) else {
+        return Ok(NormalizeOutcome::Unchanged(entry_id));
+    };
+
+    throttle::acquire_scoped().await;
+    match Entry::update_metadata(api_server, auth, entry_id, payload).await? {
+        MetadataResultOrError::Metadata(_) => Ok(NormalizeOutcome::Normalized(entry_id, changes)),
+        MetadataResultOrError::LFAPIError(error) => Ok(NormalizeOutcome::Failed(NormalizeFailure { entry_id, error: error.to_string() })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{MetadataResultFieldValue, MetadataResultValue};
+
+    fn field(name: &str, values: &[&str]) -> MetadataResultValue {
+        MetadataResultValue {
+            field_name: name.to_string(),
+            values: values.iter().enumerate()
+                .map(|(i, value)| MetadataResultFieldValue { value: Some(value.to_string()), position: i as i64 + 1 })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(Transform::Trim.apply("  Acme Corp \n"), "Acme Corp");
+        assert_eq!(Transform::Case(Case::Upper).apply("inv-42"), "INV-42");
+        assert_eq!(Transform::Case(Case::Title).apply("aCME   corp"), "Acme   Corp");
+        assert_eq!(Transform::replace(r"\s+", " ").unwrap().apply("Acme \t  Corp"), "Acme Corp");
+        assert!(Transform::replace("(", "").is_err());
+    }
+
+    #[test]
+    fn test_date_transform() {
+        let transform = Transform::date(&["M/D/YYYY", "YYYY.MM.DD"], "YYYY-MM-DD").unwrap();
+        assert_eq!(transform.apply("3/7/2024"), "2024-03-07");
+        assert_eq!(transform.apply("12/31/1999"), "1999-12-31");
+        assert_eq!(transform.apply("2024.02.29"), "2024-02-29");
+        // Not a date, or not a real one, is left alone
+        assert_eq!(transform.apply("2023.02.29"), "2023.02.29");
+        assert_eq!(transform.apply("13/1/2024"), "13/1/2024");
+        assert_eq!(transform.apply("pending"), "pending");
+
+        assert!(DateFormat::parse("MM/YYYY").is_err());
+        assert!(DateFormat::parse("MM/DD/MM").is_err());
+    }
+
+    #[test]
+    fn test_fields_payload() {
+        let normalizer = Normalizer::new()
+            .all_fields(Transform::Trim)
+            .field("Vendor", Transform::Case(Case::Upper));
+        let metadata = MetadataResult { value: vec![field("Vendor", &[" acme "]), field("Notes", &["ok", "late "])] };
+
+        assert_eq!(normalizer.fields_payload(&metadata), Some(json!({
+            "Vendor": { "values": [{ "value": "ACME", "position": 1 }] },
+            "Notes": { "values": [{ "value": "ok", "position": 1 }, { "value": "late", "position": 2 }] }
+        })));
+
+        let clean = MetadataResult { value: vec![field("Vendor", &["ACME"]), field("Notes", &["ok"])] };
+        assert_eq!(normalizer.fields_payload(&clean), None);
+    }
+
+    #[test]
+    fn test_field_replace_changes() {
+        let replace = FieldReplace::new("Vendor", r"(?i)acme\s+crop", "Acme Corp").unwrap();
+        let metadata = MetadataResult { value: vec![field("Vendor", &["ACME Crop", "Acme Corp"]), field("Notes", &["acme crop"])] };
+
+        let changes = replace.normalizer.changes(EntryId(7), &metadata);
+        assert_eq!(changes, vec![FieldChange {
+            entry_id: EntryId(7),
+            field_name: "Vendor".to_string(),
+            position: 1,
+            before: "ACME Crop".to_string(),
+            after: "Acme Corp".to_string(),
+        }]);
+        assert!(FieldReplace::new("Vendor", "(", "").is_err());
+    }
+}
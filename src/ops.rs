@@ -0,0 +1,24 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Repository operations beyond single-entry calls: search, long-running
+//! tasks, watches, checks, checksums, reports, the offline outbox, and
+//! state stores
+//!
+//! Each module is also available at the crate root under its own name.
+
+pub use crate::inspect;
+pub use crate::integrity;
+pub use crate::link_definitions;
+pub use crate::outbox;
+pub use crate::pages;
+pub use crate::preflight;
+pub use crate::reports;
+pub use crate::repositories;
+pub use crate::search;
+pub use crate::server_info;
+pub use crate::state;
+pub use crate::tag_definitions;
+pub use crate::tasks;
+pub use crate::watch;
@@ -0,0 +1,387 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::laserfiche::{
+    Auth, Entry, EntryId, EntryOrError, Error, ErrorKind, FolderId, LFAPIError, LFApiServer,
+    LFObject, MetadataResultOrError, Result, TagsOrError,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::ImportResultOrError;
+use crate::retry;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::state::FileStore;
+use crate::state::{self, StateStore};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// A change to the repository, recorded to be made later
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    /// `Entry::update_metadata` with a `fields` payload
+    UpdateFields { entry_id: EntryId, fields: Value },
+    /// `Entry::set_tags`
+    SetTags { entry_id: EntryId, tag_ids: Vec<i64> },
+    /// `Entry::patch`: move, rename, or both
+    Move { entry_id: EntryId, parent_id: Option<FolderId>, new_name: Option<String> },
+    /// `Entry::delete`
+    Delete { entry_id: EntryId, comment: String },
+    /// `Entry::new_path`
+    CreateFolder { parent_id: FolderId, name: String, volume_name: String },
+    /// `Entry::import` of a local file, which must still exist at replay
+    #[cfg(not(target_arch = "wasm32"))]
+    Import { file_path: String, file_name: String, folder_id: FolderId },
+}
+
+impl Mutation {
+    /// The existing entry the mutation changes, if any
+    pub fn target(&self) -> Option<EntryId> {
+        match self {
+            Mutation::UpdateFields { entry_id, .. }
+            | Mutation::SetTags { entry_id, .. }
+            | Mutation::Move { entry_id, .. }
+            | Mutation::Delete { entry_id, .. } => Some(*entry_id),
+            Mutation::CreateFolder { .. } => None,
+            #[cfg(not(target_arch = "wasm32"))]
+            Mutation::Import { .. } => None,
+        }
+    }
+
+    /// Make the change, returning the server's refusal if it made none
+    async fn apply(&self, api_server: LFApiServer, auth: Auth) -> Result<Option<LFAPIError>> {
+        let refusal = match self.clone() {
+            Mutation::UpdateFields { entry_id, fields } => {
+                match Entry::update_metadata(api_server, auth, entry_id, serde_json::json!({ "fields": fields })).await? {
+                    MetadataResultOrError::LFAPIError(error) => Some(error),
+                    MetadataResultOrError::Metadata(_) => None,
+                }
+            }
+            Mutation::SetTags { entry_id, tag_ids } => match Entry::set_tags(api_server, auth, entry_id, tag_ids).await? {
+                TagsOrError::LFAPIError(error) => Some(error),
+                TagsOrError::Tags(_) => None,
+            },
+            Mutation::Move { entry_id, parent_id, new_name } => {
+                match Entry::patch(api_server, auth, entry_id, parent_id, new_name).await? {
+                    LFObject::LFAPIError(error) => Some(error),
+                    _ => None,
+                }
+            }
+            Mutation::Delete { entry_id, comment } => match Entry::delete(api_server, auth, entry_id, comment).await? {
+                LFObject::LFAPIError(error) => Some(error),
+                _ => None,
+            },
+            Mutation::CreateFolder { parent_id, name, volume_name } => {
+                match Entry::new_path(api_server, auth, name, volume_name, parent_id).await? {
+                    EntryOrError::LFAPIError(error) => Some(error),
+                    EntryOrError::Entry(_) => None,
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            Mutation::Import { file_path, file_name, folder_id } => {
+                match Entry::import(api_server, auth, file_path, file_name, folder_id).await? {
+                    ImportResultOrError::LFAPIError(error) => Some(error),
+                    ImportResultOrError::ImportResult(_) => None,
+                }
+            }
+        };
+        Ok(refusal)
+    }
+}
+
+/// One queued mutation, as stored on one line of the outbox file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OutboxItem {
+    /// Position in the queue; items replay in this order
+    pub seq: u64,
+    /// Seconds since the Unix epoch when the item was queued
+    pub queued_at: u64,
+    pub mutation: Mutation,
+    /// The target's `last_modified_time` when the change was made; replay
+    /// reports a conflict instead of applying it if the entry changed since
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_modified: Option<String>,
+}
+
+/// An item replay took out of the queue without applying
+#[derive(Debug, Clone)]
+pub enum ReplayFailure {
+    /// The target changed on the server after the item was queued
+    Conflict { item: OutboxItem, current: Box<Entry> },
+    /// The server refused the change, e.g. because the target was deleted
+    /// or a folder of that name exists
+    Rejected { item: OutboxItem, error: LFAPIError },
+}
+
+/// Outcome of `Outbox::replay`
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// Items applied and removed from the queue
+    pub applied: Vec<OutboxItem>,
+    /// Items removed from the queue without being applied, for the
+    /// application to resolve
+    pub failed: Vec<ReplayFailure>,
+    /// Replay stopped because the server could not be reached; the
+    /// remaining items stay queued
+    pub interrupted: bool,
+}
+
+/// Whether `error` means the server could not be reached, so the call is
+/// worth queueing in an `Outbox` rather than reporting
+pub fn is_unreachable(error: &Error) -> bool {
+    matches!(error.kind(), ErrorKind::HttpRequest(error) if retry::is_connect_error(error) || error.is_timeout())
+}
+
+/// Mutations queued while the server is unreachable, for offline-first
+/// applications
+///
+/// The queue is JSON Lines, one `OutboxItem` per line, appended to the
+/// store on every `enqueue` so queued work survives a restart. `replay`
+/// applies items in the order they were queued once the server is back.
+#[derive(Debug)]
+pub struct Outbox {
+    store: Arc<dyn StateStore>,
+    key: String,
+    items: VecDeque<OutboxItem>,
+    next_seq: u64,
+}
+
+impl Outbox {
+    /// Open the outbox kept in the file at `path`, or an empty one if there
+    /// is no file yet
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let key = path.file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| format!("'{}' is not a file path", path.display()))?;
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        Self::open_in(Arc::new(FileStore::new(dir)), key)
+    }
+
+    /// Open the outbox kept under `key` in `store`, or an empty one if
+    /// nothing is stored there yet
+    ///
+    /// A last line that does not parse is taken to be a write cut short
+    /// by a crash and dropped; an unparsable line anywhere else fails. The
+    /// stored queue is then rewritten without it, so later items are not
+    /// appended onto the torn line.
+    pub fn open_in(store: Arc<dyn StateStore>, key: impl Into<String>) -> Result<Self> {
+        let key = key.into();
+        let contents = state::load_string(store.as_ref(), &key)?.unwrap_or_default();
+        let (items, dropped) = parse_items(&contents)?;
+        let next_seq = items.back().map_or(1, |item| item.seq + 1);
+        let outbox = Outbox { store, key, items, next_seq };
+        if dropped || !(contents.is_empty() || contents.ends_with('\n')) {
+            outbox.save()?;
+        }
+        Ok(outbox)
+    }
+
+    /// The key the queue is stored under
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Queued items, oldest first
+    pub fn pending(&self) -> impl Iterator<Item = &OutboxItem> {
+        self.items.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Queue `mutation`, returning its sequence number
+    pub fn enqueue(&mut self, mutation: Mutation) -> Result<u64> {
+        self.push(mutation, None)
+    }
+
+    /// Queue `mutation` made against the target as last modified at
+    /// `base_modified`, so replay detects changes made on the server since
+    pub fn enqueue_checked(&mut self, mutation: Mutation, base_modified: impl Into<String>) -> Result<u64> {
+        if mutation.target().is_none() {
+            return Err("Only mutations of an existing entry can be checked for conflicts".into());
+        }
+        self.push(mutation, Some(base_modified.into()))
+    }
+
+    /// Apply queued items in order, removing each from the queue once the
+    /// server has applied or refused it
+    ///
+    /// Replay stops at the first item the server cannot be reached for,
+    /// leaving it and later items queued. Other errors stop replay and are
+    /// returned, leaving the failing item queued.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn replay(&mut self, api_server: LFApiServer, auth: Auth) -> Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        while let Some(item) = self.items.front().cloned() {
+            let failure = match replay_item(&item, api_server.clone(), auth.clone()).await {
+                Ok(failure) => failure,
+                Err(error) if is_unreachable(&error) => {
+                    report.interrupted = true;
+                    break;
+                }
+                Err(error) => return Err(error),
+            };
+
+            self.items.pop_front();
+            self.save()?;
+            match failure {
+                None => report.applied.push(item),
+                Some(failure) => report.failed.push(failure),
+            }
+        }
+        Ok(report)
+    }
+
+    fn push(&mut self, mutation: Mutation, base_modified: Option<String>) -> Result<u64> {
+        let item = OutboxItem {
+            seq: self.next_seq,
+            queued_at: clock::system_now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs()),
+            mutation,
+            base_modified,
+        };
+
+        self.store.append(&self.key, item_line(&item)?.as_bytes())?;
+
+        self.next_seq += 1;
+        self.items.push_back(item);
+        Ok(self.next_seq - 1)
+    }
+
+    /// Store the items still queued, replacing the stored queue whole so a
+    /// crash leaves either the old queue or the new one
+    fn save(&self) -> Result<()> {
+        let mut contents = String::new();
+        for item in &self.items {
+            contents.push_str(&item_line(item)?);
+        }
+        self.store.save(&self.key, contents.as_bytes())
+    }
+}
+
+/// Apply `item` unless its target changed since it was queued
+async fn replay_item(item: &OutboxItem, api_server: LFApiServer, auth: Auth) -> Result<Option<ReplayFailure>> {
+    if let (Some(entry_id), Some(base_modified)) = (item.mutation.target(), &item.base_modified) {
+        match Entry::get(api_server.clone(), auth.clone(), entry_id).await? {
+            EntryOrError::Entry(current) if current.last_modified_time != *base_modified => {
+                return Ok(Some(ReplayFailure::Conflict { item: item.clone(), current: Box::new(current) }));
+            }
+            EntryOrError::Entry(_) => {}
+            EntryOrError::LFAPIError(error) => return Ok(Some(ReplayFailure::Rejected { item: item.clone(), error })),
+        }
+    }
+
+    let refusal = item.mutation.apply(api_server, auth).await?;
+    Ok(refusal.map(|error| ReplayFailure::Rejected { item: item.clone(), error }))
+}
+
+fn item_line(item: &OutboxItem) -> Result<String> {
+    let json = serde_json::to_string(item).map_err(|e| format!("Failed to serialize outbox item: {}", e))?;
+    Ok(json + "\n")
+}
+
+/// Parse stored items, and whether an incomplete last line was dropped
+fn parse_items(contents: &str) -> Result<(VecDeque<OutboxItem>, bool)> {
+    let lines: Vec<&str> = contents.lines().filter(|line| !line.trim().is_empty()).collect();
+    let mut items = VecDeque::with_capacity(lines.len());
+    let mut dropped = false;
+    for (i, line) in lines.iter().enumerate() {
+        match serde_json::from_str(line) {
+            Ok(item) => items.push_back(item),
+            Err(_) if i + 1 == lines.len() => {
+                log::warn!("Dropping incomplete last outbox line");
+                dropped = true;
+            }
+            Err(e) => return Err(format!("Failed to parse outbox line {}: {}", i + 1, e).into()),
+        }
+    }
+    Ok((items, dropped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_outbox(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("lf_outbox_{}_{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_outbox_persists_across_open() {
+        let path = temp_outbox("persist");
+        let mut outbox = Outbox::open(&path).unwrap();
+        assert!(outbox.is_empty());
+
+        let rename = Mutation::Move { entry_id: EntryId(42), parent_id: None, new_name: Some("Site 7".to_string()) };
+        assert_eq!(outbox.enqueue(rename.clone()).unwrap(), 1);
+        let tag = Mutation::SetTags { entry_id: EntryId(42), tag_ids: vec![3] };
+        assert_eq!(outbox.enqueue_checked(tag, "2024-03-01T10:00:00Z").unwrap(), 2);
+        let folder = Mutation::CreateFolder { parent_id: FolderId(1), name: "Site 7".to_string(), volume_name: "DEFAULT".to_string() };
+        assert!(outbox.enqueue_checked(folder, "2024-03-01T10:00:00Z").is_err());
+
+        let reopened = Outbox::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(reopened.len(), 2);
+        let items: Vec<&OutboxItem> = reopened.pending().collect();
+        assert_eq!(items[0].mutation, rename);
+        assert_eq!(items[1].base_modified.as_deref(), Some("2024-03-01T10:00:00Z"));
+        assert_eq!(reopened.next_seq, 3);
+    }
+
+    #[test]
+    fn test_outbox_in_memory_store() {
+        let store: Arc<dyn StateStore> = Arc::new(state::MemoryStore::new());
+        let mut outbox = Outbox::open_in(Arc::clone(&store), "outbox.jsonl").unwrap();
+        outbox.enqueue(Mutation::Delete { entry_id: EntryId(42), comment: "dup".to_string() }).unwrap();
+
+        let reopened = Outbox::open_in(store, "outbox.jsonl").unwrap();
+        assert_eq!(reopened.len(), 1);
+        assert_eq!(reopened.key(), "outbox.jsonl");
+    }
+
+    #[test]
+    fn test_parse_items_drops_torn_last_line() {
+        let line = r#"{"seq":1,"queued_at":0,"mutation":{"op":"delete","entry_id":42,"comment":"dup"}}"#;
+        let (items, dropped) = parse_items(&format!("{}\n{{\"seq\":2,\"queu", line)).unwrap();
+        assert!(dropped);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].mutation, Mutation::Delete { entry_id: EntryId(42), comment: "dup".to_string() });
+
+        assert!(parse_items(&format!("not json\n{}\n", line)).is_err());
+    }
+
+    #[test]
+    fn test_enqueue_after_torn_last_line() {
+        let line = r#"{"seq":1,"queued_at":0,"mutation":{"op":"delete","entry_id":42,"comment":"dup"}}"#;
+        for torn in [format!("{}\n{{\"seq\":2,\"queu", line), line.to_string()] {
+            let store: Arc<dyn StateStore> = Arc::new(state::MemoryStore::new());
+            store.save("outbox.jsonl", torn.as_bytes()).unwrap();
+
+            let mut outbox = Outbox::open_in(Arc::clone(&store), "outbox.jsonl").unwrap();
+            assert_eq!(outbox.enqueue(Mutation::Delete { entry_id: EntryId(43), comment: "dup".to_string() }).unwrap(), 2);
+
+            let reopened = Outbox::open_in(store, "outbox.jsonl").unwrap();
+            let ids: Vec<Option<EntryId>> = reopened.pending().map(|item| item.mutation.target()).collect();
+            assert_eq!(ids, vec![Some(EntryId(42)), Some(EntryId(43))]);
+        }
+    }
+}
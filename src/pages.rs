@@ -0,0 +1,313 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{
+    http_client, impl_into_result, Auth, BitsOrError, DocumentId, LFAPIError, LFApiServer, NextLink, RequestUrl, Result,
+};
+use crate::retry::RetryingSend;
+use crate::validation;
+
+use serde::{Deserialize, Serialize};
+
+/// One page of a scanned or imaged document
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Page {
+    /// Position in the document, from 1
+    pub page_number: u32,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub has_image: bool,
+    pub has_text: bool,
+    pub has_annotations: bool,
+}
+
+/// One page of a document's page list
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentPages {
+    pub value: Vec<Page>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.count", default, skip_serializing_if = "Option::is_none")]
+    pub odata_count: Option<i64>,
+}
+
+impl DocumentPages {
+    /// Cursor for the next page, if the server returned one
+    pub fn next_link(&self) -> Option<NextLink> {
+        self.odata_next_link.clone().map(NextLink)
+    }
+}
+
+pub enum PagesOrError {
+    Pages(DocumentPages),
+    LFAPIError(LFAPIError),
+}
+
+/// Outcome of `Pages::delete`: the number of pages removed
+pub enum PagesDeletedOrError {
+    Deleted(usize),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(
+    PagesOrError::Pages => DocumentPages,
+    PagesDeletedOrError::Deleted => usize,
+);
+
+/// Image format `Pages::export_image` renders a page as
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PageImageFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Tiff,
+    Gif,
+}
+
+impl PageImageFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PageImageFormat::Png => "png",
+            PageImageFormat::Jpeg => "jpeg",
+            PageImageFormat::Tiff => "tiff",
+            PageImageFormat::Gif => "gif",
+        }
+    }
+}
+
+/// Size and format of an image `Pages::export_image` renders
+///
+/// Unset dimensions keep the page's own size; setting one scales the other
+/// to keep the aspect ratio.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PageImageOptions {
+    width: Option<u32>,
+    height: Option<u32>,
+    format: PageImageFormat,
+}
+
+impl PageImageOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Render at most `width` pixels wide
+    pub fn width(mut self, width: u32) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Render at most `height` pixels high
+    pub fn height(mut self, height: u32) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// Render as `format` instead of PNG
+    pub fn format(mut self, format: PageImageFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+/// The pages of a document, for checking and pruning scans without
+/// exporting the whole electronic document
+pub struct Pages;
+
+impl Pages {
+    /// Fetch the first page of a document's page list
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `document_id` - Document whose pages are listed
+    pub async fn list(api_server: LFApiServer, auth: Auth, document_id: impl Into<DocumentId>) -> Result<PagesOrError> {
+        let url = Self::pages_url(&api_server, document_id.into())?.build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Fetch the part of a page list a previous part links to
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `next_link` - From `DocumentPages::next_link`
+    pub async fn list_next(api_server: LFApiServer, auth: Auth, next_link: NextLink) -> Result<PagesOrError> {
+        let url = RequestUrl::next_link(&api_server, &next_link)?.build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Every page of a document, following next links
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `document_id` - Document whose pages are listed
+    pub async fn list_all(api_server: LFApiServer, auth: Auth, document_id: impl Into<DocumentId>) -> Result<PagesOrError> {
+        let mut all = match Self::list(api_server.clone(), auth.clone(), document_id).await? {
+            PagesOrError::Pages(pages) => pages,
+            error => return Ok(error),
+        };
+        while let Some(next_link) = all.next_link() {
+            match Self::list_next(api_server.clone(), auth.clone(), next_link).await? {
+                PagesOrError::Pages(pages) => {
+                    all.value.extend(pages.value);
+                    all.odata_next_link = pages.odata_next_link;
+                }
+                error => return Ok(error),
+            }
+        }
+        Ok(PagesOrError::Pages(all))
+    }
+
+    /// Render one page as an image
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `document_id` - Document the page belongs to
+    /// * `page_number` - Page to render, from 1
+    /// * `options` - Image size and format
+    pub async fn export_image(
+        api_server: LFApiServer,
+        auth: Auth,
+        document_id: impl Into<DocumentId>,
+        page_number: u32,
+        options: &PageImageOptions
+    ) -> Result<BitsOrError> {
+        if page_number == 0 {
+            return Err("Page numbers start at 1".into());
+        }
+        let url = Self::pages_url(&api_server, document_id.into())?
+            .segment(&page_number.to_string())
+            .segment("image")
+            .query_opt("width", options.width)
+            .query_opt("height", options.height)
+            .query("format", options.format.as_str())
+            .build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(BitsOrError::LFAPIError(error));
+        }
+
+        Ok(BitsOrError::Bits(response.bytes().await?.to_vec()))
+    }
+
+    /// Delete pages from a document, keeping the rest in order
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `document_id` - Document the pages belong to
+    /// * `page_numbers` - Pages to delete, from 1, in any order
+    pub async fn delete(
+        api_server: LFApiServer,
+        auth: Auth,
+        document_id: impl Into<DocumentId>,
+        page_numbers: &[u32]
+    ) -> Result<PagesDeletedOrError> {
+        let (page_range, count) = page_range(page_numbers)?;
+        let url = Self::pages_url(&api_server, document_id.into())?
+            .query("pageRange", page_range)
+            .build()?;
+
+        let response = http_client()
+            .delete(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(PagesDeletedOrError::LFAPIError(error));
+        }
+
+        Ok(PagesDeletedOrError::Deleted(count))
+    }
+
+    fn pages_url(api_server: &LFApiServer, document_id: DocumentId) -> validation::Result<RequestUrl> {
+        let document_id = validation::validate_entry_id(document_id.as_i64())?;
+        Ok(RequestUrl::entry(api_server, document_id)?
+            .segment("Laserfiche.Repository.Document")
+            .segment("pages"))
+    }
+
+    async fn fetch_page(url: String, auth: &Auth) -> Result<PagesOrError> {
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(PagesOrError::LFAPIError(error));
+        }
+
+        let pages = response.json::<DocumentPages>().await?;
+        Ok(PagesOrError::Pages(pages))
+    }
+}
+
+/// The `pageRange` parameter for `page_numbers`, e.g. `1,3-5`, and how many
+/// distinct pages it covers
+fn page_range(page_numbers: &[u32]) -> Result<(String, usize)> {
+    if page_numbers.is_empty() {
+        return Err("No pages given to delete".into());
+    }
+    if page_numbers.contains(&0) {
+        return Err("Page numbers start at 1".into());
+    }
+
+    let mut pages = page_numbers.to_vec();
+    pages.sort_unstable();
+    pages.dedup();
+
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for page in &pages {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == *page => *end = *page,
+            _ => ranges.push((*page, *page)),
+        }
+    }
+    let range = ranges.iter()
+        .map(|(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+        .collect::<Vec<_>>()
+        .join(",");
+    Ok((range, pages.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_range() {
+        assert_eq!(page_range(&[5, 1, 3, 4, 4]).unwrap(), ("1,3-5".to_string(), 4));
+        assert_eq!(page_range(&[2]).unwrap(), ("2".to_string(), 1));
+        assert!(page_range(&[]).is_err());
+        assert!(page_range(&[0, 1]).is_err());
+    }
+
+    #[test]
+    fn test_document_pages_parsing() {
+        let pages: DocumentPages = serde_json::from_str(r#"{
+            "value": [{"pageNumber": 1, "width": 2550, "height": 3300, "hasImage": true, "hasText": true}],
+            "@odata.nextLink": "https://lf.example.com/next"
+        }"#).unwrap();
+        assert_eq!(pages.value[0].page_number, 1);
+        assert_eq!(pages.value[0].width, Some(2550));
+        assert!(!pages.value[0].has_annotations);
+        assert!(pages.next_link().is_some());
+    }
+}
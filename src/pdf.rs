@@ -0,0 +1,365 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::Result;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{Auth, Entry, FolderId, ImportResultOrError, LFApiServer};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::validation;
+
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+/// Page attributes a page may inherit from its ancestors in the page tree
+const INHERITABLE: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Content operators that put marks on a page; a page using none is blank
+const PAINTING_OPERATORS: [&str; 17] = [
+    "Tj", "TJ", "'", "\"", "Do", "sh", "BI", "S", "s", "f", "F", "f*", "B", "B*", "b", "b*", "EI",
+];
+
+/// Where to cut a scanned batch into separate documents
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitMode {
+    /// 1-based page numbers that start a new document; page 1 always does
+    AtPages(Vec<u32>),
+    /// Cut at blank separator sheets, which are dropped from the output
+    AtBlankPages,
+}
+
+fn pdf_error(error: lopdf::Error) -> crate::laserfiche::Error {
+    format!("PDF error: {}", error).into()
+}
+
+/// Split `pdf` into one PDF per document
+///
+/// Documents that would contain no pages (e.g. consecutive separator sheets)
+/// are skipped.
+pub fn split(pdf: &[u8], mode: &SplitMode) -> Result<Vec<Vec<u8>>> {
+    let document = Document::load_mem(pdf).map_err(pdf_error)?;
+    let pages = document.get_pages();
+
+    let groups: Vec<Vec<u32>> = match mode {
+        SplitMode::AtPages(starts) => {
+            let mut groups: Vec<Vec<u32>> = Vec::new();
+            for &number in pages.keys() {
+                if groups.is_empty() || starts.contains(&number) {
+                    groups.push(Vec::new());
+                }
+                if let Some(group) = groups.last_mut() {
+                    group.push(number);
+                }
+            }
+            groups
+        }
+        SplitMode::AtBlankPages => {
+            let mut groups = vec![Vec::new()];
+            for (&number, &page_id) in &pages {
+                if is_blank_page(&document, page_id) {
+                    groups.push(Vec::new());
+                } else if let Some(group) = groups.last_mut() {
+                    group.push(number);
+                }
+            }
+            groups.retain(|group| !group.is_empty());
+            groups
+        }
+    };
+
+    groups.iter()
+        .map(|numbers| {
+            let page_ids = numbers.iter().filter_map(|number| pages.get(number).copied()).collect();
+            assemble(vec![(document.clone(), page_ids)])
+        })
+        .collect()
+}
+
+/// Concatenate `pdfs` into one PDF, in order
+pub fn merge(pdfs: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let mut sources = Vec::with_capacity(pdfs.len());
+    for pdf in pdfs {
+        let document = Document::load_mem(pdf).map_err(pdf_error)?;
+        let page_ids = document.get_pages().into_values().collect();
+        sources.push((document, page_ids));
+    }
+    assemble(sources)
+}
+
+/// Number of pages in `pdf`
+pub fn page_count(pdf: &[u8]) -> Result<usize> {
+    Ok(Document::load_mem(pdf).map_err(pdf_error)?.get_pages().len())
+}
+
+/// Whether a page's content streams paint nothing
+fn is_blank_page(document: &Document, page_id: ObjectId) -> bool {
+    let content = match document.get_page_content(page_id) {
+        Ok(content) => content,
+        Err(_) => return false,
+    };
+    match Content::decode(&content) {
+        Ok(content) => !content.operations.iter()
+            .any(|operation| PAINTING_OPERATORS.contains(&operation.operator.as_str())),
+        // Unreadable content is kept rather than treated as a separator
+        Err(_) => false,
+    }
+}
+
+/// Copy attributes inherited from the page tree onto each page, so pages
+/// keep their resources once moved under a new tree
+fn flatten_inherited(document: &mut Document) {
+    for page_id in document.get_pages().into_values() {
+        let mut inherited = Vec::new();
+        let mut parent = document.get_dictionary(page_id)
+            .and_then(|page| page.get(b"Parent"))
+            .and_then(Object::as_reference)
+            .ok();
+        // Bounded walk in case of a cyclic tree
+        for _ in 0..64 {
+            let Some(node) = parent.and_then(|id| document.get_dictionary(id).ok()) else { break };
+            for key in INHERITABLE {
+                if let Ok(value) = node.get(key) {
+                    if !inherited.iter().any(|(k, _): &(&[u8], Object)| *k == key) {
+                        inherited.push((key, value.clone()));
+                    }
+                }
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+
+        if let Ok(page) = document.get_dictionary_mut(page_id) {
+            for (key, value) in inherited {
+                if !page.has(key) {
+                    page.set(key.to_vec(), value);
+                }
+            }
+        }
+    }
+}
+
+/// Build a PDF holding the listed pages of each source document, in order
+fn assemble(sources: Vec<(Document, Vec<ObjectId>)>) -> Result<Vec<u8>> {
+    let mut output = Document::with_version("1.5");
+    let mut kids = Vec::new();
+
+    for (mut source, page_ids) in sources {
+        flatten_inherited(&mut source);
+        let numbers: Vec<u32> = source.get_pages()
+            .into_iter()
+            .filter(|(_, id)| page_ids.contains(id))
+            .map(|(number, _)| number)
+            .collect();
+
+        source.renumber_objects_with(output.max_id + 1);
+        let renumbered = source.get_pages();
+        kids.extend(numbers.iter().filter_map(|number| renumbered.get(number).copied()));
+
+        for (id, object) in source.objects {
+            let node_type = object.as_dict()
+                .and_then(|dict| dict.get(b"Type"))
+                .and_then(Object::as_name)
+                .unwrap_or_default();
+            if node_type != b"Catalog" && node_type != b"Pages" {
+                output.objects.insert(id, object);
+            }
+        }
+        output.max_id = output.max_id.max(source.max_id);
+    }
+
+    let pages_id = output.new_object_id();
+    for &kid in &kids {
+        if let Ok(page) = output.get_dictionary_mut(kid) {
+            page.set("Parent", pages_id);
+        }
+    }
+    let mut pages = Dictionary::new();
+    pages.set("Type", Object::Name(b"Pages".to_vec()));
+    pages.set("Count", kids.len() as i64);
+    pages.set("Kids", kids.into_iter().map(Object::Reference).collect::<Vec<_>>());
+    output.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let mut catalog = Dictionary::new();
+    catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+    catalog.set("Pages", pages_id);
+    let catalog_id = output.add_object(catalog);
+    output.trailer.set("Root", catalog_id);
+
+    // Drops unselected pages and whatever only they referenced
+    output.prune_objects();
+
+    let mut bytes = Vec::new();
+    output.save_to(&mut bytes).map_err(|e| format!("PDF error: {}", e))?;
+    Ok(bytes)
+}
+
+/// Split a scanned PDF and import each part as its own document
+///
+/// Parts are named `{stem}-{n}.pdf` from `file_name`, numbered from 1.
+/// Results are returned in order; an API error for one part does not stop
+/// the rest.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_path` - Local PDF to split
+/// * `file_name` - Base name for the imported documents
+/// * `root_id` - Destination folder
+/// * `mode` - Where to cut the PDF
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_split(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_path: String,
+    file_name: String,
+    root_id: impl Into<FolderId>,
+    mode: &SplitMode
+) -> Result<Vec<ImportResultOrError>> {
+    let root_id = validation::validate_entry_id(root_id.into().as_i64())?;
+    let validated_path = validation::validate_file_path(&file_path)?;
+    let validated_name = validation::validate_file_name(&file_name)?;
+    let stem = Path::new(&validated_name)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(&validated_name)
+        .to_string();
+
+    let parts = split(&std::fs::read(&validated_path)?, mode)?;
+    let mut results = Vec::with_capacity(parts.len());
+    for (index, part) in parts.into_iter().enumerate() {
+        let name = validation::validate_file_name(&format!("{}-{}.pdf", stem, index + 1))?;
+        results.push(Entry::import_content(api_server.clone(), auth.clone(), part, &name, root_id, "application/pdf").await?);
+    }
+    Ok(results)
+}
+
+/// Merge local PDFs and import the result as one document
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `file_paths` - Local PDFs, in page order
+/// * `file_name` - Name for the imported document
+/// * `root_id` - Destination folder
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn import_merged(
+    api_server: LFApiServer,
+    auth: Auth,
+    file_paths: Vec<String>,
+    file_name: String,
+    root_id: impl Into<FolderId>
+) -> Result<ImportResultOrError> {
+    let root_id = validation::validate_entry_id(root_id.into().as_i64())?;
+    let validated_name = validation::validate_file_name(&file_name)?;
+
+    let mut pdfs = Vec::with_capacity(file_paths.len());
+    for file_path in &file_paths {
+        pdfs.push(std::fs::read(validation::validate_file_path(file_path)?)?);
+    }
+    let merged = merge(&pdfs)?;
+
+    Entry::import_content(api_server, auth, merged, &validated_name, root_id, "application/pdf").await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::Stream;
+
+    /// A PDF with one page per entry; `None` is a blank page. The font is
+    /// set on the page tree so pages rely on inheritance.
+    fn sample_pdf(pages: &[Option<&str>]) -> Vec<u8> {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+
+        let mut font = Dictionary::new();
+        font.set("Type", Object::Name(b"Font".to_vec()));
+        font.set("Subtype", Object::Name(b"Type1".to_vec()));
+        font.set("BaseFont", Object::Name(b"Helvetica".to_vec()));
+        let font_id = document.add_object(font);
+        let mut fonts = Dictionary::new();
+        fonts.set("F1", font_id);
+        let mut resources = Dictionary::new();
+        resources.set("Font", fonts);
+
+        let mut kids = Vec::new();
+        for text in pages {
+            let content = match text {
+                Some(text) => format!("BT /F1 12 Tf 72 720 Td ({}) Tj ET", text),
+                None => String::new(),
+            };
+            let content_id = document.add_object(Stream::new(Dictionary::new(), content.into_bytes()));
+            let mut page = Dictionary::new();
+            page.set("Type", Object::Name(b"Page".to_vec()));
+            page.set("Parent", pages_id);
+            page.set("Contents", content_id);
+            kids.push(Object::Reference(document.add_object(page)));
+        }
+
+        let mut tree = Dictionary::new();
+        tree.set("Type", Object::Name(b"Pages".to_vec()));
+        tree.set("Count", kids.len() as i64);
+        tree.set("Kids", kids);
+        tree.set("Resources", resources);
+        tree.set("MediaBox", vec![0.into(), 0.into(), 612.into(), 792.into()]);
+        document.objects.insert(pages_id, Object::Dictionary(tree));
+
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+        catalog.set("Pages", pages_id);
+        let catalog_id = document.add_object(catalog);
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes = Vec::new();
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Text shown on each page of `pdf`
+    fn page_texts(pdf: &[u8]) -> Vec<String> {
+        let document = Document::load_mem(pdf).unwrap();
+        document.get_pages().values()
+            .map(|&page_id| {
+                let page = document.get_dictionary(page_id).unwrap();
+                assert!(page.has(b"Resources") && page.has(b"MediaBox"), "inherited attributes were lost");
+                let content = document.get_page_content(page_id).unwrap();
+                String::from_utf8_lossy(&content).split(['(', ')']).nth(1).unwrap_or("").to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_at_pages() {
+        let pdf = sample_pdf(&[Some("a1"), Some("a2"), Some("b1"), Some("c1"), Some("c2")]);
+        let parts = split(&pdf, &SplitMode::AtPages(vec![3, 4])).unwrap();
+
+        let texts: Vec<Vec<String>> = parts.iter().map(|part| page_texts(part)).collect();
+        assert_eq!(texts, vec![vec!["a1", "a2"], vec!["b1"], vec!["c1", "c2"]]);
+    }
+
+    #[test]
+    fn test_split_at_blank_pages() {
+        let pdf = sample_pdf(&[Some("a1"), None, Some("b1"), Some("b2"), None, None, Some("c1"), None]);
+        let parts = split(&pdf, &SplitMode::AtBlankPages).unwrap();
+
+        let texts: Vec<Vec<String>> = parts.iter().map(|part| page_texts(part)).collect();
+        assert_eq!(texts, vec![vec!["a1"], vec!["b1", "b2"], vec!["c1"]]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = sample_pdf(&[Some("a1"), Some("a2")]);
+        let second = sample_pdf(&[Some("b1")]);
+
+        let merged = merge(&[first, second]).unwrap();
+        assert_eq!(page_count(&merged).unwrap(), 3);
+        assert_eq!(page_texts(&merged), vec!["a1", "a2", "b1"]);
+    }
+
+    #[test]
+    fn test_rejects_non_pdf() {
+        assert!(split(b"not a pdf", &SplitMode::AtBlankPages).is_err());
+        assert!(merge(&[b"not a pdf".to_vec()]).is_err());
+    }
+}
@@ -0,0 +1,688 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+use crate::config::ConcurrencyConfig;
+use crate::laserfiche::MaybeSend;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::RequestScope;
+use crate::progress::{ProgressObserver, ProgressUpdate, SharedObserver};
+use crate::stats::TransferStats;
+use crate::throttle::{self, AdaptiveThrottle};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures_util::future;
+use futures_util::stream::{self, Stream, StreamExt};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::sync::{mpsc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::task::{JoinError, JoinSet};
+
+/// Bounded producer/worker pipeline shared by bulk operations
+///
+/// Source items are enumerated on a blocking thread, or streams polled on the
+/// calling task, and pushed into a bounded channel, so a producer walking
+/// millions of items waits for the workers instead of buffering everything
+/// in memory. Under wasm32, items are read
+/// as workers free up and the workers run as futures on the calling task.
+#[derive(Debug, Clone, Default)]
+pub struct Pipeline {
+    config: ConcurrencyConfig,
+    throttle: Option<Arc<AdaptiveThrottle>>,
+    stats: Arc<TransferStats>,
+    budget: Option<Duration>,
+    progress: Option<SharedObserver>,
+}
+
+impl Pipeline {
+    /// Create a pipeline using the limits in `config`
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Pipeline { config, throttle: None, stats: Arc::default(), budget: None, progress: None }
+    }
+
+    /// Stop starting items once `budget` has passed since a run began
+    ///
+    /// Items already running finish; the rest are handed back unstarted
+    /// by `run_within_budget`, e.g. to fit a job into a maintenance window.
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// The time each run may spend starting items, if limited
+    pub fn budget(&self) -> Option<Duration> {
+        self.budget
+    }
+
+    /// Report each processed or deferred item of a run to `observer`
+    pub fn with_progress(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.progress = Some(SharedObserver(observer));
+        self
+    }
+
+    /// Pace workers with `throttle`; each item waits for a send slot
+    /// before its worker runs, and the status of every request a worker
+    /// sends is fed back to it
+    pub fn with_throttle(mut self, throttle: Arc<AdaptiveThrottle>) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// The concurrency limits this pipeline runs with
+    pub fn config(&self) -> &ConcurrencyConfig {
+        &self.config
+    }
+
+    /// The throttle pacing this pipeline
+    pub fn throttle(&self) -> Option<&Arc<AdaptiveThrottle>> {
+        self.throttle.as_ref()
+    }
+
+    /// Counters for work run through this pipeline; workers record bytes,
+    /// files, and retries, and observers may snapshot it mid-run
+    pub fn stats(&self) -> &Arc<TransferStats> {
+        &self.stats
+    }
+
+    /// Run `worker` over every item of `items`
+    ///
+    /// At most `config.effective_limit()` workers run at once, and at most
+    /// `config.queue_depth` items wait in each of the input and output
+    /// queues. `on_result` is called on the current task in completion
+    /// order; a worker that panics yields `Err(WorkerPanic)` for its item
+    /// and the remaining items keep flowing. Returns the number of items
+    /// processed.
+    ///
+    /// All tasks belong to the call: dropping the returned future aborts
+    /// the workers and any item still in progress.
+    ///
+    /// Items left when a `with_budget` budget runs out are dropped unstarted;
+    /// `run_within_budget` hands them back instead.
+    pub async fn run<I, T, W, Fut, R, C>(&self, items: I, worker: W, on_result: C) -> usize
+    where
+        I: IntoIterator<Item = T> + MaybeSend + 'static,
+        I::IntoIter: MaybeSend + 'static,
+        T: MaybeSend + 'static,
+        W: Fn(T) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(Result<R, WorkerPanic>),
+    {
+        self.run_within_budget(items, worker, on_result, |_, _| {}).await
+    }
+
+    /// `run`, passing each item the budget left unstarted to `on_deferred`
+    /// with its position in `items`
+    ///
+    /// Once the budget is spent, the rest of `items` is still read, so that
+    /// every item is either processed or deferred. Returns the number of
+    /// items processed.
+    pub async fn run_within_budget<I, T, W, Fut, R, C, D>(
+        &self,
+        items: I,
+        worker: W,
+        on_result: C,
+        on_deferred: D
+    ) -> usize
+    where
+        I: IntoIterator<Item = T> + MaybeSend + 'static,
+        I::IntoIter: MaybeSend + 'static,
+        T: MaybeSend + 'static,
+        W: Fn(T) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(Result<R, WorkerPanic>),
+        D: FnMut(usize, T),
+    {
+        let items = items.into_iter();
+        let total = exact_len(items.size_hint());
+        self.drive(total, Source::<_, stream::Empty<T>>::Iter(items), worker, on_result, on_deferred).await
+    }
+
+    /// `run` over the items of a stream, e.g. search hits read page by page
+    /// while earlier hits are processed
+    ///
+    /// The stream is polled on the current task, so it may borrow from the
+    /// caller.
+    pub async fn run_stream<St, T, W, Fut, R, C>(&self, items: St, worker: W, on_result: C) -> usize
+    where
+        St: Stream<Item = T>,
+        T: MaybeSend + 'static,
+        W: Fn(T) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(Result<R, WorkerPanic>),
+    {
+        self.run_stream_within_budget(items, worker, on_result, |_, _| {}).await
+    }
+
+    /// `run_within_budget` over the items of a stream; see `run_stream`
+    pub async fn run_stream_within_budget<St, T, W, Fut, R, C, D>(
+        &self,
+        items: St,
+        worker: W,
+        on_result: C,
+        on_deferred: D
+    ) -> usize
+    where
+        St: Stream<Item = T>,
+        T: MaybeSend + 'static,
+        W: Fn(T) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(Result<R, WorkerPanic>),
+        D: FnMut(usize, T),
+    {
+        let total = exact_len(items.size_hint());
+        self.drive(total, Source::<std::iter::Empty<T>, _>::Stream(items), worker, on_result, on_deferred).await
+    }
+
+    /// Run `worker` over `items`, keeping stats and progress
+    async fn drive<It, St, T, W, Fut, R, C, D>(
+        &self,
+        total: Option<u64>,
+        items: Source<It, St>,
+        worker: W,
+        mut on_result: C,
+        mut on_deferred: D
+    ) -> usize
+    where
+        It: Iterator<Item = T> + MaybeSend + 'static,
+        St: Stream<Item = T>,
+        T: MaybeSend + 'static,
+        W: Fn(T) -> Fut + MaybeSend + Sync + 'static,
+        Fut: Future<Output = R> + MaybeSend + 'static,
+        R: MaybeSend + 'static,
+        C: FnMut(Result<R, WorkerPanic>),
+        D: FnMut(usize, T),
+    {
+        self.stats.mark_started();
+        let deadline = self.budget.and_then(|budget| Instant::now().checked_add(budget));
+        let mut progress = ProgressUpdate { total, ..Default::default() };
+        if let Some(SharedObserver(observer)) = &self.progress {
+            observer.started(progress.total);
+        }
+
+        let mut processed = 0;
+        let on_slot = |slot: Slot<T, R>| {
+            let result = match slot {
+                Slot::Done(result) => result,
+                Slot::Deferred(index, item) => {
+                    on_deferred(index, item);
+                    progress.deferred += 1;
+                    self.report(progress);
+                    return;
+                }
+            };
+            if result.is_err() {
+                self.stats.record_failure();
+            }
+            on_result(result);
+            processed += 1;
+            progress.completed += 1;
+            self.report(progress);
+        };
+        self.work(items, worker, deadline, on_slot).await;
+
+        self.stats.mark_finished();
+        if let Some(SharedObserver(observer)) = &self.progress {
+            observer.finished(progress);
+        }
+        processed
+    }
+
+    /// Feed `items` to `worker` on spawned tasks, handing each result or
+    /// deferred item to `on_slot` on the current task
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn work<It, St, T, W, Fut, R, S>(&self, items: Source<It, St>, worker: W, deadline: Option<Instant>, mut on_slot: S)
+    where
+        It: Iterator<Item = T> + Send + 'static,
+        St: Stream<Item = T>,
+        T: Send + 'static,
+        W: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+        S: FnMut(Slot<T, R>),
+    {
+        let queue_depth = self.config.queue_depth.max(1);
+        let (item_tx, item_rx) = mpsc::channel::<(usize, T)>(queue_depth);
+        let (result_tx, mut result_rx) = mpsc::channel::<Slot<T, R>>(queue_depth);
+
+        let producer = async move {
+            match items {
+                // Enumerating iterators (directory walks, large collections)
+                // may block, so they are read on the blocking pool
+                Source::Iter(items) => {
+                    let _ = tokio::task::spawn_blocking(move || {
+                        for item in items.enumerate() {
+                            if item_tx.blocking_send(item).is_err() {
+                                break;
+                            }
+                        }
+                    }).await;
+                }
+                Source::Stream(items) => {
+                    let mut items = std::pin::pin!(items.enumerate());
+                    while let Some(item) = items.next().await {
+                        if item_tx.send(item).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        };
+
+        // Workers send requests from their own tasks, in the caller's scope
+        // and this pipeline's throttle
+        let scope = RequestScope::current();
+        let item_rx = Arc::new(Mutex::new(item_rx));
+        let worker = Arc::new(worker);
+        let mut workers = JoinSet::new();
+        for _ in 0..self.config.effective_limit() {
+            let item_rx = Arc::clone(&item_rx);
+            let result_tx = result_tx.clone();
+            let worker = Arc::clone(&worker);
+            let throttle = self.throttle.clone();
+            let scope = scope.clone();
+            workers.spawn(async move {
+                loop {
+                    let next = item_rx.lock().await.recv().await;
+                    let Some((index, item)) = next else { break };
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        if result_tx.send(Slot::Deferred(index, item)).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if let Some(throttle) = &throttle {
+                        throttle.acquire().await;
+                    }
+
+                    // Each item runs as its own task so a panic is caught at
+                    // the task boundary instead of ending this worker loop.
+                    let mut task = JoinSet::new();
+                    task.spawn(scope.clone().run(throttle::with_optional_throttle(throttle.clone(), worker(item))));
+                    let result = match task.join_next().await {
+                        Some(Ok(result)) => Ok(result),
+                        Some(Err(error)) => Err(WorkerPanic::from_join_error(index, error)),
+                        None => Err(WorkerPanic { index, message: "worker task vanished".to_string() }),
+                    };
+
+                    if result_tx.send(Slot::Done(result)).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        // The producer waits on the queue while results are handed out
+        let consumer = async {
+            while let Some(slot) = result_rx.recv().await {
+                on_slot(slot);
+            }
+        };
+        future::join(producer, consumer).await;
+        while workers.join_next().await.is_some() {}
+    }
+
+    /// Feed `items` to `worker` as concurrent futures on the current task,
+    /// which already runs in the caller's request scope
+    ///
+    /// Under wasm32 there are no threads to enumerate items on and no tokio
+    /// runtime to spawn tasks on, and a panic aborts the module, so no
+    /// `WorkerPanic` is ever reported.
+    #[cfg(target_arch = "wasm32")]
+    async fn work<It, St, T, W, Fut, R, S>(&self, items: Source<It, St>, worker: W, deadline: Option<Instant>, mut on_slot: S)
+    where
+        It: Iterator<Item = T>,
+        St: Stream<Item = T>,
+        W: Fn(T) -> Fut,
+        Fut: Future<Output = R>,
+        S: FnMut(Slot<T, R>),
+    {
+        let items = match items {
+            Source::Iter(items) => stream::iter(items).left_stream(),
+            Source::Stream(items) => items.right_stream(),
+        };
+        let worker = &worker;
+        let slots = items.enumerate()
+            .map(|(index, item)| async move {
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    return Slot::Deferred(index, item);
+                }
+                if let Some(throttle) = &self.throttle {
+                    throttle.acquire().await;
+                }
+                Slot::Done(Ok(throttle::with_optional_throttle(self.throttle.clone(), worker(item)).await))
+            })
+            .buffer_unordered(self.config.effective_limit().max(1));
+        let mut slots = std::pin::pin!(slots);
+        while let Some(slot) = slots.next().await {
+            on_slot(slot);
+        }
+    }
+
+    fn report(&self, update: ProgressUpdate) {
+        if let Some(SharedObserver(observer)) = &self.progress {
+            observer.advanced(update);
+        }
+    }
+}
+
+/// The number of items left, when a size hint is exact
+fn exact_len(size_hint: (usize, Option<usize>)) -> Option<u64> {
+    match size_hint {
+        (lower, Some(upper)) if lower == upper => Some(lower as u64),
+        _ => None,
+    }
+}
+
+/// Items grouped by key, e.g. by folder, and handed out one group at a time
+/// in turn
+///
+/// Fed to `Pipeline::run`, it keeps a group with many items from starving
+/// the rest: a group of `n` items is done after at most `n` turns of every
+/// group, so small groups finish early however large the others are.
+/// Groups take turns in the order they were first pushed.
+#[derive(Debug, Clone)]
+pub struct FairQueue<K, T> {
+    /// Keys with items waiting, next turn first
+    turns: VecDeque<K>,
+    groups: HashMap<K, VecDeque<T>>,
+    len: usize,
+}
+
+impl<K: Eq + Hash + Clone, T> FairQueue<K, T> {
+    pub fn new() -> Self {
+        FairQueue { turns: VecDeque::new(), groups: HashMap::new(), len: 0 }
+    }
+
+    /// Add `item` to the end of `key`'s group
+    pub fn push(&mut self, key: K, item: T) {
+        let group = self.groups.entry(key.clone()).or_default();
+        if group.is_empty() {
+            self.turns.push_back(key);
+        }
+        group.push_back(item);
+        self.len += 1;
+    }
+
+    /// Number of items waiting
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of groups with items waiting
+    pub fn groups(&self) -> usize {
+        self.turns.len()
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> Default for FairQueue<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash + Clone, T> Iterator for FairQueue<K, T> {
+    type Item = T;
+
+    /// The next item of the group whose turn it is
+    fn next(&mut self) -> Option<T> {
+        let key = self.turns.pop_front()?;
+        let group = self.groups.get_mut(&key)?;
+        let item = group.pop_front()?;
+        if group.is_empty() {
+            self.groups.remove(&key);
+        } else {
+            self.turns.push_back(key);
+        }
+        self.len -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+/// Where a run reads its items from
+enum Source<It, St> {
+    Iter(It),
+    Stream(St),
+}
+
+/// What a worker sends back for one item
+enum Slot<T, R> {
+    Done(Result<R, WorkerPanic>),
+    /// Not started because the budget was spent
+    Deferred(usize, T),
+}
+
+/// A pipeline worker panicked (or was cancelled) while processing one item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerPanic {
+    /// Position of the item in the input sequence
+    pub index: usize,
+    /// Panic message, when it was a string
+    pub message: String,
+}
+
+impl WorkerPanic {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn from_join_error(index: usize, error: JoinError) -> Self {
+        let message = match error.try_into_panic() {
+            Ok(payload) => match payload.downcast::<String>() {
+                Ok(message) => *message,
+                Err(payload) => payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .unwrap_or_else(|| "worker panicked".to_string()),
+            },
+            Err(_) => "worker cancelled".to_string(),
+        };
+        WorkerPanic { index, message }
+    }
+}
+
+impl std::fmt::Display for WorkerPanic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "worker panicked on item {}: {}", self.index, self.message)
+    }
+}
+
+impl std::error::Error for WorkerPanic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pipeline_processes_every_item() {
+        let pipeline = Pipeline::new(ConcurrencyConfig {
+            max_in_flight: 4,
+            per_host_limit: 4,
+            queue_depth: 2,
+        });
+
+        let mut results = Vec::new();
+        let processed = pipeline.run(0..100u32, |n| async move { n * 2 }, |r| results.push(r.unwrap())).await;
+
+        assert_eq!(processed, 100);
+        results.sort_unstable();
+        assert_eq!(results, (0..100u32).map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_contains_worker_panics() {
+        let pipeline = Pipeline::new(ConcurrencyConfig {
+            max_in_flight: 2,
+            per_host_limit: 2,
+            queue_depth: 4,
+        });
+
+        let mut ok = Vec::new();
+        let mut panics = Vec::new();
+        let processed = pipeline.run(0..10u32, |n| async move {
+            if n % 3 == 0 {
+                panic!("bad item {}", n);
+            }
+            n
+        }, |r| match r {
+            Ok(n) => ok.push(n),
+            Err(panic) => panics.push(panic),
+        }).await;
+
+        assert_eq!(processed, 10);
+        ok.sort_unstable();
+        assert_eq!(ok, vec![1, 2, 4, 5, 7, 8]);
+
+        panics.sort_by_key(|p| p.index);
+        assert_eq!(panics.iter().map(|p| p.index).collect::<Vec<_>>(), vec![0, 3, 6, 9]);
+        assert_eq!(panics[1].message, "bad item 3");
+        assert_eq!(pipeline.stats().snapshot().failures, 4);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_empty_input() {
+        let pipeline = Pipeline::default();
+        let processed = pipeline.run(Vec::<u8>::new(), |n| async move { n }, |_| {}).await;
+        assert_eq!(processed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_with_throttle() {
+        let throttle = Arc::new(AdaptiveThrottle::default());
+        let pipeline = Pipeline::default().with_throttle(Arc::clone(&throttle));
+        assert!(pipeline.throttle().is_some());
+
+        let processed = pipeline.run(0..3u8, |n| async move { n }, |_| {}).await;
+        assert_eq!(processed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_budget_defers_unstarted_items() {
+        let pipeline = Pipeline::new(ConcurrencyConfig {
+            max_in_flight: 1,
+            per_host_limit: 1,
+            queue_depth: 2,
+        }).with_budget(Duration::from_millis(30));
+
+        let mut done = Vec::new();
+        let mut deferred = Vec::new();
+        let processed = pipeline.run_within_budget(0..20u32, |n| async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            n
+        }, |r| done.push(r.unwrap()), |index, n| deferred.push((index, n))).await;
+
+        assert_eq!(processed, done.len());
+        assert!(!deferred.is_empty());
+        assert_eq!(done.len() + deferred.len(), 20);
+        assert!(deferred.iter().all(|(index, n)| *index == *n as usize && !done.contains(n)));
+
+        let unlimited = Pipeline::default().run_within_budget(0..5u8, |n| async move { n }, |_| {}, |_, _| unreachable!()).await;
+        assert_eq!(unlimited, 5);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_runs_borrowing_streams() {
+        let mut read = 0;
+        let items = stream::iter(0..10u32).inspect(|_| read += 1);
+        let mut results = Vec::new();
+        let processed = Pipeline::default().run_stream(items, |n| async move { n * 2 }, |r| results.push(r.unwrap())).await;
+        assert_eq!((processed, read), (10, 10));
+        results.sort_unstable();
+        assert_eq!(results, (0..10u32).map(|n| n * 2).collect::<Vec<_>>());
+
+        let mut deferred = Vec::new();
+        let processed = Pipeline::default()
+            .with_budget(Duration::ZERO)
+            .run_stream_within_budget(stream::iter(0..3u8), |n| async move { n }, |_| {}, |index, n| deferred.push((index, n)))
+            .await;
+        assert_eq!(processed, 0);
+        deferred.sort_unstable();
+        assert_eq!(deferred, vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_reports_progress() {
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let observer = {
+            let updates = Arc::clone(&updates);
+            move |update: ProgressUpdate| updates.lock().unwrap().push(update)
+        };
+        let pipeline = Pipeline::default().with_progress(Arc::new(observer));
+        pipeline.run(0..4u8, |n| async move { n }, |_| {}).await;
+
+        let updates = updates.lock().unwrap().clone();
+        let completed: Vec<u64> = updates.iter().map(|update| update.completed).collect();
+        assert_eq!(completed, vec![1, 2, 3, 4]);
+        assert!(updates.iter().all(|update| update.total == Some(4)));
+        assert_eq!(updates.last().unwrap().fraction(), Some(1.0));
+
+        // Filtered sources have no exact length
+        let unknown = Pipeline::default().with_progress(Arc::new(|update: ProgressUpdate| assert_eq!(update.total, None)));
+        unknown.run((0..4u8).filter(|n| n % 2 == 0), |n| async move { n }, |_| {}).await;
+    }
+
+    #[test]
+    fn test_fair_queue_takes_turns_between_groups() {
+        let mut queue = FairQueue::new();
+        for n in 0..5 {
+            queue.push("huge", format!("huge-{}", n));
+        }
+        queue.push("small", "small-0".to_string());
+        queue.push("tiny", "tiny-0".to_string());
+        queue.push("small", "small-1".to_string());
+        assert_eq!((queue.len(), queue.groups()), (8, 3));
+
+        let order: Vec<String> = queue.collect();
+        assert_eq!(order, vec![
+            "huge-0", "small-0", "tiny-0", "huge-1", "small-1", "huge-2", "huge-3", "huge-4",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_applies_backpressure() {
+        let config = ConcurrencyConfig {
+            max_in_flight: 2,
+            per_host_limit: 2,
+            queue_depth: 3,
+        };
+        let pipeline = Pipeline::new(config);
+
+        let produced = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&produced);
+        let items = (0..200).inspect(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Input queue + output queue + workers + the item held by the producer
+        let bound = 2 * config.queue_depth + config.effective_limit() + 1;
+        let mut consumed = 0;
+        let mut max_ahead = 0;
+        pipeline.run(items, |n| async move {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            n
+        }, |_| {
+            consumed += 1;
+            max_ahead = max_ahead.max(produced.load(Ordering::SeqCst) - consumed);
+        }).await;
+
+        assert_eq!(consumed, 200);
+        assert!(max_ahead <= bound, "producer ran {} items ahead (bound {})", max_ahead, bound);
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{
+    Auth, Entry, EntryId, EntryOrError, FolderId, LFAPIError, LFApiServer, LinksOrError, MetadataResultOrError,
+    Result, TagsOrError,
+};
+use crate::pipeline::Pipeline;
+use crate::throttle;
+
+use futures_util::stream::{self, StreamExt};
+
+/// A mutation whose chance of success can be checked before it is attempted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Rename,
+    /// Move into `target`
+    Move { target: FolderId },
+    Delete,
+    UpdateMetadata,
+    SetTemplate,
+    SetTags,
+    SetLinks,
+}
+
+/// Predicted outcome of an operation on one entry
+#[derive(Debug, Clone)]
+pub enum Preflight {
+    /// Every probe for the operation succeeded
+    Allowed,
+    /// A probe was refused with 401 or 403
+    NeedsElevation(LFAPIError),
+    /// The entry, or the move target, does not exist or is not visible
+    NotFound(LFAPIError),
+    /// The operation cannot apply to the entry, e.g. a move into a document
+    Invalid(String),
+    /// A probe failed for another reason, such as rate limiting
+    Unknown(LFAPIError),
+}
+
+impl Preflight {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Preflight::Allowed)
+    }
+
+    fn from_error(error: LFAPIError) -> Self {
+        if error.is_unauthorized() || error.is_forbidden() {
+            Preflight::NeedsElevation(error)
+        } else if error.is_not_found() {
+            Preflight::NotFound(error)
+        } else {
+            Preflight::Unknown(error)
+        }
+    }
+}
+
+/// An entry whose preflight could not be classified
+#[derive(Debug, Clone)]
+pub struct PreflightFailure {
+    pub entry_id: EntryId,
+    pub error: String,
+}
+
+/// Entries split by the predicted outcome of one operation
+#[derive(Debug, Clone, Default)]
+pub struct PreflightPlan {
+    pub will_succeed: Vec<EntryId>,
+    pub needs_elevation: Vec<(EntryId, LFAPIError)>,
+    pub not_found: Vec<(EntryId, LFAPIError)>,
+    pub invalid: Vec<(EntryId, String)>,
+    /// Entries whose probes failed for another reason or did not complete
+    pub failed: Vec<PreflightFailure>,
+}
+
+impl PreflightPlan {
+    /// True when every entry is predicted to succeed
+    pub fn is_clear(&self) -> bool {
+        self.needs_elevation.is_empty() && self.not_found.is_empty() && self.invalid.is_empty() && self.failed.is_empty()
+    }
+
+    fn add(&mut self, entry_id: EntryId, preflight: Result<Preflight>) {
+        match preflight {
+            Ok(Preflight::Allowed) => self.will_succeed.push(entry_id),
+            Ok(Preflight::NeedsElevation(error)) => self.needs_elevation.push((entry_id, error)),
+            Ok(Preflight::NotFound(error)) => self.not_found.push((entry_id, error)),
+            Ok(Preflight::Invalid(reason)) => self.invalid.push((entry_id, reason)),
+            Ok(Preflight::Unknown(error)) => self.failed.push(PreflightFailure { entry_id, error: error.to_string() }),
+            Err(error) => self.failed.push(PreflightFailure { entry_id, error: error.to_string() }),
+        }
+    }
+}
+
+/// Predict whether `operation` on an entry would succeed
+///
+/// The Repository API exposes no effective-rights query, so each operation
+/// is probed with the read of the resource it would write: the entry itself
+/// for renames, deletes, and template changes, its fields, tags, or links
+/// for those updates, and the target folder too for moves. A refused read
+/// means the write will be refused; a permitted read is a strong but not
+/// certain sign the write will be permitted, since write rights can be
+/// narrower than read rights.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `operation` - Operation to check
+/// * `entry_id` - Entry the operation would change
+pub async fn can(
+    api_server: LFApiServer,
+    auth: Auth,
+    operation: Operation,
+    entry_id: impl Into<EntryId>
+) -> Result<Preflight> {
+    let entry_id = entry_id.into();
+
+    let entry = match Entry::get(api_server.clone(), auth.clone(), entry_id).await? {
+        EntryOrError::Entry(entry) => entry,
+        EntryOrError::LFAPIError(error) => return Ok(Preflight::from_error(error)),
+    };
+
+    let error = match operation {
+        Operation::Rename | Operation::Delete | Operation::SetTemplate => None,
+        Operation::Move { target } => {
+            if target.as_i64() == entry.id {
+                return Ok(Preflight::Invalid("cannot move an entry into itself".to_string()));
+            }
+            match Entry::get(api_server, auth, target).await? {
+                EntryOrError::Entry(folder) if !folder.is_container => {
+                    return Ok(Preflight::Invalid(format!("move target {} is not a folder", target.as_i64())));
+                }
+                EntryOrError::Entry(_) => None,
+                EntryOrError::LFAPIError(error) => Some(error),
+            }
+        }
+        Operation::UpdateMetadata => match Entry::get_metadata(api_server, auth, entry_id).await? {
+            MetadataResultOrError::Metadata(_) => None,
+            MetadataResultOrError::LFAPIError(error) => Some(error),
+        },
+        Operation::SetTags => match Entry::get_tags(api_server, auth, entry_id).await? {
+            TagsOrError::Tags(_) => None,
+            TagsOrError::LFAPIError(error) => Some(error),
+        },
+        Operation::SetLinks => match Entry::get_links(api_server, auth, entry_id).await? {
+            LinksOrError::Links(_) => None,
+            LinksOrError::LFAPIError(error) => Some(error),
+        },
+    };
+
+    Ok(error.map_or(Preflight::Allowed, Preflight::from_error))
+}
+
+/// Split entries into those `operation` should succeed on and those that
+/// need elevation or attention, before any is changed
+///
+/// Entries are probed with `can`, at most the pipeline's effective limit at
+/// a time.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits and throttle for the probes
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `operation` - Operation to check
+/// * `entry_ids` - Entries the operation would change
+pub async fn partition(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    operation: Operation,
+    entry_ids: Vec<EntryId>
+) -> PreflightPlan {
+    let mut plan = PreflightPlan::default();
+
+    let outcomes = stream::iter(entry_ids)
+        .map(|entry_id| {
+            let (api_server, auth) = (api_server.clone(), auth.clone());
+            async move {
+                if let Some(throttle) = pipeline.throttle() {
+                    throttle.acquire().await;
+                }
+                let probe = can(api_server, auth, operation, entry_id);
+                let preflight = throttle::with_optional_throttle(pipeline.throttle().cloned(), probe).await;
+                (entry_id, preflight)
+            }
+        })
+        .buffer_unordered(pipeline.config().effective_limit());
+
+    outcomes.for_each(|(entry_id, preflight)| {
+        plan.add(entry_id, preflight);
+        std::future::ready(())
+    }).await;
+
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(status: i64) -> LFAPIError {
+        LFAPIError { status: Some(status), ..Default::default() }
+    }
+
+    #[test]
+    fn test_preflight_from_error() {
+        assert!(matches!(Preflight::from_error(api_error(401)), Preflight::NeedsElevation(_)));
+        assert!(matches!(Preflight::from_error(api_error(403)), Preflight::NeedsElevation(_)));
+        assert!(matches!(Preflight::from_error(api_error(404)), Preflight::NotFound(_)));
+        assert!(matches!(Preflight::from_error(api_error(429)), Preflight::Unknown(_)));
+    }
+
+    #[test]
+    fn test_plan_partitions() {
+        let mut plan = PreflightPlan::default();
+        assert!(plan.is_clear());
+
+        plan.add(EntryId(1), Ok(Preflight::Allowed));
+        plan.add(EntryId(2), Ok(Preflight::NeedsElevation(api_error(403))));
+        plan.add(EntryId(3), Ok(Preflight::Invalid("not a folder".to_string())));
+        plan.add(EntryId(4), Ok(Preflight::Unknown(api_error(503))));
+
+        assert_eq!(plan.will_succeed, vec![EntryId(1)]);
+        assert_eq!(plan.needs_elevation.len(), 1);
+        assert_eq!(plan.invalid[0].0, EntryId(3));
+        assert_eq!(plan.failed[0].entry_id, EntryId(4));
+        assert!(!plan.is_clear());
+    }
+}
@@ -0,0 +1,35 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! The types most applications use, for `use laserfiche_rs::prelude::*;`
+//!
+//! Brings in the client and its configuration, the wire types, typed ids,
+//! request builders, and the traits whose methods callers use. `Result`
+//! aliases are left out so they do not shadow the caller's own.
+
+pub use crate::client::{LfClient, LfClientBuilder};
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub use crate::client::blocking::LfClient as BlockingLfClient;
+pub use crate::cloud::CloudCredentials;
+pub use crate::config::{LockRetryConfig, RedirectPolicy, RetryPolicy};
+pub use crate::egress::EgressPolicy;
+pub use crate::laserfiche::prelude::*;
+pub use crate::messages::Localize;
+pub use crate::repository::LaserficheRepository;
+pub use crate::slow_requests::SlowRequestLog;
+pub use crate::v2::LfError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prelude_names_one_set_of_types() {
+        let entry: crate::types::Entry = Entry { id: 7, entry_type: EntryType::Document, ..Default::default() };
+        assert!(entry.is_document());
+        assert_eq!(EntryId::from(FolderId(7)), EntryId(entry.id));
+        let error = LFAPIError::not_found();
+        assert_eq!(error.localized(&crate::messages::MessageCatalog::new()), error.to_string());
+    }
+}
@@ -0,0 +1,289 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::sync::watch;
+
+/// How far a bulk operation has got
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProgressUpdate {
+    /// Items processed so far, successfully or not
+    pub completed: u64,
+    /// Items left unstarted because a budget ran out
+    pub deferred: u64,
+    /// Items in the operation, when known up front
+    pub total: Option<u64>,
+}
+
+impl ProgressUpdate {
+    /// Completed and deferred items as a fraction of `total`, if known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| match total {
+            0 => 1.0,
+            total => (self.completed + self.deferred) as f64 / total as f64,
+        })
+    }
+}
+
+/// Receives progress from `Pipeline` runs, and so from every bulk operation
+/// built on one
+///
+/// The crate renders nothing itself: a GUI forwards updates to its own
+/// widgets, a CLI can use `LineProgress`, and a closure taking a
+/// `ProgressUpdate` works as an observer. Calls come from the task running
+/// the pipeline, so they should return quickly.
+pub trait ProgressObserver: Send + Sync {
+    /// The operation is starting
+    fn started(&self, _total: Option<u64>) {}
+
+    /// An item was processed or deferred
+    fn advanced(&self, update: ProgressUpdate);
+
+    /// Every item has been processed or deferred
+    fn finished(&self, _update: ProgressUpdate) {}
+}
+
+impl<F> ProgressObserver for F
+where
+    F: Fn(ProgressUpdate) + Send + Sync,
+{
+    fn advanced(&self, update: ProgressUpdate) {
+        self(update)
+    }
+}
+
+/// Shared handle to an observer, so holders stay `Debug` and `Clone`
+#[derive(Clone)]
+pub(crate) struct SharedObserver(pub(crate) Arc<dyn ProgressObserver>);
+
+impl std::fmt::Debug for SharedObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedObserver")
+    }
+}
+
+/// Writes one line per update, e.g. `moves: 40/120 (33%)`, for command-line
+/// tools that want progress without a terminal crate
+pub struct LineProgress<W> {
+    label: String,
+    writer: Mutex<W>,
+}
+
+impl LineProgress<std::io::Stderr> {
+    /// Report to standard error
+    pub fn stderr(label: impl Into<String>) -> Self {
+        Self::new(label, std::io::stderr())
+    }
+}
+
+impl<W: Write + Send> LineProgress<W> {
+    pub fn new(label: impl Into<String>, writer: W) -> Self {
+        LineProgress { label: label.into(), writer: Mutex::new(writer) }
+    }
+
+    /// The writer, e.g. to inspect what was reported
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn line(&self, update: ProgressUpdate, suffix: &str) {
+        let mut line = format!("{}: {}", self.label, update.completed);
+        if let Some(total) = update.total {
+            line.push_str(&format!("/{}", total));
+        }
+        if let Some(fraction) = update.fraction() {
+            line.push_str(&format!(" ({:.0}%)", fraction * 100.0));
+        }
+        if update.deferred > 0 {
+            line.push_str(&format!(", {} deferred", update.deferred));
+        }
+        line.push_str(suffix);
+
+        // Progress is advisory; a closed pipe must not fail the operation
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(writer, "{}", line);
+    }
+}
+
+impl<W: Write + Send> ProgressObserver for LineProgress<W> {
+    fn advanced(&self, update: ProgressUpdate) {
+        self.line(update, "");
+    }
+
+    fn finished(&self, update: ProgressUpdate) {
+        self.line(update, ", done");
+    }
+}
+
+/// How much of one upload or download has moved
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransferUpdate {
+    /// Bytes sent or received so far
+    pub transferred: u64,
+    /// Size of the content, when known up front
+    pub total: Option<u64>,
+}
+
+impl TransferUpdate {
+    /// Bytes transferred as a fraction of `total`, if known
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| match total {
+            0 => 1.0,
+            total => self.transferred as f64 / total as f64,
+        })
+    }
+}
+
+/// Receives byte-level progress from one import or export, for drawing a
+/// progress bar over a large document
+///
+/// Updates arrive from the task moving the bytes, once per chunk, so the
+/// callback should return quickly. A UI that redraws on its own schedule
+/// can take a `watch` channel from `TransferProgress::channel` instead.
+#[derive(Clone)]
+pub struct TransferProgress(Arc<dyn Fn(TransferUpdate) + Send + Sync>);
+
+impl TransferProgress {
+    pub fn new(callback: impl Fn(TransferUpdate) + Send + Sync + 'static) -> Self {
+        TransferProgress(Arc::new(callback))
+    }
+
+    /// Report into a `watch` channel, whose receiver always holds the
+    /// latest update
+    pub fn channel() -> (Self, watch::Receiver<TransferUpdate>) {
+        let (sender, receiver) = watch::channel(TransferUpdate::default());
+        let progress = Self::new(move |update| {
+            sender.send_replace(update);
+        });
+        (progress, receiver)
+    }
+
+    pub fn report(&self, update: TransferUpdate) {
+        (self.0)(update)
+    }
+
+    /// Wrap `reader` so everything read through it is reported, e.g. to pass
+    /// to `Entry::import_reader`
+    pub fn reader<R>(&self, reader: R, total: Option<u64>) -> ProgressReader<R> {
+        ProgressReader { inner: reader, progress: self.clone(), update: TransferUpdate { transferred: 0, total } }
+    }
+
+    /// Wrap `writer` so everything written through it is reported, e.g. to
+    /// pass to `Entry::export_stream`
+    pub fn writer<W>(&self, writer: W, total: Option<u64>) -> ProgressWriter<W> {
+        ProgressWriter { inner: writer, progress: self.clone(), update: TransferUpdate { transferred: 0, total } }
+    }
+}
+
+impl std::fmt::Debug for TransferProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TransferProgress")
+    }
+}
+
+/// An `AsyncRead` that reports the bytes read through it; see
+/// `TransferProgress::reader`
+#[derive(Debug)]
+pub struct ProgressReader<R> {
+    inner: R,
+    progress: TransferProgress,
+    update: TransferUpdate,
+}
+
+impl<R> ProgressReader<R> {
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ProgressReader<R> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut self.inner).poll_read(cx, buf);
+        let read = (buf.filled().len() - before) as u64;
+        if read > 0 {
+            self.update.transferred += read;
+            self.progress.report(self.update);
+        }
+        poll
+    }
+}
+
+/// An `AsyncWrite` that reports the bytes written through it; see
+/// `TransferProgress::writer`
+#[derive(Debug)]
+pub struct ProgressWriter<W> {
+    inner: W,
+    progress: TransferProgress,
+    update: TransferUpdate,
+}
+
+impl<W> ProgressWriter<W> {
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for ProgressWriter<W> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let poll = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = poll {
+            if written > 0 {
+                self.update.transferred += written as u64;
+                self.progress.report(self.update);
+            }
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_progress() {
+        let progress = LineProgress::new("moves", Vec::new());
+        progress.advanced(ProgressUpdate { completed: 1, deferred: 0, total: Some(4) });
+        progress.finished(ProgressUpdate { completed: 3, deferred: 1, total: Some(4) });
+        progress.advanced(ProgressUpdate { completed: 7, deferred: 0, total: None });
+
+        let output = String::from_utf8(progress.into_inner()).unwrap();
+        assert_eq!(output, "moves: 1/4 (25%)\nmoves: 3/4 (100%), 1 deferred, done\nmoves: 7\n");
+    }
+
+    #[tokio::test]
+    async fn test_transfer_progress() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (progress, receiver) = TransferProgress::channel();
+        let mut reader = progress.reader(&b"0123456789"[..], Some(10));
+        let mut chunk = [0u8; 4];
+        reader.read_exact(&mut chunk).await.unwrap();
+        assert_eq!(*receiver.borrow(), TransferUpdate { transferred: 4, total: Some(10) });
+        reader.read_to_end(&mut Vec::new()).await.unwrap();
+        assert_eq!(receiver.borrow().fraction(), Some(1.0));
+
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let seen = updates.clone();
+        let progress = TransferProgress::new(move |update| seen.lock().unwrap().push(update.transferred));
+        let mut writer = progress.writer(Vec::new(), None);
+        writer.write_all(b"abc").await.unwrap();
+        writer.write_all(b"de").await.unwrap();
+        assert_eq!(writer.into_inner(), b"abcde");
+        assert_eq!(*updates.lock().unwrap(), vec![3, 5]);
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::laserfiche::{Auth, Entry, EntryId, FolderId, LFApiServer, ListOptions, Result};
+use crate::manifest::csv_field;
+use crate::naming::{civil_from_days, days_from_civil};
+
+use futures_util::stream::TryStreamExt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Properties `inventory` asks the server for
+const INVENTORY_SELECT: &str = "id,name,fullPath,entryType,isContainer,creator,creationTime,templateName,elecDocumentSize";
+
+/// Properties `stale` asks the server for
+const STALE_SELECT: &str = "id,name,fullPath,folderPath,entryType,isContainer,lastModifiedTime,templateName";
+
+/// Number of largest documents kept by `inventory`
+pub const DEFAULT_LARGEST_DOCUMENTS: usize = 10;
+
+/// Key counted for entries with no template, creator, or readable year
+const UNKNOWN: &str = "(none)";
+
+/// A document and the size of its electronic content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DocumentSize {
+    pub entry_id: EntryId,
+    pub full_path: String,
+    pub bytes: i64,
+}
+
+/// Summary of the entries below a folder
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryReport {
+    pub folder_id: FolderId,
+    /// Entries below the folder, not counting the folder itself
+    pub entries: u64,
+    /// Total electronic content size of all documents
+    pub document_bytes: i64,
+    pub by_template: BTreeMap<String, u64>,
+    pub by_type: BTreeMap<String, u64>,
+    pub by_creator: BTreeMap<String, u64>,
+    /// Entries by the year they were created
+    pub by_year: BTreeMap<String, u64>,
+    /// Largest documents, largest first
+    pub largest_documents: Vec<DocumentSize>,
+    #[serde(skip)]
+    largest_limit: usize,
+}
+
+impl InventoryReport {
+    /// An empty report keeping up to `largest_limit` largest documents
+    pub fn new(folder_id: impl Into<FolderId>, largest_limit: usize) -> Self {
+        InventoryReport {
+            folder_id: folder_id.into(),
+            entries: 0,
+            document_bytes: 0,
+            by_template: BTreeMap::new(),
+            by_type: BTreeMap::new(),
+            by_creator: BTreeMap::new(),
+            by_year: BTreeMap::new(),
+            largest_documents: Vec::new(),
+            largest_limit,
+        }
+    }
+
+    /// Count one entry
+    pub fn add(&mut self, entry: &Entry) {
+        self.entries += 1;
+        count(&mut self.by_template, entry.template_name.as_deref().unwrap_or_default());
+        count(&mut self.by_type, entry.entry_type.as_str());
+        count(&mut self.by_creator, &entry.creator);
+        count(&mut self.by_year, creation_year(&entry.creation_time).unwrap_or_default());
+
+        let Some(bytes) = entry.elec_document_size.filter(|_| !entry.is_container) else {
+            return;
+        };
+        self.document_bytes += bytes;
+        if self.largest_limit == 0 {
+            return;
+        }
+        let position = self.largest_documents.partition_point(|document| document.bytes >= bytes);
+        if position < self.largest_limit {
+            self.largest_documents.insert(position, DocumentSize {
+                entry_id: entry.entry_id(),
+                full_path: entry.full_path.clone(),
+                bytes,
+            });
+            self.largest_documents.truncate(self.largest_limit);
+        }
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize inventory report: {}", e).into())
+    }
+
+    /// Serialize as CSV rows of `section,key,value`
+    ///
+    /// Counts are listed under `template`, `type`, `creator`, and `year`,
+    /// and the largest documents under `largest_document` with their path
+    /// and size in bytes.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("section,key,value\r\n");
+        let mut row = |section: &str, key: &str, value: String| {
+            csv.push_str(&[section.to_string(), csv_field(key), value].join(","));
+            csv.push_str("\r\n");
+        };
+
+        row("total", "entries", self.entries.to_string());
+        row("total", "document_bytes", self.document_bytes.to_string());
+        for (section, counts) in [
+            ("template", &self.by_template),
+            ("type", &self.by_type),
+            ("creator", &self.by_creator),
+            ("year", &self.by_year),
+        ] {
+            for (key, value) in counts {
+                row(section, key, value.to_string());
+            }
+        }
+        for document in &self.largest_documents {
+            row("largest_document", &document.full_path, document.bytes.to_string());
+        }
+        csv
+    }
+}
+
+fn count(counts: &mut BTreeMap<String, u64>, key: &str) {
+    let key = if key.is_empty() { UNKNOWN } else { key };
+    *counts.entry(key.to_string()).or_default() += 1;
+}
+
+/// The `YYYY` of an ISO 8601 timestamp
+fn creation_year(timestamp: &str) -> Option<&str> {
+    timestamp.get(..4).filter(|year| year.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Summarize every entry below a folder
+///
+/// Walks the subtree, asking only for the properties the report needs, and
+/// counts entries by template, type, creator, and creation year, keeping the
+/// `DEFAULT_LARGEST_DOCUMENTS` largest documents.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `folder_id` - Folder whose subtree is summarized
+pub async fn inventory(
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: impl Into<FolderId>
+) -> Result<InventoryReport> {
+    let folder_id = folder_id.into();
+    let options = ListOptions::new().select(INVENTORY_SELECT);
+    let mut report = InventoryReport::new(folder_id, DEFAULT_LARGEST_DOCUMENTS);
+
+    let mut entries = Entry::walk(api_server, auth, folder_id, options);
+    while let Some(entry) = entries.try_next().await? {
+        report.add(&entry);
+    }
+    Ok(report)
+}
+
+/// A document not modified since the report's cutoff
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StaleDocument {
+    pub entry_id: EntryId,
+    pub name: String,
+    pub last_modified_time: String,
+}
+
+/// Stale documents sharing a folder and template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StaleGroup {
+    pub folder_path: String,
+    /// `None` for documents with no template
+    pub template: Option<String>,
+    pub documents: Vec<StaleDocument>,
+}
+
+/// Documents below a folder not modified since a cutoff
+#[derive(Debug, Clone, Serialize)]
+pub struct StaleReport {
+    pub folder_id: FolderId,
+    /// Cutoff as an RFC 3339 UTC timestamp
+    pub cutoff: String,
+    /// Documents examined
+    pub documents: u64,
+    /// Stale documents grouped by folder, then template
+    pub groups: Vec<StaleGroup>,
+    /// Documents whose modification time could not be read
+    pub undated: Vec<EntryId>,
+    #[serde(skip)]
+    cutoff_secs: i64,
+}
+
+impl StaleReport {
+    /// An empty report of documents last modified before `cutoff`
+    pub fn new(folder_id: impl Into<FolderId>, cutoff: SystemTime) -> Self {
+        let cutoff_secs = match cutoff.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+        StaleReport {
+            folder_id: folder_id.into(),
+            cutoff: format_timestamp(cutoff_secs),
+            documents: 0,
+            groups: Vec::new(),
+            undated: Vec::new(),
+            cutoff_secs,
+        }
+    }
+
+    /// Check one entry; folders and shortcuts are ignored
+    pub fn add(&mut self, entry: &Entry) {
+        if entry.is_container || !entry.is_document() {
+            return;
+        }
+        self.documents += 1;
+        match parse_timestamp(&entry.last_modified_time) {
+            Some(modified) if modified < self.cutoff_secs => {
+                let key = (entry.folder_path.as_str(), entry.template_name.as_deref());
+                let position = self.groups
+                    .binary_search_by(|group| (group.folder_path.as_str(), group.template.as_deref()).cmp(&key));
+                let position = position.unwrap_or_else(|position| {
+                    self.groups.insert(position, StaleGroup {
+                        folder_path: entry.folder_path.clone(),
+                        template: entry.template_name.clone(),
+                        documents: Vec::new(),
+                    });
+                    position
+                });
+                self.groups[position].documents.push(StaleDocument {
+                    entry_id: entry.entry_id(),
+                    name: entry.name.clone(),
+                    last_modified_time: entry.last_modified_time.clone(),
+                });
+            }
+            Some(_) => {}
+            None => self.undated.push(entry.entry_id()),
+        }
+    }
+
+    /// Number of stale documents across all groups
+    pub fn stale_documents(&self) -> usize {
+        self.groups.iter().map(|group| group.documents.len()).sum()
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize stale-content report: {}", e).into())
+    }
+
+    /// Serialize as CSV with one row per stale document
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("folder_path,template,entry_id,name,last_modified_time\r\n");
+        for group in &self.groups {
+            for document in &group.documents {
+                let row = [
+                    csv_field(&group.folder_path),
+                    csv_field(group.template.as_deref().unwrap_or("")),
+                    document.entry_id.as_i64().to_string(),
+                    csv_field(&document.name),
+                    csv_field(&document.last_modified_time),
+                ];
+                csv.push_str(&row.join(","));
+                csv.push_str("\r\n");
+            }
+        }
+        csv
+    }
+}
+
+/// Seconds since the Unix epoch of an RFC 3339 timestamp such as
+/// `2024-01-02T03:04:05.678-05:00`; a missing offset is read as UTC
+fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = timestamp.get(range)?;
+        digits.bytes().all(|b| b.is_ascii_digit()).then(|| digits.parse().ok())?
+    };
+    let bytes = timestamp.as_bytes();
+    if bytes.len() < 19 || bytes[4] != b'-' || bytes[7] != b'-' || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':' || bytes[16] != b':'
+    {
+        return None;
+    }
+    let (year, month, day) = (number(0..4)?, number(5..7)? as u32, number(8..10)? as u32);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let seconds = number(11..13)? * 3600 + number(14..16)? * 60 + number(17..19)?;
+
+    // Skip fractional seconds, then read the offset
+    let rest = timestamp[19..].trim_start_matches(|c: char| c == '.' || c.is_ascii_digit());
+    let offset = match rest.as_bytes().first() {
+        None | Some(b'Z' | b'z') => 0,
+        Some(sign @ (b'+' | b'-')) if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let minutes = rest[1..3].parse::<i64>().ok()? * 60 + rest[4..6].parse::<i64>().ok()?;
+            if *sign == b'+' { minutes * 60 } else { -minutes * 60 }
+        }
+        _ => return None,
+    };
+    Some(days_from_civil(year, month, day) * 86_400 + seconds - offset)
+}
+
+fn format_timestamp(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let time_of_day = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, time_of_day / 3600, time_of_day % 3600 / 60, time_of_day % 60
+    )
+}
+
+/// Documents below a folder not modified within `older_than`
+///
+/// Walks the subtree, requesting only the properties the report needs, and
+/// groups documents last modified before now minus `older_than` by folder
+/// and template, for retention and disposition reviews.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `folder_id` - Folder whose subtree is checked
+/// * `older_than` - Age past which an unmodified document is stale
+pub async fn stale(
+    api_server: LFApiServer,
+    auth: Auth,
+    folder_id: impl Into<FolderId>,
+    older_than: Duration
+) -> Result<StaleReport> {
+    let folder_id = folder_id.into();
+    let cutoff = clock::system_now().checked_sub(older_than).unwrap_or(UNIX_EPOCH);
+    let options = ListOptions::new().select(STALE_SELECT);
+    let mut report = StaleReport::new(folder_id, cutoff);
+
+    let mut entries = Entry::walk(api_server, auth, folder_id, options);
+    while let Some(entry) = entries.try_next().await? {
+        report.add(&entry);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::EntryType;
+
+    fn entry(id: i64, entry_type: &str, template: Option<&str>, created: &str, bytes: Option<i64>) -> Entry {
+        Entry {
+            id,
+            full_path: format!("\\Invoices\\{}", id),
+            entry_type: entry_type.into(),
+            is_container: entry_type == "Folder",
+            creator: if id % 2 == 0 { "alice".to_string() } else { String::new() },
+            creation_time: created.to_string(),
+            template_name: template.map(str::to_string),
+            elec_document_size: bytes,
+            ..Default::default()
+        }
+    }
+
+    fn sample() -> InventoryReport {
+        let mut report = InventoryReport::new(1, 2);
+        report.add(&entry(2, "Folder", None, "2023-05-01T10:00:00Z", None));
+        report.add(&entry(3, "Document", Some("Invoice"), "2023-06-01T10:00:00Z", Some(500)));
+        report.add(&entry(4, "Document", Some("Invoice"), "2024-01-02T10:00:00Z", Some(1500)));
+        report.add(&entry(5, "Document", None, "", Some(900)));
+        report
+    }
+
+    #[test]
+    fn test_inventory_counts() {
+        let report = sample();
+        assert_eq!(report.entries, 4);
+        assert_eq!(report.document_bytes, 2900);
+        assert_eq!(report.by_template.get("Invoice"), Some(&2));
+        assert_eq!(report.by_template.get(UNKNOWN), Some(&2));
+        assert_eq!(report.by_type.get("Document"), Some(&3));
+        assert_eq!(report.by_creator.get("alice"), Some(&2));
+        assert_eq!(report.by_year.get("2023"), Some(&2));
+        assert_eq!(report.by_year.get(UNKNOWN), Some(&1));
+
+        let largest: Vec<i64> = report.largest_documents.iter().map(|document| document.bytes).collect();
+        assert_eq!(largest, vec![1500, 900]);
+    }
+
+    #[test]
+    fn test_inventory_csv_and_json() {
+        let report = sample();
+        let csv = report.to_csv();
+        assert!(csv.starts_with("section,key,value\r\ntotal,entries,4\r\n"));
+        assert!(csv.contains("template,Invoice,2\r\n"));
+        assert!(csv.contains("largest_document,\\Invoices\\4,1500\r\n"));
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["folder_id"], 1);
+        assert_eq!(json["by_year"]["2024"], 1);
+        assert_eq!(json["largest_documents"][0]["entry_id"], 4);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2024-02-29T12:30:00"), Some(1_709_209_800));
+        assert_eq!(parse_timestamp("2024-02-29T12:30:00.1234567Z"), Some(1_709_209_800));
+        assert_eq!(parse_timestamp("2024-02-29T07:30:00-05:00"), Some(1_709_209_800));
+        assert_eq!(parse_timestamp("2024-02-29"), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(format_timestamp(1_709_209_800), "2024-02-29T12:30:00Z");
+    }
+
+    #[test]
+    fn test_stale_report() {
+        let cutoff = UNIX_EPOCH + Duration::from_secs(1_704_067_200); // 2024-01-01
+        let mut report = StaleReport::new(1, cutoff);
+        let document = |id: i64, folder: &str, template: Option<&str>, modified: &str| Entry {
+            id,
+            name: format!("doc{}", id),
+            folder_path: folder.to_string(),
+            entry_type: EntryType::Document,
+            last_modified_time: modified.to_string(),
+            template_name: template.map(str::to_string),
+            ..Default::default()
+        };
+        report.add(&document(2, "\\Invoices", Some("Invoice"), "2019-03-01T00:00:00Z"));
+        report.add(&document(3, "\\Invoices", Some("Invoice"), "2024-03-01T00:00:00Z"));
+        report.add(&document(4, "\\Invoices", None, "2020-03-01T00:00:00Z"));
+        report.add(&document(5, "\\Archive", Some("Invoice"), "2023-12-31T23:59:59Z"));
+        report.add(&document(6, "\\Archive", None, ""));
+        report.add(&entry(7, "Folder", None, "", None));
+
+        assert_eq!(report.cutoff, "2024-01-01T00:00:00Z");
+        assert_eq!(report.documents, 5);
+        assert_eq!(report.stale_documents(), 3);
+        assert_eq!(report.undated, vec![EntryId(6)]);
+
+        let groups: Vec<(&str, Option<&str>, usize)> = report.groups.iter()
+            .map(|group| (group.folder_path.as_str(), group.template.as_deref(), group.documents.len()))
+            .collect();
+        assert_eq!(groups, vec![("\\Archive", Some("Invoice"), 1), ("\\Invoices", None, 1), ("\\Invoices", Some("Invoice"), 1)]);
+
+        let csv = report.to_csv();
+        assert!(csv.contains("\\Invoices,Invoice,2,doc2,2019-03-01T00:00:00Z\r\n"));
+    }
+}
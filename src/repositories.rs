@@ -0,0 +1,111 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{http_client, impl_into_result, Auth, LFAPIError, LFApiServer, RequestUrl, Result};
+use crate::retry::RetryingSend;
+
+use serde::{Deserialize, Serialize};
+
+/// A repository served by an API server
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RepositoryInfo {
+    /// The id `LFApiServer::repository` takes
+    #[serde(rename = "repoId", alias = "id")]
+    pub id: String,
+    #[serde(rename = "repoName", alias = "name", default)]
+    pub name: String,
+    #[serde(rename = "webclientUrl", alias = "webClientUrl", default, skip_serializing_if = "Option::is_none")]
+    pub webclient_url: Option<String>,
+    #[serde(default)]
+    pub is_self_hosted: bool,
+}
+
+impl RepositoryInfo {
+    /// `api_server` pointed at this repository instead of its own
+    pub fn api_server(&self, api_server: &LFApiServer) -> LFApiServer {
+        LFApiServer { address: api_server.address.clone(), repository: self.id.clone() }
+    }
+}
+
+/// `GET /Repositories` as v1 (a bare array) and v2 (a `value` page) send it
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RepositoriesResponse {
+    List(Vec<RepositoryInfo>),
+    Page { value: Vec<RepositoryInfo> },
+}
+
+pub enum RepositoriesOrError {
+    Repositories(Vec<RepositoryInfo>),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(RepositoriesOrError::Repositories => Vec<RepositoryInfo>);
+
+/// The repositories an API server hosts, for tools that work across several
+/// instead of the one configured in `LFApiServer`
+pub struct Repositories;
+
+impl Repositories {
+    /// List every repository on the server
+    ///
+    /// Only `api_server.address` is used; the repository may be left empty.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn list(api_server: LFApiServer, auth: Auth) -> Result<RepositoriesOrError> {
+        let url = RequestUrl::repositories(&api_server)?.build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(RepositoriesOrError::LFAPIError(error));
+        }
+
+        let repositories = match response.json::<RepositoriesResponse>().await? {
+            RepositoriesResponse::List(repositories) => repositories,
+            RepositoriesResponse::Page { value } => value,
+        };
+        Ok(RepositoriesOrError::Repositories(repositories))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Vec<RepositoryInfo> {
+        match serde_json::from_str(json).unwrap() {
+            RepositoriesResponse::List(repositories) => repositories,
+            RepositoriesResponse::Page { value } => value,
+        }
+    }
+
+    #[test]
+    fn test_repositories_response_parsing() {
+        let v1 = parse(r#"[{"repoId": "r-1234", "repoName": "Finance", "webclientUrl": "https://lf.example.com/laserfiche", "isSelfHosted": true}]"#);
+        assert_eq!(v1[0].id, "r-1234");
+        assert_eq!(v1[0].name, "Finance");
+        assert!(v1[0].is_self_hosted);
+
+        let v2 = parse(r#"{"value": [{"id": "r-5678", "name": "Legal", "webClientUrl": "https://app.laserfiche.com/laserfiche"}]}"#);
+        assert_eq!(v2[0].id, "r-5678");
+        assert_eq!(v2[0].webclient_url.as_deref(), Some("https://app.laserfiche.com/laserfiche"));
+        assert!(!v2[0].is_self_hosted);
+    }
+
+    #[test]
+    fn test_repository_info_api_server() {
+        let configured = LFApiServer { address: "lf.example.com".to_string(), repository: String::new() };
+        let info = RepositoryInfo { id: "Finance".to_string(), ..Default::default() };
+        assert_eq!(info.api_server(&configured).base_url(), "https://lf.example.com/LFRepositoryAPI/v1/Repositories/Finance");
+    }
+}
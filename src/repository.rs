@@ -0,0 +1,96 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! The main repository operations behind a trait, for code that should run
+//! against a live server in production and an in-memory repository in tests
+//!
+//! Write business logic against `LaserficheRepository` and pass it a
+//! `v2::LfClient`; with the `test-utils` feature, tests pass a
+//! `MockRepository` instead and need no server.
+#[cfg(any(test, feature = "test-utils"))]
+mod mock;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub use mock::{MockRepository, MOCK_ROOT_ID};
+
+use crate::laserfiche::{Entry, EntryId, FolderId, MaybeSend, MetadataResult};
+use crate::v2::{LfClient, Result};
+
+use futures_util::stream::TryStreamExt;
+use std::future::Future;
+
+/// Entry, import, and metadata operations of a Laserfiche repository
+///
+/// Errors follow `v2`: an error response from the server, e.g. a missing
+/// entry, is `LfError::Api` with the server's status.
+pub trait LaserficheRepository: Send + Sync {
+    /// Fetch one entry
+    fn get_entry(&self, entry_id: EntryId) -> impl Future<Output = Result<Entry>> + MaybeSend;
+
+    /// Every child of `folder_id`, across all pages
+    fn list_children(&self, folder_id: FolderId) -> impl Future<Output = Result<Vec<Entry>>> + MaybeSend;
+
+    /// Create a folder named `folder_name` in `parent_id`
+    fn create_folder(
+        &self,
+        folder_name: &str,
+        volume_name: &str,
+        parent_id: FolderId
+    ) -> impl Future<Output = Result<Entry>> + MaybeSend;
+
+    /// Import the file at `file_path` into `folder_id` as `file_name`,
+    /// returning the new document
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_document(
+        &self,
+        file_path: &str,
+        file_name: &str,
+        folder_id: FolderId
+    ) -> impl Future<Output = Result<Entry>> + MaybeSend;
+
+    /// Fetch an entry's field values
+    fn get_metadata(&self, entry_id: EntryId) -> impl Future<Output = Result<MetadataResult>> + MaybeSend;
+
+    /// Replace an entry's field values, e.g. with `FieldValuesBuilder::build`
+    fn update_metadata(
+        &self,
+        entry_id: EntryId,
+        metadata: serde_json::Value
+    ) -> impl Future<Output = Result<MetadataResult>> + MaybeSend;
+
+    /// Delete an entry, and everything in it if it is a folder
+    fn delete_entry(&self, entry_id: EntryId, comment: &str) -> impl Future<Output = Result<()>> + MaybeSend;
+}
+
+impl LaserficheRepository for LfClient {
+    async fn get_entry(&self, entry_id: EntryId) -> Result<Entry> {
+        LfClient::get_entry(self, entry_id).await
+    }
+
+    async fn list_children(&self, folder_id: FolderId) -> Result<Vec<Entry>> {
+        self.list_all(folder_id).try_collect().await
+    }
+
+    async fn create_folder(&self, folder_name: &str, volume_name: &str, parent_id: FolderId) -> Result<Entry> {
+        LfClient::create_folder(self, folder_name.to_string(), volume_name.to_string(), parent_id).await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn import_document(&self, file_path: &str, file_name: &str, folder_id: FolderId) -> Result<Entry> {
+        let result = self.import(file_path.to_string(), file_name.to_string(), folder_id).await?;
+        self.fetch_document_link(&result).await
+    }
+
+    async fn get_metadata(&self, entry_id: EntryId) -> Result<MetadataResult> {
+        LfClient::get_metadata(self, entry_id).await
+    }
+
+    async fn update_metadata(&self, entry_id: EntryId, metadata: serde_json::Value) -> Result<MetadataResult> {
+        LfClient::update_metadata(self, entry_id, metadata).await
+    }
+
+    async fn delete_entry(&self, entry_id: EntryId, comment: &str) -> Result<()> {
+        LfClient::delete_entry(self, entry_id, comment.to_string()).await.map(|_| ())
+    }
+}
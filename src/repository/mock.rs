@@ -0,0 +1,298 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use super::LaserficheRepository;
+use crate::fixtures::FIXTURE_TIME;
+use crate::laserfiche::{
+    Entry, EntryId, EntryType, FieldType, FolderId, LFAPIError, MetadataResult, MetadataResultFieldValue, MetadataResultValue,
+};
+use crate::v2::Result;
+
+use std::collections::{BTreeMap, VecDeque};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Id of the root folder every `MockRepository` starts with
+pub const MOCK_ROOT_ID: i64 = 1;
+
+/// In-memory `LaserficheRepository` for tests, enabled by the `test-utils`
+/// feature
+///
+/// Starts with only the root folder, id 1. Entries it creates are numbered
+/// from 1000 and stamped with `fixtures::FIXTURE_TIME`. Missing entries and
+/// folders fail with `LFAPIError::not_found()`, and a name already taken in
+/// the target folder with `LFAPIError::conflict()`, as the server would.
+#[derive(Debug)]
+pub struct MockRepository {
+    state: Mutex<MockState>,
+}
+
+#[derive(Debug)]
+struct MockState {
+    entries: BTreeMap<i64, Entry>,
+    metadata: BTreeMap<i64, MetadataResult>,
+    next_id: i64,
+    failures: VecDeque<LFAPIError>,
+}
+
+impl Default for MockRepository {
+    fn default() -> Self {
+        let root = Entry {
+            id: MOCK_ROOT_ID,
+            name: String::new(),
+            full_path: "\\".to_string(),
+            creation_time: FIXTURE_TIME.to_string(),
+            last_modified_time: FIXTURE_TIME.to_string(),
+            entry_type: EntryType::Folder,
+            is_container: true,
+            volume_name: "DEFAULT".to_string(),
+            ..Default::default()
+        };
+        let state = MockState {
+            entries: BTreeMap::from([(MOCK_ROOT_ID, root)]),
+            metadata: BTreeMap::new(),
+            next_id: 1000,
+            failures: VecDeque::new(),
+        };
+        MockRepository { state: Mutex::new(state) }
+    }
+}
+
+impl MockRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace `entry`, e.g. an `Entry::fixture()`
+    pub fn with_entry(self, entry: Entry) -> Self {
+        self.insert(entry);
+        self
+    }
+
+    /// Set the field values `get_metadata` returns for `entry_id`
+    pub fn with_metadata(self, entry_id: impl Into<EntryId>, metadata: MetadataResult) -> Self {
+        self.lock().metadata.insert(entry_id.into().0, metadata);
+        self
+    }
+
+    /// Add or replace `entry`
+    pub fn insert(&self, entry: Entry) {
+        self.lock().entries.insert(entry.id, entry);
+    }
+
+    /// Fail the next operation with `error` instead of running it; queued
+    /// errors are returned in order
+    pub fn fail_next(&self, error: LFAPIError) {
+        self.lock().failures.push_back(error);
+    }
+
+    /// The entry with id `entry_id`, if it exists
+    pub fn entry(&self, entry_id: impl Into<EntryId>) -> Option<Entry> {
+        self.lock().entries.get(&entry_id.into().0).cloned()
+    }
+
+    /// The field values set on `entry_id`
+    pub fn metadata(&self, entry_id: impl Into<EntryId>) -> Option<MetadataResult> {
+        self.lock().metadata.get(&entry_id.into().0).cloned()
+    }
+
+    /// Every entry, the root folder included, in id order
+    pub fn entries(&self) -> Vec<Entry> {
+        self.lock().entries.values().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Lock the state, or take the next queued failure
+    fn begin(&self) -> Result<std::sync::MutexGuard<'_, MockState>> {
+        let mut state = self.lock();
+        match state.failures.pop_front() {
+            Some(error) => Err(error.into()),
+            None => Ok(state),
+        }
+    }
+}
+
+impl MockState {
+    fn entry(&self, entry_id: i64) -> Result<&Entry> {
+        self.entries.get(&entry_id).ok_or_else(|| LFAPIError::not_found().into())
+    }
+
+    /// A new entry named `name` in folder `parent_id`
+    fn create(&mut self, parent_id: i64, name: &str, entry_type: EntryType) -> Result<Entry> {
+        let parent = self.entry(parent_id)?;
+        if !parent.is_container {
+            return Err(LFAPIError::fixture(400, "The parent entry is not a folder.").into());
+        }
+        let taken = self.entries.values().any(|entry| entry.parent_id == parent_id && entry.name.eq_ignore_ascii_case(name));
+        if taken {
+            return Err(LFAPIError::conflict().into());
+        }
+
+        let folder_path = parent.full_path.clone();
+        let is_container = entry_type == EntryType::Folder;
+        let entry = Entry {
+            id: self.next_id,
+            name: name.to_string(),
+            parent_id,
+            full_path: format!("{}\\{}", folder_path.trim_end_matches('\\'), name),
+            folder_path,
+            creator: "mock-user".to_string(),
+            creation_time: FIXTURE_TIME.to_string(),
+            last_modified_time: FIXTURE_TIME.to_string(),
+            entry_type,
+            is_container,
+            is_leaf: !is_container,
+            volume_name: parent.volume_name.clone(),
+            ..Default::default()
+        };
+        self.next_id += 1;
+        self.entries.insert(entry.id, entry.clone());
+        Ok(entry)
+    }
+}
+
+/// Field values in the shape `update_metadata` sends, e.g. from
+/// `FieldValuesBuilder::build`
+fn parse_metadata(metadata: &serde_json::Value) -> Option<MetadataResult> {
+    let value = metadata
+        .as_object()?
+        .iter()
+        .zip(1..)
+        .map(|((name, field), field_id)| {
+            let values: Vec<MetadataResultFieldValue> = field
+                .get("values")?
+                .as_array()?
+                .iter()
+                .map(|value| {
+                    Some(MetadataResultFieldValue {
+                        value: Some(value.get("value")?.as_str()?.to_string()),
+                        position: value.get("position").and_then(|position| position.as_i64()).unwrap_or(1),
+                    })
+                })
+                .collect::<Option<_>>()?;
+            Some(MetadataResultValue {
+                field_name: name.clone(),
+                field_type: FieldType::String,
+                field_id,
+                is_multi_value: values.len() > 1,
+                values,
+                ..Default::default()
+            })
+        })
+        .collect::<Option<_>>()?;
+    Some(MetadataResult { value })
+}
+
+impl LaserficheRepository for MockRepository {
+    async fn get_entry(&self, entry_id: EntryId) -> Result<Entry> {
+        self.begin()?.entry(entry_id.0).cloned()
+    }
+
+    async fn list_children(&self, folder_id: FolderId) -> Result<Vec<Entry>> {
+        let state = self.begin()?;
+        state.entry(folder_id.0)?;
+        Ok(state.entries.values().filter(|entry| entry.parent_id == folder_id.0 && entry.id != MOCK_ROOT_ID).cloned().collect())
+    }
+
+    async fn create_folder(&self, folder_name: &str, _volume_name: &str, parent_id: FolderId) -> Result<Entry> {
+        self.begin()?.create(parent_id.0, folder_name, EntryType::Folder)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn import_document(&self, file_path: &str, file_name: &str, folder_id: FolderId) -> Result<Entry> {
+        let mut state = self.begin()?;
+        let mut entry = state.create(folder_id.0, file_name, EntryType::Document)?;
+        entry.extension = Path::new(file_name).extension().map(|extension| extension.to_string_lossy().to_lowercase());
+        entry.elec_document_size = std::fs::metadata(file_path).ok().map(|metadata| metadata.len() as i64);
+        state.entries.insert(entry.id, entry.clone());
+        Ok(entry)
+    }
+
+    async fn get_metadata(&self, entry_id: EntryId) -> Result<MetadataResult> {
+        let state = self.begin()?;
+        state.entry(entry_id.0)?;
+        Ok(state.metadata.get(&entry_id.0).cloned().unwrap_or_default())
+    }
+
+    async fn update_metadata(&self, entry_id: EntryId, metadata: serde_json::Value) -> Result<MetadataResult> {
+        let mut state = self.begin()?;
+        state.entry(entry_id.0)?;
+        let metadata = parse_metadata(&metadata).ok_or_else(|| LFAPIError::fixture(400, "Invalid field values."))?;
+        state.metadata.insert(entry_id.0, metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn delete_entry(&self, entry_id: EntryId, _comment: &str) -> Result<()> {
+        let mut state = self.begin()?;
+        state.entry(entry_id.0)?;
+        let mut pending = vec![entry_id.0];
+        while let Some(id) = pending.pop() {
+            state.entries.remove(&id);
+            state.metadata.remove(&id);
+            pending.extend(state.entries.values().filter(|entry| entry.parent_id == id).map(|entry| entry.id));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::FieldValuesBuilder;
+    use crate::v2::LfError;
+
+    /// Business logic under test: file an invoice under a vendor folder,
+    /// creating the folder on first use
+    async fn file_invoice(repository: &impl LaserficheRepository, vendor: &str, path: &str) -> Result<Entry> {
+        let root = FolderId(MOCK_ROOT_ID);
+        let folder = match repository.list_children(root).await?.into_iter().find(|entry| entry.name == vendor) {
+            Some(folder) => folder,
+            None => repository.create_folder(vendor, "DEFAULT", root).await?,
+        };
+        let document = repository.import_document(path, "invoice.PDF", FolderId(folder.id)).await?;
+        let fields = FieldValuesBuilder::new().text("Vendor", vendor).build();
+        repository.update_metadata(EntryId(document.id), fields).await?;
+        Ok(document)
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_round_trip() {
+        let repository = MockRepository::new();
+        let document = file_invoice(&repository, "Acme", "/nonexistent/invoice.pdf").await.unwrap();
+
+        assert_eq!(document.full_path, "\\Acme\\invoice.PDF");
+        assert_eq!(document.extension.as_deref(), Some("pdf"));
+        let metadata = repository.get_metadata(EntryId(document.id)).await.unwrap();
+        assert_eq!(metadata.value[0].field_name, "Vendor");
+        assert_eq!(metadata.value[0].values[0].value.as_deref(), Some("Acme"));
+
+        // The folder is reused, and the same name in it conflicts
+        let error = file_invoice(&repository, "Acme", "/nonexistent/invoice.pdf").await.unwrap_err();
+        assert!(error.api_error().is_some_and(|error| error.is_conflict()));
+        assert_eq!(repository.entries().len(), 3);
+
+        repository.delete_entry(EntryId(document.parent_id), "cleanup").await.unwrap();
+        assert!(repository.entry(document.id).is_none());
+        assert_eq!(repository.entries().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_mock_repository_errors() {
+        let repository = MockRepository::new().with_entry(Entry::fixture());
+        assert_eq!(repository.get_entry(EntryId(100)).await.unwrap().name, "Invoice 0001");
+
+        let error = repository.get_entry(EntryId(404)).await.unwrap_err();
+        assert!(error.api_error().is_some_and(|error| error.is_not_found()));
+
+        repository.fail_next(LFAPIError::rate_limited());
+        assert!(matches!(repository.get_entry(EntryId(100)).await, Err(LfError::Api(error)) if error.is_rate_limited()));
+        assert!(repository.get_entry(EntryId(100)).await.is_ok());
+
+        assert!(repository.update_metadata(EntryId(100), serde_json::json!({"Vendor": "Acme"})).await.is_err());
+    }
+}
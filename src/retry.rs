@@ -0,0 +1,530 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::{self, Instant};
+use crate::config::{LockRetryConfig, RetryPolicy};
+use crate::integrity::ChecksummedImportOrError;
+use crate::pages::PagesDeletedOrError;
+use crate::slow_requests;
+use crate::throttle::{self, AdaptiveThrottle};
+use crate::laserfiche::{
+    Auth, BoxFuture, EdocDeletedOrError, Entry, EntryId, EntryOrError, ErrorKind, ImportResultOrError, LFAPIError, LFApiServer,
+    LFObject, MetadataResultOrError, Result, TagsOrError, WrittenOrError,
+};
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+/// A response that may carry an API error instead of its value
+pub trait ApiResponse {
+    /// The API error, if the request failed
+    fn api_error(&self) -> Option<&LFAPIError>;
+}
+
+macro_rules! api_response {
+    ($($name:ident),*) => {
+        $(impl ApiResponse for $name {
+            fn api_error(&self) -> Option<&LFAPIError> {
+                match self {
+                    $name::LFAPIError(error) => Some(error),
+                    _ => None,
+                }
+            }
+        })*
+    };
+}
+
+api_response!(
+    ChecksummedImportOrError, EdocDeletedOrError, EntryOrError, ImportResultOrError, LFObject, MetadataResultOrError,
+    PagesDeletedOrError, TagsOrError, WrittenOrError
+);
+
+impl LockRetryConfig {
+    /// Whether `error` means the entry is locked under this configuration
+    pub fn is_locked(&self, error: &LFAPIError) -> bool {
+        error.is_entry_locked()
+            || error.error_code.is_some_and(|code| self.error_codes.contains(&code))
+    }
+
+    /// Wait before retry number `attempt` (from 0), or `None` once waiting
+    /// would pass the end of the window
+    fn backoff(&self, attempt: u32, elapsed: Duration) -> Option<Duration> {
+        let delay = self.initial_backoff
+            .checked_mul(2u32.saturating_pow(attempt))
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+        (elapsed + delay <= self.window).then_some(delay)
+    }
+
+    fn is_locked_response<T: ApiResponse>(&self, response: &T) -> bool {
+        response.api_error().is_some_and(|error| self.is_locked(error))
+    }
+}
+
+/// Run `call` until its response is not an entry-locked error or the retry
+/// window ends, whichever comes first
+///
+/// Transport errors and other API errors are returned straight away; a
+/// locked response still present when the window ends is returned as-is.
+pub async fn retry_while_locked<T, E, F, Fut>(config: &LockRetryConfig, mut call: F) -> std::result::Result<T, E>
+where
+    T: ApiResponse,
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = std::result::Result<T, E>>,
+{
+    let started = Instant::now();
+    let mut attempt = 0;
+    loop {
+        let response = call().await?;
+        if !config.is_locked_response(&response) {
+            return Ok(response);
+        }
+        match config.backoff(attempt, started.elapsed()) {
+            Some(delay) => clock::sleep(delay).await,
+            None => return Ok(response),
+        }
+        attempt += 1;
+    }
+}
+
+/// Blocking version of `retry_while_locked`
+pub fn retry_while_locked_blocking<T, E>(
+    config: &LockRetryConfig,
+    mut call: impl FnMut() -> std::result::Result<T, E>
+) -> std::result::Result<T, E>
+where
+    T: ApiResponse,
+{
+    let started = Instant::now();
+    let mut attempt = 0;
+    loop {
+        let response = call()?;
+        if !config.is_locked_response(&response) {
+            return Ok(response);
+        }
+        match config.backoff(attempt, started.elapsed()) {
+            Some(delay) => std::thread::sleep(delay),
+            None => return Ok(response),
+        }
+        attempt += 1;
+    }
+}
+
+/// Turn an entry-locked API error into `ErrorKind::EntryLocked`, leaving
+/// other responses as they are
+pub fn reject_locked<T: ApiResponse>(response: T, entry_id: impl Into<EntryId>) -> Result<T> {
+    match response.api_error() {
+        Some(error) if error.is_entry_locked() => Err(ErrorKind::EntryLocked(entry_id.into().as_i64()).into()),
+        _ => Ok(response),
+    }
+}
+
+/// Wait for server-side processing to release an entry
+///
+/// Probes with an empty `PATCH`, which changes nothing but is refused while
+/// the entry is locked, backing off as configured by `LockRetryConfig`.
+/// Fails with `ErrorKind::EntryLocked` if the entry is still locked after
+/// `timeout`, or `ErrorKind::ApiError` if the probe fails for another reason.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Entry to wait for
+/// * `timeout` - How long to keep polling
+pub async fn wait_until_unlocked(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<EntryId>,
+    timeout: Duration
+) -> Result<()> {
+    let entry_id = entry_id.into();
+    let config = LockRetryConfig { window: timeout, ..Default::default() };
+    let probe = retry_while_locked(&config, || {
+        Entry::patch(api_server.clone(), auth.clone(), entry_id, None, None)
+    }).await?;
+
+    match reject_locked(probe, entry_id)? {
+        LFObject::LFAPIError(error) => Err(ErrorKind::ApiError(Box::new(error)).into()),
+        _ => Ok(()),
+    }
+}
+
+tokio::task_local! {
+    static SCOPED_RETRY_POLICY: RetryPolicy;
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+thread_local! {
+    static SCOPED_RETRY_POLICY_BLOCKING: RefCell<Option<RetryPolicy>> = const { RefCell::new(None) };
+}
+
+/// Run `future` with every API request it makes retried under `policy`
+///
+/// `LfClient`s configured with a policy scope their calls this way. Pages
+/// that a stream such as `Entry::list_all` fetches after this returns are
+/// not covered.
+pub async fn with_retry_policy<F: std::future::Future>(policy: RetryPolicy, future: F) -> F::Output {
+    SCOPED_RETRY_POLICY.scope(policy, future).await
+}
+
+/// The policy scoped around the current async request, if any
+pub(crate) fn scoped_retry_policy() -> Option<RetryPolicy> {
+    SCOPED_RETRY_POLICY.try_with(RetryPolicy::clone).ok()
+}
+
+/// Run `f` with every blocking API request it makes on this thread retried
+/// under `policy`
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub fn with_retry_policy_blocking<T>(policy: &RetryPolicy, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED_RETRY_POLICY_BLOCKING.with(|scoped| scoped.replace(Some(policy.clone())));
+    let _restore = RestorePolicy(previous);
+    f()
+}
+
+/// Puts back the outer scope's policy, even if the scoped call panics
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+struct RestorePolicy(Option<RetryPolicy>);
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl Drop for RestorePolicy {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        SCOPED_RETRY_POLICY_BLOCKING.with(|scoped| *scoped.borrow_mut() = previous);
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before retry number `attempt` (from 1), given the server's
+    /// `Retry-After`, if any
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let delay = match retry_after.filter(|_| self.honor_retry_after) {
+            Some(retry_after) => retry_after,
+            None => {
+                let backoff = self.base_delay
+                    .checked_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+                    .unwrap_or(self.max_delay);
+                if self.jitter { jitter(backoff) } else { backoff }
+            }
+        };
+        delay.min(self.max_delay)
+    }
+
+    fn retries_status(&self, status: reqwest::StatusCode) -> bool {
+        self.statuses.contains(&status.as_u16())
+    }
+
+    /// Whether to retry after a transport error; only failures to connect
+    /// are retried, since the request cannot have reached the server
+    fn retries_error(error: &reqwest::Error) -> bool {
+        is_connect_error(error)
+    }
+}
+
+/// Whether `error` is a failure to connect to the server
+///
+/// Under wasm32 the browser's fetch API reports every network failure,
+/// refused connections included, as a failed request, without saying more.
+pub(crate) fn is_connect_error(error: &reqwest::Error) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        error.is_connect()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        error.is_request()
+    }
+}
+
+/// A random 50-100% of `delay`
+fn jitter(delay: Duration) -> Duration {
+    let random = RandomState::new().hash_one(clock::system_now());
+    delay.mul_f64(0.5 + (random % 1000) as f64 / 2000.0)
+}
+
+/// Wait asked for by a `Retry-After` header, in seconds or as an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    match value.parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => {
+            let at = httpdate::parse_http_date(value).ok()?;
+            Some(at.duration_since(clock::system_now()).unwrap_or_default())
+        }
+    }
+}
+
+/// `send` for request builders, retried under the scoped `RetryPolicy`,
+/// timed against the scoped `SlowRequestLog`, and reported to the scoped
+/// `AdaptiveThrottle`
+///
+/// Without a policy in scope, or for a request whose body cannot be copied
+/// (a stream), the request is sent once as usual.
+pub(crate) trait RetryingSend {
+    type Output;
+
+    fn send_retrying(self) -> Self::Output;
+}
+
+impl RetryingSend for reqwest::RequestBuilder {
+    type Output = BoxFuture<'static, reqwest::Result<reqwest::Response>>;
+
+    fn send_retrying(self) -> Self::Output {
+        Box::pin(async move {
+            let Some(log) = slow_requests::scoped() else {
+                return send_with_policy(self).await;
+            };
+            #[cfg(not(target_arch = "wasm32"))]
+            let (method, builder) = {
+                let (client, request) = self.build_split();
+                let request = request?;
+                (Some(request.method().to_string()), reqwest::RequestBuilder::from_parts(client, request))
+            };
+            // The fetch backend cannot split a builder; bodies there are
+            // always in memory, so a copy shows the method instead
+            #[cfg(target_arch = "wasm32")]
+            let (method, builder) = {
+                let method = self.try_clone().and_then(|copy| copy.build().ok()).map(|request| request.method().to_string());
+                (method, self)
+            };
+            let started = Instant::now();
+            let result = send_with_policy(builder).await;
+            log.observe(method, started.elapsed(), &result);
+            result
+        })
+    }
+}
+
+async fn send_with_policy(builder: reqwest::RequestBuilder) -> reqwest::Result<reqwest::Response> {
+    let throttle = throttle::scoped();
+    let Some(policy) = scoped_retry_policy() else {
+        return observe_status(throttle.as_deref(), builder.send().await);
+    };
+    let mut attempt = 1;
+    loop {
+        let Some(request) = builder.try_clone().filter(|_| attempt < policy.max_attempts) else {
+            return observe_status(throttle.as_deref(), builder.send().await);
+        };
+        let delay = match observe_status(throttle.as_deref(), request.send().await) {
+            Ok(response) if policy.retries_status(response.status()) => {
+                policy.delay(attempt, retry_after(response.headers()))
+            }
+            Err(error) if RetryPolicy::retries_error(&error) => policy.delay(attempt, None),
+            result => return result,
+        };
+        clock::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Feed the status of each response, retried ones included, back to
+/// `throttle`, so it slows down on 429s even when a retry then succeeds
+fn observe_status(
+    throttle: Option<&AdaptiveThrottle>,
+    result: reqwest::Result<reqwest::Response>
+) -> reqwest::Result<reqwest::Response> {
+    if let (Some(throttle), Ok(response)) = (throttle, &result) {
+        throttle.record_status(response.status().as_u16());
+    }
+    result
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl RetryingSend for reqwest::blocking::RequestBuilder {
+    type Output = reqwest::Result<reqwest::blocking::Response>;
+
+    fn send_retrying(self) -> Self::Output {
+        let Some(log) = slow_requests::scoped_blocking() else {
+            return send_with_policy_blocking(self);
+        };
+        // Blocking builders cannot be rebuilt from parts, so the method is
+        // read from a copy, which a streamed body does not allow
+        let method = self.try_clone()
+            .and_then(|builder| builder.build().ok())
+            .map(|request| request.method().to_string());
+        let started = Instant::now();
+        let result = send_with_policy_blocking(self);
+        log.observe_blocking(method, started.elapsed(), &result);
+        result
+    }
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+fn send_with_policy_blocking(builder: reqwest::blocking::RequestBuilder) -> reqwest::Result<reqwest::blocking::Response> {
+    let Some(policy) = SCOPED_RETRY_POLICY_BLOCKING.with(|scoped| scoped.borrow().clone()) else {
+        return builder.send();
+    };
+    let mut attempt = 1;
+    loop {
+        let Some(request) = builder.try_clone().filter(|_| attempt < policy.max_attempts) else {
+            return builder.send();
+        };
+        let delay = match request.send() {
+            Ok(response) if policy.retries_status(response.status()) => {
+                policy.delay(attempt, retry_after(response.headers()))
+            }
+            Err(error) if RetryPolicy::retries_error(&error) => policy.delay(attempt, None),
+            result => return result,
+        };
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::Tags;
+    use std::time::SystemTime;
+
+    fn locked() -> TagsOrError {
+        TagsOrError::LFAPIError(LFAPIError {
+            status: Some(409),
+            title: Some("Entry is locked by another user.".to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn tags() -> TagsOrError {
+        TagsOrError::Tags(Tags { value: Vec::new() })
+    }
+
+    fn fast_config() -> LockRetryConfig {
+        LockRetryConfig {
+            window: Duration::from_millis(200),
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            error_codes: vec![9013],
+        }
+    }
+
+    #[test]
+    fn test_is_locked() {
+        let config = fast_config();
+        assert!(config.is_locked(&LFAPIError { status: Some(423), ..Default::default() }));
+        assert!(config.is_locked(&LFAPIError { error_code: Some(9013), ..Default::default() }));
+        assert!(!config.is_locked(&LFAPIError {
+            status: Some(404),
+            title: Some("Entry not found.".to_string()),
+            ..Default::default()
+        }));
+    }
+
+    #[test]
+    fn test_reject_locked() {
+        let Err(error) = reject_locked(locked(), 42) else { panic!("locked response was not rejected") };
+        assert!(matches!(error.kind(), ErrorKind::EntryLocked(42)));
+        assert_eq!(error.to_string(), "Entry 42 is locked");
+
+        assert!(matches!(reject_locked(tags(), 42), Ok(TagsOrError::Tags(_))));
+        let not_found = TagsOrError::LFAPIError(LFAPIError { status: Some(404), ..Default::default() });
+        assert!(matches!(reject_locked(not_found, 42), Ok(TagsOrError::LFAPIError(_))));
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap_and_stops_at_window() {
+        let config = fast_config();
+        assert_eq!(config.backoff(0, Duration::ZERO), Some(Duration::from_millis(1)));
+        assert_eq!(config.backoff(1, Duration::ZERO), Some(Duration::from_millis(2)));
+        assert_eq!(config.backoff(5, Duration::ZERO), Some(Duration::from_millis(4)));
+        assert_eq!(config.backoff(40, Duration::ZERO), Some(Duration::from_millis(4)));
+        assert_eq!(config.backoff(0, Duration::from_millis(200)), None);
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_unlocked() {
+        let mut calls = 0;
+        let result: std::result::Result<TagsOrError, ()> = retry_while_locked(&fast_config(), || {
+            calls += 1;
+            let response = if calls < 3 { locked() } else { tags() };
+            async move { Ok(response) }
+        }).await;
+
+        assert!(matches!(result, Ok(TagsOrError::Tags(_))));
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(1),
+            jitter: false,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1, None), Duration::from_millis(100));
+        assert_eq!(policy.delay(3, None), Duration::from_millis(400));
+        assert_eq!(policy.delay(50, None), Duration::from_secs(1));
+        // Retry-After wins, but is still capped
+        assert_eq!(policy.delay(1, Some(Duration::from_millis(700))), Duration::from_millis(700));
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(120))), Duration::from_secs(1));
+
+        let ignoring = RetryPolicy { honor_retry_after: false, ..policy };
+        assert_eq!(ignoring.delay(1, Some(Duration::from_millis(700))), Duration::from_millis(100));
+
+        let jittered = RetryPolicy { jitter: true, ..RetryPolicy::default() };
+        let delay = jittered.delay(1, None);
+        assert!(delay >= Duration::from_millis(250) && delay <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        let at = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        headers.insert(RETRY_AFTER, at.parse().unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(55) && wait <= Duration::from_secs(60));
+
+        headers.insert(RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_send_retrying_honors_policy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Answers 503 twice, then 200
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            for status in ["503 Service Unavailable", "503 Service Unavailable", "200 OK"] {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await.unwrap();
+                let response = format!("HTTP/1.1 {}\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = reqwest::Client::new();
+        let unscoped = client.get(&url).send_retrying().await.unwrap();
+        assert_eq!(unscoped.status(), 503);
+
+        let policy = RetryPolicy { max_attempts: 3, ..Default::default() };
+        let retried = with_retry_policy(policy, client.get(&url).send_retrying()).await.unwrap();
+        assert_eq!(retried.status(), 200);
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_gives_up_after_window() {
+        let config = LockRetryConfig { window: Duration::from_millis(20), ..fast_config() };
+        let mut calls = 0;
+        let result: std::result::Result<TagsOrError, ()> = retry_while_locked_blocking(&config, || {
+            calls += 1;
+            Ok(locked())
+        });
+
+        assert!(matches!(result, Ok(TagsOrError::LFAPIError(_))));
+        assert!(calls > 1);
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{
+    self, http_client, impl_into_result, Auth, BoxStream, EntriesOrError, Entry, LFAPIError, LFApiServer, ListOptions,
+    RequestUrl, Result,
+};
+use crate::retry::RetryingSend;
+use crate::tasks::poll_until_finished;
+
+pub use crate::tasks::{CancelledOrError, OperationError, OperationProgress, OperationStatus, ProgressOrError};
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How far a search may differ from the search command
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FuzzyType {
+    #[default]
+    None,
+    /// `fuzzy_factor` is the percentage of letters that must match
+    Percentage,
+    /// `fuzzy_factor` is the number of letters that may differ
+    NumberOfLetters,
+}
+
+/// A Laserfiche search command to run as a long-running operation
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRequest {
+    search_command: String,
+    fuzzy_type: FuzzyType,
+    fuzzy_factor: u32,
+}
+
+impl SearchRequest {
+    /// A search for `search_command`, e.g. `{LF:Name="invoice*", Type="D"}`
+    pub fn new(search_command: impl Into<String>) -> Self {
+        SearchRequest {
+            search_command: search_command.into(),
+            fuzzy_type: FuzzyType::None,
+            fuzzy_factor: 0,
+        }
+    }
+
+    /// Match approximately, by `factor` interpreted according to `fuzzy_type`
+    pub fn fuzzy(mut self, fuzzy_type: FuzzyType, factor: u32) -> Self {
+        self.fuzzy_type = fuzzy_type;
+        self.fuzzy_factor = factor;
+        self
+    }
+}
+
+/// Token identifying a started search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchToken {
+    pub token: String,
+}
+
+impl SearchToken {
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+}
+
+/// Where a search term was found in a result
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ContextHit {
+    /// Where the hit is, e.g. `"Page"`, `"Field"`, `"Edoc"`, or `"Annotation"`
+    pub hit_type: Option<String>,
+    pub is_annotation_hit: bool,
+    pub annotation_id: Option<i64>,
+    pub page_number: Option<i64>,
+    pub page_offset: Option<i64>,
+    /// Text surrounding the hit
+    pub context: Option<String>,
+    pub highlight1_offset: Option<i64>,
+    pub highlight1_length: Option<i64>,
+    pub highlight2_offset: Option<i64>,
+    pub highlight2_length: Option<i64>,
+    pub hit_number: Option<i64>,
+    pub hit_width: Option<i64>,
+    pub field_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ContextHits {
+    pub value: Vec<ContextHit>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Cancelled {
+    value: bool,
+}
+
+/// A finished search and the first page of its results
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub token: SearchToken,
+    pub entries: laserfiche::Entries,
+}
+
+pub enum SearchTokenOrError {
+    Token(SearchToken),
+    LFAPIError(LFAPIError),
+}
+
+pub enum ContextHitsOrError {
+    ContextHits(ContextHits),
+    LFAPIError(LFAPIError),
+}
+
+pub enum SearchResultsOrError {
+    Results(SearchResults),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(
+    SearchTokenOrError::Token => SearchToken,
+    ContextHitsOrError::ContextHits => ContextHits,
+    SearchResultsOrError::Results => SearchResults,
+);
+
+fn search_url(api_server: &LFApiServer, token: &SearchToken) -> Result<RequestUrl> {
+    if token.token.trim().is_empty() {
+        return Err("Search token must not be empty".into());
+    }
+    Ok(RequestUrl::repository(api_server)?.segment("Searches").segment(&token.token))
+}
+
+/// Start a search, returning the token used to follow it
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `request` - Search command and fuzziness
+pub async fn start(api_server: LFApiServer, auth: Auth, request: &SearchRequest) -> Result<SearchTokenOrError> {
+    let url = RequestUrl::repository(&api_server)?.segment("Searches").build()?;
+
+    let response = http_client()
+        .post(url)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .json(request)
+        .send_retrying()
+        .await?;
+
+    if response.status() != reqwest::StatusCode::CREATED {
+        let error = response.json::<LFAPIError>().await?;
+        return Ok(SearchTokenOrError::LFAPIError(error));
+    }
+
+    let token = response.json::<SearchToken>().await?;
+    Ok(SearchTokenOrError::Token(token))
+}
+
+/// Check how far a search has got
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `token` - Token returned by `start`
+pub async fn status(api_server: LFApiServer, auth: Auth, token: &SearchToken) -> Result<ProgressOrError> {
+    let url = search_url(&api_server, token)?.build()?;
+
+    let response = http_client()
+        .get(url)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying()
+        .await?;
+
+    // 202 while the search runs, 200 or 201 once it has finished
+    if !response.status().is_success() {
+        let error = response.json::<LFAPIError>().await?;
+        return Ok(ProgressOrError::LFAPIError(error));
+    }
+
+    let progress = response.json::<OperationProgress>().await?;
+    Ok(ProgressOrError::Progress(progress))
+}
+
+/// One page of a finished search's results
+///
+/// Later pages are fetched with `Entry::list_custom` and the page's
+/// `next_link()`.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `token` - Token of a completed search
+/// * `options` - OData and field options for the page
+pub async fn results(
+    api_server: LFApiServer,
+    auth: Auth,
+    token: &SearchToken,
+    options: &ListOptions
+) -> Result<EntriesOrError> {
+    let url = options.apply(search_url(&api_server, token)?.segment("Results")).build()?;
+
+    let response = http_client()
+        .get(url)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying()
+        .await?;
+
+    Entry::handle_entries_response(response).await
+}
+
+/// Every result of a finished search, following `@odata.nextLink` as the
+/// stream is read
+///
+/// An API error on any page ends the stream with `ErrorKind::ApiError`.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `token` - Token of a completed search
+/// * `options` - OData and field options applied to the first page
+pub fn results_all(
+    api_server: LFApiServer,
+    auth: Auth,
+    token: SearchToken,
+    options: ListOptions
+) -> BoxStream<'static, Result<Entry>> {
+    laserfiche::paginate(move |next_link| {
+        let (api_server, auth, token, options) = (api_server.clone(), auth.clone(), token.clone(), options.clone());
+        async move {
+            match next_link {
+                None => results(api_server, auth, &token, &options).await,
+                Some(next_link) => Entry::list_custom(api_server, auth, next_link).await,
+            }
+        }
+    })
+}
+
+/// Where the search terms were found in one result
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `token` - Token of a completed search
+/// * `row_number` - `Entry::row_number` of the result
+pub async fn context_hits(
+    api_server: LFApiServer,
+    auth: Auth,
+    token: &SearchToken,
+    row_number: i64
+) -> Result<ContextHitsOrError> {
+    let url = search_url(&api_server, token)?
+        .segment("Results")
+        .segment(&row_number.to_string())
+        .segment("ContextHits")
+        .build()?;
+
+    let response = http_client()
+        .get(url)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying()
+        .await?;
+
+    if response.status() != reqwest::StatusCode::OK {
+        let error = response.json::<LFAPIError>().await?;
+        return Ok(ContextHitsOrError::LFAPIError(error));
+    }
+
+    let hits = response.json::<ContextHits>().await?;
+    Ok(ContextHitsOrError::ContextHits(hits))
+}
+
+/// Cancel a running search, or release a finished one's results
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `token` - Token returned by `start`
+pub async fn cancel(api_server: LFApiServer, auth: Auth, token: &SearchToken) -> Result<CancelledOrError> {
+    let url = search_url(&api_server, token)?.build()?;
+
+    let response = http_client()
+        .delete(url)
+        .header("Authorization", format!("Bearer {}", auth.access_token))
+        .send_retrying()
+        .await?;
+
+    if response.status() != reqwest::StatusCode::OK {
+        let error = response.json::<LFAPIError>().await?;
+        return Ok(CancelledOrError::LFAPIError(error));
+    }
+
+    let cancelled = response.json::<Cancelled>().await?;
+    Ok(CancelledOrError::Cancelled(cancelled.value))
+}
+
+/// Run a search to completion and fetch the first page of its results
+///
+/// Status is polled with a backoff from 250ms up to 2s between checks. A
+/// search still running after `timeout` is cancelled and reported as an
+/// error, as is one that fails or is cancelled on the server.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `request` - Search command and fuzziness
+/// * `options` - OData and field options for the first page
+/// * `timeout` - Longest time to wait for the search to finish
+pub async fn search_and_wait(
+    api_server: LFApiServer,
+    auth: Auth,
+    request: &SearchRequest,
+    options: &ListOptions,
+    timeout: Duration
+) -> Result<SearchResultsOrError> {
+    let token = match start(api_server.clone(), auth.clone(), request).await? {
+        SearchTokenOrError::Token(token) => token,
+        SearchTokenOrError::LFAPIError(error) => return Ok(SearchResultsOrError::LFAPIError(error)),
+    };
+
+    let finished = poll_until_finished(|| status(api_server.clone(), auth.clone(), &token), timeout).await?;
+    match finished {
+        Some(ProgressOrError::Progress(progress)) if progress.status == OperationStatus::Completed => {}
+        Some(ProgressOrError::Progress(progress)) => {
+            return Err(format!("Search {} did not complete: {}", token.token, progress.error_summary()).into());
+        }
+        Some(ProgressOrError::LFAPIError(error)) => return Ok(SearchResultsOrError::LFAPIError(error)),
+        None => {
+            // Best effort: the timeout is the error worth reporting
+            let _ = cancel(api_server, auth, &token).await;
+            return Err(format!("Search {} did not finish within {:?}", token.token, timeout).into());
+        }
+    }
+
+    match results(api_server, auth, &token, options).await? {
+        EntriesOrError::Entries(entries) => Ok(SearchResultsOrError::Results(SearchResults { token, entries })),
+        EntriesOrError::LFAPIError(error) => Ok(SearchResultsOrError::LFAPIError(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_server() -> LFApiServer {
+        LFApiServer { address: "lf.example.com".to_string(), repository: "repo".to_string() }
+    }
+
+    #[test]
+    fn test_search_request_serialization() {
+        let request = SearchRequest::new(r#"{LF:Name="invoice*"}"#).fuzzy(FuzzyType::Percentage, 80);
+        assert_eq!(serde_json::to_value(&request).unwrap(), serde_json::json!({
+            "searchCommand": "{LF:Name=\"invoice*\"}",
+            "fuzzyType": "Percentage",
+            "fuzzyFactor": 80,
+        }));
+    }
+
+    #[test]
+    fn test_search_urls() {
+        let token = SearchToken { token: "a/b c".to_string() };
+        let url = search_url(&api_server(), &token).unwrap()
+            .segment("Results")
+            .segment("3")
+            .segment("ContextHits")
+            .build()
+            .unwrap();
+        assert!(url.ends_with("/Repositories/repo/Searches/a%2Fb%20c/Results/3/ContextHits"), "{}", url);
+
+        let url = ListOptions::new().top(50).apply(search_url(&api_server(), &token).unwrap().segment("Results")).build().unwrap();
+        assert!(url.contains("/Searches/a%2Fb%20c/Results?"), "{}", url);
+        assert!(url.ends_with("?$top=50"), "{}", url);
+
+        assert!(search_url(&api_server(), &SearchToken { token: " ".to_string() }).is_err());
+    }
+}
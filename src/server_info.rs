@@ -0,0 +1,224 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{http_client, Auth, ErrorKind, LFAPIError, LFApiServer, RequestUrl, Result};
+use crate::retry::RetryingSend;
+
+use reqwest::header::HeaderMap;
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+use std::cell::Cell;
+use std::fmt;
+
+/// Response header listing the API versions a server supports
+pub const SUPPORTED_VERSIONS_HEADER: &str = "api-supported-versions";
+
+/// A Repository API version, e.g. `1.0`
+///
+/// Also selects which routes requests use: a client configured with
+/// `LfClientBuilder::api_version` builds request URLs for that major
+/// version, e.g. `.../v2/Repositories/{repository}/Entries/{id}/Folder/Children`
+/// instead of v1's `.../Laserfiche.Repository.Folder/children`, and refuses
+/// calls whose routes have no v2 form yet. Responses are read into the
+/// same models, which accept either version's payloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    pub const V1: ApiVersion = ApiVersion { major: 1, minor: 0 };
+    pub const V2: ApiVersion = ApiVersion { major: 2, minor: 0 };
+
+    /// Parse `1`, `1.0`, or `v1.0`
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim().trim_start_matches(['v', 'V']);
+        let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+        Some(ApiVersion { major: major.parse().ok()?, minor: minor.parse().ok()? })
+    }
+
+    /// Path segment naming this version in request URLs, e.g. `v2`
+    pub fn path_segment(self) -> String {
+        format!("v{}", self.major)
+    }
+
+    /// Whether requests use the v2 routes
+    pub(crate) fn uses_v2_routes(self) -> bool {
+        self.major >= 2
+    }
+}
+
+impl Default for ApiVersion {
+    fn default() -> Self {
+        ApiVersion::V1
+    }
+}
+
+tokio::task_local! {
+    static SCOPED_API_VERSION: ApiVersion;
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+thread_local! {
+    static SCOPED_API_VERSION_BLOCKING: Cell<Option<ApiVersion>> = const { Cell::new(None) };
+}
+
+/// Run `future` with every request URL it builds routed to `version`
+///
+/// `LfClient`s scope their calls this way. Pages that a stream such as
+/// `Entry::list_all` fetches after this returns follow the server's next
+/// links, which already carry the version.
+pub async fn with_api_version<F: std::future::Future>(version: ApiVersion, future: F) -> F::Output {
+    SCOPED_API_VERSION.scope(version, future).await
+}
+
+/// Run `f` with every blocking request URL it builds on this thread routed
+/// to `version`
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub fn with_api_version_blocking<T>(version: ApiVersion, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED_API_VERSION_BLOCKING.with(|scoped| scoped.replace(Some(version)));
+    let _restore = RestoreVersion(previous);
+    f()
+}
+
+/// Puts back the outer scope's version, even if the scoped call panics
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+struct RestoreVersion(Option<ApiVersion>);
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl Drop for RestoreVersion {
+    fn drop(&mut self) {
+        SCOPED_API_VERSION_BLOCKING.with(|scoped| scoped.set(self.0));
+    }
+}
+
+/// The version scoped around the current async request, if any
+pub(crate) fn scoped_api_version() -> Option<ApiVersion> {
+    SCOPED_API_VERSION.try_with(|version| *version).ok()
+}
+
+/// Version the request being built is routed to: the one scoped by
+/// `with_api_version` or `with_api_version_blocking`, or `1.0`
+pub(crate) fn current_api_version() -> ApiVersion {
+    if let Some(version) = scoped_api_version() {
+        return version;
+    }
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    if let Some(version) = SCOPED_API_VERSION_BLOCKING.with(Cell::get) {
+        return version;
+    }
+    ApiVersion::V1
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// What a server reported about itself when a client connected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Supported API versions, lowest first
+    pub versions: Vec<ApiVersion>,
+}
+
+impl ServerInfo {
+    /// Ask the server which API versions it supports
+    ///
+    /// Reads the `api-supported-versions` header from a request for the
+    /// repository's root folder, which every version serves. Servers that
+    /// send no such header predate versioning and are recorded as `1.0`.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn detect(api_server: LFApiServer, auth: Auth) -> Result<Self> {
+        let url = RequestUrl::entry(&api_server, 1)?.query("$select", "id").build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status().is_client_error() || response.status().is_server_error() {
+            let error = response.json::<LFAPIError>().await?;
+            return Err(ErrorKind::ApiError(Box::new(error)).into());
+        }
+
+        Ok(Self::from_headers(response.headers()))
+    }
+
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let mut versions: Vec<ApiVersion> = headers
+            .get_all(SUPPORTED_VERSIONS_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(ApiVersion::parse)
+            .collect();
+        versions.sort();
+        versions.dedup();
+        if versions.is_empty() {
+            versions.push(ApiVersion::V1);
+        }
+        ServerInfo { versions }
+    }
+
+    /// Newest API version the server supports
+    pub fn latest(&self) -> ApiVersion {
+        self.versions.last().copied().unwrap_or(ApiVersion::V1)
+    }
+
+    /// Whether the server supports `version` or a later minor version of it
+    pub fn supports(&self, version: ApiVersion) -> bool {
+        self.versions.iter().any(|supported| supported.major == version.major && supported.minor >= version.minor)
+    }
+
+    /// Fail with `ErrorKind::UnsupportedByServer` unless the server supports
+    /// `version`, which `feature` needs
+    pub fn require(&self, version: ApiVersion, feature: &str) -> Result<()> {
+        if self.supports(version) {
+            return Ok(());
+        }
+        Err(ErrorKind::UnsupportedByServer(feature.to_string(), version.to_string(), self.latest().to_string()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_api_version_parse() {
+        assert_eq!(ApiVersion::parse("1.0"), Some(ApiVersion::V1));
+        assert_eq!(ApiVersion::parse(" v2 "), Some(ApiVersion::V2));
+        assert_eq!(ApiVersion::parse("2.1"), Some(ApiVersion { major: 2, minor: 1 }));
+        assert_eq!(ApiVersion::parse("beta"), None);
+        assert!(ApiVersion::V1 < ApiVersion::V2);
+    }
+
+    #[test]
+    fn test_server_info_from_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ServerInfo::from_headers(&headers).versions, vec![ApiVersion::V1]);
+
+        headers.insert(SUPPORTED_VERSIONS_HEADER, HeaderValue::from_static("2.0, 1.0"));
+        let info = ServerInfo::from_headers(&headers);
+        assert_eq!(info.versions, vec![ApiVersion::V1, ApiVersion::V2]);
+        assert_eq!(info.latest(), ApiVersion::V2);
+    }
+
+    #[test]
+    fn test_require() {
+        let info = ServerInfo { versions: vec![ApiVersion::V1] };
+        assert!(info.require(ApiVersion::V1, "listing").is_ok());
+
+        let error = info.require(ApiVersion::V2, "attribute editing").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::UnsupportedByServer(..)));
+        assert_eq!(error.to_string(), "attribute editing needs Repository API 2.0, but the server supports up to 1.0");
+    }
+}
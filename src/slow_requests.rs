@@ -0,0 +1,255 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An API call that took longer than a `SlowRequestLog` threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowRequest {
+    /// HTTP method, when it could be read without consuming a streamed body
+    pub method: Option<String>,
+    /// Path below the repository with numeric ids replaced by `{id}`, e.g.
+    /// `Entries/{id}/fields`, so calls to different entries group together
+    pub operation: String,
+    /// Entry the call addressed, if any
+    pub entry_id: Option<i64>,
+    /// Time from sending to the response headers, across any retries
+    pub duration: Duration,
+    /// Response status; `None` if the request failed without one
+    pub status: Option<u16>,
+}
+
+impl SlowRequest {
+    fn new(method: Option<String>, url: Option<&reqwest::Url>, duration: Duration, status: Option<u16>) -> Self {
+        let (operation, entry_id) = url.map(operation).unwrap_or_default();
+        SlowRequest { method, operation, entry_id, duration, status }
+    }
+}
+
+impl fmt::Display for SlowRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method.as_deref().unwrap_or("?"), self.operation)?;
+        if let Some(entry_id) = self.entry_id {
+            write!(f, " (entry {})", entry_id)?;
+        }
+        write!(f, " took {}ms", self.duration.as_millis())?;
+        if let Some(status) = self.status {
+            write!(f, ", status {}", status)?;
+        }
+        Ok(())
+    }
+}
+
+type SlowRequestCallback = Arc<dyn Fn(&SlowRequest) + Send + Sync>;
+
+/// Reports API calls slower than `threshold`
+///
+/// By default each slow call is logged at `warn` level through the `log`
+/// crate; `on_slow_request` replaces that with a callback, e.g. to emit a
+/// metric or event instead.
+#[derive(Clone)]
+pub struct SlowRequestLog {
+    threshold: Duration,
+    on_slow_request: Option<SlowRequestCallback>,
+}
+
+impl fmt::Debug for SlowRequestLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SlowRequestLog")
+            .field("threshold", &self.threshold)
+            .field("on_slow_request", &self.on_slow_request.is_some())
+            .finish()
+    }
+}
+
+impl SlowRequestLog {
+    pub fn new(threshold: Duration) -> Self {
+        SlowRequestLog { threshold, on_slow_request: None }
+    }
+
+    /// Call `f` for each slow request instead of logging it
+    pub fn on_slow_request(mut self, f: impl Fn(&SlowRequest) + Send + Sync + 'static) -> Self {
+        self.on_slow_request = Some(Arc::new(f));
+        self
+    }
+
+    pub fn threshold(&self) -> Duration {
+        self.threshold
+    }
+
+    fn report(&self, request: SlowRequest) {
+        match &self.on_slow_request {
+            Some(f) => f(&request),
+            None => log::warn!("Slow Laserfiche request: {}", request),
+        }
+    }
+
+    /// Report a finished async request if it took longer than the threshold
+    pub(crate) fn observe(
+        &self,
+        method: Option<String>,
+        duration: Duration,
+        result: &reqwest::Result<reqwest::Response>
+    ) {
+        if duration <= self.threshold {
+            return;
+        }
+        let (url, status) = match result {
+            Ok(response) => (Some(response.url()), Some(response.status().as_u16())),
+            Err(error) => (error.url(), error.status().map(|status| status.as_u16())),
+        };
+        self.report(SlowRequest::new(method, url, duration, status));
+    }
+
+    /// Report a finished blocking request if it took longer than the threshold
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    pub(crate) fn observe_blocking(
+        &self,
+        method: Option<String>,
+        duration: Duration,
+        result: &reqwest::Result<reqwest::blocking::Response>
+    ) {
+        if duration <= self.threshold {
+            return;
+        }
+        let (url, status) = match result {
+            Ok(response) => (Some(response.url()), Some(response.status().as_u16())),
+            Err(error) => (error.url(), error.status().map(|status| status.as_u16())),
+        };
+        self.report(SlowRequest::new(method, url, duration, status));
+    }
+}
+
+/// Split a request URL into its operation and the entry it addresses
+fn operation(url: &reqwest::Url) -> (String, Option<i64>) {
+    let segments: Vec<&str> = url.path_segments().map(|segments| segments.collect()).unwrap_or_default();
+    let below_repository = segments
+        .iter()
+        .position(|segment| *segment == "Repositories")
+        .map_or(0, |i| (i + 2).min(segments.len()));
+    let segments = &segments[below_repository..];
+
+    let entry_id = segments
+        .windows(2)
+        .find(|pair| pair[0] == "Entries")
+        .and_then(|pair| pair[1].parse().ok());
+    let operation = segments
+        .iter()
+        .map(|segment| if segment.parse::<i64>().is_ok() { "{id}" } else { segment })
+        .collect::<Vec<_>>()
+        .join("/");
+    (operation, entry_id)
+}
+
+tokio::task_local! {
+    static SCOPED_SLOW_REQUEST_LOG: SlowRequestLog;
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+thread_local! {
+    static SCOPED_SLOW_REQUEST_LOG_BLOCKING: RefCell<Option<SlowRequestLog>> = const { RefCell::new(None) };
+}
+
+/// Run `future` with every API request it makes timed against `log`
+///
+/// `LfClient`s configured with a slow-request log scope their calls this way.
+pub async fn with_slow_request_log<F: std::future::Future>(log: SlowRequestLog, future: F) -> F::Output {
+    SCOPED_SLOW_REQUEST_LOG.scope(log, future).await
+}
+
+/// Run `f` with every blocking API request it makes on this thread timed
+/// against `log`
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub fn with_slow_request_log_blocking<T>(log: &SlowRequestLog, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED_SLOW_REQUEST_LOG_BLOCKING.with(|scoped| scoped.replace(Some(log.clone())));
+    let _restore = RestoreLog(previous);
+    f()
+}
+
+/// Puts back the outer scope's log, even if the scoped call panics
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+struct RestoreLog(Option<SlowRequestLog>);
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl Drop for RestoreLog {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        SCOPED_SLOW_REQUEST_LOG_BLOCKING.with(|scoped| *scoped.borrow_mut() = previous);
+    }
+}
+
+/// The log scoped around the current async request, if any
+pub(crate) fn scoped() -> Option<SlowRequestLog> {
+    SCOPED_SLOW_REQUEST_LOG.try_with(SlowRequestLog::clone).ok()
+}
+
+/// The log scoped around the current blocking request, if any
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub(crate) fn scoped_blocking() -> Option<SlowRequestLog> {
+    SCOPED_SLOW_REQUEST_LOG_BLOCKING.with(|scoped| scoped.borrow().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_operation_from_url() {
+        let url = reqwest::Url::parse(
+            "https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/Entries/42/Laserfiche.Repository.Folder/children?$top=5"
+        ).unwrap();
+        assert_eq!(operation(&url), ("Entries/{id}/Laserfiche.Repository.Folder/children".to_string(), Some(42)));
+
+        let url = reqwest::Url::parse("https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/Tags").unwrap();
+        assert_eq!(operation(&url), ("Tags".to_string(), None));
+    }
+
+    #[test]
+    fn test_slow_request_display() {
+        let url = reqwest::Url::parse("https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/Entries/7/fields").unwrap();
+        let request = SlowRequest::new(Some("GET".to_string()), Some(&url), Duration::from_millis(2500), Some(200));
+        assert_eq!(request.to_string(), "GET Entries/{id}/fields (entry 7) took 2500ms, status 200");
+    }
+
+    #[tokio::test]
+    async fn test_reports_requests_over_threshold() {
+        use crate::retry::RetryingSend;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/LFRepositoryAPI/v1/Repositories/repo/Entries/9", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 1024];
+                let _ = socket.read(&mut request).await.unwrap();
+                socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await.unwrap();
+            }
+        });
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reported);
+        let client = reqwest::Client::new();
+
+        let fast = SlowRequestLog::new(Duration::from_secs(60)).on_slow_request(|_| panic!("not slow"));
+        with_slow_request_log(fast, client.get(&url).send_retrying()).await.unwrap();
+
+        let slow = SlowRequestLog::new(Duration::ZERO)
+            .on_slow_request(move |request| sink.lock().unwrap().push(request.clone()));
+        with_slow_request_log(slow, client.delete(&url).send_retrying()).await.unwrap();
+        server.await.unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0].method.as_deref(), Some("DELETE"));
+        assert_eq!(reported[0].operation, "Entries/{id}");
+        assert_eq!(reported[0].entry_id, Some(9));
+        assert_eq!(reported[0].status, Some(200));
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{
+    impl_into_result, Auth, Entry, EntryId, EntryOrError, FullEntryOrError, LFAPIError, LFApiServer, Links,
+    MetadataResult, MetadataResultOrError, Result, Tags, TagsOrError,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Portable record of an entry's classification
+///
+/// Captures the template, field values, tags, and links of one entry so
+/// they can be copied onto another entry, in this or another repository.
+/// Fields are keyed by name rather than id. Tags are applied by id, so
+/// across repositories the tag ids must match.
+///
+/// Serialized form (schema version 1):
+///
+/// ```json
+/// {
+///   "schemaVersion": 1,
+///   "templateName": "Invoice",
+///   "fields": [
+///     { "name": "Invoice Number", "fieldType": "String", "values": ["INV-001"] }
+///   ],
+///   "tags": [ { "id": 3, "name": "Confidential" } ],
+///   "links": [ { "targetId": 88, "linkType": "Supporting Document", "description": null } ]
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct EntrySnapshot {
+    pub schema_version: u32,
+    pub template_name: Option<String>,
+    #[serde(default)]
+    pub fields: Vec<SnapshotField>,
+    #[serde(default)]
+    pub tags: Vec<SnapshotTag>,
+    #[serde(default)]
+    pub links: Vec<SnapshotLink>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotField {
+    pub name: String,
+    pub field_type: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotTag {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotLink {
+    pub target_id: i64,
+    pub link_type: String,
+    pub description: Option<String>,
+}
+
+pub enum SnapshotOrError {
+    Snapshot(EntrySnapshot),
+    LFAPIError(LFAPIError),
+}
+
+pub enum AppliedOrError {
+    Applied,
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(SnapshotOrError::Snapshot => EntrySnapshot);
+
+impl AppliedOrError {
+    /// `Ok` once applied, or `Err` with `LfError::Api`
+    pub fn into_result(self) -> crate::v2::Result<()> {
+        match self {
+            AppliedOrError::Applied => Ok(()),
+            AppliedOrError::LFAPIError(error) => Err(error.into()),
+        }
+    }
+}
+
+impl EntrySnapshot {
+    /// Schema version written by this crate
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    /// Assemble a snapshot from already-fetched parts
+    pub fn from_parts(entry: &Entry, metadata: &MetadataResult, tags: &Tags, links: &Links) -> Self {
+        let fields = metadata.value
+            .iter()
+            .map(|field| SnapshotField {
+                name: field.field_name.clone(),
+                field_type: field.field_type.to_string(),
+                values: field.values.iter().filter_map(|v| v.value.clone()).collect(),
+            })
+            .collect();
+
+        let tags = tags.value
+            .iter()
+            .map(|tag| SnapshotTag { id: tag.id, name: tag.name.clone() })
+            .collect();
+
+        // Only outgoing links are recorded; incoming ones belong to the other entry
+        let links = links.value
+            .iter()
+            .filter(|link| link.source_id == entry.id)
+            .map(|link| SnapshotLink {
+                target_id: link.target_id,
+                link_type: link.link_type.clone(),
+                description: link.description.clone(),
+            })
+            .collect();
+
+        EntrySnapshot {
+            schema_version: Self::SCHEMA_VERSION,
+            template_name: entry.template_name.clone().filter(|name| !name.is_empty()),
+            fields,
+            tags,
+            links,
+        }
+    }
+
+    /// Parse a snapshot, rejecting schema versions this crate does not understand
+    pub fn from_json(json: &str) -> Result<Self> {
+        let snapshot: EntrySnapshot = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid entry snapshot: {}", e))?;
+
+        if snapshot.schema_version != Self::SCHEMA_VERSION {
+            return Err(format!(
+                "Unsupported entry snapshot schema version {} (expected {})",
+                snapshot.schema_version,
+                Self::SCHEMA_VERSION
+            ).into());
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Serialize to pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize entry snapshot: {}", e).into())
+    }
+
+    /// Field values in the shape expected by `Entry::update_metadata`
+    pub fn fields_payload(&self) -> serde_json::Value {
+        let mut payload = serde_json::Map::new();
+
+        for field in &self.fields {
+            let values: Vec<serde_json::Value> = field.values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| json!({ "value": value, "position": i + 1 }))
+                .collect();
+            payload.insert(field.name.clone(), json!({ "values": values }));
+        }
+
+        serde_json::Value::Object(payload)
+    }
+
+    /// Tag ids to assign with `Entry::set_tags`
+    pub fn tag_ids(&self) -> Vec<i64> {
+        self.tags.iter().map(|tag| tag.id).collect()
+    }
+}
+
+/// Capture the template, fields, tags, and links of an entry
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Entry to capture
+pub async fn snapshot(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<EntryId>
+) -> Result<SnapshotOrError> {
+    match Entry::get_full(api_server, auth, entry_id).await? {
+        FullEntryOrError::Full(full) => {
+            Ok(SnapshotOrError::Snapshot(EntrySnapshot::from_parts(&full.entry, &full.metadata, &full.tags, &full.links)))
+        }
+        FullEntryOrError::LFAPIError(error) => Ok(SnapshotOrError::LFAPIError(error)),
+    }
+}
+
+/// Apply a snapshot's template, field values, and tags to an entry
+///
+/// The template is assigned first so its fields exist before values are
+/// written. Links are recorded in snapshots but not re-created here.
+///
+/// # Arguments
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `entry_id` - Entry to update
+/// * `snapshot` - Snapshot to apply
+pub async fn apply_snapshot(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: impl Into<EntryId>,
+    snapshot: &EntrySnapshot
+) -> Result<AppliedOrError> {
+    let entry_id = entry_id.into();
+
+    if let Some(template_name) = &snapshot.template_name {
+        let result = Entry::set_template(api_server.clone(), auth.clone(), entry_id, template_name.clone()).await?;
+        if let EntryOrError::LFAPIError(error) = result {
+            return Ok(AppliedOrError::LFAPIError(error));
+        }
+    }
+
+    if !snapshot.fields.is_empty() {
+        let result = Entry::update_metadata(api_server.clone(), auth.clone(), entry_id, snapshot.fields_payload()).await?;
+        if let MetadataResultOrError::LFAPIError(error) = result {
+            return Ok(AppliedOrError::LFAPIError(error));
+        }
+    }
+
+    if !snapshot.tags.is_empty() {
+        let result = Entry::set_tags(api_server, auth, entry_id, snapshot.tag_ids()).await?;
+        if let TagsOrError::LFAPIError(error) = result {
+            return Ok(AppliedOrError::LFAPIError(error));
+        }
+    }
+
+    Ok(AppliedOrError::Applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{FieldType, Link, MetadataResultFieldValue, MetadataResultValue, Tag};
+
+    fn sample_parts() -> (Entry, MetadataResult, Tags, Links) {
+        let entry = Entry {
+            id: 10,
+            name: "invoice.pdf".to_string(),
+            template_name: Some("Invoice".to_string()),
+            ..Default::default()
+        };
+
+        let metadata = MetadataResult {
+            value: vec![MetadataResultValue {
+                field_name: "Invoice Number".to_string(),
+                field_type: FieldType::String,
+                field_id: 5,
+                values: vec![
+                    MetadataResultFieldValue { value: Some("INV-001".to_string()), position: 1 },
+                    MetadataResultFieldValue { value: None, position: 2 },
+                ],
+                ..Default::default()
+            }],
+        };
+
+        let tags = Tags {
+            value: vec![Tag { id: 3, name: "Confidential".to_string(), ..Default::default() }],
+        };
+
+        let links = Links {
+            value: vec![
+                Link { id: 1, source_id: 10, target_id: 88, link_type: "Supporting Document".to_string(), link_type_id: 3, description: None },
+                Link { id: 2, source_id: 77, target_id: 10, link_type: "Supporting Document".to_string(), link_type_id: 3, description: None },
+            ],
+        };
+
+        (entry, metadata, tags, links)
+    }
+
+    #[test]
+    fn test_snapshot_from_parts() {
+        let (entry, metadata, tags, links) = sample_parts();
+        let snapshot = EntrySnapshot::from_parts(&entry, &metadata, &tags, &links);
+
+        assert_eq!(snapshot.schema_version, EntrySnapshot::SCHEMA_VERSION);
+        assert_eq!(snapshot.template_name.as_deref(), Some("Invoice"));
+        assert_eq!(snapshot.fields.len(), 1);
+        assert_eq!(snapshot.fields[0].values, vec!["INV-001".to_string()]);
+        assert_eq!(snapshot.tag_ids(), vec![3]);
+        assert_eq!(snapshot.links.len(), 1);
+        assert_eq!(snapshot.links[0].target_id, 88);
+    }
+
+    #[test]
+    fn test_snapshot_json_round_trip() {
+        let (entry, metadata, tags, links) = sample_parts();
+        let snapshot = EntrySnapshot::from_parts(&entry, &metadata, &tags, &links);
+
+        let json = snapshot.to_json().unwrap();
+        assert!(json.contains("\"schemaVersion\": 1"));
+        assert!(json.contains("\"templateName\": \"Invoice\""));
+        assert_eq!(EntrySnapshot::from_json(&json).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_unknown_version() {
+        let json = r#"{"schemaVersion": 99, "templateName": null}"#;
+        assert!(EntrySnapshot::from_json(json).is_err());
+        assert!(EntrySnapshot::from_json("not json").is_err());
+
+        let minimal = r#"{"schemaVersion": 1, "templateName": null}"#;
+        let snapshot = EntrySnapshot::from_json(minimal).unwrap();
+        assert!(snapshot.fields.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_fields_payload() {
+        let (entry, metadata, tags, links) = sample_parts();
+        let snapshot = EntrySnapshot::from_parts(&entry, &metadata, &tags, &links);
+
+        assert_eq!(snapshot.fields_payload(), json!({
+            "Invoice Number": { "values": [ { "value": "INV-001", "position": 1 } ] }
+        }));
+    }
+}
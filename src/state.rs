@@ -0,0 +1,300 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::Result;
+
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::Write;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where the crate keeps state between runs: the outbox queue and job
+/// manifests
+///
+/// Values are opaque bytes under string keys such as `outbox.jsonl`.
+/// `FileStore` keeps them as files in a directory, `MemoryStore` in memory,
+/// and `SqliteStore` (feature `sqlite`) in one SQLite database; an embedder
+/// can implement the trait over its own database so the crate writes
+/// nothing to disk itself.
+pub trait StateStore: std::fmt::Debug + Send + Sync {
+    /// The value under `key`, or `None` if nothing is stored there
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Store `value` under `key`, replacing any value whole: a reader sees
+    /// the old value or the new one, never a mix
+    fn save(&self, key: &str, value: &[u8]) -> Result<()>;
+
+    /// Add `value` to the end of the value under `key`, durably before
+    /// returning
+    ///
+    /// The default loads and saves the whole value; stores that can
+    /// append in place should.
+    fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        let mut existing = self.load(key)?.unwrap_or_default();
+        existing.extend_from_slice(value);
+        self.save(key, &existing)
+    }
+
+    /// Delete the value under `key`; removing a missing key succeeds
+    fn remove(&self, key: &str) -> Result<()>;
+}
+
+/// Keeps each key as a file of that name in one directory
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStore {
+    /// Store files in `dir`, which is created on first write
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileStore { dir: dir.into() }
+    }
+
+    /// The directory files are stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The file `key` is stored in
+    ///
+    /// Keys are file names, so separators and `..` are refused rather than
+    /// letting a key reach outside the directory.
+    pub fn path(&self, key: &str) -> Result<PathBuf> {
+        if key.is_empty() || key == "." || key.contains("..") || key.contains(['/', '\\', '\0']) {
+            return Err(format!("Invalid state key '{}'", key).into());
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StateStore for FileStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)?) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Written to a temporary file beside the target, flushed to disk, and
+    /// renamed over it
+    fn save(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let mut temp_path = path.clone().into_os_string();
+        temp_path.push(".tmp");
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(value)?;
+        // Otherwise a crash after the rename can leave an empty file
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        let path = self.path(key)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(value)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps values in memory, for tests and for state that need not outlive
+/// the process
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    values: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys with a value stored, in order
+    pub fn keys(&self) -> Vec<String> {
+        self.values().keys().cloned().collect()
+    }
+
+    fn values(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Vec<u8>>> {
+        self.values.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl StateStore for MemoryStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        Ok(self.values().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.values().insert(key.to_string(), value.to_vec());
+        Ok(())
+    }
+
+    fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.values().entry(key.to_string()).or_default().extend_from_slice(value);
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.values().remove(key);
+        Ok(())
+    }
+}
+
+/// Keeps every key as a row of one table in a SQLite database, so a desktop
+/// or edge app's state lives in a single file it may already have
+#[cfg(feature = "sqlite")]
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteStore {
+    /// Open or create the database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(rusqlite::Connection::open(path).map_err(sqlite_error)?)
+    }
+
+    /// A database that lives only as long as the store, for tests
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(rusqlite::Connection::open_in_memory().map_err(sqlite_error)?)
+    }
+
+    /// Keep state in an existing connection's database, in a table named
+    /// `laserfiche_state`
+    pub fn with_connection(connection: rusqlite::Connection) -> Result<Self> {
+        connection
+            .execute_batch(
+                "PRAGMA synchronous = FULL;
+                 CREATE TABLE IF NOT EXISTS laserfiche_state (key TEXT PRIMARY KEY, value BLOB NOT NULL);"
+            )
+            .map_err(sqlite_error)?;
+        Ok(SqliteStore { connection: Mutex::new(connection) })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.connection.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl StateStore for SqliteStore {
+    fn load(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+        self.connection()
+            .query_row("SELECT value FROM laserfiche_state WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn save(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.connection()
+            .execute(
+                "INSERT INTO laserfiche_state (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+                rusqlite::params![key, value]
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    /// Appended in one statement, so the row is never read and rewritten
+    fn append(&self, key: &str, value: &[u8]) -> Result<()> {
+        self.connection()
+            .execute(
+                "INSERT INTO laserfiche_state (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = CAST(value || excluded.value AS BLOB)",
+                rusqlite::params![key, value]
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &str) -> Result<()> {
+        self.connection()
+            .execute("DELETE FROM laserfiche_state WHERE key = ?1", [key])
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn sqlite_error(error: rusqlite::Error) -> crate::laserfiche::Error {
+    format!("SQLite state store: {}", error).into()
+}
+
+/// The value under `key` as UTF-8 text
+pub(crate) fn load_string(store: &dyn StateStore, key: &str) -> Result<Option<String>> {
+    store.load(key)?
+        .map(|value| String::from_utf8(value).map_err(|_| format!("State '{}' is not valid UTF-8", key).into()))
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(store: &dyn StateStore) {
+        assert_eq!(store.load("queue.jsonl").unwrap(), None);
+        store.append("queue.jsonl", b"one\n").unwrap();
+        store.append("queue.jsonl", b"two\n").unwrap();
+        assert_eq!(load_string(store, "queue.jsonl").unwrap().as_deref(), Some("one\ntwo\n"));
+
+        store.save("queue.jsonl", b"two\n").unwrap();
+        assert_eq!(store.load("queue.jsonl").unwrap(), Some(b"two\n".to_vec()));
+
+        store.remove("queue.jsonl").unwrap();
+        store.remove("queue.jsonl").unwrap();
+        assert_eq!(store.load("queue.jsonl").unwrap(), None);
+    }
+
+    #[test]
+    fn test_memory_store() {
+        let store = MemoryStore::new();
+        exercise(&store);
+        store.save("a", b"1").unwrap();
+        assert_eq!(store.keys(), vec!["a".to_string()]);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_store() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        exercise(&store);
+
+        // Appends keep arbitrary bytes, not just text
+        store.append("raw", &[0xff, 0x00]).unwrap();
+        store.append("raw", &[0x01]).unwrap();
+        assert_eq!(store.load("raw").unwrap(), Some(vec![0xff, 0x00, 0x01]));
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = std::env::temp_dir().join(format!("lf_state_{}", std::process::id()));
+        let store = FileStore::new(&dir);
+        exercise(&store);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(store.path("../escape").is_err());
+        assert!(store.path("nested/key").is_err());
+        assert_eq!(store.path("outbox.jsonl").unwrap(), dir.join("outbox.jsonl"));
+    }
+}
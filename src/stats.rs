@@ -0,0 +1,205 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of per-item throughput samples kept for percentiles
+const MAX_SAMPLES: usize = 10_000;
+
+/// Live counters for a bulk operation or tree transfer
+///
+/// Shared between workers and observers; call `snapshot` at any time,
+/// mid-run or after completion, for a consistent view.
+#[derive(Debug, Default)]
+pub struct TransferStats {
+    bytes: AtomicU64,
+    files: AtomicU64,
+    retries: AtomicU64,
+    failures: AtomicU64,
+    timing: Mutex<Timing>,
+}
+
+#[derive(Debug, Default)]
+struct Timing {
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    /// Most recent per-item throughputs, bytes per second
+    samples: VecDeque<f64>,
+}
+
+/// Point-in-time copy of `TransferStats`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransferStatsSnapshot {
+    pub bytes: u64,
+    pub files: u64,
+    pub retries: u64,
+    pub failures: u64,
+    /// Time since the operation started, frozen once it finishes
+    pub wall_time: Duration,
+    /// Per-item throughput percentiles, bytes per second
+    pub throughput: ThroughputPercentiles,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ThroughputPercentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl TransferStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start the wall clock if it is not already running
+    pub fn mark_started(&self) {
+        let mut timing = self.timing();
+        if timing.started.is_none() {
+            timing.started = Some(Instant::now());
+        }
+        timing.finished = None;
+    }
+
+    /// Stop the wall clock
+    pub fn mark_finished(&self) {
+        let mut timing = self.timing();
+        if timing.started.is_some() {
+            timing.finished = Some(Instant::now());
+        }
+    }
+
+    /// Count one completed item that moved `bytes` in `elapsed`
+    pub fn record_file(&self, bytes: u64, elapsed: Duration) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.files.fetch_add(1, Ordering::Relaxed);
+
+        if bytes > 0 && !elapsed.is_zero() {
+            let mut timing = self.timing();
+            if timing.samples.len() == MAX_SAMPLES {
+                timing.samples.pop_front();
+            }
+            timing.samples.push_back(bytes as f64 / elapsed.as_secs_f64());
+        }
+    }
+
+    /// Count one retried request
+    pub fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count one item that failed for good
+    pub fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Current totals, wall time, and throughput percentiles
+    pub fn snapshot(&self) -> TransferStatsSnapshot {
+        let timing = self.timing();
+        let wall_time = match (timing.started, timing.finished) {
+            (Some(started), Some(finished)) => finished.duration_since(started),
+            (Some(started), None) => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+
+        let mut samples: Vec<f64> = timing.samples.iter().copied().collect();
+        drop(timing);
+        samples.sort_by(|a, b| a.total_cmp(b));
+
+        TransferStatsSnapshot {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            files: self.files.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            wall_time,
+            throughput: ThroughputPercentiles {
+                p50: percentile(&samples, 0.50),
+                p90: percentile(&samples, 0.90),
+                p99: percentile(&samples, 0.99),
+            },
+        }
+    }
+
+    fn timing(&self) -> std::sync::MutexGuard<'_, Timing> {
+        self.timing.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl TransferStatsSnapshot {
+    /// Overall bytes per second across the wall time
+    pub fn bytes_per_second(&self) -> f64 {
+        if self.wall_time.is_zero() {
+            return 0.0;
+        }
+        self.bytes as f64 / self.wall_time.as_secs_f64()
+    }
+}
+
+/// Nearest-rank percentile of sorted `samples`
+fn percentile(samples: &[f64], p: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let rank = (p * samples.len() as f64).ceil() as usize;
+    samples[rank.clamp(1, samples.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counters_and_percentiles() {
+        let stats = TransferStats::new();
+        stats.mark_started();
+        for kb in 1..=100u64 {
+            stats.record_file(kb * 1000, Duration::from_secs(1));
+        }
+        stats.record_retry();
+        stats.record_failure();
+        stats.mark_finished();
+
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.files, 100);
+        assert_eq!(snapshot.bytes, 5_050_000);
+        assert_eq!(snapshot.retries, 1);
+        assert_eq!(snapshot.failures, 1);
+        assert_eq!(snapshot.throughput.p50, 50_000.0);
+        assert_eq!(snapshot.throughput.p90, 90_000.0);
+        assert_eq!(snapshot.throughput.p99, 99_000.0);
+
+        // Wall time is frozen after mark_finished
+        assert_eq!(stats.snapshot().wall_time, snapshot.wall_time);
+    }
+
+    #[test]
+    fn test_empty_stats() {
+        let snapshot = TransferStats::new().snapshot();
+        assert_eq!(snapshot, TransferStatsSnapshot::default());
+        assert_eq!(snapshot.bytes_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_zero_byte_items_skip_samples() {
+        let stats = TransferStats::new();
+        stats.record_file(0, Duration::from_millis(5));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.files, 1);
+        assert_eq!(snapshot.throughput.p50, 0.0);
+    }
+
+    #[test]
+    fn test_sample_window_is_bounded() {
+        let stats = TransferStats::new();
+        for _ in 0..MAX_SAMPLES + 10 {
+            stats.record_file(10, Duration::from_secs(1));
+        }
+        assert_eq!(stats.timing().samples.len(), MAX_SAMPLES);
+    }
+}
@@ -0,0 +1,807 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+use crate::export::{export_file_name, CollisionPolicy};
+use crate::laserfiche::{
+    Auth, Entry, EntryOrError, Error, ErrorKind, FolderId, ImportResultOrError, LFAPIError, LFApiServer, LFObject,
+    ListOptions, Result, WrittenOrError,
+};
+use crate::manifest::{self, from_versioned_json, to_versioned_json};
+use crate::mime;
+use crate::naming::NamingPolicy;
+use crate::pipeline::Pipeline;
+use crate::state::{self, FileStore, StateStore};
+
+use futures_util::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// Key `FolderSync` keeps its state under unless told otherwise
+pub const STATE_KEY: &str = ".lfsync.json";
+
+/// Which side of a `FolderSync` is copied to the other
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    /// The local directory is the source; the repository folder follows it
+    #[default]
+    Upload,
+    /// The repository folder is the source; the local directory follows it
+    Download,
+}
+
+/// How a local file is judged changed since it was last synced
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeDetection {
+    /// Its size or modification time differs
+    #[default]
+    SizeAndModified,
+    /// Its size or SHA-256 differs, so a file touched but not edited is
+    /// left alone; files synced without a hash fall back to modification time
+    Hash,
+}
+
+/// What a sync did, or would do on a dry run, to one file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    /// Copied to the other side for the first time
+    Create,
+    /// Copied over the other side's older copy
+    Update,
+    /// Removed from the other side because the source no longer has it
+    Delete,
+}
+
+/// One file as of the last sync that copied it
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncedFile {
+    pub entry_id: i64,
+    /// Size of the local copy in bytes
+    pub size: u64,
+    /// Modification time of the local copy, in Unix milliseconds
+    pub modified: i64,
+    /// Lowercase hex SHA-256 of the local copy, with `ChangeDetection::Hash`
+    #[serde(default)]
+    pub sha256: Option<String>,
+    /// The entry's `last_modified_time`, for downloads
+    #[serde(default)]
+    pub repository_modified: Option<String>,
+}
+
+/// What `FolderSync` last copied, by path relative to the local directory
+/// with `/` separators
+///
+/// Kept in a `StateStore` between runs, so each run transfers only what
+/// changed since the one before.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncState {
+    pub files: BTreeMap<String, SyncedFile>,
+}
+
+impl SyncState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serialize as pretty-printed JSON, tagged with `manifest::FORMAT_VERSION`
+    pub fn to_json(&self) -> Result<String> {
+        to_versioned_json("sync", self)
+    }
+
+    /// Parse JSON written by `to_json` of this or an earlier crate version
+    pub fn from_json(json: &str) -> Result<Self> {
+        from_versioned_json("sync", json)
+    }
+
+    /// Read the state saved under `key` by `save_to`, if any
+    pub fn load_from(store: &dyn StateStore, key: &str) -> Result<Option<Self>> {
+        state::load_string(store, key)?.map(|json| Self::from_json(&json)).transpose()
+    }
+
+    /// Save the state as JSON under `key`, replacing any saved before
+    pub fn save_to(&self, store: &dyn StateStore, key: &str) -> Result<()> {
+        store.save(key, self.to_json()?.as_bytes())
+    }
+}
+
+/// A file `FolderSync::run` copied or deleted, or would on a dry run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SyncChange {
+    /// Path relative to the local directory, with `/` separators
+    pub path: String,
+    pub action: SyncAction,
+    /// The repository entry, once known; unset for a file not yet uploaded
+    pub entry_id: Option<i64>,
+    /// Bytes transferred; 0 for deletes and dry runs
+    pub bytes: u64,
+}
+
+/// A file `FolderSync::run` could not sync; it is tried again next run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SyncFailure {
+    pub path: String,
+    pub action: SyncAction,
+    pub error: String,
+}
+
+/// Consolidated result of `FolderSync::run`, serializable for scripts and
+/// dashboards with `to_json`
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SyncReport {
+    pub direction: SyncDirection,
+    /// True if nothing was transferred or deleted and `changes` is the plan
+    pub dry_run: bool,
+    pub changes: Vec<SyncChange>,
+    /// Files already the same on both sides
+    pub unchanged: usize,
+    pub failed: Vec<SyncFailure>,
+    /// Changes not started because the pipeline's budget ran out
+    pub deferred: Vec<SyncChange>,
+    #[serde(skip)]
+    pub stats: crate::stats::TransferStatsSnapshot,
+}
+
+impl SyncReport {
+    /// True when every change was applied
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.deferred.is_empty()
+    }
+
+    /// Serialize as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialize sync report: {}", e).into())
+    }
+}
+
+/// Mirrors a local directory into a repository folder, or the folder into
+/// the directory, transferring only what changed since the last run
+///
+/// The first run copies everything. Each run records what it copied in a
+/// `SyncState`, by default in `.lfsync.json` in the local directory, and
+/// the next compares against it: with `SyncDirection::Upload`, local files
+/// that are new or changed by `ChangeDetection` are imported or have their
+/// content replaced; with `SyncDirection::Download`, documents that are new
+/// or modified in the repository, or whose local copy was changed, are
+/// downloaded. Files gone from the source are deleted from the other side
+/// only with `delete_missing`. Hidden files (names starting with `.`) are
+/// never synced, and folders are created but not deleted.
+///
+/// Transfers run through a `Pipeline`. A failed file is reported and left
+/// out of the state, so the next run tries it again.
+#[derive(Debug, Clone)]
+pub struct FolderSync {
+    local_dir: PathBuf,
+    folder_id: FolderId,
+    direction: SyncDirection,
+    detection: ChangeDetection,
+    delete_missing: bool,
+    dry_run: bool,
+    volume_name: String,
+    pipeline: Pipeline,
+    store: Arc<dyn StateStore>,
+    state_key: String,
+}
+
+impl FolderSync {
+    /// Sync `local_dir` with the repository folder `folder_id`, copying in
+    /// `direction`
+    pub fn new(local_dir: impl Into<PathBuf>, folder_id: impl Into<FolderId>, direction: SyncDirection) -> Self {
+        let local_dir = local_dir.into();
+        FolderSync {
+            store: Arc::new(FileStore::new(&local_dir)),
+            local_dir,
+            folder_id: folder_id.into(),
+            direction,
+            detection: ChangeDetection::default(),
+            delete_missing: false,
+            dry_run: false,
+            volume_name: "DEFAULTVOL".to_string(),
+            pipeline: Pipeline::default(),
+            state_key: STATE_KEY.to_string(),
+        }
+    }
+
+    /// How local changes are detected (default size and modification time)
+    pub fn detection(mut self, detection: ChangeDetection) -> Self {
+        self.detection = detection;
+        self
+    }
+
+    /// Delete files from the destination once the source no longer has them
+    /// (default off)
+    pub fn delete_missing(mut self, delete_missing: bool) -> Self {
+        self.delete_missing = delete_missing;
+        self
+    }
+
+    /// Report what would change without transferring or deleting anything
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Volume for repository folders created for new local directories
+    /// (default `DEFAULTVOL`)
+    pub fn volume_name(mut self, volume_name: impl Into<String>) -> Self {
+        self.volume_name = volume_name.into();
+        self
+    }
+
+    /// Transfer through `pipeline`, e.g. one with a throttle, budget, or
+    /// progress observer
+    pub fn with_pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Keep the sync state under `key` in `store` instead of in the local
+    /// directory
+    pub fn with_state(mut self, store: Arc<dyn StateStore>, key: impl Into<String>) -> Self {
+        self.store = store;
+        self.state_key = key.into();
+        self
+    }
+
+    /// The pipeline transfers run through
+    pub fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// The state saved by the last run, or an empty one before the first
+    pub fn state(&self) -> Result<SyncState> {
+        Ok(SyncState::load_from(self.store.as_ref(), &self.state_key)?.unwrap_or_default())
+    }
+
+    /// Compare both sides and copy what changed
+    ///
+    /// The state is saved after each file, so an interrupted run keeps
+    /// what it finished. Errors listing either side fail the run before
+    /// anything is changed; errors syncing a file are listed in the report.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn run(&self, api_server: LFApiServer, auth: Auth) -> Result<SyncReport> {
+        let mut state = self.state()?;
+        let plan = match self.direction {
+            SyncDirection::Upload => self.plan_upload(&state)?,
+            SyncDirection::Download => self.plan_download(api_server.clone(), auth.clone(), &state).await?,
+        };
+
+        let mut report = SyncReport {
+            direction: self.direction,
+            dry_run: self.dry_run,
+            unchanged: plan.unchanged,
+            ..Default::default()
+        };
+        if self.dry_run {
+            report.changes = plan.tasks.iter().map(SyncTask::planned).collect();
+            return Ok(report);
+        }
+
+        let tasks = self.resolve_folders(&api_server, &auth, plan.tasks, &mut report).await?;
+        let planned: Vec<SyncChange> = tasks.iter().map(SyncTask::planned).collect();
+        let stats = Arc::clone(self.pipeline.stats());
+        let detection = self.detection;
+        let mut save_error = None;
+        let mut deferred = Vec::new();
+        self.pipeline.run_within_budget(tasks, move |task: SyncTask| {
+            let (api_server, auth, stats) = (api_server.clone(), auth.clone(), Arc::clone(&stats));
+            async move {
+                let planned = task.planned();
+                let started = Instant::now();
+                // Boxed so the large transfer future stays off the worker's stack
+                match Box::pin(task.run(api_server, auth, detection)).await {
+                    Ok(applied) => {
+                        stats.record_file(applied.change.bytes, started.elapsed());
+                        Ok(applied)
+                    }
+                    Err(error) => Err(SyncFailure { path: planned.path, action: planned.action, error: describe(&error) }),
+                }
+            }
+        }, |outcome| {
+            match outcome {
+                Ok(Ok(applied)) => {
+                    match applied.synced {
+                        Some(synced) => state.files.insert(applied.change.path.clone(), synced),
+                        None => state.files.remove(&applied.change.path),
+                    };
+                    if let Err(error) = state.save_to(self.store.as_ref(), &self.state_key) {
+                        save_error.get_or_insert(error);
+                    }
+                    report.changes.push(applied.change);
+                }
+                Ok(Err(failure)) => {
+                    self.pipeline.stats().record_failure();
+                    report.failed.push(failure);
+                }
+                Err(panic) => {
+                    let change = &planned[panic.index];
+                    report.failed.push(SyncFailure { path: change.path.clone(), action: change.action, error: panic.to_string() });
+                }
+            }
+        }, |_, task| deferred.push(task.planned())).await;
+
+        if let Some(error) = save_error {
+            return Err(error);
+        }
+        report.deferred = deferred;
+        report.stats = self.pipeline.stats().snapshot();
+        Ok(report)
+    }
+
+    /// Compare the local directory against `state`: new files are
+    /// imported, changed ones replaced, and missing ones deleted
+    fn plan_upload(&self, state: &SyncState) -> Result<SyncPlan> {
+        let local = scan(&self.local_dir)?;
+        let mut plan = SyncPlan::default();
+        for (path, file) in &local {
+            match state.files.get(path) {
+                None => plan.tasks.push(SyncTask::Import { path: path.clone(), local: file.path.clone(), folder_id: None }),
+                Some(synced) if file.differs(synced, self.detection)? => plan.tasks.push(SyncTask::Replace {
+                    path: path.clone(),
+                    local: file.path.clone(),
+                    entry_id: synced.entry_id,
+                }),
+                Some(_) => plan.unchanged += 1,
+            }
+        }
+        if self.delete_missing {
+            for (path, synced) in &state.files {
+                if !local.contains_key(path) {
+                    plan.tasks.push(SyncTask::DeleteEntry { path: path.clone(), entry_id: synced.entry_id });
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Compare the repository folder against `state` and the local copies:
+    /// new or modified documents, and ones whose copy was changed or
+    /// removed locally, are downloaded; copies of documents gone from the
+    /// folder are deleted
+    async fn plan_download(&self, api_server: LFApiServer, auth: Auth, state: &SyncState) -> Result<SyncPlan> {
+        let mut directories = BTreeMap::from([(self.folder_id.as_i64(), String::new())]);
+        let mut listed = BTreeSet::new();
+        let mut plan = SyncPlan::default();
+
+        let mut entries = Entry::walk(api_server, auth, self.folder_id, ListOptions::default());
+        while let Some(entry) = entries.try_next().await? {
+            let Some(directory) = directories.get(&entry.parent_id).cloned() else {
+                continue;
+            };
+            if entry.is_container {
+                directories.insert(entry.id, join(&directory, &NamingPolicy::sanitize(entry.name.trim())));
+                continue;
+            }
+            if !entry.is_document() {
+                continue;
+            }
+
+            // Documents whose names clash are told apart as an export would
+            let resolved = CollisionPolicy::AppendEntryId.resolve(
+                &export_file_name(&entry),
+                entry.id,
+                &self.local_dir.join(&directory),
+                |name| listed.contains(&join(&directory, name)),
+            )?;
+            let Some(name) = resolved else { continue };
+            let path = join(&directory, &name);
+            listed.insert(path.clone());
+
+            let local = local_file(&self.local_path(&path))?;
+            let task = |action| SyncTask::Download {
+                path: path.clone(),
+                local: self.local_path(&path),
+                entry_id: entry.id,
+                repository_modified: entry.last_modified_time.clone(),
+                action,
+            };
+            match (state.files.get(&path), local) {
+                (None, _) => plan.tasks.push(task(SyncAction::Create)),
+                (Some(_), None) => plan.tasks.push(task(SyncAction::Update)),
+                (Some(synced), Some(local)) => {
+                    let modified = synced.entry_id != entry.id
+                        || synced.repository_modified.as_deref() != Some(entry.last_modified_time.as_str());
+                    if modified || local.differs(synced, self.detection)? {
+                        plan.tasks.push(task(SyncAction::Update));
+                    } else {
+                        plan.unchanged += 1;
+                    }
+                }
+            }
+        }
+
+        if self.delete_missing {
+            for (path, synced) in &state.files {
+                if !listed.contains(path) {
+                    plan.tasks.push(SyncTask::DeleteFile {
+                        path: path.clone(),
+                        local: self.local_path(path),
+                        entry_id: synced.entry_id,
+                    });
+                }
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Find or create the repository folder each import goes in, one
+    /// directory at a time; imports whose folder cannot be made are failed
+    async fn resolve_folders(
+        &self,
+        api_server: &LFApiServer,
+        auth: &Auth,
+        tasks: Vec<SyncTask>,
+        report: &mut SyncReport
+    ) -> Result<Vec<SyncTask>> {
+        let mut folders: BTreeMap<String, std::result::Result<FolderId, String>> = BTreeMap::new();
+        let mut resolved = Vec::with_capacity(tasks.len());
+        for mut task in tasks {
+            if let SyncTask::Import { path, folder_id, .. } = &mut task {
+                let directory = path.rsplit_once('/').map_or("", |(directory, _)| directory).to_string();
+                if !folders.contains_key(&directory) {
+                    let folder = self.ensure_folder(api_server, auth, &directory).await?;
+                    folders.insert(directory.clone(), folder);
+                }
+                match &folders[&directory] {
+                    Ok(id) => *folder_id = Some(*id),
+                    Err(error) => {
+                        report.failed.push(SyncFailure { path: path.clone(), action: SyncAction::Create, error: error.clone() });
+                        continue;
+                    }
+                }
+            }
+            resolved.push(task);
+        }
+        Ok(resolved)
+    }
+
+    async fn ensure_folder(
+        &self,
+        api_server: &LFApiServer,
+        auth: &Auth,
+        directory: &str
+    ) -> Result<std::result::Result<FolderId, String>> {
+        if directory.is_empty() {
+            return Ok(Ok(self.folder_id));
+        }
+        let folder = Entry::ensure_path_under(
+            api_server.clone(), auth.clone(), self.folder_id, directory, self.volume_name.clone()
+        ).await;
+        Ok(match folder {
+            Ok(EntryOrError::Entry(entry)) => Ok(FolderId(entry.id)),
+            Ok(EntryOrError::LFAPIError(error)) => Err(error.to_string()),
+            // A name the repository refuses fails that directory's files, not the run
+            Err(Error(ErrorKind::ValidationError(error), _)) => Err(error.to_string()),
+            Err(error) => return Err(error),
+        })
+    }
+
+    fn local_path(&self, path: &str) -> PathBuf {
+        path.split('/').fold(self.local_dir.clone(), |local, segment| local.join(segment))
+    }
+}
+
+#[derive(Default)]
+struct SyncPlan {
+    tasks: Vec<SyncTask>,
+    unchanged: usize,
+}
+
+/// One change a sync applies
+enum SyncTask {
+    /// Import a new local file into `folder_id`, resolved before the run
+    Import { path: String, local: PathBuf, folder_id: Option<FolderId> },
+    /// Replace a document's content with its changed local file
+    Replace { path: String, local: PathBuf, entry_id: i64 },
+    /// Delete a document whose local file is gone
+    DeleteEntry { path: String, entry_id: i64 },
+    /// Save a document to its local file
+    Download { path: String, local: PathBuf, entry_id: i64, repository_modified: String, action: SyncAction },
+    /// Delete the local copy of a document gone from the repository
+    DeleteFile { path: String, local: PathBuf, entry_id: i64 },
+}
+
+/// A task's result: the change made and the file's new state, or `None`
+/// once it is deleted
+struct Applied {
+    change: SyncChange,
+    synced: Option<SyncedFile>,
+}
+
+impl SyncTask {
+    /// The change as a dry run reports it
+    fn planned(&self) -> SyncChange {
+        let (path, action, entry_id) = match self {
+            SyncTask::Import { path, .. } => (path, SyncAction::Create, None),
+            SyncTask::Replace { path, entry_id, .. } => (path, SyncAction::Update, Some(*entry_id)),
+            SyncTask::DeleteEntry { path, entry_id } | SyncTask::DeleteFile { path, entry_id, .. } => {
+                (path, SyncAction::Delete, Some(*entry_id))
+            }
+            SyncTask::Download { path, entry_id, action, .. } => (path, *action, Some(*entry_id)),
+        };
+        SyncChange { path: path.clone(), action, entry_id, bytes: 0 }
+    }
+
+    async fn run(self, api_server: LFApiServer, auth: Auth, detection: ChangeDetection) -> Result<Applied> {
+        let mut change = self.planned();
+        let synced = match self {
+            SyncTask::Import { local, folder_id, .. } => {
+                let folder_id = folder_id.ok_or("Import folder was not resolved")?;
+                let file_name = file_name(&local)?;
+                let entry_id = match Entry::import(api_server, auth, path_string(&local)?, file_name, folder_id).await? {
+                    ImportResultOrError::ImportResult(result) => result.operations.entry_create.entry_id,
+                    ImportResultOrError::LFAPIError(error) => return Err(api_error(error)),
+                };
+                change.entry_id = Some(entry_id);
+                Some(SyncedFile::of_local(&local, entry_id, None, detection)?)
+            }
+            SyncTask::Replace { local, entry_id, .. } => {
+                let file = tokio::fs::File::open(&local).await?;
+                let length = file.metadata().await?.len();
+                let mime_type = mime::detect_mime_type_of_file(&file_name(&local)?, &local)?;
+                if let WrittenOrError::LFAPIError(error) = Entry::set_edoc(api_server, auth, entry_id, file, length, &mime_type).await? {
+                    return Err(api_error(error));
+                }
+                Some(SyncedFile::of_local(&local, entry_id, None, detection)?)
+            }
+            SyncTask::DeleteEntry { entry_id, .. } => {
+                match Entry::delete(api_server, auth, entry_id, "Removed by folder sync".to_string()).await? {
+                    LFObject::LFAPIError(error) if !error.is_not_found() => return Err(api_error(error)),
+                    _ => None,
+                }
+            }
+            SyncTask::Download { local, entry_id, repository_modified, .. } => {
+                download(api_server, auth, entry_id, &local).await?;
+                Some(SyncedFile::of_local(&local, entry_id, Some(repository_modified), detection)?)
+            }
+            SyncTask::DeleteFile { local, .. } => {
+                match std::fs::remove_file(&local) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => None,
+                }
+            }
+        };
+        if let (Some(synced), SyncAction::Create | SyncAction::Update) = (&synced, change.action) {
+            change.bytes = synced.size;
+        }
+        Ok(Applied { change, synced })
+    }
+}
+
+impl SyncedFile {
+    /// The state of `local` just after it was synced with `entry_id`
+    fn of_local(
+        local: &Path,
+        entry_id: i64,
+        repository_modified: Option<String>,
+        detection: ChangeDetection
+    ) -> Result<Self> {
+        let file = local_file(local)?.ok_or_else(|| format!("'{}' is missing after sync", local.display()))?;
+        let sha256 = match detection {
+            ChangeDetection::Hash => Some(manifest::sha256_file(local)?),
+            ChangeDetection::SizeAndModified => None,
+        };
+        Ok(SyncedFile { entry_id, size: file.size, modified: file.modified, sha256, repository_modified })
+    }
+}
+
+/// A local file as found by `scan`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LocalFile {
+    path: PathBuf,
+    size: u64,
+    modified: i64,
+}
+
+impl LocalFile {
+    /// True if this file is not the one recorded in `synced`
+    fn differs(&self, synced: &SyncedFile, detection: ChangeDetection) -> Result<bool> {
+        if self.size != synced.size {
+            return Ok(true);
+        }
+        match (detection, &synced.sha256) {
+            (ChangeDetection::Hash, Some(sha256)) => Ok(manifest::sha256_file(&self.path)? != *sha256),
+            _ => Ok(self.modified != synced.modified),
+        }
+    }
+}
+
+/// The file at `path`, or `None` if there is none
+fn local_file(path: &Path) -> Result<Option<LocalFile>> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let modified = metadata.modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| i64::try_from(since.as_millis()).unwrap_or(i64::MAX));
+    Ok(Some(LocalFile { path: path.to_path_buf(), size: metadata.len(), modified }))
+}
+
+/// Every file below `dir` that is not hidden, by relative path with `/`
+/// separators; a missing directory has none
+fn scan(dir: &Path) -> Result<BTreeMap<String, LocalFile>> {
+    let mut files = BTreeMap::new();
+    let mut pending = vec![(dir.to_path_buf(), String::new())];
+    while let Some((directory, relative)) = pending.pop() {
+        let children = match std::fs::read_dir(&directory) {
+            Ok(children) => children,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && relative.is_empty() => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for child in children {
+            let child = child?;
+            let name = child.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = join(&relative, &name);
+            let file_type = child.file_type()?;
+            if file_type.is_dir() {
+                pending.push((child.path(), path));
+            } else if let Some(file) = local_file(&child.path())? {
+                files.insert(path, file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// `name` below the relative directory `directory`
+fn join(directory: &str, name: &str) -> String {
+    if directory.is_empty() { name.to_string() } else { format!("{}/{}", directory, name) }
+}
+
+/// Save a document to `local` through a `.part` file beside it, so a
+/// failure leaves any earlier copy in place
+async fn download(api_server: LFApiServer, auth: Auth, entry_id: i64, local: &Path) -> Result<u64> {
+    if let Some(parent) = local.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let mut part = local.as_os_str().to_owned();
+    part.push(".part");
+    let part = PathBuf::from(part);
+
+    let mut file = tokio::fs::File::create(&part).await?;
+    let written = Entry::export_stream(api_server, auth, entry_id, &mut file).await;
+    drop(file);
+    let bytes = match written {
+        Ok(WrittenOrError::Written(bytes)) => bytes,
+        Ok(WrittenOrError::LFAPIError(error)) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(api_error(error));
+        }
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&part).await;
+            return Err(error);
+        }
+    };
+    tokio::fs::rename(&part, local).await?;
+    Ok(bytes)
+}
+
+fn api_error(error: LFAPIError) -> Error {
+    ErrorKind::ApiError(Box::new(error)).into()
+}
+
+fn describe(error: &Error) -> String {
+    match error.kind() {
+        ErrorKind::ApiError(error) => error.detail.clone()
+            .or_else(|| error.title.clone())
+            .unwrap_or_else(|| format!("API error (status {:?})", error.status)),
+        _ => error.to_string(),
+    }
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("'{}' has no file name", path.display()).into())
+}
+
+fn path_string(path: &Path) -> Result<String> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("'{}' is not valid UTF-8", path.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lf_sync_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("2024")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_scan_skips_hidden_files() {
+        let dir = scratch("scan");
+        std::fs::write(dir.join("a.pdf"), b"one").unwrap();
+        std::fs::write(dir.join("2024").join("b.pdf"), b"two").unwrap();
+        std::fs::write(dir.join(STATE_KEY), b"{}").unwrap();
+
+        let files = scan(&dir).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec!["2024/b.pdf", "a.pdf"]);
+        assert_eq!(files["2024/b.pdf"].size, 3);
+        assert!(scan(&dir.join("missing")).unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plan_upload() {
+        let dir = scratch("plan");
+        for name in ["new.pdf", "same.pdf", "edited.pdf"] {
+            std::fs::write(dir.join(name), b"content").unwrap();
+        }
+        let files = scan(&dir).unwrap();
+        let synced = |path: &str, entry_id| (path.to_string(), SyncedFile {
+            entry_id,
+            size: files[path].size,
+            modified: files[path].modified,
+            ..Default::default()
+        });
+        let mut state = SyncState { files: BTreeMap::from([synced("same.pdf", 1), synced("edited.pdf", 2)]) };
+        state.files.get_mut("edited.pdf").unwrap().modified -= 1;
+        state.files.insert("gone.pdf".to_string(), SyncedFile { entry_id: 3, ..Default::default() });
+
+        let sync = FolderSync::new(&dir, FolderId(10), SyncDirection::Upload).delete_missing(true);
+        let plan = sync.plan_upload(&state).unwrap();
+        let planned: Vec<SyncChange> = plan.tasks.iter().map(SyncTask::planned).collect();
+        let actions: Vec<(&str, SyncAction, Option<i64>)> = planned.iter()
+            .map(|change| (change.path.as_str(), change.action, change.entry_id))
+            .collect();
+        assert_eq!(actions, vec![
+            ("edited.pdf", SyncAction::Update, Some(2)),
+            ("new.pdf", SyncAction::Create, None),
+            ("gone.pdf", SyncAction::Delete, Some(3)),
+        ]);
+        assert_eq!(plan.unchanged, 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_hash_detection_ignores_touched_files() {
+        let dir = scratch("hash");
+        let path = dir.join("a.pdf");
+        std::fs::write(&path, b"content").unwrap();
+        let synced = SyncedFile::of_local(&path, 1, None, ChangeDetection::Hash).unwrap();
+        let touched = LocalFile { modified: synced.modified + 1000, ..local_file(&path).unwrap().unwrap() };
+
+        assert!(!touched.differs(&synced, ChangeDetection::Hash).unwrap());
+        assert!(touched.differs(&synced, ChangeDetection::SizeAndModified).unwrap());
+        std::fs::write(&path, b"CONTENT").unwrap();
+        assert!(touched.differs(&synced, ChangeDetection::Hash).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sync_state_round_trip() {
+        let store = state::MemoryStore::new();
+        let state = SyncState {
+            files: BTreeMap::from([("2024/a.pdf".to_string(), SyncedFile {
+                entry_id: 7,
+                size: 3,
+                modified: 1_700_000_000_000,
+                sha256: Some("ab".repeat(32)),
+                repository_modified: None,
+            })]),
+        };
+        state.save_to(&store, STATE_KEY).unwrap();
+        assert_eq!(SyncState::load_from(&store, STATE_KEY).unwrap(), Some(state));
+        assert_eq!(SyncState::load_from(&store, "other.json").unwrap(), None);
+    }
+}
@@ -0,0 +1,254 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::laserfiche::{
+    box_stream, http_client, impl_into_result, Auth, BoxStream, Error, ErrorKind, LFAPIError, LFApiServer, MaybeSend, NextLink,
+    RequestUrl, Result, Tag,
+};
+use crate::retry::RetryingSend;
+
+use futures_util::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+/// One page of the repository's tag definitions
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TagDefinitionsPage {
+    pub value: Vec<Tag>,
+    #[serde(rename = "@odata.nextLink", default, skip_serializing_if = "Option::is_none")]
+    pub odata_next_link: Option<String>,
+    #[serde(rename = "@odata.count", default, skip_serializing_if = "Option::is_none")]
+    pub odata_count: Option<i64>,
+}
+
+impl TagDefinitionsPage {
+    /// Cursor for the next page, if the server returned one
+    pub fn next_link(&self) -> Option<NextLink> {
+        self.odata_next_link.clone().map(NextLink)
+    }
+}
+
+pub enum TagDefinitionsOrError {
+    TagDefinitions(TagDefinitionsPage),
+    LFAPIError(LFAPIError),
+}
+
+pub enum TagOrError {
+    Tag(Tag),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(
+    TagDefinitionsOrError::TagDefinitions => TagDefinitionsPage,
+    TagOrError::Tag => Tag,
+);
+
+/// Tags defined in the repository, for looking up the ids `Entry::set_tags`
+/// takes
+pub struct TagDefinitions;
+
+impl TagDefinitions {
+    /// Fetch the first page of tag definitions, ordered by name
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub async fn list(api_server: LFApiServer, auth: Auth) -> Result<TagDefinitionsOrError> {
+        let url = RequestUrl::repository(&api_server)?
+            .segment("TagDefinitions")
+            .query("$orderby", "name asc")
+            .build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Fetch the page of tag definitions a previous page links to
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `next_link` - From `TagDefinitionsPage::next_link`
+    pub async fn list_next(api_server: LFApiServer, auth: Auth, next_link: NextLink) -> Result<TagDefinitionsOrError> {
+        let url = RequestUrl::next_link(&api_server, &next_link)?.build()?;
+        Self::fetch_page(url, &auth).await
+    }
+
+    /// Every tag definition, following next links as the stream is read
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    pub fn list_all(api_server: LFApiServer, auth: Auth) -> BoxStream<'static, Result<Tag>> {
+        paginate_tags(move |next_link| {
+            let (api_server, auth) = (api_server.clone(), auth.clone());
+            async move {
+                match next_link {
+                    None => Self::list(api_server, auth).await,
+                    Some(next_link) => Self::list_next(api_server, auth, next_link).await,
+                }
+            }
+        })
+    }
+
+    /// Fetch one tag definition
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `tag_id` - Tag definition ID
+    pub async fn get(api_server: LFApiServer, auth: Auth, tag_id: i64) -> Result<TagOrError> {
+        let url = RequestUrl::repository(&api_server)?
+            .segment("TagDefinitions")
+            .segment(&tag_id.to_string())
+            .build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(TagOrError::LFAPIError(error));
+        }
+
+        let tag = response.json::<Tag>().await?;
+        Ok(TagOrError::Tag(tag))
+    }
+
+    /// Find the tag definition named `name`, ignoring case
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `name` - Tag name
+    pub async fn find_by_name(api_server: LFApiServer, auth: Auth, name: &str) -> Result<Option<Tag>> {
+        let mut tags = Self::list_all(api_server, auth);
+        while let Some(tag) = tags.try_next().await? {
+            if tag.name.eq_ignore_ascii_case(name) {
+                return Ok(Some(tag));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Translate tag names to the ids `Entry::set_tags` takes, in the same
+    /// order, ignoring case
+    ///
+    /// Fails naming every tag that is not defined.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `names` - Tag names
+    pub async fn resolve_names(api_server: LFApiServer, auth: Auth, names: &[&str]) -> Result<Vec<i64>> {
+        let tags: Vec<Tag> = Self::list_all(api_server, auth).try_collect().await?;
+        ids_for_names(&tags, names)
+    }
+
+    async fn fetch_page(url: String, auth: &Auth) -> Result<TagDefinitionsOrError> {
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if response.status() != reqwest::StatusCode::OK {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(TagDefinitionsOrError::LFAPIError(error));
+        }
+
+        let page = response.json::<TagDefinitionsPage>().await?;
+        Ok(TagDefinitionsOrError::TagDefinitions(page))
+    }
+}
+
+fn ids_for_names(tags: &[Tag], names: &[&str]) -> Result<Vec<i64>> {
+    let mut ids = Vec::with_capacity(names.len());
+    let mut unknown = Vec::new();
+    for name in names {
+        match tags.iter().find(|tag| tag.name.eq_ignore_ascii_case(name)) {
+            Some(tag) => ids.push(tag.id),
+            None => unknown.push(*name),
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(format!("Unknown tags: {}", unknown.join(", ")).into());
+    }
+    Ok(ids)
+}
+
+/// Every tag definition across pages, fetching each page only once the
+/// previous one is used up
+///
+/// `fetch` is given `None` for the first page and the next link after that.
+/// An API error ends the stream with `ErrorKind::ApiError`.
+pub(crate) fn paginate_tags<F, Fut>(fetch: F) -> BoxStream<'static, Result<Tag>>
+where
+    F: FnMut(Option<NextLink>) -> Fut + MaybeSend + 'static,
+    Fut: std::future::Future<Output = Result<TagDefinitionsOrError>> + MaybeSend + 'static,
+{
+    let tags = futures_util::stream::try_unfold((Some(None), fetch), |(cursor, mut fetch)| async move {
+        let Some(next_link) = cursor else {
+            return Ok(None);
+        };
+        match fetch(next_link).await? {
+            TagDefinitionsOrError::TagDefinitions(page) => {
+                let cursor = page.next_link().map(Some);
+                Ok(Some((page.value, (cursor, fetch))))
+            }
+            TagDefinitionsOrError::LFAPIError(error) => Err(Error::from(ErrorKind::ApiError(Box::new(error)))),
+        }
+    })
+    .map_ok(|page| futures_util::stream::iter(page.into_iter().map(Ok)))
+    .try_flatten();
+    box_stream(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(id: i64, name: &str) -> Tag {
+        Tag { id, name: name.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_tag_definitions_page_parsing() {
+        let page: TagDefinitionsPage = serde_json::from_str(r#"{
+            "@odata.nextLink": "https://lf.example.com/LFRepositoryAPI/v1/Repositories/repo/TagDefinitions?$skip=1",
+            "value": [{"id": 3, "name": "Confidential", "description": "", "isSecure": true}]
+        }"#).unwrap();
+        assert_eq!(page.value[0].name, "Confidential");
+        assert!(page.value[0].is_secure);
+        assert!(page.next_link().is_some());
+    }
+
+    #[test]
+    fn test_ids_for_names() {
+        let tags = vec![tag(1, "Confidential"), tag(2, "Legal Hold")];
+        assert_eq!(ids_for_names(&tags, &["legal hold", "Confidential"]).unwrap(), vec![2, 1]);
+
+        let error = ids_for_names(&tags, &["Confidential", "Draft", "Final"]).unwrap_err();
+        assert_eq!(error.to_string(), "Unknown tags: Draft, Final");
+    }
+
+    #[tokio::test]
+    async fn test_paginate_tags_follows_next_links() {
+        let tags: Vec<Tag> = paginate_tags(|next_link: Option<NextLink>| async move {
+            Ok(TagDefinitionsOrError::TagDefinitions(match next_link {
+                None => TagDefinitionsPage {
+                    value: vec![tag(1, "A")],
+                    odata_next_link: Some("https://example.com/page2".to_string()),
+                    odata_count: None,
+                },
+                Some(_) => TagDefinitionsPage { value: vec![tag(2, "B")], ..Default::default() },
+            }))
+        })
+        .try_collect()
+        .await
+        .unwrap();
+        assert_eq!(tags.iter().map(|tag| tag.id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+}
@@ -0,0 +1,179 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock::Instant;
+use crate::laserfiche::{
+    Auth, Entry, EntryId, LFApiServer, Result, SearchOptions, TagsOrError,
+};
+use crate::normalize::Scope;
+use crate::pipeline::Pipeline;
+use crate::stats::TransferStatsSnapshot;
+use crate::throttle;
+
+use std::sync::Arc;
+
+/// An entry whose tags could not be updated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagFailure {
+    pub entry_id: EntryId,
+    pub error: String,
+}
+
+/// Consolidated result of `apply_tags_to_search`
+#[derive(Debug, Clone, Default)]
+pub struct TagReport {
+    /// Entries that gained at least one tag
+    pub tagged: Vec<EntryId>,
+    /// Entries that already had every tag
+    pub unchanged: Vec<EntryId>,
+    pub failed: Vec<TagFailure>,
+    /// Hits a pipeline budget left unstarted
+    pub deferred: Vec<EntryId>,
+    /// Why the search stopped early, if it did; entries found before then
+    /// are still processed
+    pub search_error: Option<String>,
+    pub stats: TransferStatsSnapshot,
+}
+
+impl TagReport {
+    /// True when the whole search was read and every hit was tagged
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty() && self.deferred.is_empty() && self.search_error.is_none()
+    }
+}
+
+enum TagOutcome {
+    Tagged(EntryId),
+    Unchanged(EntryId),
+    Failed(TagFailure),
+}
+
+/// Add tags to every entry matched by a search
+///
+/// Hits are read page by page while earlier ones are being tagged, with at
+/// most the pipeline's effective limit of entries in flight. Existing tags
+/// are kept: each entry's tags are read and the union written back. A hit
+/// whose task panics is listed as failed, and hits not started within the
+/// pipeline's budget as deferred.
+///
+/// # Arguments
+/// * `pipeline` - Concurrency limits, throttle, budget, and stats for the run
+/// * `api_server` - API server configuration
+/// * `auth` - Authentication token
+/// * `search_query` - Repository search query
+/// * `options` - OData options for the search
+/// * `tag_ids` - Tags to add
+pub async fn apply_tags_to_search(
+    pipeline: &Pipeline,
+    api_server: LFApiServer,
+    auth: Auth,
+    search_query: String,
+    options: SearchOptions,
+    tag_ids: Vec<i64>
+) -> TagReport {
+    let mut report = TagReport::default();
+    let scope = Scope::Search { query: search_query, options };
+
+    let worker = {
+        let (api_server, auth) = (api_server.clone(), auth.clone());
+        let tag_ids = Arc::new(tag_ids);
+        let stats = Arc::clone(pipeline.stats());
+        move |entry: Entry| {
+            let (api_server, auth, tag_ids) = (api_server.clone(), auth.clone(), Arc::clone(&tag_ids));
+            let stats = Arc::clone(&stats);
+            async move {
+                let entry_id = EntryId(entry.id);
+                let started = Instant::now();
+                match add_tags(api_server, auth, entry_id, &tag_ids).await {
+                    Ok(outcome) => {
+                        if !matches!(outcome, TagOutcome::Failed(_)) {
+                            stats.record_file(0, started.elapsed());
+                        }
+                        outcome
+                    }
+                    Err(error) => TagOutcome::Failed(TagFailure { entry_id, error: error.to_string() }),
+                }
+            }
+        }
+    };
+
+    let mut deferred = Vec::new();
+    let search_error = scope.run(pipeline, api_server, auth, worker, |outcome| match outcome {
+        Ok(TagOutcome::Tagged(entry_id)) => report.tagged.push(entry_id),
+        Ok(TagOutcome::Unchanged(entry_id)) => report.unchanged.push(entry_id),
+        Ok(TagOutcome::Failed(failure)) => {
+            pipeline.stats().record_failure();
+            report.failed.push(failure);
+        }
+        Err((entry_id, panic)) => report.failed.push(TagFailure { entry_id, error: panic.to_string() }),
+    }, |entry_id| deferred.push(entry_id)).await;
+
+    report.deferred = deferred;
+    report.search_error = search_error;
+    report.stats = pipeline.stats().snapshot();
+    report
+}
+
+async fn add_tags(
+    api_server: LFApiServer,
+    auth: Auth,
+    entry_id: EntryId,
+    tag_ids: &[i64]
+) -> Result<TagOutcome> {
+    let current = match Entry::get_tags(api_server.clone(), auth.clone(), entry_id).await? {
+        TagsOrError::Tags(tags) => tags.value.into_iter().map(|tag| tag.id).collect::<Vec<_>>(),
+        TagsOrError::LFAPIError(error) => return Ok(TagOutcome::Failed(TagFailure { entry_id, error: error.to_string() })),
+    };
+
+    let Some(merged) = merge_tag_ids(&current, tag_ids) else {
+        return Ok(TagOutcome::Unchanged(entry_id));
+    };
+
+    throttle::acquire_scoped().await;
+    match Entry::set_tags(api_server, auth, entry_id, merged).await? {
+        TagsOrError::Tags(_) => Ok(TagOutcome::Tagged(entry_id)),
+        TagsOrError::LFAPIError(error) => Ok(TagOutcome::Failed(TagFailure { entry_id, error: error.to_string() })),
+    }
+}
+
+/// `current` plus any of `added` it lacks, or `None` if it lacks none
+fn merge_tag_ids(current: &[i64], added: &[i64]) -> Option<Vec<i64>> {
+    let mut merged = current.to_vec();
+    for tag_id in added {
+        if !merged.contains(tag_id) {
+            merged.push(*tag_id);
+        }
+    }
+    (merged.len() > current.len()).then_some(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_tag_ids() {
+        assert_eq!(merge_tag_ids(&[1, 2], &[2, 3]), Some(vec![1, 2, 3]));
+        assert_eq!(merge_tag_ids(&[], &[4, 4]), Some(vec![4]));
+        assert_eq!(merge_tag_ids(&[1, 2], &[2, 1]), None);
+        assert_eq!(merge_tag_ids(&[1], &[]), None);
+    }
+
+    #[test]
+    fn test_report_is_complete() {
+        let mut report = TagReport::default();
+        assert!(report.is_complete());
+
+        report.search_error = Some("HTTP 500".to_string());
+        assert!(!report.is_complete());
+
+        report.search_error = None;
+        report.deferred.push(EntryId(8));
+        assert!(!report.is_complete());
+
+        report.deferred.clear();
+        report.failed.push(TagFailure { entry_id: EntryId(7), error: "Access denied".to_string() });
+        assert!(!report.is_complete());
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::laserfiche::{http_client, impl_into_result, Auth, LFAPIError, LFApiServer, RequestUrl, Result};
+use crate::retry::RetryingSend;
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+// tokio's `Instant`, so tests can pause time
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use crate::clock::Instant;
+
+/// First wait between status checks while polling an operation
+const FIRST_POLL_DELAY: Duration = Duration::from_millis(250);
+
+/// Longest wait between status checks while polling an operation
+const MAX_POLL_DELAY: Duration = Duration::from_secs(2);
+
+/// State of a long-running operation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OperationStatus {
+    #[default]
+    NotStarted,
+    InProgress,
+    Completed,
+    Failed,
+    Canceled,
+    #[serde(other)]
+    Unknown,
+}
+
+impl OperationStatus {
+    /// True once the operation will make no further progress
+    pub fn is_finished(self) -> bool {
+        matches!(self, OperationStatus::Completed | OperationStatus::Failed | OperationStatus::Canceled)
+    }
+}
+
+/// An error reported by a long-running operation
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OperationError {
+    pub error_code: Option<i64>,
+    pub error_type: Option<String>,
+    pub message: Option<String>,
+}
+
+/// Progress of a long-running operation such as a delete or search
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OperationProgress {
+    pub operation_token: String,
+    pub operation_type: Option<String>,
+    pub percent_complete: i64,
+    pub status: OperationStatus,
+    pub errors: Vec<OperationError>,
+    /// Entry the operation acted on, when it reports one
+    pub entry_id: Option<i64>,
+}
+
+impl OperationProgress {
+    /// The operation's error messages joined into one line
+    pub fn error_summary(&self) -> String {
+        let messages: Vec<&str> = self.errors
+            .iter()
+            .filter_map(|error| error.message.as_deref().or(error.error_type.as_deref()))
+            .collect();
+        if messages.is_empty() {
+            format!("{:?}", self.status)
+        } else {
+            messages.join("; ")
+        }
+    }
+}
+
+pub enum ProgressOrError {
+    Progress(OperationProgress),
+    LFAPIError(LFAPIError),
+}
+
+pub enum CancelledOrError {
+    /// Whether the server still had the operation to cancel
+    Cancelled(bool),
+    LFAPIError(LFAPIError),
+}
+
+impl_into_result!(
+    ProgressOrError::Progress => OperationProgress,
+    CancelledOrError::Cancelled => bool,
+);
+
+/// Long-running operations started by other calls, such as `Entry::delete`,
+/// followed by the token they return
+pub struct Task;
+
+impl Task {
+    /// Check how far an operation has got
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `token` - Operation token, e.g. `DeletedObject::token`
+    pub async fn status(api_server: LFApiServer, auth: Auth, token: &str) -> Result<ProgressOrError> {
+        let url = Self::task_url(&api_server, token)?.build()?;
+
+        let response = http_client()
+            .get(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        // 202 while the operation runs, 200 or 201 once it has finished
+        if !response.status().is_success() {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(ProgressOrError::LFAPIError(error));
+        }
+
+        let progress = response.json::<OperationProgress>().await?;
+        Ok(ProgressOrError::Progress(progress))
+    }
+
+    /// Ask the server to stop an operation
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `token` - Operation token, e.g. `DeletedObject::token`
+    pub async fn cancel(api_server: LFApiServer, auth: Auth, token: &str) -> Result<CancelledOrError> {
+        let url = Self::task_url(&api_server, token)?.build()?;
+
+        let response = http_client()
+            .delete(url)
+            .header("Authorization", format!("Bearer {}", auth.access_token))
+            .send_retrying()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = response.json::<LFAPIError>().await?;
+            return Ok(CancelledOrError::LFAPIError(error));
+        }
+
+        Ok(CancelledOrError::Cancelled(true))
+    }
+
+    /// Poll an operation until it finishes, returning its final progress
+    ///
+    /// Status is checked with a backoff from 250ms up to 2s between checks.
+    /// The final progress may report `Failed` or `Canceled`; check its
+    /// `status`. An operation still running after `timeout` is left running
+    /// and reported as an error.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `token` - Operation token, e.g. `DeletedObject::token`
+    /// * `timeout` - Longest time to wait for the operation to finish
+    pub async fn wait_for_completion(
+        api_server: LFApiServer,
+        auth: Auth,
+        token: &str,
+        timeout: Duration
+    ) -> Result<ProgressOrError> {
+        let finished = poll_until_finished(
+            || Self::status(api_server.clone(), auth.clone(), token),
+            timeout
+        ).await?;
+
+        finished.ok_or_else(|| format!("Operation {} did not finish within {:?}", token, timeout).into())
+    }
+
+    fn task_url(api_server: &LFApiServer, token: &str) -> Result<RequestUrl> {
+        if token.trim().is_empty() {
+            return Err("Operation token must not be empty".into());
+        }
+        Ok(RequestUrl::repository(api_server)?.segment("Tasks").segment(token))
+    }
+}
+
+/// Call `check` until it reports a finished operation or an API error, or
+/// `None` once `timeout` has passed
+pub(crate) async fn poll_until_finished<F, Fut>(mut check: F, timeout: Duration) -> Result<Option<ProgressOrError>>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<ProgressOrError>>,
+{
+    let deadline = Instant::now() + timeout;
+    let mut delay = FIRST_POLL_DELAY;
+    loop {
+        match check().await? {
+            ProgressOrError::Progress(progress) if !progress.status.is_finished() => {}
+            finished => return Ok(Some(finished)),
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        clock::sleep(delay.min(deadline - now)).await;
+        delay = next_poll_delay(delay);
+    }
+}
+
+fn next_poll_delay(delay: Duration) -> Duration {
+    (delay * 2).min(MAX_POLL_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn progress(status: OperationStatus) -> ProgressOrError {
+        ProgressOrError::Progress(OperationProgress { status, ..Default::default() })
+    }
+
+    #[test]
+    fn test_operation_progress_parsing() {
+        let progress: OperationProgress = serde_json::from_str(r#"{
+            "operationToken": "abc",
+            "operationType": "Search",
+            "percentComplete": 100,
+            "status": "Failed",
+            "errors": [{"errorCode": 9013, "message": "Invalid search syntax."}]
+        }"#).unwrap();
+        assert_eq!(progress.status, OperationStatus::Failed);
+        assert!(progress.status.is_finished());
+        assert_eq!(progress.error_summary(), "Invalid search syntax.");
+
+        let progress: OperationProgress = serde_json::from_str(r#"{"status": "Paused"}"#).unwrap();
+        assert_eq!(progress.status, OperationStatus::Unknown);
+        assert!(!OperationStatus::InProgress.is_finished());
+    }
+
+    #[test]
+    fn test_next_poll_delay() {
+        assert_eq!(next_poll_delay(FIRST_POLL_DELAY), Duration::from_millis(500));
+        assert_eq!(next_poll_delay(Duration::from_millis(1500)), MAX_POLL_DELAY);
+    }
+
+    #[test]
+    fn test_task_url() {
+        let api_server = LFApiServer { address: "lf.example.com".to_string(), repository: "repo".to_string() };
+        let url = Task::task_url(&api_server, "4f2a-1").unwrap().build().unwrap();
+        assert!(url.ends_with("/Repositories/repo/Tasks/4f2a-1"), "{}", url);
+        assert!(Task::task_url(&api_server, "").is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_poll_until_finished() {
+        let checks = AtomicUsize::new(0);
+        let finished = poll_until_finished(|| {
+            let n = checks.fetch_add(1, Ordering::SeqCst);
+            async move {
+                Ok(progress(if n < 2 { OperationStatus::InProgress } else { OperationStatus::Completed }))
+            }
+        }, Duration::from_secs(60)).await.unwrap();
+        assert!(matches!(finished, Some(ProgressOrError::Progress(p)) if p.status == OperationStatus::Completed));
+        assert_eq!(checks.load(Ordering::SeqCst), 3);
+
+        let timed_out = poll_until_finished(|| async { Ok(progress(OperationStatus::InProgress)) }, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert!(timed_out.is_none());
+    }
+}
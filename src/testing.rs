@@ -0,0 +1,190 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! A local stand-in for the Repository API, enabled by the `testing` feature
+//!
+//! `MockLaserfiche` runs a wiremock server behind a local HTTPS proxy that
+//! terminates every tunnel with a bundled certificate for `lf.example.com`,
+//! issued by a bundled test CA. Its clients send requests through that
+//! proxy and trust the CA, so requests for `LFApiServer::fixture()` go out
+//! exactly as they would to a real server, https-only URL checks included,
+//! whether reqwest uses `native-tls` or `rustls-tls`. The
+//! canned responses are the `fixtures` values serialized as the API sends
+//! them; anything else can be mounted on `server()` with wiremock directly.
+
+use crate::client::LfClient;
+use crate::laserfiche::{self, Auth, Entries, Entry, FolderId, LFAPIError, LFApiServer};
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+/// Test CA the mock's clients trust, valid until 2126
+const CA_CERTIFICATE: &[u8] = include_bytes!("testing/ca.pem");
+/// Certificate for `lf.example.com` issued by the test CA, and its key
+const CERTIFICATE: &[u8] = include_bytes!("testing/cert.pem");
+const PRIVATE_KEY: &[u8] = include_bytes!("testing/key.pem");
+
+/// A mock Repository API server for `LFApiServer::fixture()`
+///
+/// Stops when dropped.
+pub struct MockLaserfiche {
+    server: MockServer,
+    address: SocketAddr,
+    proxy: JoinHandle<()>,
+}
+
+impl MockLaserfiche {
+    /// Start the wiremock server and the proxy in front of it
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let acceptor = tls_acceptor();
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("loopback port is available");
+        let address = listener.local_addr().expect("listener has an address");
+        let upstream = *server.address();
+
+        let proxy = tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if accept_connect(&mut socket).await.is_err() {
+                        return;
+                    }
+                    let Ok(mut socket) = acceptor.accept(socket).await else { return };
+                    let Ok(mut backend) = TcpStream::connect(upstream).await else { return };
+                    let _ = tokio::io::copy_bidirectional(&mut socket, &mut backend).await;
+                });
+            }
+        });
+        MockLaserfiche { server, address, proxy }
+    }
+
+    /// The wiremock server, for mounting responses the helpers here do not cover
+    pub fn server(&self) -> &MockServer {
+        &self.server
+    }
+
+    /// Address of the proxy
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// The server the mock answers for
+    pub fn api_server(&self) -> LFApiServer {
+        LFApiServer::fixture()
+    }
+
+    /// HTTP client whose https requests all reach the mock
+    pub fn http_client(&self) -> reqwest::Client {
+        let certificate = reqwest::Certificate::from_pem(CA_CERTIFICATE).expect("bundled CA certificate is valid");
+        let proxy = reqwest::Proxy::https(format!("http://{}", self.address)).expect("proxy address is valid");
+        reqwest::Client::builder()
+            .proxy(proxy)
+            .add_root_certificate(certificate)
+            .build()
+            .expect("HTTP client builds")
+    }
+
+    /// A client for the mock holding `Auth::fixture_fresh()`
+    pub fn client(&self) -> LfClient {
+        LfClient::with_http_client(self.api_server(), Auth::fixture_fresh(), self.http_client())
+    }
+
+    /// Run `future`, e.g. a call to `Auth::new` or `Entry::get`, with its
+    /// API requests sent to the mock
+    pub async fn scope<F: std::future::Future>(&self, future: F) -> F::Output {
+        laserfiche::with_http_client(self.http_client(), future).await
+    }
+
+    /// Path of `route` below the fixture repository, e.g. `Entries/100`,
+    /// for wiremock's `path` matcher
+    pub fn repository_path(&self, route: &str) -> String {
+        format!("/LFRepositoryAPI/v1/Repositories/{}/{}", self.api_server().repository, route)
+    }
+
+    /// Answer token requests with `auth`, e.g. `Auth::fixture()`
+    pub async fn mock_token(&self, auth: &Auth) {
+        self.mount("POST", "Token", ResponseTemplate::new(200).set_body_json(auth)).await;
+    }
+
+    /// Answer `GET Entries/{id}` with `entry`
+    pub async fn mock_entry(&self, entry: &Entry) {
+        self.mount("GET", &format!("Entries/{}", entry.id), ResponseTemplate::new(200).set_body_json(entry)).await;
+    }
+
+    /// Answer listings of `folder_id` with `page`, e.g. `Entries::fixture_page(3)`
+    pub async fn mock_children(&self, folder_id: impl Into<FolderId>, page: &Entries) {
+        let route = format!("Entries/{}/Laserfiche.Repository.Folder/children", folder_id.into().as_i64());
+        self.mount("GET", &route, ResponseTemplate::new(200).set_body_json(page)).await;
+    }
+
+    /// Answer every `Entries/Search` query with `page`
+    pub async fn mock_search(&self, page: &Entries) {
+        self.mount("GET", "Entries/Search", ResponseTemplate::new(200).set_body_json(page)).await;
+    }
+
+    /// Answer `http_method` requests to `route` with `error`, at its status
+    /// or 500 if it has none
+    pub async fn mock_error(&self, http_method: &str, route: &str, error: &LFAPIError) {
+        let status = error.status_code().unwrap_or(500);
+        self.mount(http_method, route, ResponseTemplate::new(status).set_body_json(error)).await;
+    }
+
+    async fn mount(&self, http_method: &str, route: &str, response: ResponseTemplate) {
+        Mock::given(method(http_method))
+            .and(path(self.repository_path(route)))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+}
+
+impl Drop for MockLaserfiche {
+    fn drop(&mut self) {
+        self.proxy.abort();
+    }
+}
+
+/// Read a proxy `CONNECT` request from `socket` and open the tunnel
+async fn accept_connect(socket: &mut TcpStream) -> std::io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = socket.read(&mut buffer).await?;
+        if read == 0 || request.len() > 8192 {
+            return Err(std::io::ErrorKind::InvalidData.into());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    if !request.starts_with(b"CONNECT ") {
+        return Err(std::io::ErrorKind::InvalidData.into());
+    }
+    socket.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").await
+}
+
+/// TLS acceptor presenting the bundled `lf.example.com` certificate, built
+/// on rustls whichever TLS backend the crate's clients use
+fn tls_acceptor() -> tokio_rustls::TlsAcceptor {
+    let chain = rustls_pemfile::certs(&mut &CERTIFICATE[..])
+        .expect("bundled certificate is valid")
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+    let key = rustls_pemfile::pkcs8_private_keys(&mut &PRIVATE_KEY[..])
+        .expect("bundled key is valid")
+        .pop()
+        .map(rustls::PrivateKey)
+        .expect("bundled key is present");
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(chain, key)
+        .expect("bundled certificate matches its key");
+    tokio_rustls::TlsAcceptor::from(Arc::new(config))
+}
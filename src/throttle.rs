@@ -0,0 +1,241 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::config::{ConfigError, ThrottleConfig};
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+// tokio's `Instant`, so tests can pause time
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use crate::clock::Instant;
+
+/// Request rate limiter that learns the server's limits (AIMD)
+///
+/// Each `acquire` reserves the next send slot at the current rate. Throttled
+/// responses (429/503) cut the rate multiplicatively; successes raise it
+/// additively back toward `max_rate`, so long-running jobs settle just
+/// under the tenant's limit without manual tuning.
+#[derive(Debug)]
+pub struct AdaptiveThrottle {
+    config: ThrottleConfig,
+    state: Mutex<ThrottleState>,
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    rate: f64,
+    next_slot: Instant,
+    last_decrease: Option<Instant>,
+}
+
+impl AdaptiveThrottle {
+    /// Create a throttle starting at `config.initial_rate`
+    ///
+    /// Fails if `config` does not pass `ThrottleConfig::validate`, e.g. a
+    /// rate that is zero, negative, or NaN.
+    pub fn new(config: ThrottleConfig) -> Result<Self, ConfigError> {
+        config.validate()?;
+        Ok(AdaptiveThrottle {
+            config,
+            state: Mutex::new(ThrottleState {
+                rate: config.initial_rate,
+                next_slot: Instant::now(),
+                last_decrease: None,
+            }),
+        })
+    }
+
+    /// The tuning this throttle runs with
+    pub fn config(&self) -> &ThrottleConfig {
+        &self.config
+    }
+
+    /// Current allowed requests per second
+    pub fn rate(&self) -> f64 {
+        self.state().rate
+    }
+
+    /// Wait until the next request may be sent
+    pub async fn acquire(&self) {
+        let slot = {
+            let mut state = self.state();
+            let now = Instant::now();
+            let slot = state.next_slot.max(now);
+            state.next_slot = slot + Duration::from_secs_f64(1.0 / state.rate);
+            slot
+        };
+        clock::sleep(slot.saturating_duration_since(Instant::now())).await;
+    }
+
+    /// Feed back the HTTP status of a completed request
+    pub fn record_status(&self, status: u16) {
+        match status {
+            429 | 503 => self.record_throttled(),
+            200..=399 => self.record_success(),
+            _ => {}
+        }
+    }
+
+    /// Raise the rate after a successful request
+    pub fn record_success(&self) {
+        let mut state = self.state();
+        // Per-request share of the per-second increase
+        state.rate = (state.rate + self.config.additive_increase / state.rate).min(self.config.max_rate);
+    }
+
+    /// Cut the rate after a throttled request
+    pub fn record_throttled(&self) {
+        let mut state = self.state();
+        let now = Instant::now();
+        let cooling = state.last_decrease
+            .is_some_and(|at| now.duration_since(at) < self.config.decrease_cooldown);
+        if cooling {
+            return;
+        }
+
+        state.rate = (state.rate * self.config.multiplicative_decrease).max(self.config.min_rate);
+        state.last_decrease = Some(now);
+        // Requests already scheduled at the old rate are pushed back too
+        state.next_slot = state.next_slot.max(now) + Duration::from_secs_f64(1.0 / state.rate);
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, ThrottleState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for AdaptiveThrottle {
+    fn default() -> Self {
+        Self::new(ThrottleConfig::default()).expect("default throttle config is valid")
+    }
+}
+
+tokio::task_local! {
+    static SCOPED_THROTTLE: Arc<AdaptiveThrottle>;
+}
+
+/// Run `future` with the status of every API request it makes fed back to
+/// `throttle`
+///
+/// `Pipeline`s with a throttle scope their workers this way, so a 429 or 503
+/// anywhere in a bulk job slows the whole job down.
+pub async fn with_throttle<F: Future>(throttle: Arc<AdaptiveThrottle>, future: F) -> F::Output {
+    SCOPED_THROTTLE.scope(throttle, future).await
+}
+
+/// `with_throttle` when there is a throttle, otherwise just `future`
+pub(crate) async fn with_optional_throttle<F: Future>(throttle: Option<Arc<AdaptiveThrottle>>, future: F) -> F::Output {
+    match throttle {
+        Some(throttle) => with_throttle(throttle, future).await,
+        None => future.await,
+    }
+}
+
+/// The throttle scoped around the current async request, if any
+pub(crate) fn scoped() -> Option<Arc<AdaptiveThrottle>> {
+    SCOPED_THROTTLE.try_with(Arc::clone).ok()
+}
+
+/// Wait for a send slot from the scoped throttle, if any, e.g. before each
+/// request after the first in a pipeline worker
+pub(crate) async fn acquire_scoped() {
+    if let Some(throttle) = scoped() {
+        throttle.acquire().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ThrottleConfig {
+        ThrottleConfig {
+            initial_rate: 10.0,
+            min_rate: 1.0,
+            max_rate: 12.0,
+            additive_increase: 10.0,
+            multiplicative_decrease: 0.5,
+            decrease_cooldown: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn test_throttled_halves_once_per_cooldown() {
+        let throttle = AdaptiveThrottle::new(config()).unwrap();
+
+        throttle.record_status(429);
+        assert_eq!(throttle.rate(), 5.0);
+
+        // Same burst: ignored until the cooldown elapses
+        throttle.record_status(503);
+        assert_eq!(throttle.rate(), 5.0);
+    }
+
+    #[test]
+    fn test_rate_respects_bounds() {
+        let throttle = AdaptiveThrottle::new(ThrottleConfig { decrease_cooldown: Duration::ZERO, ..config() }).unwrap();
+
+        for _ in 0..10 {
+            throttle.record_throttled();
+        }
+        assert_eq!(throttle.rate(), 1.0);
+
+        for _ in 0..1000 {
+            throttle.record_success();
+        }
+        assert_eq!(throttle.rate(), 12.0);
+    }
+
+    #[test]
+    fn test_rejects_rates_that_would_stall() {
+        for rate in [0.0, -1.0, f64::NAN] {
+            let config = ThrottleConfig { initial_rate: rate, min_rate: rate, ..config() };
+            assert!(AdaptiveThrottle::new(config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_success_increases_additively() {
+        let throttle = AdaptiveThrottle::new(config()).unwrap();
+        throttle.record_status(200);
+        assert!((throttle.rate() - 11.0).abs() < 1e-9);
+
+        // Client errors say nothing about load
+        throttle.record_status(404);
+        assert!((throttle.rate() - 11.0).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_with_throttle_scopes_requests() {
+        assert!(scoped().is_none());
+        let throttle = Arc::new(AdaptiveThrottle::new(config()).unwrap());
+        let rate = with_throttle(Arc::clone(&throttle), async {
+            scoped().unwrap().record_status(429);
+            scoped().unwrap().rate()
+        }).await;
+        assert_eq!((rate, throttle.rate()), (5.0, 5.0));
+        assert!(scoped().is_none());
+        assert!(with_optional_throttle(None, async { scoped().is_none() }).await);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_spaces_requests() {
+        let throttle = AdaptiveThrottle::new(ThrottleConfig {
+            initial_rate: 100.0,
+            max_rate: 100.0,
+            ..config()
+        }).unwrap();
+
+        let start = Instant::now();
+        for _ in 0..5 {
+            throttle.acquire().await;
+        }
+        // First slot is immediate, the next four are 10ms apart
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! `chrono` timestamps for entries and tokens
+//!
+//! `Entry` keeps the server's timestamp strings as they were sent, so
+//! enabling this feature changes no field types for other crates in the
+//! build; the accessors here parse them into `DateTime<Utc>` on demand.
+
+use crate::laserfiche::{Auth, Entry};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::time::SystemTime;
+
+/// Parse a Laserfiche timestamp such as `2024-01-02T03:04:05.678-05:00`;
+/// a missing offset is read as UTC
+pub fn parse(timestamp: &str) -> Option<DateTime<Utc>> {
+    let timestamp = timestamp.trim();
+    if let Ok(parsed) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(parsed.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f")
+        .ok()
+        .map(|naive| naive.and_utc())
+}
+
+impl Entry {
+    /// `creation_time` as a UTC timestamp, if the server sent one
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        parse(&self.creation_time)
+    }
+
+    /// `last_modified_time` as a UTC timestamp, if the server sent one
+    pub fn last_modified_at(&self) -> Option<DateTime<Utc>> {
+        parse(&self.last_modified_time)
+    }
+}
+
+impl Auth {
+    /// When the token was issued, by the local clock
+    pub fn issued_at_utc(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.timestamp, 0).unwrap_or(DateTime::UNIX_EPOCH)
+    }
+
+    /// `expires_at` as a UTC timestamp
+    pub fn expires_at_utc(&self) -> DateTime<Utc> {
+        DateTime::<Utc>::from(self.expires_at().max(SystemTime::UNIX_EPOCH))
+    }
+}
+
+/// Serde helpers for `Option<DateTime<Utc>>` fields holding Laserfiche
+/// timestamps, for use with `#[serde(with = "laserfiche::timestamps::lenient")]`
+///
+/// Deserializes anything `parse` accepts, and an empty string or `null` as
+/// `None`; serializes as RFC 3339, which deserializes back to the same value.
+pub mod lenient {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(timestamp: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match timestamp {
+            Some(timestamp) => serializer.serialize_str(&timestamp.to_rfc3339()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        if text.trim().is_empty() {
+            return Ok(None);
+        }
+        parse(&text)
+            .map(Some)
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp: {}", text)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_parse() {
+        let expected = Utc.with_ymd_and_hms(2024, 1, 2, 8, 4, 5).unwrap();
+        assert_eq!(parse("2024-01-02T03:04:05-05:00"), Some(expected));
+        assert_eq!(parse("2024-01-02T08:04:05Z"), Some(expected));
+        assert_eq!(parse("2024-01-02T08:04:05"), Some(expected));
+        assert!(parse("2024-01-02T08:04:05.678Z").is_some());
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("yesterday"), None);
+    }
+
+    #[test]
+    fn test_entry_and_auth_timestamps() {
+        let entry = Entry {
+            creation_time: "2024-01-01T00:00:00Z".to_string(),
+            last_modified_time: "2024-03-01T12:00:00+01:00".to_string(),
+            ..Default::default()
+        };
+        assert!(entry.last_modified_at() > entry.created_at());
+        assert_eq!(Entry::default().created_at(), None);
+
+        let auth = Auth { timestamp: 1_700_000_000, expires_in: 3600, ..Default::default() };
+        assert_eq!(auth.issued_at_utc(), Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        assert_eq!(auth.expires_at_utc() - auth.issued_at_utc(), chrono::Duration::hours(1));
+    }
+
+    #[test]
+    fn test_lenient_round_trip() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Modified {
+            #[serde(with = "lenient")]
+            at: Option<DateTime<Utc>>,
+        }
+
+        let parsed: Modified = serde_json::from_str(r#"{"at": "2024-01-02T03:04:05-05:00"}"#).unwrap();
+        assert_eq!(parsed.at, Some(Utc.with_ymd_and_hms(2024, 1, 2, 8, 4, 5).unwrap()));
+        let round_tripped: Modified = serde_json::from_str(&serde_json::to_string(&parsed).unwrap()).unwrap();
+        assert_eq!(round_tripped, parsed);
+
+        let empty: Modified = serde_json::from_str(r#"{"at": ""}"#).unwrap();
+        assert_eq!(empty.at, None);
+        assert!(serde_json::from_str::<Modified>(r#"{"at": "soon"}"#).is_err());
+    }
+}
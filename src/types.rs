@@ -0,0 +1,18 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! Every wire type of the Repository API in one place
+//!
+//! The async and blocking modules share these types, so a value read
+//! through either can be passed to the other. Prefer these paths to
+//! `laserfiche::blocking::*`, which re-exports the same types next to the
+//! blocking calls.
+
+pub use crate::laserfiche::types::*;
+pub use crate::link_definitions::{LinkDefinition, LinkDefinitionsPage};
+pub use crate::pages::{DocumentPages, Page, PageImageFormat, PageImageOptions};
+pub use crate::repositories::RepositoryInfo;
+pub use crate::search::{ContextHit, ContextHits, FuzzyType, SearchRequest, SearchResults, SearchToken};
+pub use crate::tag_definitions::TagDefinitionsPage;
+pub use crate::tasks::{OperationError, OperationProgress, OperationStatus};
@@ -0,0 +1,578 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+//! `Result`-returning API surface
+//!
+//! Every operation returns `Result<T, LfError>`, with an error response from
+//! the server as `LfError::Api`, so calls compose with `?` instead of a match
+//! on an `XOrError` enum. Existing code can migrate one call at a time with
+//! the `into_result()` adapters on those enums.
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+
+use crate::client;
+use crate::link_definitions::LinkDefinitionsPage;
+use crate::pages::{DocumentPages, PageImageOptions};
+use crate::integrity::Checksum;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::integrity::ChecksummedImport;
+use crate::progress::TransferProgress;
+use crate::watch::EntryEvent;
+use crate::laserfiche::{
+    self, box_stream, AuditReasons, Auth, BoxStream, DeleteOptions, DocumentId, EdocInfo, EdocReader, Entries, Entry, EntryId, ErrorKind, FolderId, FullEntry, ImportResult,
+    LFAPIError, LFApiServer, LFObject, Links, ListOptions, MetadataResult, NewLink, NextLink, SearchOptions,
+    SharedAuth, Tags, Template,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::laserfiche::{ExportOptions, ImportRequest};
+
+use futures_util::stream::StreamExt;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Error from a `v2` operation
+#[derive(Debug)]
+pub enum LfError {
+    /// The server answered with an error body
+    Api(Box<LFAPIError>),
+    /// The async request could not be made or its response read
+    Request(laserfiche::Error),
+    /// The blocking request could not be made or its response read
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    BlockingRequest(laserfiche::blocking::Error),
+}
+
+impl LfError {
+    /// The server's error body, if the server answered with one
+    pub fn api_error(&self) -> Option<&LFAPIError> {
+        match self {
+            LfError::Api(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LfError::Api(error) => write!(f, "API error: {}", error),
+            LfError::Request(error) => error.fmt(f),
+            #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+            LfError::BlockingRequest(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for LfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LfError::Api(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<LFAPIError> for LfError {
+    fn from(error: LFAPIError) -> Self {
+        LfError::Api(Box::new(error))
+    }
+}
+
+impl From<laserfiche::Error> for LfError {
+    fn from(error: laserfiche::Error) -> Self {
+        match error {
+            laserfiche::Error(ErrorKind::ApiError(error), _) => LfError::Api(error),
+            error => LfError::Request(error),
+        }
+    }
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl From<laserfiche::blocking::Error> for LfError {
+    fn from(error: laserfiche::blocking::Error) -> Self {
+        match error {
+            laserfiche::blocking::Error(laserfiche::blocking::ErrorKind::ApiError(error), _) => LfError::Api(error),
+            error => LfError::BlockingRequest(error),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, LfError>;
+
+/// Repository handle whose operations return `Result<T, LfError>`
+///
+/// Wraps a `client::LfClient`, sharing its token, HTTP client, and lock
+/// retry settings.
+#[derive(Debug, Clone)]
+pub struct LfClient {
+    inner: client::LfClient,
+}
+
+impl From<client::LfClient> for LfClient {
+    fn from(inner: client::LfClient) -> Self {
+        LfClient { inner }
+    }
+}
+
+impl LfClient {
+    /// Create a client for `api_server` using an existing token
+    pub fn new(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Self {
+        client::LfClient::new(api_server, auth).into()
+    }
+
+    /// Authenticate and build a client
+    pub async fn login(api_server: LFApiServer, username: String, password: String) -> Result<Self> {
+        client::LfClient::login(api_server, username, password).await?.into_result().map(Self::from)
+    }
+
+    /// The wrapped client, for operations that return `XOrError` enums
+    pub fn inner(&self) -> &client::LfClient {
+        &self.inner
+    }
+
+    pub fn api_server(&self) -> &LFApiServer {
+        self.inner.api_server()
+    }
+
+    /// The stored token, without checking its expiry
+    pub fn auth(&self) -> Auth {
+        self.inner.auth()
+    }
+
+    /// Request a new token and store it
+    pub async fn refresh(&self) -> Result<Auth> {
+        self.inner.refresh().await?.into_result()
+    }
+
+    /// Get an entry by id
+    pub async fn get_entry(&self, entry_id: impl Into<EntryId>) -> Result<Entry> {
+        self.inner.get_entry(entry_id).await?.into_result()
+    }
+
+    /// Get an entry with its field values, tags, and links, fetched concurrently
+    pub async fn get_full_entry(&self, entry_id: impl Into<EntryId>) -> Result<FullEntry> {
+        self.inner.get_full_entry(entry_id).await?.into_result()
+    }
+
+    /// List the children of a folder
+    pub async fn list_entries(&self, folder_id: impl Into<FolderId>) -> Result<Entries> {
+        self.inner.list_entries(folder_id).await?.into_result()
+    }
+
+    /// List the children of a folder with OData and field options
+    pub async fn list_entries_with_options(&self, folder_id: impl Into<FolderId>, options: &ListOptions) -> Result<Entries> {
+        self.inner.list_entries_with_options(folder_id, options).await?.into_result()
+    }
+
+    /// Ids of a folder's children named `names`, keyed by the name as given
+    pub async fn find_children_by_names(&self, folder_id: impl Into<FolderId>, names: &[&str]) -> Result<BTreeMap<String, EntryId>> {
+        Ok(self.inner.find_children_by_names(folder_id, names).await?)
+    }
+
+    /// Poll an entry every `interval`, yielding an event each time it changes
+    pub fn watch_entry(&self, entry_id: impl Into<EntryId>, interval: Duration) -> BoxStream<'static, Result<EntryEvent>> {
+        box_stream(self.inner.watch_entry(entry_id, interval).map(|event| Ok(event?)))
+    }
+
+    /// Every child of a folder, following next links as the stream is read
+    pub fn list_all(&self, folder_id: impl Into<FolderId>) -> BoxStream<'static, Result<Entry>> {
+        box_stream(self.inner.list_all(folder_id).map(|entry| Ok(entry?)))
+    }
+
+    /// Fetch the next page of a list or search
+    pub async fn list_next(&self, next_link: NextLink) -> Result<Entries> {
+        self.inner.list_next(next_link).await?.into_result()
+    }
+
+    /// Search for entries
+    pub async fn search(&self, search_query: String, options: SearchOptions) -> Result<Entries> {
+        self.inner.search(search_query, options).await?.into_result()
+    }
+
+    /// Wait up to `timeout` for server-side processing to release an entry
+    pub async fn wait_until_unlocked(&self, entry_id: impl Into<EntryId>, timeout: Duration) -> Result<()> {
+        Ok(self.inner.wait_until_unlocked(entry_id, timeout).await?)
+    }
+
+    /// Import a local file into a folder
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import(&self, file_path: String, file_name: String, folder_id: impl Into<FolderId>) -> Result<ImportResult> {
+        self.inner.import(file_path, file_name, folder_id).await?.into_result()
+    }
+
+    /// Import a local file, reporting the bytes sent to `progress`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_progress(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        progress: &TransferProgress
+    ) -> Result<ImportResult> {
+        self.inner.import_with_progress(file_path, file_name, folder_id, progress).await?.into_result()
+    }
+
+    /// Import a local file, returning the SHA-256 of the content sent
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_checksummed(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>
+    ) -> Result<ChecksummedImport> {
+        self.inner.import_checksummed(file_path, file_name, folder_id).await?.into_result()
+    }
+
+    /// Import a local file with its template, field values, and tags
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_with_request(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        request: &ImportRequest
+    ) -> Result<ImportResult> {
+        self.inner.import_with_request(file_path, file_name, folder_id, request).await?.into_result()
+    }
+
+    /// Get the entry an import created, from its `document_link`
+    pub async fn fetch_document_link(&self, import_result: &ImportResult) -> Result<Entry> {
+        self.inner.fetch_document_link(import_result).await?.into_result()
+    }
+
+    /// Import a document streamed from `reader`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn import_reader<R>(
+        &self,
+        reader: R,
+        length: u64,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResult>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        self.inner.import_reader(reader, length, file_name, folder_id, mime_type).await?.into_result()
+    }
+
+    /// Import a document from content in memory
+    pub async fn import_bytes(
+        &self,
+        content: Vec<u8>,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        mime_type: Option<&str>
+    ) -> Result<ImportResult> {
+        self.inner.import_bytes(content, file_name, folder_id, mime_type).await?.into_result()
+    }
+
+    /// Download a document's content into memory
+    pub async fn export_bytes(&self, document_id: impl Into<DocumentId>) -> Result<Vec<u8>> {
+        self.inner.export_bytes(document_id).await?.into_result()
+    }
+
+    /// Download a document's content to `file_path`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export(&self, document_id: impl Into<DocumentId>, file_path: &str) -> Result<Vec<u8>> {
+        self.inner.export(document_id, file_path).await?.into_result()
+    }
+
+    /// Download a document's content to `file_path`, replacing an existing
+    /// file only if `options` allow it
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_with_options(
+        &self,
+        document_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<Vec<u8>> {
+        self.inner.export_with_options(document_id, file_path, options).await?.into_result()
+    }
+
+    /// Open a document's content for streaming reads
+    pub async fn open(&self, document_id: impl Into<DocumentId>) -> Result<EdocReader> {
+        self.inner.open(document_id).await?.into_result()
+    }
+
+    /// Download a document's content into `writer`, returning the bytes written
+    pub async fn export_stream<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W
+    ) -> Result<u64> {
+        self.inner.export_stream(document_id, writer).await?.into_result()
+    }
+
+    /// Download a document's content into `writer`, checking its SHA-256
+    /// against `expected` and any digest the server reports
+    pub async fn export_checksummed<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W,
+        expected: Option<&str>
+    ) -> Result<Checksum> {
+        self.inner.export_checksummed(document_id, writer, expected).await?.into_result()
+    }
+
+    /// Check a document's content against the SHA-256 recorded for it
+    pub async fn verify_checksum(&self, document_id: impl Into<DocumentId>, expected: &str) -> Result<Checksum> {
+        self.inner.verify_checksum(document_id, expected).await?.into_result()
+    }
+
+    /// Download bytes `start..=end` of a document's content, or from `start`
+    /// to the end when `end` is `None`
+    pub async fn export_range(&self, document_id: impl Into<DocumentId>, start: u64, end: Option<u64>) -> Result<Vec<u8>> {
+        self.inner.export_range(document_id, start, end).await?.into_result()
+    }
+
+    /// Download a document into `partial_file`, continuing an interrupted
+    /// download of the same version, and returning the bytes written
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn export_resume(&self, document_id: impl Into<DocumentId>, partial_file: &str) -> Result<u64> {
+        self.inner.export_resume(document_id, partial_file).await?.into_result()
+    }
+
+    /// Download a document's content into `writer`, reporting the bytes
+    /// received to `progress`, and returning the bytes written
+    pub async fn export_stream_with_progress<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        document_id: impl Into<DocumentId>,
+        writer: &mut W,
+        progress: &TransferProgress
+    ) -> Result<u64> {
+        self.inner.export_stream_with_progress(document_id, writer, progress).await?.into_result()
+    }
+
+    /// Replace a document's content with content in memory, returning the bytes uploaded
+    pub async fn set_edoc_bytes(&self, document_id: impl Into<DocumentId>, content: Vec<u8>, mime_type: &str) -> Result<u64> {
+        self.inner.set_edoc_bytes(document_id, content, mime_type).await?.into_result()
+    }
+
+    /// Replace a document's content with content streamed from `reader`
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn set_edoc<R>(&self, document_id: impl Into<DocumentId>, reader: R, length: u64, mime_type: &str) -> Result<u64>
+    where
+        R: tokio::io::AsyncRead + Send + Sync + 'static,
+    {
+        self.inner.set_edoc(document_id, reader, length, mime_type).await?.into_result()
+    }
+
+    /// Remove a document's content, keeping the entry
+    pub async fn delete_edoc(&self, document_id: impl Into<DocumentId>) -> Result<DocumentId> {
+        self.inner.delete_edoc(document_id).await?.into_result()
+    }
+
+    /// Every page of a document
+    pub async fn list_pages(&self, document_id: impl Into<DocumentId>) -> Result<DocumentPages> {
+        self.inner.list_pages(document_id).await?.into_result()
+    }
+
+    /// Render one page of a document as an image
+    pub async fn export_page_image(
+        &self,
+        document_id: impl Into<DocumentId>,
+        page_number: u32,
+        options: &PageImageOptions
+    ) -> Result<Vec<u8>> {
+        self.inner.export_page_image(document_id, page_number, options).await?.into_result()
+    }
+
+    /// Delete pages from a document, returning how many were removed
+    pub async fn delete_pages(&self, document_id: impl Into<DocumentId>, page_numbers: &[u32]) -> Result<usize> {
+        self.inner.delete_pages(document_id, page_numbers).await?.into_result()
+    }
+
+    /// Get a document's content headers
+    pub async fn edoc_head(&self, document_id: impl Into<DocumentId>) -> Result<EdocInfo> {
+        self.inner.edoc_head(document_id).await?.into_result()
+    }
+
+    /// Create a folder
+    pub async fn create_folder(&self, folder_name: String, volume_name: String, parent_id: impl Into<FolderId>) -> Result<Entry> {
+        self.inner.create_folder(folder_name, volume_name, parent_id).await?.into_result()
+    }
+
+    /// Get the folder at `path`, creating any missing folders
+    pub async fn ensure_path(&self, path: &str, volume_name: String) -> Result<Entry> {
+        self.inner.ensure_path(path, volume_name).await?.into_result()
+    }
+
+    /// Delete an entry
+    pub async fn delete_entry(&self, entry_id: impl Into<EntryId>, comment: String) -> Result<LFObject> {
+        self.inner.delete_entry(entry_id, comment).await?.into_result()
+    }
+
+    /// Delete an entry citing an audit reason
+    pub async fn delete_entry_with_options(&self, entry_id: impl Into<EntryId>, options: &DeleteOptions) -> Result<LFObject> {
+        self.inner.delete_entry_with_options(entry_id, options).await?.into_result()
+    }
+
+    /// Audit reasons configured in the repository
+    pub async fn audit_reasons(&self) -> Result<AuditReasons> {
+        self.inner.audit_reasons().await?.into_result()
+    }
+
+    /// Move and/or rename an entry
+    pub async fn patch_entry(
+        &self,
+        entry_id: impl Into<EntryId>,
+        parent_id: Option<FolderId>,
+        new_name: Option<String>
+    ) -> Result<LFObject> {
+        self.inner.patch_entry(entry_id, parent_id, new_name).await?.into_result()
+    }
+
+    /// Copy an entry into a folder
+    pub async fn copy_entry(
+        &self,
+        entry_id: impl Into<EntryId>,
+        target_folder_id: impl Into<FolderId>,
+        new_name: Option<String>
+    ) -> Result<Entry> {
+        self.inner.copy_entry(entry_id, target_folder_id, new_name).await?.into_result()
+    }
+
+    /// Get an entry's field values
+    pub async fn get_metadata(&self, entry_id: impl Into<EntryId>) -> Result<MetadataResult> {
+        self.inner.get_metadata(entry_id).await?.into_result()
+    }
+
+    /// Replace an entry's field values
+    pub async fn update_metadata(&self, entry_id: impl Into<EntryId>, metadata: serde_json::Value) -> Result<MetadataResult> {
+        self.inner.update_metadata(entry_id, metadata).await?.into_result()
+    }
+
+    /// Get all fields of an entry
+    pub async fn get_fields(&self, entry_id: impl Into<EntryId>) -> Result<LFObject> {
+        self.inner.get_fields(entry_id).await?.into_result()
+    }
+
+    /// Get one field of an entry
+    pub async fn get_field(&self, entry_id: impl Into<EntryId>, field_id: i64) -> Result<LFObject> {
+        self.inner.get_field(entry_id, field_id).await?.into_result()
+    }
+
+    /// Get the template assigned to an entry
+    pub async fn get_template(&self, entry_id: impl Into<EntryId>) -> Result<Template> {
+        self.inner.get_template(entry_id).await?.into_result()
+    }
+
+    /// Assign a template to an entry
+    pub async fn set_template(&self, entry_id: impl Into<EntryId>, template_name: String) -> Result<Entry> {
+        self.inner.set_template(entry_id, template_name).await?.into_result()
+    }
+
+    /// Remove an entry's template
+    pub async fn remove_template(&self, entry_id: impl Into<EntryId>) -> Result<Entry> {
+        self.inner.remove_template(entry_id).await?.into_result()
+    }
+
+    /// Get the tags assigned to an entry
+    pub async fn get_tags(&self, entry_id: impl Into<EntryId>) -> Result<Tags> {
+        self.inner.get_tags(entry_id).await?.into_result()
+    }
+
+    /// Replace the tags assigned to an entry
+    pub async fn set_tags(&self, entry_id: impl Into<EntryId>, tag_ids: Vec<i64>) -> Result<Tags> {
+        self.inner.set_tags(entry_id, tag_ids).await?.into_result()
+    }
+
+    /// Get the links of an entry
+    pub async fn get_links(&self, entry_id: impl Into<EntryId>) -> Result<Links> {
+        self.inner.get_links(entry_id).await?.into_result()
+    }
+
+    /// Replace the links of an entry
+    pub async fn set_links(&self, entry_id: impl Into<EntryId>, links: Vec<NewLink>) -> Result<Links> {
+        self.inner.set_links(entry_id, links).await?.into_result()
+    }
+
+    /// Link an entry to another, keeping its existing links
+    pub async fn add_link(
+        &self,
+        source_id: impl Into<EntryId>,
+        target_id: impl Into<EntryId>,
+        link_type_id: i64,
+        description: Option<String>
+    ) -> Result<Links> {
+        self.inner.add_link(source_id, target_id, link_type_id, description).await?.into_result()
+    }
+
+    /// Remove every link from an entry to other entries
+    pub async fn delete_links(&self, entry_id: impl Into<EntryId>) -> Result<Links> {
+        self.inner.delete_links(entry_id).await?.into_result()
+    }
+
+    /// Every link definition in the repository
+    pub async fn list_link_definitions(&self) -> Result<LinkDefinitionsPage> {
+        self.inner.list_link_definitions().await?.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{EntryOrError, TagsOrError};
+
+    fn not_found() -> LFAPIError {
+        LFAPIError {
+            status: Some(404),
+            title: Some("Entry not found.".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_into_result() {
+        let tags = TagsOrError::Tags(Tags { value: Vec::new() }).into_result().unwrap();
+        assert!(tags.value.is_empty());
+
+        let Err(error) = EntryOrError::LFAPIError(not_found()).into_result() else { panic!("API error became Ok") };
+        assert_eq!(error.api_error().and_then(|error| error.status), Some(404));
+    }
+
+    #[test]
+    fn test_api_errors_unify() {
+        let from_body = LfError::from(not_found());
+        assert_eq!(from_body.api_error().and_then(|error| error.status), Some(404));
+        assert_eq!(from_body.to_string(), "API error: Entry not found.");
+
+        let from_kind = LfError::from(laserfiche::Error::from(ErrorKind::ApiError(Box::new(not_found()))));
+        assert!(matches!(from_kind, LfError::Api(_)));
+
+        #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+        {
+            let from_blocking = LfError::from(laserfiche::blocking::Error::from(
+                laserfiche::blocking::ErrorKind::ApiError(Box::new(not_found()))
+            ));
+            assert!(matches!(from_blocking, LfError::Api(_)));
+        }
+
+        let locked = LfError::from(laserfiche::Error::from(ErrorKind::EntryLocked(7)));
+        assert!(locked.api_error().is_none());
+        assert_eq!(locked.to_string(), "Entry 7 is locked");
+    }
+
+    #[tokio::test]
+    async fn test_client_validates_before_sending() {
+        let api_server = LFApiServer {
+            address: "test.laserfiche.com".to_string(),
+            repository: "test-repo".to_string(),
+        };
+        let auth = Auth {
+            access_token: "token".to_string(),
+            expires_in: 3600,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs() as i64,
+            api_server: api_server.clone(),
+            ..Default::default()
+        };
+        let client = LfClient::new(api_server, auth);
+        assert!(matches!(client.get_entry(-1).await, Err(LfError::Request(_))));
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use super::Result;
+use crate::client::blocking as client;
+use crate::laserfiche::blocking::Auth;
+use crate::laserfiche::{
+    AuditReasons, DeleteOptions, DocumentId, Entries, Entry, ExportOptions, EntryId, FolderId, ImportRequest, ImportResult, LFApiServer, LFObject, ListOptions,
+    MetadataResult, NextLink, SearchOptions, SharedAuth,
+};
+
+use std::collections::BTreeMap;
+
+/// Blocking repository handle whose operations return `Result<T, LfError>`
+///
+/// Wraps a `client::blocking::LfClient`, sharing its token, HTTP client, and
+/// lock retry settings.
+#[derive(Debug, Clone)]
+pub struct LfClient {
+    inner: client::LfClient,
+}
+
+impl From<client::LfClient> for LfClient {
+    fn from(inner: client::LfClient) -> Self {
+        LfClient { inner }
+    }
+}
+
+impl LfClient {
+    /// Create a client for `api_server` using an existing token
+    pub fn new(api_server: LFApiServer, auth: impl Into<SharedAuth>) -> Self {
+        client::LfClient::new(api_server, auth).into()
+    }
+
+    /// Authenticate and build a client
+    pub fn login(api_server: LFApiServer, username: String, password: String) -> Result<Self> {
+        client::LfClient::login(api_server, username, password)?.into_result().map(Self::from)
+    }
+
+    /// The wrapped client, for operations that return `XOrError` enums
+    pub fn inner(&self) -> &client::LfClient {
+        &self.inner
+    }
+
+    pub fn api_server(&self) -> &LFApiServer {
+        self.inner.api_server()
+    }
+
+    /// The stored token, without checking its expiry
+    pub fn auth(&self) -> Auth {
+        self.inner.auth()
+    }
+
+    /// Request a new token and store it
+    pub fn refresh(&self) -> Result<Auth> {
+        self.inner.refresh()?.into_result()
+    }
+
+    /// Get an entry by id
+    pub fn get_entry(&self, entry_id: impl Into<EntryId>) -> Result<Entry> {
+        self.inner.get_entry(entry_id)?.into_result()
+    }
+
+    /// List the children of a folder
+    pub fn list_entries(&self, folder_id: impl Into<FolderId>) -> Result<Entries> {
+        self.inner.list_entries(folder_id)?.into_result()
+    }
+
+    /// List the children of a folder with OData and field options
+    pub fn list_entries_with_options(&self, folder_id: impl Into<FolderId>, options: &ListOptions) -> Result<Entries> {
+        self.inner.list_entries_with_options(folder_id, options)?.into_result()
+    }
+
+    /// Ids of a folder's children named `names`, keyed by the name as given
+    pub fn find_children_by_names(&self, folder_id: impl Into<FolderId>, names: &[&str]) -> Result<BTreeMap<String, EntryId>> {
+        Ok(self.inner.find_children_by_names(folder_id, names)?)
+    }
+
+    /// Every child of a folder, following next links as the iterator is read
+    pub fn list_all(&self, folder_id: impl Into<FolderId>) -> impl Iterator<Item = Result<Entry>> + '_ {
+        self.inner.list_all(folder_id).map(|entry| Ok(entry?))
+    }
+
+    /// Fetch the next page of a list or search
+    pub fn list_next(&self, next_link: NextLink) -> Result<Entries> {
+        self.inner.list_next(next_link)?.into_result()
+    }
+
+    /// Search for entries
+    pub fn search(&self, search_query: String, options: SearchOptions) -> Result<Entries> {
+        self.inner.search(search_query, options)?.into_result()
+    }
+
+    /// Import a local file into a folder
+    pub fn import(&self, file_path: String, file_name: String, folder_id: impl Into<FolderId>) -> Result<ImportResult> {
+        self.inner.import(file_path, file_name, folder_id)?.into_result()
+    }
+
+    /// Import a local file with its template, field values, and tags
+    pub fn import_with_request(
+        &self,
+        file_path: String,
+        file_name: String,
+        folder_id: impl Into<FolderId>,
+        request: &ImportRequest
+    ) -> Result<ImportResult> {
+        self.inner.import_with_request(file_path, file_name, folder_id, request)?.into_result()
+    }
+
+    /// Get the entry an import created, from its `document_link`
+    pub fn fetch_document_link(&self, import_result: &ImportResult) -> Result<Entry> {
+        self.inner.fetch_document_link(import_result)?.into_result()
+    }
+
+    /// Download a document's content into memory
+    pub fn export_bytes(&self, document_id: impl Into<DocumentId>) -> Result<Vec<u8>> {
+        self.inner.export_bytes(document_id)?.into_result()
+    }
+
+    /// Download a document's content to `file_path`
+    pub fn export(&self, document_id: impl Into<DocumentId>, file_path: &str) -> Result<Vec<u8>> {
+        self.inner.export(document_id, file_path)?.into_result()
+    }
+
+    /// Download a document's content to `file_path`, replacing an existing
+    /// file only if `options` allow it
+    pub fn export_with_options(
+        &self,
+        document_id: impl Into<DocumentId>,
+        file_path: &str,
+        options: &ExportOptions
+    ) -> Result<Vec<u8>> {
+        self.inner.export_with_options(document_id, file_path, options)?.into_result()
+    }
+
+    /// Delete an entry
+    pub fn delete_entry(&self, entry_id: impl Into<EntryId>, comment: String) -> Result<LFObject> {
+        self.inner.delete_entry(entry_id, comment)?.into_result()
+    }
+
+    /// Delete an entry citing an audit reason
+    pub fn delete_entry_with_options(&self, entry_id: impl Into<EntryId>, options: &DeleteOptions) -> Result<LFObject> {
+        self.inner.delete_entry_with_options(entry_id, options)?.into_result()
+    }
+
+    /// Audit reasons configured in the repository
+    pub fn audit_reasons(&self) -> Result<AuditReasons> {
+        self.inner.audit_reasons()?.into_result()
+    }
+
+    /// Get an entry's field values
+    pub fn get_metadata(&self, entry_id: impl Into<EntryId>) -> Result<MetadataResult> {
+        self.inner.get_metadata(entry_id)?.into_result()
+    }
+
+    /// Replace an entry's field values
+    pub fn update_metadata(&self, entry_id: impl Into<EntryId>, metadata: serde_json::Value) -> Result<MetadataResult> {
+        self.inner.update_metadata(entry_id, metadata)?.into_result()
+    }
+}
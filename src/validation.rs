@@ -75,12 +75,88 @@ error_chain! {
             description("Invalid file name")
             display("Invalid file name: {}", name)
         }
+        UntrustedUrl(url: String) {
+            description("URL does not target the configured server")
+            display("Untrusted URL: {}. Only URLs on the configured Laserfiche server and repository are followed.", url)
+        }
+        InvalidMimeType(mime_type: String) {
+            description("Invalid MIME type")
+            display("Invalid MIME type: {}. Expected type/subtype, e.g. application/pdf.", mime_type)
+        }
+        EgressDenied(url: String) {
+            description("URL is outside the egress allow-list")
+            display("Egress denied: {}. The client's egress policy does not allow this host.", url)
+        }
     }
 }
 
-/// Maximum file size for uploads (100MB)
+/// Scheme every API request URL must use
+pub const API_SCHEME: &str = "https";
+
+/// Laserfiche Cloud domains; each region serves the repository API from
+/// `api.{domain}` and issues tokens from `signin.{domain}`
+pub const CLOUD_DOMAINS: [&str; 2] = ["laserfiche.com", "laserfiche.ca"];
+
+/// Default maximum file size for uploads (100MB); raise it with
+/// `with_max_file_size` or `LfClientBuilder::max_file_size`
 pub const MAX_FILE_SIZE: u64 = 100 * 1024 * 1024;
 
+tokio::task_local! {
+    static SCOPED_MAX_FILE_SIZE: u64;
+}
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+thread_local! {
+    static SCOPED_MAX_FILE_SIZE_BLOCKING: std::cell::Cell<Option<u64>> = const { std::cell::Cell::new(None) };
+}
+
+/// Run `future` with uploads up to `limit` bytes accepted instead of
+/// `MAX_FILE_SIZE`, e.g. for engineering drawings
+///
+/// `LfClient`s with a `max_file_size` scope their calls this way.
+pub async fn with_max_file_size<F: std::future::Future>(limit: u64, future: F) -> F::Output {
+    SCOPED_MAX_FILE_SIZE.scope(limit, future).await
+}
+
+/// Run `f` with blocking uploads on this thread accepted up to `limit`
+/// bytes instead of `MAX_FILE_SIZE`
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub fn with_max_file_size_blocking<T>(limit: u64, f: impl FnOnce() -> T) -> T {
+    let previous = SCOPED_MAX_FILE_SIZE_BLOCKING.with(|scoped| scoped.replace(Some(limit)));
+    let _restore = RestoreLimit(previous);
+    f()
+}
+
+/// Puts back the outer scope's limit, even if the scoped call panics
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+struct RestoreLimit(Option<u64>);
+
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+impl Drop for RestoreLimit {
+    fn drop(&mut self) {
+        SCOPED_MAX_FILE_SIZE_BLOCKING.with(|scoped| scoped.set(self.0));
+    }
+}
+
+/// The limit scoped around the current async request, if any
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn scoped_max_file_size() -> Option<u64> {
+    SCOPED_MAX_FILE_SIZE.try_with(|limit| *limit).ok()
+}
+
+/// Largest upload accepted: the limit scoped by `with_max_file_size` or
+/// `with_max_file_size_blocking`, or `MAX_FILE_SIZE`
+pub fn max_file_size() -> u64 {
+    if let Ok(limit) = SCOPED_MAX_FILE_SIZE.try_with(|limit| *limit) {
+        return limit;
+    }
+    #[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+    if let Some(limit) = SCOPED_MAX_FILE_SIZE_BLOCKING.with(std::cell::Cell::get) {
+        return limit;
+    }
+    MAX_FILE_SIZE
+}
+
 /// Maximum field value length (10KB)
 pub const MAX_FIELD_VALUE_LENGTH: usize = 10 * 1024;
 
@@ -114,10 +190,8 @@ pub fn validate_file_path(path: &str) -> Result<PathBuf> {
     }
 
     // Additional checks for Windows-specific path traversal
-    if cfg!(windows) {
-        if path.contains(r"..\" ) || path.contains(r"\..") {
-            return Err(ErrorKind::PathTraversalAttempt(path.to_string()).into());
-        }
+    if cfg!(windows) && (path.contains(r"..\") || path.contains(r"\..")) {
+        return Err(ErrorKind::PathTraversalAttempt(path.to_string()).into());
     }
 
     let path_buf = PathBuf::from(path);
@@ -186,7 +260,7 @@ pub fn validate_api_url(url: &str) -> Result<String> {
         .map_err(|_| ErrorKind::InvalidUrl(url.to_string()))?;
 
     // Check for HTTPS (required for security)
-    if parsed_url.scheme() != "https" {
+    if parsed_url.scheme() != API_SCHEME {
         return Err(ErrorKind::InsecureUrl(url.to_string()).into());
     }
 
@@ -203,6 +277,43 @@ pub fn validate_api_url(url: &str) -> Result<String> {
     Ok(url.to_string())
 }
 
+/// Validate a server-supplied link (e.g. `@odata.nextLink`) before credentials are sent to it
+///
+/// The link must use `API_SCHEME`, carry no userinfo or explicit port, and point at the
+/// `LFRepositoryAPI` routes of `repository` on `address`, under any API version.
+pub fn validate_next_link(url: &str, address: &str, repository: &str) -> Result<String> {
+    let parsed_url = Url::parse(url)
+        .map_err(|_| ErrorKind::InvalidUrl(url.to_string()))?;
+
+    if parsed_url.scheme() != API_SCHEME {
+        return Err(ErrorKind::InsecureUrl(url.to_string()).into());
+    }
+
+    let same_host = parsed_url.host_str()
+        .map(|host| host.eq_ignore_ascii_case(address))
+        .unwrap_or(false);
+    if !same_host || parsed_url.port().is_some() {
+        return Err(ErrorKind::UntrustedUrl(url.to_string()).into());
+    }
+
+    if !parsed_url.username().is_empty() || parsed_url.password().is_some() {
+        return Err(ErrorKind::UntrustedUrl(url.to_string()).into());
+    }
+
+    // Any API version's routes, since clients routed to v2 are sent v2 links
+    let path = parsed_url.path().to_ascii_lowercase();
+    let base_prefix = format!("/{}/", api_base_path(address)).to_ascii_lowercase();
+    let repository_prefix = format!("repositories/{}/", repository).to_ascii_lowercase();
+    let in_repository = path.strip_prefix(&base_prefix)
+        .and_then(|rest| rest.split_once('/'))
+        .is_some_and(|(version, rest)| is_version_segment(version) && rest.starts_with(&repository_prefix));
+    if !in_repository {
+        return Err(ErrorKind::UntrustedUrl(url.to_string()).into());
+    }
+
+    Ok(parsed_url.to_string())
+}
+
 /// Validate an API server address (hostname or FQDN)
 pub fn validate_server_address(address: &str) -> Result<String> {
     // Check for empty address
@@ -239,6 +350,31 @@ pub fn validate_server_address(address: &str) -> Result<String> {
     Ok(address.to_string())
 }
 
+/// Whether `address` is a Laserfiche Cloud repository API host, e.g.
+/// `api.laserfiche.com` or `api.eu.laserfiche.com`
+pub fn is_cloud_api_host(address: &str) -> bool {
+    let address = address.to_ascii_lowercase();
+    address.strip_prefix("api.").is_some_and(|domain| {
+        CLOUD_DOMAINS.iter().any(|cloud| domain == *cloud || domain.ends_with(&format!(".{}", cloud)))
+    })
+}
+
+/// `v` followed by a major version number, e.g. `v2`
+fn is_version_segment(segment: &str) -> bool {
+    segment.strip_prefix('v')
+        .is_some_and(|major| !major.is_empty() && major.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// First path segment of the repository API on `address`: `repository` on
+/// Laserfiche Cloud, `LFRepositoryAPI` on self-hosted servers
+pub fn api_base_path(address: &str) -> &'static str {
+    if is_cloud_api_host(address) {
+        "repository"
+    } else {
+        "LFRepositoryAPI"
+    }
+}
+
 /// Validate a field name
 pub fn validate_field_name(name: &str) -> Result<String> {
     // Check for empty name
@@ -286,8 +422,7 @@ pub fn validate_field_value(value: &str) -> Result<String> {
     let sanitized = value
         .replace('\'', "''")  // Escape single quotes
         .replace('\\', "\\\\") // Escape backslashes
-        .replace('\0', "")     // Remove null bytes
-        .replace('\x1a', "");  // Remove SUB character
+        .replace(['\0', '\x1a'], ""); // Remove null bytes and SUB character
 
     Ok(sanitized)
 }
@@ -344,14 +479,27 @@ pub fn validate_file_name(name: &str) -> Result<String> {
     Ok(name.to_string())
 }
 
-/// Validate file size
+/// Validate file size against `max_file_size`
 pub fn validate_file_size(size: u64) -> Result<u64> {
-    if size > MAX_FILE_SIZE {
-        return Err(ErrorKind::FileSizeTooLarge(size, MAX_FILE_SIZE).into());
+    let max = max_file_size();
+    if size > max {
+        return Err(ErrorKind::FileSizeTooLarge(size, max).into());
     }
     Ok(size)
 }
 
+/// Validate a `type/subtype` MIME type, returning it lowercased
+pub fn validate_mime_type(mime_type: &str) -> Result<String> {
+    let is_token = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+    };
+
+    match mime_type.split_once('/') {
+        Some((kind, subtype)) if is_token(kind) && is_token(subtype) => Ok(mime_type.to_ascii_lowercase()),
+        _ => Err(ErrorKind::InvalidMimeType(mime_type.to_string()).into()),
+    }
+}
+
 /// Validate JSON metadata object
 pub fn validate_metadata_json(metadata: &serde_json::Value) -> Result<serde_json::Value> {
     match metadata {
@@ -452,6 +600,28 @@ mod tests {
         assert!(validate_server_address(&"a".repeat(254)).is_err());
     }
 
+    #[test]
+    fn test_validate_next_link() {
+        let base = "https://api.example.com/LFRepositoryAPI/v1/Repositories/repo1";
+
+        // Valid links
+        assert!(validate_next_link(&format!("{}/Entries/1/Laserfiche.Repository.Folder/children?$skip=100", base), "api.example.com", "repo1").is_ok());
+        assert!(validate_next_link(&format!("{}/Entries/Search?q=x&$skip=50", base), "API.example.com", "repo1").is_ok());
+        assert!(validate_next_link("https://api.example.com/LFRepositoryAPI/v2/Repositories/repo1/Entries/1/Folder/Children?$skip=100", "api.example.com", "repo1").is_ok());
+
+        // Invalid links
+        assert!(validate_next_link("not a url", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("http://api.example.com/LFRepositoryAPI/v1/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://evil.example.net/LFRepositoryAPI/v1/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://api.example.com.evil.net/LFRepositoryAPI/v1/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://api.example.com:8443/LFRepositoryAPI/v1/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://user:pw@api.example.com/LFRepositoryAPI/v1/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link(&format!("{}/Entries/1", base), "api.example.com", "other-repo").is_err());
+        assert!(validate_next_link("https://api.example.com/LFRepositoryAPI/latest/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://api.example.com/LFRepositoryAPI/v2/Other/Repositories/repo1/Entries/1", "api.example.com", "repo1").is_err());
+        assert!(validate_next_link("https://api.example.com/somewhere/else", "api.example.com", "repo1").is_err());
+    }
+
     #[test]
     fn test_validate_field_name() {
         // Valid names
@@ -518,6 +688,45 @@ mod tests {
         assert!(validate_file_size(MAX_FILE_SIZE + 1).is_err());
     }
 
+    #[tokio::test]
+    async fn test_scoped_max_file_size() {
+        let drawing = 3 * MAX_FILE_SIZE;
+        assert!(with_max_file_size(4 * MAX_FILE_SIZE, async { validate_file_size(drawing) }).await.is_ok());
+        match with_max_file_size(1024, async { validate_file_size(2048) }).await {
+            Err(Error(ErrorKind::FileSizeTooLarge(2048, 1024), _)) => {}
+            other => panic!("expected FileSizeTooLarge, got {:?}", other),
+        }
+        assert!(validate_file_size(drawing).is_err());
+    }
+
+    #[test]
+    fn test_cloud_api_hosts() {
+        assert!(is_cloud_api_host("api.laserfiche.com"));
+        assert!(is_cloud_api_host("API.eu.laserfiche.com"));
+        assert!(is_cloud_api_host("api.laserfiche.ca"));
+        assert!(!is_cloud_api_host("test.laserfiche.com"));
+        assert!(!is_cloud_api_host("api.notlaserfiche.com"));
+        assert!(!is_cloud_api_host("api.example.com"));
+
+        assert_eq!(api_base_path("api.laserfiche.com"), "repository");
+        assert_eq!(api_base_path("lf.example.com"), "LFRepositoryAPI");
+
+        let next = "https://api.laserfiche.com/repository/v1/Repositories/r-1234/Entries/1/Laserfiche.Repository.Folder/children?$skip=100";
+        assert!(validate_next_link(next, "api.laserfiche.com", "r-1234").is_ok());
+    }
+
+    #[test]
+    fn test_validate_mime_type() {
+        assert_eq!(validate_mime_type("application/vnd.ms-outlook").unwrap(), "application/vnd.ms-outlook");
+        assert_eq!(validate_mime_type("Image/HEIC").unwrap(), "image/heic");
+
+        assert!(validate_mime_type("").is_err());
+        assert!(validate_mime_type("pdf").is_err());
+        assert!(validate_mime_type("application/").is_err());
+        assert!(validate_mime_type("text/plain; charset=utf-8").is_err());
+        assert!(validate_mime_type("a/b/c").is_err());
+    }
+
     #[test]
     fn test_validate_metadata_json() {
         // Valid metadata
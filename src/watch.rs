@@ -0,0 +1,228 @@
+// Copyright 2023-2024 The Open Sam Foundation (OSF)
+// Developed by Caleb Mitchell Smith (PixelCoda)
+// Licensed under GPLv3....see LICENSE file.
+
+use crate::clock;
+use crate::laserfiche::{
+    box_stream, Auth, BoxStream, Entry, EntryId, EntryOrError, Error, ErrorKind, LFApiServer, MaybeSend, MetadataResult,
+    MetadataResultOrError, Result,
+};
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// One way a watched entry differs from the previous poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryChange {
+    /// `last_modified_time` moved on
+    Modified { previous: String, current: String },
+    /// Fields whose values changed, were set, or were cleared, by name
+    Fields(Vec<String>),
+    /// The entry was moved to another folder
+    Moved { from: i64, to: i64 },
+}
+
+/// An entry and its field values as of one poll
+#[derive(Debug, Clone, Default)]
+pub struct EntryState {
+    pub entry: Entry,
+    pub metadata: MetadataResult,
+}
+
+impl EntryState {
+    /// What changed between `previous` and this state; empty if nothing did
+    pub fn changes_since(&self, previous: &EntryState) -> Vec<EntryChange> {
+        let mut changes = Vec::new();
+        if self.entry.last_modified_time != previous.entry.last_modified_time {
+            changes.push(EntryChange::Modified {
+                previous: previous.entry.last_modified_time.clone(),
+                current: self.entry.last_modified_time.clone(),
+            });
+        }
+
+        let (before, after) = (field_values(&previous.metadata), field_values(&self.metadata));
+        let mut changed: Vec<String> = after
+            .iter()
+            .filter(|(name, values)| before.get(*name) != Some(*values))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        changed.extend(before.keys().filter(|name| !after.contains_key(*name)).map(|name| name.to_string()));
+        if !changed.is_empty() {
+            changed.sort();
+            changes.push(EntryChange::Fields(changed));
+        }
+
+        if self.entry.parent_id != previous.entry.parent_id {
+            changes.push(EntryChange::Moved { from: previous.entry.parent_id, to: self.entry.parent_id });
+        }
+        changes
+    }
+}
+
+/// Each field's values in position order, by field name
+fn field_values(metadata: &MetadataResult) -> BTreeMap<&str, Vec<Option<&str>>> {
+    metadata
+        .value
+        .iter()
+        .map(|field| {
+            let mut values: Vec<_> = field.values.iter().collect();
+            values.sort_by_key(|value| value.position);
+            (field.field_name.as_str(), values.into_iter().map(|value| value.value.as_deref()).collect())
+        })
+        .collect()
+}
+
+/// A poll that found a watched entry changed
+#[derive(Debug, Clone)]
+pub struct EntryEvent {
+    /// The entry and its field values after the change
+    pub state: EntryState,
+    pub changes: Vec<EntryChange>,
+}
+
+/// Polls a single entry, e.g. the document an approval screen shows, for
+/// changes to its modification time, field values, or folder
+pub struct EntryWatch;
+
+impl EntryWatch {
+    /// Poll `entry_id` every `interval`, yielding an event each time it
+    /// has changed since the previous poll
+    ///
+    /// The first poll records the starting state and yields nothing. Any
+    /// error, including the API error once the entry is deleted, ends the
+    /// stream. Every poll uses `auth` as given; `LfClient::watch_entry`
+    /// refreshes the token as the watch runs.
+    ///
+    /// # Arguments
+    /// * `api_server` - API server configuration
+    /// * `auth` - Authentication token
+    /// * `entry_id` - Entry to watch
+    /// * `interval` - Wait between polls
+    pub fn watch(
+        api_server: LFApiServer,
+        auth: Auth,
+        entry_id: impl Into<EntryId>,
+        interval: Duration
+    ) -> BoxStream<'static, Result<EntryEvent>> {
+        let entry_id = entry_id.into();
+        watch_with(
+            move || {
+                let (api_server, auth) = (api_server.clone(), auth.clone());
+                async move {
+                    let entry = Entry::get(api_server.clone(), auth.clone(), entry_id).await?;
+                    let metadata = Entry::get_metadata(api_server, auth, entry_id).await?;
+                    Self::state(entry, metadata)
+                }
+            },
+            interval,
+        )
+    }
+
+    /// Combine one poll's responses, turning an API error into
+    /// `ErrorKind::ApiError`
+    pub(crate) fn state(entry: EntryOrError, metadata: MetadataResultOrError) -> Result<EntryState> {
+        let entry = match entry {
+            EntryOrError::Entry(entry) => entry,
+            EntryOrError::LFAPIError(error) => return Err(Error::from(ErrorKind::ApiError(Box::new(error)))),
+        };
+        let metadata = match metadata {
+            MetadataResultOrError::Metadata(metadata) => metadata,
+            MetadataResultOrError::LFAPIError(error) => return Err(Error::from(ErrorKind::ApiError(Box::new(error)))),
+        };
+        Ok(EntryState { entry, metadata })
+    }
+}
+
+/// Call `poll` every `interval`, yielding the changes between consecutive
+/// states
+pub(crate) fn watch_with<F, Fut>(poll: F, interval: Duration) -> BoxStream<'static, Result<EntryEvent>>
+where
+    F: FnMut() -> Fut + MaybeSend + 'static,
+    Fut: std::future::Future<Output = Result<EntryState>> + MaybeSend + 'static,
+{
+    let events = futures_util::stream::try_unfold((None, poll), move |(mut previous, mut poll)| async move {
+        loop {
+            if previous.is_some() {
+                clock::sleep(interval).await;
+            }
+            let current = poll().await?;
+            let changes = previous.as_ref().map(|previous| current.changes_since(previous)).unwrap_or_default();
+            if changes.is_empty() {
+                previous = Some(current);
+                continue;
+            }
+            let event = EntryEvent { state: current.clone(), changes };
+            return Ok(Some((event, (Some(current), poll))));
+        }
+    });
+    box_stream(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::laserfiche::{MetadataResultFieldValue, MetadataResultValue};
+    use futures_util::stream::{StreamExt, TryStreamExt};
+    use std::sync::{Arc, Mutex};
+
+    fn state(modified: &str, parent_id: i64, fields: &[(&str, &[&str])]) -> EntryState {
+        let entry = Entry { id: 7, parent_id, last_modified_time: modified.to_string(), ..Default::default() };
+        let value = fields
+            .iter()
+            .map(|(name, values)| MetadataResultValue {
+                field_name: name.to_string(),
+                values: values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, value)| MetadataResultFieldValue { value: Some(value.to_string()), position: i as i64 + 1 })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        EntryState { entry, metadata: MetadataResult { value } }
+    }
+
+    #[test]
+    fn test_changes_since() {
+        let before = state("2024-01-01T00:00:00Z", 1, &[("Status", &["Pending"]), ("Notes", &["a"])]);
+        assert!(before.changes_since(&before).is_empty());
+
+        let after = state("2024-01-02T00:00:00Z", 5, &[("Status", &["Approved"]), ("Approver", &["kim"])]);
+        assert_eq!(after.changes_since(&before), vec![
+            EntryChange::Modified {
+                previous: "2024-01-01T00:00:00Z".to_string(),
+                current: "2024-01-02T00:00:00Z".to_string(),
+            },
+            EntryChange::Fields(vec!["Approver".to_string(), "Notes".to_string(), "Status".to_string()]),
+            EntryChange::Moved { from: 1, to: 5 },
+        ]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_watch_yields_only_changes() {
+        let polls = Arc::new(Mutex::new(vec![
+            state("t1", 1, &[("Status", &["Pending"])]),
+            state("t1", 1, &[("Status", &["Pending"])]),
+            state("t2", 1, &[("Status", &["Approved"])]),
+            state("t2", 3, &[("Status", &["Approved"])]),
+        ]));
+        let events: Vec<EntryEvent> = watch_with(
+            move || {
+                let next = {
+                    let mut polls = polls.lock().unwrap();
+                    (!polls.is_empty()).then(|| polls.remove(0))
+                };
+                async move { next.ok_or_else(|| Error::from("entry deleted")) }
+            },
+            Duration::from_secs(30),
+        )
+        .take(2)
+        .try_collect()
+        .await
+        .unwrap();
+
+        assert_eq!(events[0].changes.len(), 2);
+        assert_eq!(events[0].state.entry.last_modified_time, "t2");
+        assert_eq!(events[1].changes, vec![EntryChange::Moved { from: 1, to: 3 }]);
+    }
+}
@@ -1,6 +1,5 @@
 use laserfiche_rs::config::Config;
 use std::env;
-use std::process::Command;
 
 #[test]
 fn test_application_fails_without_env_vars() {
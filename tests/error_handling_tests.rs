@@ -118,10 +118,9 @@ async fn test_search_with_invalid_filter() {
         config.api_server.clone(),
         auth,
         "".to_string(),
-        Some("invalid filter syntax $@#".to_string()),  // Invalid OData filter
-        None,
-        None,
-        Some(5),
+        SearchOptions::new()
+            .filter("invalid filter syntax $@#")  // Invalid OData filter
+            .top(5),
     ).await;
 
     // The API might accept the request but return an error in the response
@@ -1,6 +1,6 @@
 use laserfiche_rs::laserfiche::*;
 use std::env;
-use std::time::{SystemTime, UNIX_EPOCH, Duration};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 mod test_helpers;
 use test_helpers::*;
@@ -66,6 +66,7 @@ async fn test_token_refresh() {
 }
 
 #[test]
+#[cfg(feature = "blocking")]
 fn test_blocking_authentication() {
     let config = skip_if_no_config!();
     
@@ -180,10 +181,7 @@ async fn test_search_entries() {
         config.api_server.clone(),
         auth,
         "".to_string(),  // No search term - get all accessible entries
-        None,  // No filter
-        None,  // No orderby  
-        None,  // No select
-        Some(10),  // Top 10 results
+        SearchOptions::new().top(10),  // Top 10 results
     ).await;
 
     assert!(search_result.is_ok(), 
@@ -281,17 +279,17 @@ async fn test_year_2038_compatibility() {
     let username = env::var("LF_TEST_USERNAME").ok();
     let password = env::var("LF_TEST_PASSWORD").ok();
 
-    if address.is_some() && repository.is_some() && username.is_some() && password.is_some() {
+    if let (Some(address), Some(repository), Some(username), Some(password)) = (address, repository, username, password) {
         let api_server = LFApiServer {
-            address: address.unwrap(),
-            repository: repository.unwrap(),
+            address,
+            repository,
         };
 
         // Create auth and verify it handles current time correctly
         let auth_result = Auth::new(
             api_server,
-            username.unwrap(),
-            password.unwrap()
+            username,
+            password
         ).await;
 
         if let Ok(AuthOrError::Auth(mut auth)) = auth_result {
@@ -309,6 +307,7 @@ async fn test_year_2038_compatibility() {
 }
 
 #[test]
+#[cfg(feature = "blocking")]
 fn test_blocking_future_timestamps() {
     // Test blocking API with future timestamp handling
     let address = env::var("LF_TEST_API_ADDRESS").ok();
@@ -0,0 +1,1169 @@
+use laserfiche_rs::laserfiche::*;
+use laserfiche_rs::fixtures::{FIXTURE_DOCUMENT_ID, FIXTURE_FOLDER_ID};
+use laserfiche_rs::testing::MockLaserfiche;
+
+#[tokio::test]
+async fn test_authentication_flow() {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_token(&Auth::fixture()).await;
+
+    let auth = mock.scope(Auth::new(mock.api_server(), "fixture-user".to_string(), "secret".to_string())).await.unwrap();
+    match auth {
+        AuthOrError::Auth(auth) => {
+            assert_eq!(auth.access_token, Auth::fixture().access_token);
+            assert_eq!(auth.username, "fixture-user");
+        }
+        AuthOrError::LFAPIError(error) => panic!("Authentication failed: {:?}", error),
+    }
+}
+
+#[tokio::test]
+async fn test_get_entry() {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_entry(&Entry::fixture()).await;
+
+    let entry = mock.client().get_entry(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(entry.name, "Invoice 0001");
+    assert!(entry.is_document());
+}
+
+#[tokio::test]
+async fn test_get_full_entry() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_entry(&Entry::fixture()).await;
+    let routes = [
+        ("fields", serde_json::to_value(MetadataResult::fixture(&[("Vendor", "Acme")])).unwrap()),
+        ("tags", serde_json::json!({ "value": [{ "id": 3, "name": "Paid", "isSecure": false }] })),
+        ("links", serde_json::json!({ "value": [] })),
+    ];
+    for (route, body) in routes {
+        Mock::given(method("GET"))
+            .and(path(mock.repository_path(&format!("Entries/{}/{}", FIXTURE_DOCUMENT_ID, route))))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(mock.server())
+            .await;
+    }
+
+    let full = mock.client().get_full_entry(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(full.entry.name, "Invoice 0001");
+    assert_eq!(full.metadata.value[0].field_name, "Vendor");
+    assert_eq!(full.tags.value[0].name, "Paid");
+    assert!(full.links.value.is_empty());
+
+    // Any refused part fails the whole read
+    mock.mock_error("GET", &format!("Entries/{}", FIXTURE_DOCUMENT_ID + 1), &LFAPIError::fixture(404, "Entry not found")).await;
+    let missing = mock.client().get_full_entry(FIXTURE_DOCUMENT_ID + 1).await.unwrap();
+    assert!(matches!(missing, FullEntryOrError::LFAPIError(_)));
+}
+
+#[tokio::test]
+async fn test_list_entries() {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(3)).await;
+
+    let entries = mock.client().list_entries(FIXTURE_FOLDER_ID).await.unwrap().into_result().unwrap();
+    let names: Vec<&str> = entries.value.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, vec!["Invoice 0001", "Invoice 0002", "Invoice 0003"]);
+}
+
+#[tokio::test]
+async fn test_list_entries_v2_routes() {
+    use laserfiche_rs::server_info::ApiVersion;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let route = format!("/LFRepositoryAPI/v2/Repositories/{}/Entries/{}/Folder/Children", mock.api_server().repository, FIXTURE_FOLDER_ID);
+    Mock::given(method("GET"))
+        .and(path(route))
+        .respond_with(ResponseTemplate::new(200).set_body_json(Entries::fixture_page(2)))
+        .mount(mock.server())
+        .await;
+
+    let client = mock.client().with_api_version(ApiVersion::V2);
+    let entries = client.list_entries(FIXTURE_FOLDER_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(entries.value.len(), 2);
+
+    // Calls without v2 routes yet fail before sending anything
+    match client.export_bytes(FIXTURE_DOCUMENT_ID).await {
+        Err(Error(ErrorKind::NotRoutedForVersion(route, version), _)) => {
+            assert!(route.contains("edoc"), "{}", route);
+            assert_eq!(version, "2.0");
+        }
+        Err(error) => panic!("expected NotRoutedForVersion, got {}", error),
+        Ok(_) => panic!("expected NotRoutedForVersion"),
+    }
+}
+
+#[tokio::test]
+async fn test_ensure_path() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let children = |id: i64| mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Folder/children", id));
+    let listing = |value: serde_json::Value| ResponseTemplate::new(200).set_body_json(serde_json::json!({ "value": value }));
+
+    // \Projects exists; \Projects\2024 is created by another client
+    // between our lookup and create; \Projects\2024\Invoices is new
+    Mock::given(method("GET")).and(path(children(1)))
+        .respond_with(listing(serde_json::json!([{ "id": 10, "name": "projects" }])))
+        .mount(mock.server()).await;
+    Mock::given(method("GET")).and(path(children(10)))
+        .respond_with(listing(serde_json::json!([])))
+        .up_to_n_times(1)
+        .mount(mock.server()).await;
+    Mock::given(method("GET")).and(path(children(10)))
+        .respond_with(listing(serde_json::json!([{ "id": 11, "name": "2024" }])))
+        .mount(mock.server()).await;
+    mock.mock_error("POST", "Entries/10/Laserfiche.Repository.Folder/children", &LFAPIError::fixture(409, "Entry already exists")).await;
+    Mock::given(method("GET")).and(path(children(11)))
+        .respond_with(listing(serde_json::json!([])))
+        .mount(mock.server()).await;
+    let invoices = Entry { id: 12, name: "Invoices".to_string(), parent_id: 11, ..Entry::fixture_folder() };
+    Mock::given(method("POST")).and(path(children(11)))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&invoices))
+        .expect(1)
+        .mount(mock.server()).await;
+
+    let folder = mock.client().ensure_path("\\Projects\\2024\\Invoices", "DEFAULT".to_string()).await.unwrap().into_result().unwrap();
+    assert_eq!(folder.id, 12);
+
+    // A path that already exists is only looked up
+    mock.mock_entry(&Entry { id: 10, name: "Projects".to_string(), ..Entry::fixture_folder() }).await;
+    let folder = mock.client().ensure_path("/Projects/", "DEFAULT".to_string()).await.unwrap().into_result().unwrap();
+    assert_eq!(folder.id, 10);
+}
+
+#[tokio::test]
+async fn test_list_repositories() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let repositories = serde_json::json!([
+        { "repoId": mock.api_server().repository, "repoName": "Fixture", "isSelfHosted": true },
+        { "repoId": "Archive", "repoName": "Archive", "isSelfHosted": true },
+    ]);
+    Mock::given(method("GET"))
+        .and(path("/LFRepositoryAPI/v1/Repositories"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(repositories))
+        .mount(mock.server())
+        .await;
+
+    let repositories = mock.client().list_repositories().await.unwrap().into_result().unwrap();
+    let ids: Vec<&str> = repositories.iter().map(|repository| repository.id.as_str()).collect();
+    assert_eq!(ids, vec![mock.api_server().repository.as_str(), "Archive"]);
+}
+
+#[tokio::test]
+async fn test_search_entries() {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_search(&Entries::fixture_page(2)).await;
+
+    let entries = mock.client()
+        .search("{LF:Name=\"Invoice*\"}".to_string(), SearchOptions::default())
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(entries.value.len(), 2);
+}
+
+#[tokio::test]
+async fn test_api_error() {
+    let mock = MockLaserfiche::start().await;
+    mock.mock_error("GET", "Entries/404", &LFAPIError::not_found()).await;
+
+    match mock.client().get_entry(404).await.unwrap() {
+        EntryOrError::LFAPIError(error) => {
+            assert!(error.is_not_found());
+            assert_eq!(error.title.as_deref(), Some("Entry not found."));
+        }
+        EntryOrError::Entry(entry) => panic!("Expected an API error, got {:?}", entry),
+    }
+}
+
+#[tokio::test]
+async fn test_download_tree_collisions() {
+    use laserfiche_rs::export::{CollisionPolicy, ExportManifest, ExportOutcome};
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let mut page = Entries::fixture_page(3);
+    page.value[1].name = "Invoice 0001".to_string();
+    mock.mock_children(FIXTURE_FOLDER_ID, &page).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server())
+        .await;
+
+    let target = std::env::temp_dir().join(format!("laserfiche-rs-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target);
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(target.join("Invoice 0003.pdf"), b"kept").unwrap();
+
+    let mut manifest = ExportManifest::new();
+    mock.client()
+        .download_tree(FIXTURE_FOLDER_ID, &target, CollisionPolicy::AppendEntryId, &mut manifest)
+        .await
+        .unwrap();
+
+    let saved: Vec<(String, ExportOutcome)> = manifest.entries.iter()
+        .map(|entry| (entry.target_path.rsplit(['/', '\\']).next().unwrap().to_string(), entry.outcome))
+        .collect();
+    assert_eq!(saved, vec![
+        ("Invoice 0001.pdf".to_string(), ExportOutcome::Written),
+        (format!("Invoice 0001 [{}].pdf", FIXTURE_DOCUMENT_ID + 1), ExportOutcome::Renamed),
+        (format!("Invoice 0003 [{}].pdf", FIXTURE_DOCUMENT_ID + 2), ExportOutcome::Renamed),
+    ]);
+    assert_eq!(std::fs::read(target.join("Invoice 0003.pdf")).unwrap(), b"kept");
+    assert_eq!(manifest.entries[0].bytes, 8);
+
+    // Skipping leaves every existing file alone on a second run
+    let mut rerun = ExportManifest::new();
+    mock.client().download_tree(FIXTURE_FOLDER_ID, &target, CollisionPolicy::Skip, &mut rerun).await.unwrap();
+    assert!(rerun.entries.iter().all(|entry| entry.outcome == ExportOutcome::Skipped));
+    std::fs::remove_dir_all(&target).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_tree_with_pipeline() {
+    use laserfiche_rs::export::{CollisionPolicy, ExportManifest, ExportOutcome};
+    use laserfiche_rs::pipeline::Pipeline;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(3)).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server())
+        .await;
+
+    let target = std::env::temp_dir().join(format!("laserfiche-rs-pipeline-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target);
+
+    let pipeline = Pipeline::default();
+    let mut manifest = ExportManifest::new();
+    mock.client()
+        .download_tree_with_pipeline(&pipeline, FIXTURE_FOLDER_ID, &target, CollisionPolicy::default(), &mut manifest)
+        .await
+        .unwrap();
+
+    let mut ids: Vec<i64> = manifest.entries.iter().map(|entry| entry.entry_id).collect();
+    ids.sort_unstable();
+    assert_eq!(ids, vec![FIXTURE_DOCUMENT_ID, FIXTURE_DOCUMENT_ID + 1, FIXTURE_DOCUMENT_ID + 2]);
+    assert!(manifest.entries.iter().all(|entry| entry.outcome == ExportOutcome::Written && entry.bytes == 8));
+    assert_eq!(pipeline.stats().snapshot().files, 3);
+    assert_eq!(std::fs::read_dir(&target).unwrap().count(), 3);
+    std::fs::remove_dir_all(&target).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_tree_backs_off_on_429() {
+    use laserfiche_rs::config::ThrottleConfig;
+    use laserfiche_rs::export::{CollisionPolicy, ExportManifest};
+    use laserfiche_rs::pipeline::Pipeline;
+    use laserfiche_rs::throttle::AdaptiveThrottle;
+    use std::sync::Arc;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(1)).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+        .respond_with(ResponseTemplate::new(429).set_body_json(LFAPIError::fixture(429, "Too many requests")))
+        .mount(mock.server())
+        .await;
+
+    let target = std::env::temp_dir().join(format!("laserfiche-rs-throttled-export-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target);
+
+    let throttle = Arc::new(AdaptiveThrottle::new(ThrottleConfig::default()).unwrap());
+    let pipeline = Pipeline::default().with_throttle(Arc::clone(&throttle));
+    let mut manifest = ExportManifest::new();
+    let _ = mock.client()
+        .download_tree_with_pipeline(&pipeline, FIXTURE_FOLDER_ID, &target, CollisionPolicy::default(), &mut manifest)
+        .await;
+
+    // The refused download halved the rate
+    assert_eq!(throttle.rate(), ThrottleConfig::default().initial_rate / 2.0);
+    let _ = std::fs::remove_dir_all(&target);
+}
+
+#[tokio::test]
+async fn test_export_tree_overwrite() {
+    use laserfiche_rs::export::{self, CollisionPolicy, ExportOutcome};
+    use laserfiche_rs::pipeline::Pipeline;
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let page = Entries::fixture_page(2);
+    mock.mock_children(FIXTURE_FOLDER_ID, &page).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server())
+        .await;
+
+    let target = std::env::temp_dir().join(format!("laserfiche-rs-export-tree-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target);
+    std::fs::create_dir_all(&target).unwrap();
+    let existing = target.join(export::export_file_name(&page.value[0]));
+    std::fs::write(&existing, b"stale copy").unwrap();
+
+    let manifest = mock.scope(Entry::export_tree(
+        mock.api_server(), Auth::fixture(), FIXTURE_FOLDER_ID, &target, CollisionPolicy::Overwrite, &Pipeline::default()
+    )).await.unwrap();
+
+    let outcome = |id: i64| manifest.entries.iter().find(|entry| entry.entry_id == id).unwrap().outcome;
+    assert_eq!(outcome(page.value[0].id), ExportOutcome::Overwritten);
+    assert_eq!(outcome(page.value[1].id), ExportOutcome::Written);
+    assert_eq!(std::fs::read(&existing).unwrap(), b"%PDF-1.7");
+    assert_eq!(std::fs::read_dir(&target).unwrap().count(), 2);
+    std::fs::remove_dir_all(&target).unwrap();
+}
+
+#[tokio::test]
+async fn test_download_tree_within_spent_budget() {
+    use laserfiche_rs::export::{CollisionPolicy, ExportManifest, ExportOutcome};
+    use std::time::Duration;
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(2)).await;
+
+    let target = std::env::temp_dir().join(format!("laserfiche-rs-budget-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&target);
+
+    // Nothing is downloaded, and every document is listed as left to do
+    let mut manifest = ExportManifest::new();
+    mock.client()
+        .download_tree_within_budget(FIXTURE_FOLDER_ID, &target, CollisionPolicy::default(), Duration::ZERO, &mut manifest)
+        .await
+        .unwrap();
+    assert_eq!(manifest.entries.len(), 2);
+    assert!(manifest.entries.iter().all(|entry| entry.outcome == ExportOutcome::Deferred && entry.bytes == 0));
+    assert_eq!(std::fs::read_dir(&target).unwrap().count(), 0);
+    std::fs::remove_dir_all(&target).unwrap();
+}
+
+#[tokio::test]
+async fn test_import_and_export_bytes() {
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let created = ImportResult {
+        operations: Operations {
+            entry_create: EntryCreate { entry_id: FIXTURE_DOCUMENT_ID, exceptions: Vec::new() },
+            ..Default::default()
+        },
+        document_link: String::new(),
+    };
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/note.txt", FIXTURE_FOLDER_ID))))
+        .and(body_string_contains("hello from memory"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(&created))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("GET"))
+        .and(path(mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"hello from memory".to_vec()))
+        .mount(mock.server())
+        .await;
+
+    let client = mock.client();
+    let result = client
+        .import_bytes(b"hello from memory".to_vec(), "note.txt".to_string(), FIXTURE_FOLDER_ID, None)
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(result.operations.entry_create.entry_id, FIXTURE_DOCUMENT_ID);
+
+    let content = client.export_bytes(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(content, b"hello from memory");
+}
+
+#[tokio::test]
+async fn test_import_sniffs_content_type() {
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let upload = |name: &str| mock.repository_path(&format!("Entries/{}/{}", FIXTURE_FOLDER_ID, name));
+    // A scanner's extensionless output is labelled by its magic bytes
+    Mock::given(method("POST")).and(path(upload("SCAN0001")))
+        .and(body_string_contains("application/pdf"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server()).await;
+    // Streamed content is sniffed without losing the bytes read to sniff it
+    Mock::given(method("POST")).and(path(upload("SCAN0002")))
+        .and(body_string_contains("application/pdf"))
+        .and(body_string_contains("%PDF-1.7\nstreamed"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server()).await;
+    Mock::given(method("POST")).and(path(upload("site-plan.bin")))
+        .and(body_string_contains("image/vnd.dwg"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server()).await;
+
+    let client = mock.client();
+    client.import_bytes(b"%PDF-1.7\n".to_vec(), "SCAN0001".to_string(), FIXTURE_FOLDER_ID, None)
+        .await.unwrap().into_result().unwrap();
+    let content = b"%PDF-1.7\nstreamed";
+    client.import_reader(&content[..], content.len() as u64, "SCAN0002".to_string(), FIXTURE_FOLDER_ID, None)
+        .await.unwrap().into_result().unwrap();
+
+    let file = std::env::temp_dir().join(format!("lf_sniff_{}.bin", std::process::id()));
+    std::fs::write(&file, b"AC1032").unwrap();
+    let request = ImportRequest::new().mime_type("image/vnd.dwg");
+    let result = client
+        .import_with_request(file.to_string_lossy().into_owned(), "site-plan.bin".to_string(), FIXTURE_FOLDER_ID, &request)
+        .await;
+    std::fs::remove_file(&file).unwrap();
+    result.unwrap().into_result().unwrap();
+}
+
+#[tokio::test]
+async fn test_transfer_progress() {
+    use laserfiche_rs::progress::{TransferProgress, TransferUpdate};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let content = vec![7u8; 200_000];
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/large.bin", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("GET"))
+        .and(path(mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(content.clone()))
+        .mount(mock.server())
+        .await;
+
+    let file = std::env::temp_dir().join(format!("lf_transfer_progress_{}.bin", std::process::id()));
+    std::fs::write(&file, &content).unwrap();
+    let client = mock.client();
+    let (progress, receiver) = TransferProgress::channel();
+    client
+        .import_with_progress(file.to_string_lossy().into_owned(), "large.bin".to_string(), FIXTURE_FOLDER_ID, &progress)
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    std::fs::remove_file(&file).unwrap();
+    assert_eq!(*receiver.borrow(), TransferUpdate { transferred: 200_000, total: Some(200_000) });
+
+    let updates = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let seen = updates.clone();
+    let progress = TransferProgress::new(move |update| seen.lock().unwrap().push(update));
+    let mut downloaded = Vec::new();
+    let written = client
+        .export_stream_with_progress(FIXTURE_DOCUMENT_ID, &mut downloaded, &progress)
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(written, 200_000);
+    assert_eq!(downloaded, content);
+    let updates = updates.lock().unwrap();
+    assert!(updates.windows(2).all(|pair| pair[0].transferred < pair[1].transferred));
+    assert_eq!(updates.last(), Some(&TransferUpdate { transferred: 200_000, total: Some(200_000) }));
+}
+
+#[tokio::test]
+async fn test_client_max_file_size() {
+    use laserfiche_rs::validation;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/drawing.dwg", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let import = |client: laserfiche_rs::client::LfClient| async move {
+        client.import_bytes(vec![0u8; 64], "drawing.dwg".to_string(), FIXTURE_FOLDER_ID, None).await
+    };
+    match import(mock.client().with_max_file_size(32)).await {
+        Err(Error(ErrorKind::ValidationError(validation::Error(validation::ErrorKind::FileSizeTooLarge(64, 32), _)), _)) => {}
+        Err(error) => panic!("expected FileSizeTooLarge, got {}", error),
+        Ok(_) => panic!("expected FileSizeTooLarge"),
+    }
+    import(mock.client().with_max_file_size(64)).await.unwrap().into_result().unwrap();
+}
+
+#[tokio::test]
+async fn test_bulk_import_keeps_max_file_size() {
+    use laserfiche_rs::importer::{BulkImporter, ImportJob};
+    use laserfiche_rs::validation;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/small.dwg", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("lf_bulk_max_file_size_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("small.dwg"), [0u8; 16]).unwrap();
+    std::fs::write(dir.join("large.dwg"), [0u8; 64]).unwrap();
+    let jobs = ["small.dwg", "large.dwg"].map(|name| ImportJob::new(dir.join(name), FolderId(FIXTURE_FOLDER_ID)));
+
+    // Uploads run on pipeline tasks, which must see the caller's limit
+    let importer = BulkImporter::new(2).retry_delay(std::time::Duration::ZERO);
+    let run = importer.run(mock.api_server(), Auth::fixture(), jobs);
+    let report = mock.scope(validation::with_max_file_size(32, run)).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(report.imported.len(), 1);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].path.ends_with("large.dwg"));
+    assert!(report.failed[0].error.contains("64"), "{}", report.failed[0].error);
+}
+
+#[tokio::test]
+async fn test_checksummed_transfers() {
+    use laserfiche_rs::integrity::Checksum;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    // A checksum recorded for some other content
+    const OTHER_SHA256: &str = "5d1eaba1ab1d44cc1d8b2e3d7f8a3dd2e6437c9a2b3d6c2ee7d6b0e59b5fa2c2";
+    let mock = MockLaserfiche::start().await;
+    Mock::given(method("POST"))
+        .and(path(mock.repository_path(&format!("Entries/{}/scan.pdf", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .mount(mock.server())
+        .await;
+    let edoc = |entry_id: i64| mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", entry_id));
+    Mock::given(method("GET")).and(path(edoc(FIXTURE_DOCUMENT_ID)))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server()).await;
+    // A server reporting a digest that does not match what it sent
+    Mock::given(method("GET")).and(path(edoc(FIXTURE_DOCUMENT_ID + 1)))
+        .respond_with(ResponseTemplate::new(200)
+            .insert_header("Repr-Digest", "sha-256=:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=:")
+            .set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server()).await;
+
+    let file = std::env::temp_dir().join(format!("lf_checksum_{}.pdf", std::process::id()));
+    std::fs::write(&file, b"%PDF-1.7").unwrap();
+    let client = mock.client();
+    let import = client
+        .import_checksummed(file.to_string_lossy().into_owned(), "scan.pdf".to_string(), FIXTURE_FOLDER_ID)
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    std::fs::remove_file(&file).unwrap();
+    let sha256 = import.checksum.sha256.clone();
+    assert_eq!(import.checksum.bytes, 8);
+    assert_eq!(sha256.len(), 64);
+
+    let verified = client.verify_checksum(FIXTURE_DOCUMENT_ID, &sha256).await.unwrap().into_result().unwrap();
+    assert_eq!(verified, Checksum { bytes: 8, sha256: sha256.clone() });
+    match client.verify_checksum(FIXTURE_DOCUMENT_ID, OTHER_SHA256).await {
+        Err(Error(ErrorKind::IntegrityError(entry_id, expected, actual), _)) => {
+            assert_eq!((entry_id, expected.as_str(), actual), (FIXTURE_DOCUMENT_ID, OTHER_SHA256, sha256.clone()));
+        }
+        Err(error) => panic!("expected IntegrityError, got {}", error),
+        Ok(_) => panic!("expected IntegrityError"),
+    }
+
+    let mut content = Vec::new();
+    match client.export_checksummed(FIXTURE_DOCUMENT_ID + 1, &mut content, None).await {
+        Err(Error(ErrorKind::IntegrityError(_, expected, actual), _)) => {
+            assert_eq!(expected, "0".repeat(64));
+            assert_eq!(actual, sha256);
+        }
+        Err(error) => panic!("expected IntegrityError, got {}", error),
+        Ok(_) => panic!("expected IntegrityError"),
+    }
+}
+
+#[tokio::test]
+async fn test_ranged_and_resumed_export() {
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let edoc = mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID));
+    let content = b"0123456789abcdef";
+    Mock::given(method("GET")).and(path(edoc.clone())).and(header("Range", "bytes=4-7"))
+        .respond_with(ResponseTemplate::new(206).insert_header("Content-Range", "bytes 4-7/16").set_body_bytes(&content[4..8]))
+        .mount(mock.server()).await;
+    // Only the current version resumes; the If-Range of an older one gets it in full
+    Mock::given(method("GET")).and(path(edoc.clone())).and(header("If-Range", "\"v2\""))
+        .respond_with(ResponseTemplate::new(206)
+            .insert_header("Content-Range", "bytes 10-15/16")
+            .insert_header("ETag", "\"v2\"")
+            .set_body_bytes(&content[10..]))
+        .mount(mock.server()).await;
+    Mock::given(method("GET")).and(path(edoc))
+        .respond_with(ResponseTemplate::new(200).insert_header("ETag", "\"v2\"").set_body_bytes(&content[..]))
+        .mount(mock.server()).await;
+
+    let client = mock.client();
+    let range = client.export_range(FIXTURE_DOCUMENT_ID, 4, Some(7)).await.unwrap().into_result().unwrap();
+    assert_eq!(range, b"4567");
+    // A server ignoring Range still yields just the requested bytes
+    let tail = client.export_range(FIXTURE_DOCUMENT_ID, 12, None).await.unwrap().into_result().unwrap();
+    assert_eq!(tail, b"cdef");
+
+    let file = std::env::temp_dir().join(format!("lf_resume_{}.bin", std::process::id()));
+    let etag_file = file.with_extension("bin.etag");
+    let partial = file.to_string_lossy().into_owned();
+    std::fs::write(&file, &content[..10]).unwrap();
+    std::fs::write(&etag_file, "\"v2\"").unwrap();
+    let written = client.export_resume(FIXTURE_DOCUMENT_ID, &partial).await.unwrap().into_result().unwrap();
+    assert_eq!(written, 6);
+    assert_eq!(std::fs::read(&file).unwrap(), content);
+    assert!(!etag_file.exists());
+
+    // Part of an older version is replaced rather than extended
+    std::fs::write(&file, b"stale").unwrap();
+    std::fs::write(&etag_file, "\"v1\"").unwrap();
+    let written = client.export_resume(FIXTURE_DOCUMENT_ID, &partial).await.unwrap().into_result().unwrap();
+    assert_eq!(written, 16);
+    assert_eq!(std::fs::read(&file).unwrap(), content);
+    assert!(!etag_file.exists());
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[tokio::test]
+async fn test_bulk_import() {
+    use laserfiche_rs::importer::{BulkImporter, ImportJob};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let dir = std::env::temp_dir().join(format!("lf_bulk_import_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let upload = |name: &str| mock.repository_path(&format!("Entries/{}/{}", FIXTURE_FOLDER_ID, name));
+    let created = |entry_id: i64| ImportResult {
+        operations: Operations { entry_create: EntryCreate { entry_id, exceptions: Vec::new() }, ..Default::default() },
+        document_link: String::new(),
+    };
+
+    Mock::given(method("POST")).and(path(upload("a.pdf")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(created(201)))
+        .expect(1)
+        .mount(mock.server()).await;
+    // Locked on the first attempt only
+    Mock::given(method("POST")).and(path(upload("b.pdf")))
+        .respond_with(ResponseTemplate::new(423).set_body_json(LFAPIError::fixture(423, "Entry locked")))
+        .up_to_n_times(1)
+        .mount(mock.server()).await;
+    Mock::given(method("POST")).and(path(upload("b.pdf")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(created(202)))
+        .mount(mock.server()).await;
+    Mock::given(method("POST")).and(path(upload("c.pdf")))
+        .respond_with(ResponseTemplate::new(400).set_body_json(LFAPIError::fixture(400, "Template not found")))
+        .expect(1)
+        .mount(mock.server()).await;
+
+    let jobs: Vec<ImportJob> = ["a.pdf", "b.pdf", "c.pdf"].iter().map(|name| {
+        std::fs::write(dir.join(name), b"%PDF-1.4").unwrap();
+        ImportJob::new(dir.join(name), FolderId(FIXTURE_FOLDER_ID))
+    }).collect();
+    let importer = BulkImporter::new(2).retry_delay(std::time::Duration::ZERO);
+    let report = mock.scope(importer.run(mock.api_server(), Auth::fixture(), jobs)).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut imported: Vec<(i64, u32)> = report.imported.iter()
+        .map(|success| (success.result.operations.entry_create.entry_id, success.attempts))
+        .collect();
+    imported.sort();
+    assert_eq!(imported, vec![(201, 1), (202, 2)]);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].path.ends_with("c.pdf"));
+    assert_eq!(report.failed[0].attempts, 1);
+    assert_eq!(report.stats.retries, 1);
+}
+
+#[tokio::test]
+async fn test_bulk_import_inspector() {
+    use futures_util::future::BoxFuture;
+    use laserfiche_rs::importer::{BulkImporter, ImportJob};
+    use laserfiche_rs::inspect::{ContentInspector, ContentRejected};
+    use tokio::io::{AsyncRead, AsyncReadExt};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    struct SignatureScanner;
+
+    impl ContentInspector for SignatureScanner {
+        fn inspect<'a>(
+            &'a self,
+            _file_name: &'a str,
+            content: &'a mut (dyn AsyncRead + Unpin + Send)
+        ) -> BoxFuture<'a, std::result::Result<(), ContentRejected>> {
+            Box::pin(async move {
+                let mut buffer = Vec::new();
+                content.read_to_end(&mut buffer).await.map_err(|e| ContentRejected::new(e.to_string()))?;
+                if buffer.windows(5).any(|window| window == b"EICAR") {
+                    return Err(ContentRejected::new("EICAR test signature found"));
+                }
+                Ok(())
+            })
+        }
+    }
+
+    let mock = MockLaserfiche::start().await;
+    let upload = |name: &str| mock.repository_path(&format!("Entries/{}/{}", FIXTURE_FOLDER_ID, name));
+    Mock::given(method("POST")).and(path(upload("clean.txt")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(1)
+        .mount(mock.server()).await;
+    Mock::given(method("POST")).and(path(upload("infected.txt")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(ImportResult::default()))
+        .expect(0)
+        .mount(mock.server()).await;
+
+    let dir = std::env::temp_dir().join(format!("lf_bulk_inspector_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("clean.txt"), b"quarterly report").unwrap();
+    std::fs::write(dir.join("infected.txt"), b"X5O!P%@AP EICAR-STANDARD").unwrap();
+    let jobs = ["clean.txt", "infected.txt"].map(|name| ImportJob::new(dir.join(name), FolderId(FIXTURE_FOLDER_ID)));
+
+    let importer = BulkImporter::new(2).inspector(std::sync::Arc::new(SignatureScanner));
+    let report = mock.scope(importer.run(mock.api_server(), Auth::fixture(), jobs)).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(report.imported.len(), 1);
+    assert_eq!(report.failed.len(), 1);
+    assert!(report.failed[0].path.ends_with("infected.txt"));
+    assert_eq!(report.failed[0].attempts, 0);
+    assert_eq!(report.failed[0].rejected, Some(ContentRejected::new("EICAR test signature found")));
+}
+
+#[tokio::test]
+async fn test_edoc_head() {
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    Mock::given(method("HEAD"))
+        .and(path(mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200)
+            .insert_header("content-type", "application/pdf")
+            .insert_header("content-length", "48213")
+            .insert_header("etag", "\"v7\"")
+            .insert_header("last-modified", "Tue, 02 Jan 2024 00:00:00 GMT"))
+        .mount(mock.server())
+        .await;
+
+    let client = mock.client();
+    let info = client.edoc_head(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(info.content_type.as_deref(), Some("application/pdf"));
+    assert_eq!(info.content_length, Some(48213));
+    assert_eq!(info.etag.as_deref(), Some("\"v7\""));
+    assert_eq!(info.last_modified, Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_704_153_600)));
+
+    // Unmatched HEAD requests get a 404 with no body to parse
+    match client.edoc_head(FIXTURE_DOCUMENT_ID + 1).await.unwrap() {
+        EdocInfoOrError::LFAPIError(error) => assert!(error.is_not_found()),
+        EdocInfoOrError::Info(info) => panic!("expected an error, got {:?}", info),
+    }
+}
+
+#[tokio::test]
+async fn test_set_and_delete_edoc() {
+    use wiremock::matchers::{body_string, header, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let edoc_path = mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/edoc", FIXTURE_DOCUMENT_ID));
+    Mock::given(method("PUT"))
+        .and(path(edoc_path.clone()))
+        .and(header("content-type", "text/plain"))
+        .and(body_string("revised content"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(2)
+        .mount(mock.server())
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path(edoc_path))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let client = mock.client();
+    let written = client
+        .set_edoc_bytes(FIXTURE_DOCUMENT_ID, b"revised content".to_vec(), "text/plain")
+        .await
+        .unwrap()
+        .into_result()
+        .unwrap();
+    assert_eq!(written, 15);
+
+    let reader = std::io::Cursor::new(b"revised content".to_vec());
+    let written = client.set_edoc(FIXTURE_DOCUMENT_ID, reader, 15, "text/plain").await.unwrap().into_result().unwrap();
+    assert_eq!(written, 15);
+
+    let deleted = client.delete_edoc(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    assert_eq!(deleted, DocumentId(FIXTURE_DOCUMENT_ID));
+}
+
+#[tokio::test]
+async fn test_document_pages() {
+    use laserfiche_rs::pages::{PageImageFormat, PageImageOptions};
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let pages_path = mock.repository_path(&format!("Entries/{}/Laserfiche.Repository.Document/pages", FIXTURE_DOCUMENT_ID));
+    Mock::given(method("GET"))
+        .and(path(pages_path.clone()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "value": [{"pageNumber": 1, "hasImage": true}, {"pageNumber": 2, "hasImage": true}, {"pageNumber": 3}]
+        })))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("{}/2/image", pages_path)))
+        .and(query_param("width", "200"))
+        .and(query_param("format", "jpeg"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"jpeg bytes".to_vec()))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("DELETE"))
+        .and(path(pages_path))
+        .and(query_param("pageRange", "1,3"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let client = mock.client();
+    let pages = client.list_pages(FIXTURE_DOCUMENT_ID).await.unwrap().into_result().unwrap();
+    let blank: Vec<u32> = pages.value.iter().filter(|page| !page.has_image).map(|page| page.page_number).collect();
+    assert_eq!(blank, vec![3]);
+
+    let options = PageImageOptions::new().width(200).format(PageImageFormat::Jpeg);
+    let image = client.export_page_image(FIXTURE_DOCUMENT_ID, 2, &options).await.unwrap().into_result().unwrap();
+    assert_eq!(image, b"jpeg bytes");
+
+    let deleted = client.delete_pages(FIXTURE_DOCUMENT_ID, &[3, 1]).await.unwrap().into_result().unwrap();
+    assert_eq!(deleted, 2);
+}
+
+#[tokio::test]
+async fn test_apply_tags_within_spent_budget() {
+    use laserfiche_rs::pipeline::Pipeline;
+    use laserfiche_rs::tagging;
+    use std::time::Duration;
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_search(&Entries::fixture_page(2)).await;
+
+    // No tags are read or written; every hit is left to do
+    let pipeline = Pipeline::default().with_budget(Duration::ZERO);
+    let report = mock.scope(tagging::apply_tags_to_search(
+        &pipeline, mock.api_server(), Auth::fixture(), "{LF:Name=\"*\"}".to_string(), SearchOptions::default(), vec![1]
+    )).await;
+    assert!(report.tagged.is_empty() && report.failed.is_empty() && !report.is_complete());
+    assert_eq!(report.deferred.len(), 2);
+    assert!(mock.server().received_requests().await.unwrap().iter().all(|request| !request.url.path().contains("/tags")));
+}
+
+#[tokio::test]
+async fn test_replace_field_value_dry_run() {
+    use laserfiche_rs::normalize::{self, FieldReplace, Scope};
+    use laserfiche_rs::pipeline::Pipeline;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(1)).await;
+    let fields_path = mock.repository_path(&format!("Entries/{}/fields", FIXTURE_DOCUMENT_ID));
+    let metadata = MetadataResult::fixture(&[("Vendor", "Acme Crop"), ("Notes", "Acme Crop invoice")]);
+    Mock::given(method("GET"))
+        .and(path(fields_path.clone()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&metadata))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(fields_path))
+        .and(body_string_contains("Acme Corp"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&metadata))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let replace = FieldReplace::new("Vendor", r"Acme\s+Crop", "Acme Corp").unwrap();
+    let scope = Scope::Folder(FolderId(FIXTURE_FOLDER_ID));
+    let pipeline = Pipeline::default();
+
+    let preview = mock.scope(normalize::replace_field_value(
+        &pipeline, mock.api_server(), Auth::fixture(), scope.clone(), &replace.clone().dry_run(true)
+    )).await;
+    assert!(preview.dry_run && preview.is_complete());
+    assert_eq!(preview.preview(), format!("{} Vendor[1]: \"Acme Crop\" -> \"Acme Corp\"\n", FIXTURE_DOCUMENT_ID));
+
+    let report = mock.scope(normalize::replace_field_value(&pipeline, mock.api_server(), Auth::fixture(), scope, &replace)).await;
+    assert!(!report.dry_run && report.is_complete());
+    assert_eq!(report.normalized, vec![EntryId(FIXTURE_DOCUMENT_ID)]);
+    assert_eq!(report.changes, preview.changes);
+
+    // A spent budget leaves the entry unread and listed as left to do
+    let pipeline = Pipeline::default().with_budget(std::time::Duration::ZERO);
+    let deferred = mock.scope(normalize::replace_field_value(
+        &pipeline, mock.api_server(), Auth::fixture(), Scope::Folder(FolderId(FIXTURE_FOLDER_ID)), &replace
+    )).await;
+    assert!(deferred.normalized.is_empty() && !deferred.is_complete());
+    assert_eq!(deferred.deferred, vec![EntryId(FIXTURE_DOCUMENT_ID)]);
+}
+
+#[tokio::test]
+async fn test_migrate_templates() {
+    use laserfiche_rs::migration::{self, TemplateMigration};
+    use laserfiche_rs::normalize::Scope;
+    use laserfiche_rs::pipeline::Pipeline;
+    use wiremock::matchers::{body_json, method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let mut page = Entries::fixture_page(2);
+    page.value[1].template_name = Some("Contract".to_string());
+    mock.mock_children(FIXTURE_FOLDER_ID, &page).await;
+    Mock::given(method("GET"))
+        .and(path(mock.repository_path(&format!("Entries/{}/fields", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResult::fixture(&[("Vendor Name", "acme")])))
+        .mount(mock.server())
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(mock.repository_path(&format!("Entries/{}/template", FIXTURE_DOCUMENT_ID))))
+        .and(body_json(serde_json::json!({ "templateName": "Vendor Invoice" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(Entry::fixture()))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(mock.repository_path(&format!("Entries/{}/fields", FIXTURE_DOCUMENT_ID))))
+        .and(body_json(serde_json::json!({ "Vendor": { "values": [{ "value": "ACME", "position": 1 }] } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResult::default()))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+
+    let migration = TemplateMigration::from_toml(r#"
+        [[templates]]
+        from = "Invoice"
+        to = "Vendor Invoice"
+
+        [[templates.fields]]
+        from = "Vendor Name"
+        to = "Vendor"
+        transforms = [{ case = "upper" }]
+    "#).unwrap();
+
+    let report = mock.scope(migration::migrate_templates(
+        &Pipeline::default(), mock.api_server(), Auth::fixture(), Scope::Folder(FolderId(FIXTURE_FOLDER_ID)), &migration, false
+    )).await;
+    assert!(report.is_complete());
+    assert_eq!(report.migrated, vec![EntryId(FIXTURE_DOCUMENT_ID)]);
+    assert_eq!(report.skipped, vec![EntryId(FIXTURE_DOCUMENT_ID + 1)]);
+}
+
+#[tokio::test]
+async fn test_migrate_templates_within_spent_budget() {
+    use laserfiche_rs::migration::{self, TemplateMigration};
+    use laserfiche_rs::normalize::Scope;
+    use laserfiche_rs::pipeline::Pipeline;
+    use std::time::Duration;
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(2)).await;
+    let migration = TemplateMigration::from_toml(r#"
+        [[templates]]
+        from = "Invoice"
+        to = "Vendor Invoice"
+    "#).unwrap();
+
+    // Nothing is read or written, and every entry is listed as left to do
+    let pipeline = Pipeline::default().with_budget(Duration::ZERO);
+    let report = mock.scope(migration::migrate_templates(
+        &pipeline, mock.api_server(), Auth::fixture(), Scope::Folder(FolderId(FIXTURE_FOLDER_ID)), &migration, false
+    )).await;
+    assert!(report.migrated.is_empty() && report.skipped.is_empty() && report.failed.is_empty());
+    let mut deferred = report.deferred.clone();
+    deferred.sort_by_key(|entry_id| entry_id.0);
+    assert_eq!(deferred, vec![EntryId(FIXTURE_DOCUMENT_ID), EntryId(FIXTURE_DOCUMENT_ID + 1)]);
+    assert!(!report.is_complete());
+    assert!(mock.server().received_requests().await.unwrap().iter().all(|request| request.method.as_str() == "GET"
+        && !request.url.path().contains("/fields")));
+}
+
+#[tokio::test]
+async fn test_replay_outbox() {
+    use laserfiche_rs::fixtures::FIXTURE_TIME;
+    use laserfiche_rs::outbox::{Mutation, Outbox, ReplayFailure};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_entry(&Entry::fixture()).await;
+    Mock::given(method("PATCH"))
+        .and(path(mock.repository_path(&format!("Entries/{}", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_json(Entry::fixture()))
+        .expect(1)
+        .mount(mock.server())
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(mock.repository_path(&format!("Entries/{}/tags", FIXTURE_DOCUMENT_ID))))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "value": [] })))
+        .expect(0)
+        .mount(mock.server())
+        .await;
+    mock.mock_error("DELETE", &format!("Entries/{}", FIXTURE_DOCUMENT_ID + 1), &LFAPIError::fixture(404, "Entry not found")).await;
+
+    let outbox_path = std::env::temp_dir().join(format!("lf_outbox_replay_{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&outbox_path);
+    let mut outbox = Outbox::open(&outbox_path).unwrap();
+    let entry_id = EntryId(FIXTURE_DOCUMENT_ID);
+    outbox.enqueue_checked(Mutation::Move { entry_id, parent_id: None, new_name: Some("Invoice 0001 (signed)".to_string()) }, FIXTURE_TIME).unwrap();
+    // Made against an older version than the server now has
+    outbox.enqueue_checked(Mutation::SetTags { entry_id, tag_ids: vec![3] }, "2023-12-01T00:00:00Z").unwrap();
+    outbox.enqueue(Mutation::Delete { entry_id: EntryId(FIXTURE_DOCUMENT_ID + 1), comment: "duplicate".to_string() }).unwrap();
+
+    let report = mock.client().replay_outbox(&mut outbox).await.unwrap();
+    let reopened = Outbox::open(&outbox_path).unwrap();
+    std::fs::remove_file(&outbox_path).unwrap();
+
+    assert!(!report.interrupted);
+    assert_eq!(report.applied.len(), 1);
+    assert!(matches!(&report.failed[0], ReplayFailure::Conflict { item, .. } if item.seq == 2));
+    assert!(matches!(&report.failed[1], ReplayFailure::Rejected { error, .. } if error.is_not_found()));
+    assert!(outbox.is_empty());
+    assert!(reopened.is_empty());
+}
+
+#[tokio::test]
+async fn test_folder_sync_download() {
+    use laserfiche_rs::sync::{FolderSync, SyncAction, SyncDirection};
+    use wiremock::matchers::{method, path_regex};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries::fixture_page(2)).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"/Laserfiche\.Repository\.Document/edoc$"))
+        .respond_with(ResponseTemplate::new(200).set_body_bytes(b"%PDF-1.7".to_vec()))
+        .mount(mock.server())
+        .await;
+
+    let dir = std::env::temp_dir().join(format!("lf_sync_download_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sync = FolderSync::new(&dir, FIXTURE_FOLDER_ID, SyncDirection::Download);
+    let run = || mock.scope(sync.run(mock.api_server(), Auth::fixture()));
+
+    let first = run().await.unwrap();
+    assert!(first.is_complete());
+    let created: Vec<(&str, SyncAction, u64)> = first.changes.iter()
+        .map(|change| (change.path.as_str(), change.action, change.bytes))
+        .collect();
+    assert_eq!(created.len(), 2);
+    assert!(created.contains(&("Invoice 0001.pdf", SyncAction::Create, 8)));
+    assert_eq!(std::fs::read(dir.join("Invoice 0002.pdf")).unwrap(), b"%PDF-1.7");
+
+    // Nothing changed on either side
+    let second = run().await.unwrap();
+    assert!(second.changes.is_empty());
+    assert_eq!(second.unchanged, 2);
+
+    // A local edit is put back from the repository
+    std::fs::write(dir.join("Invoice 0001.pdf"), b"edited locally").unwrap();
+    let third = run().await.unwrap();
+    assert_eq!(third.changes.len(), 1);
+    assert_eq!((third.changes[0].path.as_str(), third.changes[0].action), ("Invoice 0001.pdf", SyncAction::Update));
+    assert_eq!(std::fs::read(dir.join("Invoice 0001.pdf")).unwrap(), b"%PDF-1.7");
+    assert!(third.to_json().unwrap().contains("\"action\": \"update\""));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_folder_sync_upload() {
+    use laserfiche_rs::sync::{ChangeDetection, FolderSync, SyncAction, SyncDirection};
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, ResponseTemplate};
+
+    let mock = MockLaserfiche::start().await;
+    let created = |entry_id: i64| ImportResult {
+        operations: Operations { entry_create: EntryCreate { entry_id, exceptions: Vec::new() }, ..Default::default() },
+        document_link: String::new(),
+    };
+    // The `scans` subfolder already exists in the repository
+    let scans = Entry { id: 500, name: "scans".to_string(), parent_id: FIXTURE_FOLDER_ID, ..Entry::fixture_folder() };
+    mock.mock_children(FIXTURE_FOLDER_ID, &Entries { value: vec![scans.clone()], ..Default::default() }).await;
+    mock.mock_entry(&scans).await;
+    Mock::given(method("POST")).and(path(mock.repository_path(&format!("Entries/{}/a.pdf", FIXTURE_FOLDER_ID))))
+        .respond_with(ResponseTemplate::new(201).set_body_json(created(601)))
+        .expect(1)
+        .mount(mock.server()).await;
+    Mock::given(method("POST")).and(path(mock.repository_path("Entries/500/b.pdf")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(created(602)))
+        .expect(1)
+        .mount(mock.server()).await;
+    Mock::given(method("PUT")).and(path(mock.repository_path("Entries/601/Laserfiche.Repository.Document/edoc")))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(mock.server()).await;
+    Mock::given(method("DELETE")).and(path(mock.repository_path("Entries/602")))
+        .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({ "token": "delete-602" })))
+        .expect(1)
+        .mount(mock.server()).await;
+
+    let dir = std::env::temp_dir().join(format!("lf_sync_upload_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("scans")).unwrap();
+    std::fs::write(dir.join("a.pdf"), b"first draft").unwrap();
+    std::fs::write(dir.join("scans").join("b.pdf"), b"scan").unwrap();
+    let sync = FolderSync::new(&dir, FIXTURE_FOLDER_ID, SyncDirection::Upload)
+        .detection(ChangeDetection::Hash)
+        .delete_missing(true);
+    let run = || mock.scope(sync.run(mock.api_server(), Auth::fixture()));
+
+    let first = run().await.unwrap();
+    assert!(first.is_complete(), "{:?}", first.failed);
+    let mut ids: Vec<(String, Option<i64>)> = first.changes.iter().map(|change| (change.path.clone(), change.entry_id)).collect();
+    ids.sort();
+    assert_eq!(ids, vec![("a.pdf".to_string(), Some(601)), ("scans/b.pdf".to_string(), Some(602))]);
+
+    // A dry run lists the edit and the removal without sending either
+    std::fs::write(dir.join("a.pdf"), b"second draft").unwrap();
+    std::fs::remove_file(dir.join("scans").join("b.pdf")).unwrap();
+    let planned = mock.scope(sync.clone().dry_run(true).run(mock.api_server(), Auth::fixture())).await.unwrap();
+    assert_eq!(planned.changes.len(), 2);
+
+    let second = run().await.unwrap();
+    let mut actions: Vec<(String, SyncAction)> = second.changes.iter().map(|change| (change.path.clone(), change.action)).collect();
+    actions.sort_by(|a, b| a.0.cmp(&b.0));
+    assert_eq!(actions, vec![("a.pdf".to_string(), SyncAction::Update), ("scans/b.pdf".to_string(), SyncAction::Delete)]);
+    assert_eq!(sync.state().unwrap().files.keys().collect::<Vec<_>>(), vec!["a.pdf"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
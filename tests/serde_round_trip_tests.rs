@@ -0,0 +1,131 @@
+//! Round trips of every public wire type through real response payloads
+//!
+//! `tests/payloads` holds one sample per wire type from a self-hosted
+//! server and from Laserfiche Cloud. Each must parse, serialize back to JSON
+//! that parses to the same value, and keep every value it models unchanged.
+
+use laserfiche_rs::laserfiche::*;
+use laserfiche_rs::link_definitions::LinkDefinitionsPage;
+use laserfiche_rs::search::{ContextHits, SearchToken};
+use laserfiche_rs::tag_definitions::TagDefinitionsPage;
+use laserfiche_rs::tasks::OperationProgress;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+fn payloads(file: &str) -> Map<String, Value> {
+    let path = format!("{}/tests/payloads/{}", env!("CARGO_MANIFEST_DIR"), file);
+    let text = std::fs::read_to_string(&path).unwrap_or_else(|error| panic!("{}: {}", path, error));
+    serde_json::from_str(&text).unwrap_or_else(|error| panic!("{}: {}", path, error))
+}
+
+/// Parse `payload` as `T`, checking that the value serializes to JSON that
+/// parses back to itself and agrees with `payload` wherever both have a key
+fn round_trip<T: Serialize + DeserializeOwned>(label: &str, payload: &Value) -> T {
+    let parsed: T = serde_json::from_value(payload.clone()).unwrap_or_else(|error| panic!("{} does not parse: {}", label, error));
+    let serialized = serde_json::to_value(&parsed).unwrap();
+    let reparsed: T = serde_json::from_value(serialized.clone())
+        .unwrap_or_else(|error| panic!("{} does not parse its own output: {}", label, error));
+    assert_eq!(serde_json::to_value(&reparsed).unwrap(), serialized, "{} changes on a second round trip", label);
+    assert_agrees(label, &serialized, payload);
+    parsed
+}
+
+fn check<T: Serialize + DeserializeOwned>(label: &str, payload: &Value) {
+    round_trip::<T>(label, payload);
+}
+
+fn assert_agrees(path: &str, serialized: &Value, payload: &Value) {
+    match (serialized, payload) {
+        (Value::Object(ours), Value::Object(theirs)) => {
+            for (key, value) in ours {
+                if let Some(other) = theirs.get(key) {
+                    assert_agrees(&format!("{}.{}", path, key), value, other);
+                }
+            }
+        }
+        (Value::Array(ours), Value::Array(theirs)) => {
+            assert_eq!(ours.len(), theirs.len(), "{} lost array items", path);
+            for (i, (value, other)) in ours.iter().zip(theirs).enumerate() {
+                assert_agrees(&format!("{}[{}]", path, i), value, other);
+            }
+        }
+        _ => assert_eq!(serialized, payload, "{} changed", path),
+    }
+}
+
+fn check_all(file: &str) {
+    for (name, payload) in &payloads(file) {
+        let label = format!("{} {}", file, name);
+        match name.as_str() {
+            "Token" => check::<Auth>(&label, payload),
+            "Entry" => check::<Entry>(&label, payload),
+            "Entries" => check::<Entries>(&label, payload),
+            "Fields" => check::<Fields>(&label, payload),
+            "MetadataResult" => check::<MetadataResult>(&label, payload),
+            "Template" => check::<Template>(&label, payload),
+            "Tags" => check::<Tags>(&label, payload),
+            "Links" => check::<Links>(&label, payload),
+            "NewLink" => check::<NewLink>(&label, payload),
+            "ImportResult" => check::<ImportResult>(&label, payload),
+            "DeletedObject" => check::<DeletedObject>(&label, payload),
+            "AuditReasons" => check::<AuditReasons>(&label, payload),
+            "OperationProgress" => check::<OperationProgress>(&label, payload),
+            "SearchToken" => check::<SearchToken>(&label, payload),
+            "ContextHits" => check::<ContextHits>(&label, payload),
+            "TagDefinitionsPage" => check::<TagDefinitionsPage>(&label, payload),
+            "LinkDefinitionsPage" => check::<LinkDefinitionsPage>(&label, payload),
+            "LFAPIError" => check::<LFAPIError>(&label, payload),
+            other => panic!("{}: no wire type is checked for {}", file, other),
+        }
+    }
+}
+
+#[test]
+fn test_self_hosted_payloads_round_trip() {
+    check_all("self_hosted.json");
+}
+
+#[test]
+fn test_cloud_payloads_round_trip() {
+    check_all("cloud.json");
+}
+
+#[test]
+fn test_field_values_survive() {
+    let fields: Fields = round_trip("Fields", &payloads("self_hosted.json")["Fields"]);
+    let line_items = &fields.value[1];
+    let values: Vec<(Option<&str>, i64)> = line_items.values.iter().map(|value| (value.value.as_deref(), value.position)).collect();
+    assert_eq!(values, vec![(Some("Paper"), 1), (Some("Toner"), 2)]);
+    assert_eq!(line_items.field_type, FieldType::List);
+}
+
+#[test]
+fn test_entry_kinds_survive() {
+    let entries: Entries = round_trip("Entries", &payloads("self_hosted.json")["Entries"]);
+    assert!(entries.value[0].is_folder());
+    assert!(entries.value[1].is_shortcut());
+    assert!(entries.next_link().is_some());
+
+    let entries: Entries = round_trip("Entries", &payloads("cloud.json")["Entries"]);
+    assert!(entries.value[0].is_record_series());
+}
+
+#[test]
+fn test_odata_annotations_omitted_when_absent() {
+    let serialized = serde_json::to_value(Entries::default()).unwrap();
+    assert_eq!(serialized, serde_json::json!({"value": []}));
+
+    let serialized = serde_json::to_value(Auth { access_token: "t".to_string(), ..Default::default() }).unwrap();
+    assert!(serialized.get("@odata.context").is_none());
+
+    let entries: Entries = serde_json::from_value(serde_json::json!({
+        "value": [],
+        "@odata.nextLink": "https://lf.example.com/next",
+        "@odata.count": 0
+    })).unwrap();
+    let serialized = serde_json::to_value(&entries).unwrap();
+    assert_eq!(serialized["@odata.nextLink"], "https://lf.example.com/next");
+    assert_eq!(serialized["@odata.count"], 0);
+}
@@ -1,3 +1,6 @@
+// Shared by several integration test crates; not every crate uses every helper.
+#![allow(dead_code)]
+
 use laserfiche_rs::laserfiche::*;
 use std::env;
 use std::fmt;
@@ -53,6 +56,7 @@ impl TestConfig {
     }
 
     /// Create an authenticated connection for blocking tests
+    #[cfg(feature = "blocking")]
     pub fn authenticate_blocking(&self) -> std::result::Result<Auth, String> {
         let auth_result = match Auth::new_blocking(
             self.api_server.clone(),
@@ -27,6 +27,7 @@ async fn test_invalid_entry_id_validation() {
                 timestamp: 0,
                 api_server: api_server.clone(),
                 odata_context: String::new(),
+                cloud_credentials: None,
             }
         }
     };
@@ -62,6 +63,7 @@ async fn test_invalid_file_path_validation() {
         timestamp: 0,
         api_server: api_server.clone(),
         odata_context: String::new(),
+        cloud_credentials: None,
     };
 
     // Test path traversal attempts
@@ -110,6 +112,7 @@ async fn test_invalid_file_name_validation() {
         timestamp: 0,
         api_server: api_server.clone(),
         odata_context: String::new(),
+        cloud_credentials: None,
     };
 
     // Test file name with path traversal
@@ -224,6 +227,7 @@ async fn test_metadata_field_validation() {
         timestamp: 0,
         api_server: api_server.clone(),
         odata_context: String::new(),
+        cloud_credentials: None,
     };
 
     // Test metadata with SQL injection in field name